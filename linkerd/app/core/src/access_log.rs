@@ -0,0 +1,1018 @@
+//! Access logs for the requests and connections served by the proxy.
+//!
+//! When enabled, a record is written as a line of JSON for each HTTP request,
+//! once its response completes, and for each accepted TCP connection, once it
+//! closes. The fields included in each record are configured by a `Format`.
+
+use crate::{
+    annotate::Annotations,
+    io::{self, PeerAddr},
+    metrics::Direction,
+    proxy::http::{h1, BoxBody, ClientHandle, HttpBody},
+    svc::{self, stack::Param},
+    tls,
+    transport::{
+        labels::Key,
+        metrics::{SensorIo, Totals},
+    },
+    Conditional, Error,
+};
+use bytes::Buf;
+use futures::ready;
+use indexmap::IndexMap;
+use linkerd_metrics::{metrics, Counter, FmtMetrics};
+use linkerd_trace_context as trace_context;
+use pin_project::pin_project;
+use std::{
+    fmt::{self, Write},
+    future::Future,
+    net::SocketAddr,
+    path::PathBuf,
+    pin::Pin,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+use tracing::{debug, warn};
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub output: Output,
+    pub format: Format,
+}
+
+/// Where access log records are written.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output {
+    Stdout,
+    File(PathBuf),
+}
+
+/// The fields that are written in each record, in order.
+///
+/// Fields that don't apply to a record (e.g. `status` for a TCP connection)
+/// are omitted from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Format(Vec<Field>);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Field {
+    Timestamp,
+    Direction,
+    Protocol,
+    ClientAddr,
+    TargetAddr,
+    Tls,
+    ClientId,
+    ServerId,
+    Method,
+    Authority,
+    Path,
+    Status,
+    Route,
+    TraceId,
+    LatencyMs,
+    DurationMs,
+    ReceivedBytes,
+    SentBytes,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidField(String);
+
+/// Sends records to the task that writes them.
+#[derive(Clone, Debug)]
+pub struct AccessLog {
+    format: Arc<Format>,
+    tx: mpsc::Sender<String>,
+    metrics: Metrics,
+}
+
+pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+metrics! {
+    access_log_dropped_records_total: Counter {
+        "Total count of access log records dropped because the log's buffer was full"
+    }
+}
+
+/// Counts the records that are dropped before they are written.
+#[derive(Clone, Debug)]
+pub struct Metrics(Arc<Counter>);
+
+#[derive(Clone, Debug)]
+pub struct Report(Arc<Counter>);
+
+/// Writes an access log record for each request served by the inner service.
+#[derive(Clone, Debug)]
+pub struct HttpLog<S> {
+    inner: S,
+    log: Option<AccessLog>,
+    direction: Direction,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseFuture<F> {
+    #[pin]
+    inner: F,
+    tracker: Option<Tracker>,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseBody<B> {
+    #[pin]
+    inner: B,
+    tracker: Option<Tracker>,
+}
+
+/// Writes an access log record for each connection served by the inner
+/// service.
+#[derive(Clone, Debug)]
+pub struct NewTcpLog<N> {
+    inner: N,
+    log: Option<AccessLog>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TcpLog<S> {
+    inner: S,
+    log: Option<AccessLog>,
+    key: Key,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct TcpFuture<F> {
+    #[pin]
+    inner: F,
+    tracker: Option<Tracker>,
+}
+
+/// Counts a request body's bytes.
+#[pin_project]
+struct RequestBody {
+    #[pin]
+    inner: BoxBody,
+    received: Arc<AtomicU64>,
+}
+
+/// Writes a record when it is dropped, i.e. when a response completes or a
+/// connection closes.
+#[derive(Debug)]
+struct Tracker {
+    log: AccessLog,
+    record: Record,
+    t0: Instant,
+    annotations: Option<Annotations>,
+    counts: Counts,
+}
+
+#[derive(Debug)]
+enum Counts {
+    Http { received: Arc<AtomicU64>, sent: u64 },
+    Tcp(Totals),
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    timestamp: Option<Rfc3339>,
+    direction: Option<Direction>,
+    protocol: Option<&'static str>,
+    client_addr: Option<SocketAddr>,
+    target_addr: Option<SocketAddr>,
+    tls: Option<&'static str>,
+    client_id: Option<tls::ClientId>,
+    server_id: Option<tls::ServerId>,
+    method: Option<::http::Method>,
+    authority: Option<String>,
+    path: Option<String>,
+    status: Option<::http::StatusCode>,
+    route: Option<Arc<IndexMap<String, String>>>,
+    trace_id: Option<String>,
+    latency: Option<Duration>,
+    duration: Option<Duration>,
+    received_bytes: Option<u64>,
+    sent_bytes: Option<u64>,
+    error: Option<String>,
+}
+
+enum Value<'r> {
+    Str(&'r dyn fmt::Display),
+    Number(u64),
+    Millis(Duration),
+    Labels(&'r IndexMap<String, String>),
+}
+
+/// Escapes the characters written to a JSON string.
+struct JsonStr<'s>(&'s mut String);
+
+/// Formats a timestamp as an RFC 3339 UTC date-time with millisecond precision.
+#[derive(Debug)]
+struct Rfc3339(SystemTime);
+
+pub fn server<S>(
+    log: Option<AccessLog>,
+    direction: Direction,
+) -> impl svc::layer::Layer<S, Service = HttpLog<S>> + Clone {
+    svc::layer::mk(move |inner| HttpLog {
+        inner,
+        log: log.clone(),
+        direction,
+    })
+}
+
+// === impl Config ===
+
+impl Config {
+    const BUFFER_CAPACITY: usize = 10_000;
+
+    /// Opens the log's output and returns a handle for sending records along
+    /// with a task that writes them.
+    pub fn build(self, metrics: Metrics) -> std::io::Result<(AccessLog, Task)> {
+        let out: Box<dyn AsyncWrite + Send + Unpin> = match self.output {
+            Output::Stdout => Box::new(tokio::io::stdout()),
+            Output::File(path) => {
+                let file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                Box::new(tokio::fs::File::from_std(file))
+            }
+        };
+
+        let (tx, rx) = mpsc::channel(Self::BUFFER_CAPACITY);
+        let log = AccessLog {
+            format: Arc::new(self.format),
+            tx,
+            metrics,
+        };
+        Ok((log, Box::pin(write_records(rx, out))))
+    }
+}
+
+/// Writes records as they are received.
+///
+/// Records are buffered so that a busy proxy doesn't issue a write for each
+/// one. The buffer is flushed when it fills, periodically while records are
+/// pending, and once all senders have been dropped.
+async fn write_records(mut rx: mpsc::Receiver<String>, out: Box<dyn AsyncWrite + Send + Unpin>) {
+    const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+    let mut out = tokio::io::BufWriter::new(out);
+    let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+    let mut pending = false;
+    loop {
+        tokio::select! {
+            line = rx.recv() => match line {
+                Some(line) => match out.write_all(line.as_bytes()).await {
+                    Ok(()) => pending = true,
+                    Err(error) => warn!(%error, "Failed to write access log record"),
+                },
+                None => break,
+            },
+            _ = flush.tick(), if pending => {
+                pending = false;
+                if let Err(error) = out.flush().await {
+                    warn!(%error, "Failed to flush access log records");
+                }
+            }
+        }
+    }
+
+    if let Err(error) = out.flush().await {
+        warn!(%error, "Failed to flush access log records");
+    }
+}
+
+// === impl Format ===
+
+impl Default for Format {
+    fn default() -> Self {
+        Self(Field::ALL.to_vec())
+    }
+}
+
+impl FromStr for Format {
+    type Err = InvalidField;
+
+    /// Parses a comma-separated list of field names.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s
+            .split(',')
+            .map(|f| f.trim().parse())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self(fields))
+    }
+}
+
+// === impl Field ===
+
+impl Field {
+    const ALL: [Field; 19] = [
+        Self::Timestamp,
+        Self::Direction,
+        Self::Protocol,
+        Self::ClientAddr,
+        Self::TargetAddr,
+        Self::Tls,
+        Self::ClientId,
+        Self::ServerId,
+        Self::Method,
+        Self::Authority,
+        Self::Path,
+        Self::Status,
+        Self::Route,
+        Self::TraceId,
+        Self::LatencyMs,
+        Self::DurationMs,
+        Self::ReceivedBytes,
+        Self::SentBytes,
+        Self::Error,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Timestamp => "timestamp",
+            Self::Direction => "direction",
+            Self::Protocol => "protocol",
+            Self::ClientAddr => "client_addr",
+            Self::TargetAddr => "target_addr",
+            Self::Tls => "tls",
+            Self::ClientId => "client_id",
+            Self::ServerId => "server_id",
+            Self::Method => "method",
+            Self::Authority => "authority",
+            Self::Path => "path",
+            Self::Status => "status",
+            Self::Route => "route",
+            Self::TraceId => "trace_id",
+            Self::LatencyMs => "latency_ms",
+            Self::DurationMs => "duration_ms",
+            Self::ReceivedBytes => "received_bytes",
+            Self::SentBytes => "sent_bytes",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = InvalidField;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|f| f.name() == s)
+            .copied()
+            .ok_or_else(|| InvalidField(s.to_string()))
+    }
+}
+
+impl fmt::Display for InvalidField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid access log field: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidField {}
+
+// === impl AccessLog ===
+
+impl AccessLog {
+    fn send(&self, record: &Record) {
+        let line = record.to_json(&self.format);
+        if self.tx.try_send(line).is_err() {
+            debug!("Access log record dropped");
+            self.metrics.0.incr();
+        }
+    }
+}
+
+// === impl Metrics ===
+
+pub fn metrics() -> (Metrics, Report) {
+    let dropped = Arc::new(Counter::default());
+    (Metrics(dropped.clone()), Report(dropped))
+}
+
+impl FmtMetrics for Report {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        access_log_dropped_records_total.fmt_help(f)?;
+        access_log_dropped_records_total.fmt_metric(f, &self.0)?;
+        Ok(())
+    }
+}
+
+// === impl HttpLog ===
+
+impl<S, B> svc::Service<::http::Request<BoxBody>> for HttpLog<S>
+where
+    S: svc::Service<::http::Request<BoxBody>, Response = ::http::Response<B>>,
+    S::Error: fmt::Display,
+{
+    type Response = ::http::Response<ResponseBody<B>>;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ::http::Request<BoxBody>) -> Self::Future {
+        let log = match self.log.as_ref() {
+            Some(log) => log.clone(),
+            None => {
+                return ResponseFuture {
+                    inner: self.inner.call(req),
+                    tracker: None,
+                }
+            }
+        };
+
+        let record = Record {
+            timestamp: Some(Rfc3339(SystemTime::now())),
+            direction: Some(self.direction),
+            protocol: Some("http"),
+            client_addr: req.extensions().get::<ClientHandle>().map(|c| c.addr),
+            method: Some(req.method().clone()),
+            authority: req
+                .uri()
+                .authority()
+                .cloned()
+                .or_else(|| h1::authority_from_host(&req))
+                .map(|a| a.to_string()),
+            path: Some(req.uri().path().to_string()),
+            trace_id: trace_context::unpack_trace_context(&req).map(|c| c.trace_id.to_string()),
+            ..Record::default()
+        };
+
+        let annotations = Annotations::get_or_insert(&mut req);
+
+        let received = Arc::new(AtomicU64::new(0));
+        let req = req.map(|inner| {
+            BoxBody::new(RequestBody {
+                inner,
+                received: received.clone(),
+            })
+        });
+
+        ResponseFuture {
+            inner: self.inner.call(req),
+            tracker: Some(Tracker {
+                log,
+                record,
+                t0: Instant::now(),
+                annotations: Some(annotations),
+                counts: Counts::Http { received, sent: 0 },
+            }),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<::http::Response<B>, E>>,
+    E: fmt::Display,
+{
+    type Output = Result<::http::Response<ResponseBody<B>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.inner.poll(cx));
+        let mut tracker = this.tracker.take();
+        match res {
+            Ok(rsp) => {
+                if let Some(t) = tracker.as_mut() {
+                    t.record.status = Some(rsp.status());
+                    t.record.latency = Some(t.t0.elapsed());
+                }
+                Poll::Ready(Ok(rsp.map(|inner| ResponseBody { inner, tracker })))
+            }
+            Err(e) => {
+                if let Some(t) = tracker.as_mut() {
+                    t.record.error = Some(e.to_string());
+                }
+                Poll::Ready(Err(e))
+            }
+        }
+    }
+}
+
+// === impl ResponseBody ===
+
+impl<B> HttpBody for ResponseBody<B>
+where
+    B: HttpBody,
+    B::Error: fmt::Display,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let res = ready!(this.inner.poll_data(cx));
+        match res {
+            Some(Ok(ref data)) => {
+                if let Some(Tracker {
+                    counts: Counts::Http { ref mut sent, .. },
+                    ..
+                }) = this.tracker
+                {
+                    *sent += data.remaining() as u64;
+                }
+            }
+            Some(Err(ref e)) => {
+                if let Some(t) = this.tracker.as_mut() {
+                    t.record.error = Some(e.to_string());
+                }
+                // Write the record.
+                this.tracker.take();
+            }
+            // Write the record when the stream ends; trailers need not be
+            // polled.
+            None => drop(this.tracker.take()),
+        }
+        Poll::Ready(res)
+    }
+
+    #[inline]
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<::http::HeaderMap>, Self::Error>> {
+        let this = self.project();
+        let res = ready!(this.inner.poll_trailers(cx));
+        drop(this.tracker.take());
+        Poll::Ready(res)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<B: Default> Default for ResponseBody<B> {
+    fn default() -> Self {
+        Self {
+            inner: B::default(),
+            tracker: None,
+        }
+    }
+}
+
+// === impl RequestBody ===
+
+impl HttpBody for RequestBody {
+    type Data = <BoxBody as HttpBody>::Data;
+    type Error = Error;
+
+    #[inline]
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.project();
+        let res = ready!(this.inner.poll_data(cx));
+        if let Some(Ok(ref data)) = res {
+            this.received
+                .fetch_add(data.remaining() as u64, Ordering::Relaxed);
+        }
+        Poll::Ready(res)
+    }
+
+    #[inline]
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<::http::HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// === impl NewTcpLog ===
+
+impl<N> NewTcpLog<N> {
+    pub fn layer(log: Option<AccessLog>) -> impl svc::layer::Layer<N, Service = Self> + Clone {
+        svc::layer::mk(move |inner| Self {
+            inner,
+            log: log.clone(),
+        })
+    }
+}
+
+impl<T, N> svc::NewService<T> for NewTcpLog<N>
+where
+    T: Param<Key>,
+    N: svc::NewService<T>,
+{
+    type Service = TcpLog<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let key = target.param();
+        let inner = self.inner.new_service(target);
+        TcpLog {
+            inner,
+            log: self.log.clone(),
+            key,
+        }
+    }
+}
+
+// === impl TcpLog ===
+
+impl<I, S> svc::Service<SensorIo<I>> for TcpLog<S>
+where
+    I: io::PeerAddr,
+    S: svc::Service<SensorIo<I>, Response = ()>,
+    S::Error: fmt::Display,
+{
+    type Response = ();
+    type Error = S::Error;
+    type Future = TcpFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, io: SensorIo<I>) -> Self::Future {
+        let key = &self.key;
+        let tracker = self.log.clone().map(|log| {
+            let mut record = Record {
+                timestamp: Some(Rfc3339(SystemTime::now())),
+                protocol: Some("tcp"),
+                client_addr: io.peer_addr().ok(),
+                ..Record::default()
+            };
+            if let Key::Accept {
+                direction,
+                tls,
+                target_addr,
+            } = key
+            {
+                record.direction = Some(*direction);
+                record.target_addr = Some(*target_addr);
+                record.tls = Some(tls_status(tls));
+                if let Conditional::Some(tls::ServerTls::Established {
+                    client_id: Some(id),
+                    ..
+                }) = tls
+                {
+                    record.client_id = Some(id.clone());
+                }
+            }
+            Tracker {
+                log,
+                record,
+                t0: Instant::now(),
+                annotations: None,
+                counts: Counts::Tcp(io.sensor().totals()),
+            }
+        });
+
+        TcpFuture {
+            inner: self.inner.call(io),
+            tracker,
+        }
+    }
+}
+
+fn tls_status(tls: &tls::ConditionalServerTls) -> &'static str {
+    match tls {
+        Conditional::None(tls::NoServerTls::Disabled) => "disabled",
+        Conditional::None(_) => "no_identity",
+        Conditional::Some(tls::ServerTls::Established { .. }) => "true",
+        Conditional::Some(tls::ServerTls::Passthru { .. }) => "opaque",
+    }
+}
+
+// === impl TcpFuture ===
+
+impl<F, E> Future for TcpFuture<F>
+where
+    F: Future<Output = Result<(), E>>,
+    E: fmt::Display,
+{
+    type Output = Result<(), E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.inner.poll(cx));
+        if let Some(mut t) = this.tracker.take() {
+            if let Err(ref e) = res {
+                t.record.error = Some(e.to_string());
+            }
+        }
+        Poll::Ready(res)
+    }
+}
+
+// === impl Tracker ===
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        let record = &mut self.record;
+        record.duration = Some(self.t0.elapsed());
+        if let Some(annotations) = self.annotations.take() {
+            let annotation = annotations.get();
+            record.target_addr = annotation.target_addr;
+            record.client_id = annotation.client_id().cloned();
+            record.server_id = annotation.server_id().cloned();
+            record.route = annotation.route;
+        }
+        match self.counts {
+            Counts::Http { ref received, sent } => {
+                record.received_bytes = Some(received.load(Ordering::Relaxed));
+                record.sent_bytes = Some(sent);
+            }
+            Counts::Tcp(ref totals) => {
+                record.received_bytes = Some(totals.read_bytes());
+                record.sent_bytes = Some(totals.write_bytes());
+            }
+        }
+        self.log.send(record);
+    }
+}
+
+// === impl Record ===
+
+impl Record {
+    fn to_json(&self, format: &Format) -> String {
+        let mut out = String::with_capacity(512);
+        out.push('{');
+        for field in format.0.iter() {
+            if let Some(value) = self.value(*field) {
+                if out.len() > 1 {
+                    out.push(',');
+                }
+                write_str(&mut out, field.name());
+                out.push(':');
+                value.write(&mut out);
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn value(&self, field: Field) -> Option<Value<'_>> {
+        fn string<T: fmt::Display>(v: &Option<T>) -> Option<Value<'_>> {
+            v.as_ref().map(|v| Value::Str(v))
+        }
+
+        match field {
+            Field::Timestamp => string(&self.timestamp),
+            Field::Direction => string(&self.direction),
+            Field::Protocol => string(&self.protocol),
+            Field::ClientAddr => string(&self.client_addr),
+            Field::TargetAddr => string(&self.target_addr),
+            Field::Tls => string(&self.tls),
+            Field::ClientId => string(&self.client_id),
+            Field::ServerId => string(&self.server_id),
+            Field::Method => string(&self.method),
+            Field::Authority => string(&self.authority),
+            Field::Path => string(&self.path),
+            Field::Status => self.status.map(|s| Value::Number(s.as_u16().into())),
+            Field::Route => self.route.as_ref().map(|r| Value::Labels(r)),
+            Field::TraceId => string(&self.trace_id),
+            Field::LatencyMs => self.latency.map(Value::Millis),
+            Field::DurationMs => self.duration.map(Value::Millis),
+            Field::ReceivedBytes => self.received_bytes.map(Value::Number),
+            Field::SentBytes => self.sent_bytes.map(Value::Number),
+            Field::Error => string(&self.error),
+        }
+    }
+}
+
+// === impl Value ===
+
+impl<'r> Value<'r> {
+    fn write(&self, out: &mut String) {
+        match self {
+            Self::Str(v) => {
+                out.push('"');
+                write!(JsonStr(out), "{}", v).expect("formatting must succeed");
+                out.push('"');
+            }
+            Self::Number(n) => write!(out, "{}", n).expect("formatting must succeed"),
+            Self::Millis(d) => {
+                write!(out, "{:.3}", d.as_secs_f64() * 1000.0).expect("formatting must succeed")
+            }
+            Self::Labels(labels) => {
+                out.push('{');
+                for (i, (k, v)) in labels.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_str(out, k);
+                    out.push(':');
+                    write_str(out, v);
+                }
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_str(out: &mut String, s: &str) {
+    Value::Str(&s).write(out)
+}
+
+// === impl JsonStr ===
+
+impl<'s> fmt::Write for JsonStr<'s> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '"' => self.0.push_str("\\\""),
+                '\\' => self.0.push_str("\\\\"),
+                '\n' => self.0.push_str("\\n"),
+                '\r' => self.0.push_str("\\r"),
+                '\t' => self.0.push_str("\\t"),
+                c if c.is_control() => write!(self.0, "\\u{:04x}", c as u32)?,
+                c => self.0.push(c),
+            }
+        }
+        Ok(())
+    }
+}
+
+// === impl Rfc3339 ===
+
+impl fmt::Display for Rfc3339 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self.0.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs();
+        let (days, secs) = (secs / 86_400, secs % 86_400);
+
+        // Converts the number of days since the epoch to a date in the
+        // proleptic Gregorian calendar. See
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year,
+            month,
+            day,
+            secs / 3_600,
+            secs % 3_600 / 60,
+            secs % 60,
+            since_epoch.subsec_millis()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_format() {
+        assert_eq!(
+            " timestamp,status ,trace_id".parse::<Format>(),
+            Ok(Format(vec![
+                Field::Timestamp,
+                Field::Status,
+                Field::TraceId
+            ]))
+        );
+        assert_eq!(
+            "status,bogus".parse::<Format>(),
+            Err(InvalidField("bogus".to_string()))
+        );
+        assert!("".parse::<Format>().is_err());
+        assert!("status,".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn formats_records() {
+        let mut route = IndexMap::new();
+        route.insert("route".to_string(), "GET /books/{id}".to_string());
+        let record = Record {
+            timestamp: Some(Rfc3339(UNIX_EPOCH + Duration::from_millis(951_782_400_123))),
+            direction: Some(Direction::In),
+            protocol: Some("http"),
+            method: Some(::http::Method::GET),
+            path: Some("/books/\"1\"\n".to_string()),
+            status: Some(::http::StatusCode::NOT_FOUND),
+            route: Some(Arc::new(route)),
+            latency: Some(Duration::from_micros(1_500)),
+            received_bytes: Some(0),
+            sent_bytes: Some(12),
+            ..Record::default()
+        };
+
+        assert_eq!(
+            record.to_json(&Format::default()),
+            "{\"timestamp\":\"2000-02-29T00:00:00.123Z\",\"direction\":\"inbound\",\
+             \"protocol\":\"http\",\"method\":\"GET\",\"path\":\"/books/\\\"1\\\"\\n\",\
+             \"status\":404,\"route\":{\"route\":\"GET /books/{id}\"},\"latency_ms\":1.500,\
+             \"received_bytes\":0,\"sent_bytes\":12}\n"
+        );
+
+        let format = "status,trace_id,path".parse().unwrap();
+        assert_eq!(
+            record.to_json(&format),
+            "{\"status\":404,\"path\":\"/books/\\\"1\\\"\\n\"}\n"
+        );
+        assert_eq!(Record::default().to_json(&format), "{}\n");
+    }
+
+    #[test]
+    fn counts_dropped_records() {
+        let (metrics, report) = metrics();
+        let (tx, _rx) = mpsc::channel(1);
+        let log = AccessLog {
+            format: Arc::new(Format::default()),
+            tx,
+            metrics,
+        };
+
+        // The first record fills the buffer, so the second is dropped.
+        log.send(&Record::default());
+        log.send(&Record::default());
+        assert_eq!(report.0.value(), 1.0);
+        assert!(report
+            .as_display()
+            .to_string()
+            .contains("access_log_dropped_records_total 1\n"));
+    }
+
+    #[test]
+    fn formats_timestamps() {
+        for (secs, expected) in &[
+            (0, "1970-01-01T00:00:00.000Z"),
+            (951_782_399, "2000-02-28T23:59:59.000Z"),
+            (951_868_800, "2000-03-01T00:00:00.000Z"),
+            (1_616_000_000, "2021-03-17T16:53:20.000Z"),
+        ] {
+            let t = Rfc3339(UNIX_EPOCH + Duration::from_secs(*secs));
+            assert_eq!(t.to_string(), *expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_buffered_records() {
+        use tokio::io::AsyncReadExt;
+
+        let (client, mut server) = tokio::io::duplex(1024);
+        let (tx, rx) = mpsc::channel(2);
+        let task = tokio::spawn(write_records(rx, Box::new(client)));
+
+        tx.send("{\"status\":200}\n".to_string()).await.unwrap();
+        tx.send("{\"status\":404}\n".to_string()).await.unwrap();
+
+        // Pending records are flushed periodically.
+        let mut buf = [0u8; 30];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf[..], &b"{\"status\":200}\n{\"status\":404}\n"[..]);
+
+        // Records buffered when the log is dropped are written.
+        tx.send("{\"status\":500}\n".to_string()).await.unwrap();
+        drop(tx);
+        task.await.unwrap();
+        let mut rest = Vec::new();
+        server.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"{\"status\":500}\n");
+    }
+}
@@ -0,0 +1,210 @@
+//! Describes each request's targets as it is routed through the proxy's stacks.
+//!
+//! Stacks annotate requests via `NewAnnotate` for each target that implements
+//! `Param<Annotation>`. The annotations are accumulated in a request extension,
+//! `Annotations`, from which the request's access log record and spans are
+//! described once its response is received.
+
+use crate::{
+    dst,
+    svc::{self, stack::Param},
+    tls, Conditional,
+};
+use indexmap::IndexMap;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+/// Describes a request's target.
+///
+/// Unset fields don't clear a prior annotation.
+#[derive(Clone, Debug, Default)]
+pub struct Annotation {
+    /// The address to which the request is sent, as seen by the application.
+    pub target_addr: Option<SocketAddr>,
+
+    /// The address of the client that sent the request to an inbound proxy.
+    pub client_addr: Option<SocketAddr>,
+    pub client_tls: Option<tls::ConditionalServerTls>,
+
+    /// The address of the endpoint to which an outbound proxy sends the
+    /// request.
+    pub endpoint_addr: Option<SocketAddr>,
+    pub endpoint_tls: Option<tls::ConditionalClientTls>,
+    pub endpoint_labels: Option<Arc<IndexMap<String, String>>>,
+
+    pub route: Option<Arc<IndexMap<String, String>>>,
+}
+
+/// A request extension that accumulates the annotations set by inner stacks.
+///
+/// It is inserted by each service that describes a request (e.g. the access
+/// log or a span), and it is shared with any that are inserted later.
+#[derive(Clone, Debug, Default)]
+pub struct Annotations(Arc<Mutex<Annotation>>);
+
+/// Annotates each request with its target.
+#[derive(Clone, Debug)]
+pub struct NewAnnotate<N> {
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct Annotate<S> {
+    inner: S,
+    annotation: Annotation,
+}
+
+// === impl Annotation ===
+
+impl Annotation {
+    /// Returns the identity of the client that sent the request to an inbound
+    /// proxy, if it was authenticated.
+    pub fn client_id(&self) -> Option<&tls::ClientId> {
+        match self.client_tls {
+            Some(Conditional::Some(tls::ServerTls::Established {
+                client_id: Some(ref id),
+                ..
+            })) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Returns the identity of the endpoint to which an outbound proxy sends
+    /// the request, if it is secured by mTLS.
+    pub fn server_id(&self) -> Option<&tls::ServerId> {
+        self.endpoint_tls
+            .as_ref()
+            .and_then(|tls| tls.value())
+            .map(|tls| &tls.server_id)
+    }
+
+    fn merge(&mut self, annotation: &Annotation) {
+        if annotation.target_addr.is_some() {
+            self.target_addr = annotation.target_addr;
+        }
+        if annotation.client_addr.is_some() {
+            self.client_addr = annotation.client_addr;
+        }
+        if annotation.client_tls.is_some() {
+            self.client_tls = annotation.client_tls.clone();
+        }
+        if annotation.endpoint_addr.is_some() {
+            self.endpoint_addr = annotation.endpoint_addr;
+        }
+        if annotation.endpoint_tls.is_some() {
+            self.endpoint_tls = annotation.endpoint_tls.clone();
+        }
+        if annotation.endpoint_labels.is_some() {
+            self.endpoint_labels = annotation.endpoint_labels.clone();
+        }
+        if annotation.route.is_some() {
+            self.route = annotation.route.clone();
+        }
+    }
+}
+
+impl Param<Annotation> for dst::Route {
+    fn param(&self) -> Annotation {
+        let labels = self.route.labels();
+        Annotation {
+            route: Some(labels.clone()).filter(|l| !l.is_empty()),
+            ..Annotation::default()
+        }
+    }
+}
+
+// === impl Annotations ===
+
+impl Annotations {
+    /// Returns the request's annotations, inserting them if the request has
+    /// none.
+    pub fn get_or_insert<B>(req: &mut http::Request<B>) -> Self {
+        if let Some(annotations) = req.extensions().get::<Self>() {
+            return annotations.clone();
+        }
+        let annotations = Self::default();
+        req.extensions_mut().insert(annotations.clone());
+        annotations
+    }
+
+    /// Returns the annotations that have been set so far.
+    pub fn get(&self) -> Annotation {
+        self.0.lock().map(|a| a.clone()).unwrap_or_default()
+    }
+
+    fn merge(&self, annotation: &Annotation) {
+        if let Ok(mut a) = self.0.lock() {
+            a.merge(annotation);
+        }
+    }
+}
+
+// === impl NewAnnotate ===
+
+impl<N> NewAnnotate<N> {
+    pub fn layer() -> impl svc::layer::Layer<N, Service = Self> + Copy {
+        svc::layer::mk(|inner| Self { inner })
+    }
+}
+
+impl<T, N> svc::NewService<T> for NewAnnotate<N>
+where
+    T: Param<Annotation>,
+    N: svc::NewService<T>,
+{
+    type Service = Annotate<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let annotation = target.param();
+        let inner = self.inner.new_service(target);
+        Annotate { inner, annotation }
+    }
+}
+
+// === impl Annotate ===
+
+impl<S> Annotate<S> {
+    fn annotate<B>(&self, req: &http::Request<B>) {
+        if let Some(annotations) = req.extensions().get::<Annotations>() {
+            annotations.merge(&self.annotation);
+        }
+    }
+}
+
+impl<P, S, B> svc::stack::Proxy<http::Request<B>, S> for Annotate<P>
+where
+    P: svc::stack::Proxy<http::Request<B>, S>,
+    S: svc::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = P::Error;
+    type Future = P::Future;
+
+    fn proxy(&self, svc: &mut S, req: http::Request<B>) -> Self::Future {
+        self.annotate(&req);
+        self.inner.proxy(svc, req)
+    }
+}
+
+impl<S, B> svc::Service<http::Request<B>> for Annotate<S>
+where
+    S: svc::Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.annotate(&req);
+        self.inner.call(req)
+    }
+}
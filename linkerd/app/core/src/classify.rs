@@ -26,6 +26,13 @@ pub enum Eos {
     Default(http::StatusCode),
     Grpc(GrpcEos),
     Profile(Class),
+    /// A profile's response classes depend on the response's trailers, so
+    /// classification is deferred until the end of the stream.
+    ProfileTrailers {
+        classes: profiles::http::ResponseClasses,
+        head: profiles::http::ResponseHead,
+        grpc: Option<Class>,
+    },
     Error(&'static str),
 }
 
@@ -103,22 +110,29 @@ impl Default for Response {
 }
 
 impl Response {
-    fn match_class<B>(
-        rsp: &http::Response<B>,
-        classes: &[profiles::http::ResponseClass],
-    ) -> Option<Class> {
-        for class in classes {
-            if class.is_match(rsp) {
-                let result = if class.is_failure() {
-                    SuccessOrFailure::Failure
-                } else {
-                    SuccessOrFailure::Success
-                };
-                return Some(Class::Default(result));
-            }
+    /// Classifies a response from its head, unless a response class that
+    /// depends on trailers must be evaluated first.
+    fn start_profile<B>(rsp: &http::Response<B>, classes: &profiles::http::ResponseClasses) -> Eos {
+        match classes.match_head(rsp) {
+            profiles::http::HeadMatch::Class(class) => Eos::Profile(Self::profile_class(class)),
+            profiles::http::HeadMatch::Trailers(head) => Eos::ProfileTrailers {
+                classes: classes.clone(),
+                head,
+                grpc: grpc_class(rsp.headers()),
+            },
+            profiles::http::HeadMatch::None => grpc_class(rsp.headers())
+                .map(|c| Eos::Grpc(GrpcEos::NoBody(c)))
+                .unwrap_or_else(|| Eos::Default(rsp.status())),
         }
+    }
 
-        None
+    fn profile_class(class: &profiles::http::ResponseClass) -> Class {
+        let result = if class.is_failure() {
+            SuccessOrFailure::Failure
+        } else {
+            SuccessOrFailure::Success
+        };
+        Class::Default(result)
     }
 }
 
@@ -134,13 +148,7 @@ impl classify::ClassifyResponse for Response {
             Response::Grpc => grpc_class(rsp.headers())
                 .map(|c| Eos::Grpc(GrpcEos::NoBody(c)))
                 .unwrap_or(Eos::Grpc(GrpcEos::Open)),
-            Response::Profile(ref classes) => Self::match_class(rsp, classes.as_ref())
-                .map(Eos::Profile)
-                .unwrap_or_else(|| {
-                    grpc_class(rsp.headers())
-                        .map(|c| Eos::Grpc(GrpcEos::NoBody(c)))
-                        .unwrap_or_else(|| Eos::Default(rsp.status()))
-                }),
+            Response::Profile(ref classes) => Self::start_profile(rsp, classes),
         }
     }
 
@@ -157,6 +165,13 @@ impl classify::ClassifyResponse for Response {
 
 // === impl Eos ===
 
+impl Eos {
+    /// Returns true if the response's class may depend on its trailers.
+    pub(crate) fn awaits_trailers(&self) -> bool {
+        matches!(self, Eos::Grpc(GrpcEos::Open) | Eos::ProfileTrailers { .. })
+    }
+}
+
 impl classify::ClassifyEos for Eos {
     type Class = Class;
 
@@ -173,6 +188,17 @@ impl classify::ClassifyEos for Eos {
                 .and_then(grpc_class)
                 .unwrap_or(Class::Grpc(SuccessOrFailure::Success, 0)),
             Eos::Profile(class) => class,
+            Eos::ProfileTrailers {
+                classes,
+                head,
+                grpc,
+            } => classes
+                .match_eos(&head, trailers)
+                .map(Response::profile_class)
+                .or(grpc)
+                .unwrap_or_else(|| {
+                    classify::ClassifyEos::eos(Eos::Default(head.status()), trailers)
+                }),
             Eos::Error(msg) => Class::Stream(SuccessOrFailure::Failure, msg.into()),
         }
     }
@@ -215,16 +241,28 @@ fn h2_error(err: &Error) -> String {
 
 impl Class {
     pub(super) fn is_failure(&self) -> bool {
-        matches!(self,
+        matches!(
+            self,
             Class::Default(SuccessOrFailure::Failure)
-            | Class::Grpc(SuccessOrFailure::Failure, _)
-            | Class::Stream(SuccessOrFailure::Failure, _))
+                | Class::Grpc(SuccessOrFailure::Failure, _)
+                | Class::Stream(SuccessOrFailure::Failure, _)
+        )
+    }
+}
+
+impl classify::IsFailure for Class {
+    fn is_failure(&self) -> bool {
+        Class::is_failure(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Class, SuccessOrFailure};
+    use crate::profiles::{
+        self,
+        http::{ResponseClass, ResponseMatch, ValueMatch},
+    };
     use http::{HeaderMap, Response, StatusCode};
     use linkerd_http_classify::{ClassifyEos, ClassifyResponse};
 
@@ -316,4 +354,152 @@ mod tests {
             .eos(Some(&trailers));
         assert_eq!(class, Class::Grpc(SuccessOrFailure::Failure, 4));
     }
+
+    fn response_classes(
+        classes: Vec<profiles::http::ResponseClass>,
+    ) -> profiles::http::ResponseClasses {
+        profiles::http::Route::new(std::iter::empty(), classes)
+            .response_classes()
+            .clone()
+    }
+
+    #[test]
+    fn profile_grpc_status_trailer_failure() {
+        let classes = response_classes(vec![ResponseClass::new(
+            true,
+            ResponseMatch::GrpcStatus(vec![14]),
+        )]);
+        let rsp = Response::builder().status(StatusCode::OK).body(()).unwrap();
+
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", 14.into());
+        let class = super::Response::Profile(classes.clone())
+            .start(&rsp)
+            .eos(Some(&trailers));
+        assert_eq!(class, Class::Default(SuccessOrFailure::Failure));
+
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", 0.into());
+        let class = super::Response::Profile(classes)
+            .start(&rsp)
+            .eos(Some(&trailers));
+        assert_eq!(class, Class::Grpc(SuccessOrFailure::Success, 0));
+    }
+
+    #[test]
+    fn profile_grpc_status_trailers_only() {
+        let classes = response_classes(vec![ResponseClass::new(
+            true,
+            ResponseMatch::GrpcStatus(vec![14]),
+        )]);
+        let rsp = Response::builder()
+            .header("grpc-status", "14")
+            .status(StatusCode::OK)
+            .body(())
+            .unwrap();
+        let class = super::Response::Profile(classes).start(&rsp).eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Failure));
+    }
+
+    #[test]
+    fn profile_header_failure() {
+        let classes = response_classes(vec![ResponseClass::new(
+            true,
+            ResponseMatch::Header(
+                http::header::HeaderName::from_static("x-app-error"),
+                ValueMatch::Prefix("fatal".into()),
+            ),
+        )]);
+
+        let rsp = Response::builder()
+            .header("x-app-error", "fatal: db unavailable")
+            .status(StatusCode::OK)
+            .body(())
+            .unwrap();
+        let class = super::Response::Profile(classes.clone())
+            .start(&rsp)
+            .eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Failure));
+
+        let rsp = Response::builder().status(StatusCode::OK).body(()).unwrap();
+        let class = super::Response::Profile(classes).start(&rsp).eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Success));
+    }
+
+    #[test]
+    fn profile_trailer_failure() {
+        let classes = response_classes(vec![
+            ResponseClass::new(
+                false,
+                ResponseMatch::Status {
+                    min: StatusCode::NOT_FOUND,
+                    max: StatusCode::NOT_FOUND,
+                },
+            ),
+            ResponseClass::new(
+                true,
+                ResponseMatch::Trailer(
+                    http::header::HeaderName::from_static("x-app-status"),
+                    ValueMatch::Exact("error".into()),
+                ),
+            ),
+        ]);
+
+        // Classes that precede a trailer match are still evaluated on the
+        // response head.
+        let rsp = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(())
+            .unwrap();
+        let class = super::Response::Profile(classes.clone())
+            .start(&rsp)
+            .eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Success));
+
+        let rsp = Response::builder().status(StatusCode::OK).body(()).unwrap();
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-app-status", "error".parse().unwrap());
+        let class = super::Response::Profile(classes.clone())
+            .start(&rsp)
+            .eos(Some(&trailers));
+        assert_eq!(class, Class::Default(SuccessOrFailure::Failure));
+
+        let class = super::Response::Profile(classes).start(&rsp).eos(None);
+        assert_eq!(class, Class::Default(SuccessOrFailure::Success));
+    }
+
+    #[test]
+    fn profile_header_and_trailer_failure() {
+        let classes = response_classes(vec![ResponseClass::new(
+            true,
+            ResponseMatch::All(vec![
+                ResponseMatch::Header(
+                    http::header::HeaderName::from_static("x-app"),
+                    ValueMatch::Exact("batch".into()),
+                ),
+                ResponseMatch::Trailer(
+                    http::header::HeaderName::from_static("x-app-status"),
+                    ValueMatch::Exact("error".into()),
+                ),
+            ]),
+        )]);
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-app-status", "error".parse().unwrap());
+
+        let rsp = Response::builder()
+            .header("x-app", "batch")
+            .status(StatusCode::OK)
+            .body(())
+            .unwrap();
+        let class = super::Response::Profile(classes.clone())
+            .start(&rsp)
+            .eos(Some(&trailers));
+        assert_eq!(class, Class::Default(SuccessOrFailure::Failure));
+
+        let rsp = Response::builder().status(StatusCode::OK).body(()).unwrap();
+        let class = super::Response::Profile(classes)
+            .start(&rsp)
+            .eos(Some(&trailers));
+        assert_eq!(class, Class::Default(SuccessOrFailure::Success));
+    }
 }
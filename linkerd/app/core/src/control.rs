@@ -4,7 +4,7 @@ use crate::{
     reconnect,
     svc::{self, stack::Param, NewService},
     tls,
-    transport::ConnectTcp,
+    transport::{metrics::Handshakes, ConnectTcp},
     Addr, Error,
 };
 use futures::{future::Either, StreamExt};
@@ -56,7 +56,12 @@ impl Config {
         B: http::HttpBody + Send + 'static,
         B::Data: Send,
         B::Error: Into<Error> + Send + Sync,
-        L: Clone + Param<tls::client::Config> + Send + 'static,
+        L: Clone
+            + Param<tls::client::Config>
+            + Param<tls::SharedDenylist>
+            + Param<Handshakes>
+            + Send
+            + 'static,
     {
         let connect_backoff = {
             let backoff = self.connect.backoff;
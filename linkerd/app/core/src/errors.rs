@@ -5,8 +5,10 @@ use linkerd_error_metrics as metrics;
 use linkerd_error_respond as respond;
 pub use linkerd_error_respond::RespondLayer;
 use linkerd_proxy_http::{client_handle::Close, ClientHandle, HasH2Reason};
+use linkerd_retry::PerTryTimeout;
 use linkerd_timeout::{error::ResponseTimeout, FailFastError};
 use linkerd_tls as tls;
+use linkerd_trace_context::SpanAttributes;
 use pin_project::pin_project;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -40,6 +42,9 @@ pub struct HttpError {
 pub enum Reason {
     DispatchTimeout,
     ResponseTimeout,
+    PerTryTimeout,
+    RateLimited,
+    Unauthorized,
     IdentityRequired,
     Io(Option<Errno>),
     FailFast,
@@ -56,6 +61,7 @@ pub struct Respond {
     version: http::Version,
     is_grpc: bool,
     close: Option<Close>,
+    span: Option<SpanAttributes>,
 }
 
 #[pin_project(project = ResponseBodyProj)]
@@ -146,6 +152,7 @@ impl<ReqB, RspB: Default + hyper::body::HttpBody>
             .extensions()
             .get::<ClientHandle>()
             .map(|h| h.close.clone());
+        let span = req.extensions().get::<SpanAttributes>().cloned();
         match req.version() {
             http::Version::HTTP_2 => {
                 let is_grpc = req
@@ -156,12 +163,14 @@ impl<ReqB, RspB: Default + hyper::body::HttpBody>
                 Respond {
                     is_grpc,
                     close,
+                    span,
                     version: http::Version::HTTP_2,
                 }
             }
             version => Respond {
                 version,
                 close,
+                span,
                 is_grpc: false,
             },
         }
@@ -183,6 +192,16 @@ impl<RspB: Default + hyper::body::HttpBody> respond::Respond<http::Response<RspB
             Err(error) => {
                 warn!("Failed to proxy request: {}", error);
 
+                // Describe the failure on the request's span, if one is recorded.
+                if let Some(span) = self.span.as_ref() {
+                    let reason = LabelError::reason(&*error);
+                    span.insert("error", reason.as_str());
+                    if let Reason::Io(Some(errno)) = reason {
+                        span.insert("error.errno", errno.to_string());
+                    }
+                    span.insert("error.message", error.to_string());
+                }
+
                 if self.version == http::Version::HTTP_2 {
                     if let Some(reset) = error.h2_reason() {
                         debug!(%reset, "Propagating HTTP2 reset");
@@ -223,8 +242,41 @@ impl<RspB: Default + hyper::body::HttpBody> respond::Respond<http::Response<RspB
     }
 }
 
+/// Returns true if a request that failed with `error` may be safely retried,
+/// because the error indicates that the request was never processed by a
+/// server.
+///
+/// This is the case when the connection could not be established or when the
+/// server refused the HTTP/2 stream. A balancer in fail-fast has no endpoints
+/// to retry against, so its errors are not retried.
+pub fn is_safe_to_retry(error: &(dyn std::error::Error + 'static)) -> bool {
+    let is_connect = matches!(error.downcast_ref::<hyper::Error>(), Some(e) if e.is_connect());
+    let is_refused = matches!(
+        error.downcast_ref::<std::io::Error>(),
+        Some(e) if e.kind() == std::io::ErrorKind::ConnectionRefused
+    );
+    let is_refused_stream = matches!(
+        error.downcast_ref::<h2::Error>(),
+        Some(e) if e.reason() == Some(h2::Reason::REFUSED_STREAM)
+    );
+    if is_connect || is_refused || is_refused_stream {
+        return true;
+    }
+
+    error.source().map(is_safe_to_retry).unwrap_or(false)
+}
+
 fn should_teardown_connection(error: &(dyn std::error::Error + 'static)) -> bool {
-    if error.is::<ResponseTimeout>() || error.is::<tower::timeout::error::Elapsed>() {
+    // Requests rejected by policy don't affect the connection's other requests.
+    let is_rejected = matches!(
+        error.downcast_ref::<HttpError>(),
+        Some(e) if e.reason == Reason::RateLimited || e.reason == Reason::Unauthorized
+    );
+    if is_rejected
+        || error.is::<ResponseTimeout>()
+        || error.is::<PerTryTimeout>()
+        || error.is::<tower::timeout::error::Elapsed>()
+    {
         false
     } else if let Some(e) = error.source() {
         should_teardown_connection(e)
@@ -236,7 +288,7 @@ fn should_teardown_connection(error: &(dyn std::error::Error + 'static)) -> bool
 fn http_status(error: &(dyn std::error::Error + 'static)) -> StatusCode {
     if let Some(HttpError { http, .. }) = error.downcast_ref::<HttpError>() {
         *http
-    } else if error.is::<ResponseTimeout>() {
+    } else if error.is::<ResponseTimeout>() || error.is::<PerTryTimeout>() {
         http::StatusCode::GATEWAY_TIMEOUT
     } else if error.is::<FailFastError>() || error.is::<tower::timeout::error::Elapsed>() {
         http::StatusCode::SERVICE_UNAVAILABLE
@@ -260,7 +312,7 @@ fn set_grpc_status(
         headers.insert(GRPC_STATUS, code_header(*grpc));
         headers.insert(GRPC_MESSAGE, HeaderValue::from_static(message));
         *grpc
-    } else if error.is::<ResponseTimeout>() {
+    } else if error.is::<ResponseTimeout>() || error.is::<PerTryTimeout>() {
         let code = Code::DeadlineExceeded;
         headers.insert(GRPC_STATUS, code_header(code));
         headers.insert(GRPC_MESSAGE, HeaderValue::from_static("request timed out"));
@@ -332,6 +384,12 @@ fn code_header(code: grpc::Code) -> HeaderValue {
     }
 }
 
+/// Indicates that a connection was refused by an authorization policy.
+#[derive(Debug)]
+pub struct UnauthorizedConnection {
+    pub port: u16,
+}
+
 #[derive(Debug)]
 pub struct IdentityRequired {
     pub required: tls::client::ServerId,
@@ -357,18 +415,30 @@ impl std::fmt::Display for IdentityRequired {
 
 impl std::error::Error for IdentityRequired {}
 
+impl std::fmt::Display for UnauthorizedConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "connection to port {} not authorized", self.port)
+    }
+}
+
+impl std::error::Error for UnauthorizedConnection {}
+
 impl LabelError {
     fn reason(err: &(dyn std::error::Error + 'static)) -> Reason {
         if let Some(HttpError { reason, .. }) = err.downcast_ref::<HttpError>() {
             *reason
         } else if err.is::<ResponseTimeout>() {
             Reason::ResponseTimeout
+        } else if err.is::<PerTryTimeout>() {
+            Reason::PerTryTimeout
         } else if err.is::<FailFastError>() {
             Reason::FailFast
         } else if err.is::<tower::timeout::error::Elapsed>() {
             Reason::DispatchTimeout
         } else if err.is::<IdentityRequired>() {
             Reason::IdentityRequired
+        } else if err.is::<UnauthorizedConnection>() {
+            Reason::Unauthorized
         } else if let Some(e) = err.downcast_ref::<std::io::Error>() {
             Reason::Io(e.raw_os_error().map(Errno::from))
         } else if let Some(e) = err.source() {
@@ -387,22 +457,27 @@ impl metrics::LabelError<Error> for LabelError {
     }
 }
 
+impl Reason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Reason::FailFast => "failfast",
+            Reason::DispatchTimeout => "dispatch timeout",
+            Reason::ResponseTimeout => "response timeout",
+            Reason::PerTryTimeout => "per-try timeout",
+            Reason::RateLimited => "rate limited",
+            Reason::Unauthorized => "unauthorized",
+            Reason::IdentityRequired => "identity required",
+            Reason::GatewayLoop => "gateway loop",
+            Reason::NotFound => "not found",
+            Reason::Io(_) => "i/o",
+            Reason::Unexpected => "unexpected",
+        }
+    }
+}
+
 impl metrics::FmtLabels for Reason {
     fn fmt_labels(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "message=\"{}\"",
-            match self {
-                Reason::FailFast => "failfast",
-                Reason::DispatchTimeout => "dispatch timeout",
-                Reason::ResponseTimeout => "response timeout",
-                Reason::IdentityRequired => "identity required",
-                Reason::GatewayLoop => "gateway loop",
-                Reason::NotFound => "not found",
-                Reason::Io(_) => "i/o",
-                Reason::Unexpected => "unexpected",
-            }
-        )?;
+        write!(f, "message=\"{}\"", self.as_str())?;
 
         if let Reason::Io(Some(errno)) = self {
             write!(f, ",errno=\"{}\"", errno)?;
@@ -445,6 +520,24 @@ impl HttpError {
         }
     }
 
+    pub fn unauthorized(message: &'static str) -> Self {
+        Self {
+            message,
+            http: http::StatusCode::FORBIDDEN,
+            grpc: Code::PermissionDenied,
+            reason: Reason::Unauthorized,
+        }
+    }
+
+    pub fn rate_limited() -> Self {
+        Self {
+            message: "rate limit exceeded",
+            http: http::StatusCode::TOO_MANY_REQUESTS,
+            grpc: Code::ResourceExhausted,
+            reason: Reason::RateLimited,
+        }
+    }
+
     pub fn gateway_loop() -> Self {
         Self {
             message: "gateway loop detected",
@@ -466,3 +559,57 @@ impl std::fmt::Display for HttpError {
 }
 
 impl std::error::Error for HttpError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[derive(Debug)]
+    struct Wrapped(Error);
+
+    impl std::fmt::Display for Wrapped {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "wrapped: {}", self.0)
+        }
+    }
+
+    impl std::error::Error for Wrapped {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&*self.0)
+        }
+    }
+
+    #[test]
+    fn safe_to_retry() {
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert!(is_safe_to_retry(&refused));
+
+        let refused_stream = h2::Error::from(h2::Reason::REFUSED_STREAM);
+        assert!(is_safe_to_retry(&refused_stream));
+
+        let wrapped = Wrapped(refused.into());
+        assert!(is_safe_to_retry(&wrapped));
+    }
+
+    #[test]
+    fn unsafe_to_retry() {
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(!is_safe_to_retry(&reset));
+
+        let cancel = h2::Error::from(h2::Reason::CANCEL);
+        assert!(!is_safe_to_retry(&cancel));
+
+        let wrapped = Wrapped(reset.into());
+        assert!(!is_safe_to_retry(&wrapped));
+    }
+
+    #[test]
+    fn unauthorized_connection_reason() {
+        let err = UnauthorizedConnection { port: 8080 };
+        assert_eq!(LabelError::reason(&err), Reason::Unauthorized);
+
+        let wrapped = Wrapped(err.into());
+        assert_eq!(LabelError::reason(&wrapped), Reason::Unauthorized);
+    }
+}
@@ -1,10 +1,25 @@
+use crate::{
+    annotate::{Annotation, Annotations},
+    svc, tls, Conditional,
+};
+use futures::ready;
 use linkerd_error::Error;
 use linkerd_opencensus::proto::trace::v1 as oc;
-use linkerd_stack::layer;
+use linkerd_stack::layer::{self, Layer};
 use linkerd_trace_context::{self as trace_context, TraceContext};
-use std::{collections::HashMap, error, fmt, sync::Arc};
+use pin_project::pin_project;
+use std::{
+    collections::HashMap,
+    error, fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
 use tokio::sync::mpsc;
 
+pub use linkerd_trace_context::{Sampler, SpanAttributes};
+
 pub type OpenCensusSink = Option<mpsc::Sender<oc::Span>>;
 pub type Labels = Arc<HashMap<String, String>>;
 
@@ -19,6 +34,26 @@ pub struct SpanConverter {
     labels: Labels,
 }
 
+/// Describes a request's span with the request's annotations once its response
+/// is received.
+///
+/// Server spans describe the client, and client spans describe the endpoint;
+/// both describe the route.
+#[derive(Clone, Debug)]
+pub struct Annotated<S> {
+    inner: S,
+    kind: Kind,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct AnnotatedFuture<F> {
+    #[pin]
+    inner: F,
+    kind: Kind,
+    span: Option<(trace_context::SpanAttributes, Annotations)>,
+}
+
 #[derive(Debug)]
 pub struct IdLengthError {
     id: Vec<u8>,
@@ -38,18 +73,23 @@ impl fmt::Display for IdLengthError {
     }
 }
 
+/// Records server spans. The sampler decides whether traces that the proxy
+/// receives unsampled, or without a trace context, are sampled.
 pub fn server<S>(
     sink: OpenCensusSink,
+    sampler: Sampler,
     labels: impl Into<Labels>,
-) -> impl layer::Layer<S, Service = TraceContext<Option<SpanConverter>, S>> + Clone {
-    SpanConverter::layer(Kind::Server, sink, labels)
+) -> impl layer::Layer<S, Service = TraceContext<Option<SpanConverter>, Annotated<S>>> + Clone {
+    SpanConverter::layer(Kind::Server, sink, sampler, labels)
 }
 
+/// Records client spans. Sampling decisions are made by the server, so client
+/// spans are only recorded for sampled traces.
 pub fn client<S>(
     sink: OpenCensusSink,
     labels: impl Into<Labels>,
-) -> impl layer::Layer<S, Service = TraceContext<Option<SpanConverter>, S>> + Clone {
-    SpanConverter::layer(Kind::Client, sink, labels)
+) -> impl layer::Layer<S, Service = TraceContext<Option<SpanConverter>, Annotated<S>>> + Clone {
+    SpanConverter::layer(Kind::Client, sink, Sampler::default(), labels)
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -62,13 +102,18 @@ impl SpanConverter {
     fn layer<S>(
         kind: Kind,
         sink: OpenCensusSink,
+        sampler: Sampler,
         labels: impl Into<Labels>,
-    ) -> impl layer::Layer<S, Service = TraceContext<Option<Self>, S>> + Clone {
-        TraceContext::layer(sink.map(move |sink| Self {
-            kind,
-            sink,
-            labels: labels.into(),
-        }))
+    ) -> impl layer::Layer<S, Service = TraceContext<Option<Self>, Annotated<S>>> + Clone {
+        let trace = TraceContext::layer(
+            sink.map(move |sink| Self {
+                kind,
+                sink,
+                labels: labels.into(),
+            }),
+            sampler,
+        );
+        layer::mk(move |inner| trace.layer(Annotated { inner, kind }))
     }
 
     fn mk_span(&self, mut span: trace_context::Span) -> Result<oc::Span, IdLengthError> {
@@ -85,17 +130,23 @@ impl SpanConverter {
         }
         for (k, v) in span.labels.drain() {
             attributes.insert(
-                k.to_string(),
+                k,
                 oc::AttributeValue {
                     value: Some(oc::attribute_value::Value::StringValue(truncatable(v))),
                 },
             );
         }
+        // A span that starts a trace has no parent.
+        let parent_span_id = if span.parent_id.as_ref().is_empty() {
+            Vec::new()
+        } else {
+            into_bytes(span.parent_id, 8)?
+        };
         Ok(oc::Span {
             trace_id: into_bytes(span.trace_id, 16)?,
             span_id: into_bytes(span.span_id, 8)?,
             tracestate: None,
-            parent_span_id: into_bytes(span.parent_id, 8)?,
+            parent_span_id,
             name: Some(truncatable(span.span_name)),
             kind: self.kind as i32,
             start_time: Some(span.start.into()),
@@ -127,6 +178,102 @@ impl trace_context::SpanSink for SpanConverter {
     }
 }
 
+// === impl Kind ===
+
+impl Kind {
+    /// Describes the span's peer--the client, for a server span, or the
+    /// endpoint, for a client span--and the request's route.
+    fn annotate(self, attributes: &trace_context::SpanAttributes, annotation: Annotation) {
+        match self {
+            Kind::Server => {
+                if let Some(addr) = annotation.client_addr {
+                    attributes.insert("peer.addr", addr.to_string());
+                }
+                match annotation.client_tls {
+                    Some(Conditional::Some(tls::ServerTls::Established { client_id, .. })) => {
+                        attributes.insert("peer.tls", "true");
+                        if let Some(id) = client_id {
+                            attributes.insert("peer.id", id.to_string());
+                        }
+                    }
+                    Some(Conditional::Some(tls::ServerTls::Passthru { .. })) => {
+                        attributes.insert("peer.tls", "opaque");
+                    }
+                    Some(Conditional::None(reason)) => {
+                        attributes.insert("peer.tls", reason.to_string());
+                    }
+                    None => {}
+                }
+            }
+            Kind::Client => {
+                if let Some(addr) = annotation.endpoint_addr {
+                    attributes.insert("peer.addr", addr.to_string());
+                }
+                match annotation.endpoint_tls {
+                    Some(Conditional::Some(endpoint)) => {
+                        attributes.insert("peer.tls", "true");
+                        attributes.insert("peer.id", endpoint.server_id.to_string());
+                    }
+                    Some(Conditional::None(reason)) => {
+                        attributes.insert("peer.tls", reason.to_string());
+                    }
+                    None => {}
+                }
+                for (k, v) in annotation.endpoint_labels.iter().flat_map(|l| l.iter()) {
+                    attributes.insert(format!("dst.{}", k), v.clone());
+                }
+            }
+        }
+
+        for (k, v) in annotation.route.iter().flat_map(|l| l.iter()) {
+            attributes.insert(format!("rt.{}", k), v.clone());
+        }
+    }
+}
+
+// === impl Annotated ===
+
+impl<S, B> svc::Service<http::Request<B>> for Annotated<S>
+where
+    S: svc::Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = AnnotatedFuture<S::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        // Requests only have span attributes if their spans may be recorded.
+        let span = req
+            .extensions()
+            .get::<trace_context::SpanAttributes>()
+            .cloned()
+            .map(|attributes| (attributes, Annotations::get_or_insert(&mut req)));
+        AnnotatedFuture {
+            inner: self.inner.call(req),
+            kind: self.kind,
+            span,
+        }
+    }
+}
+
+impl<F: Future> Future for AnnotatedFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let out = ready!(this.inner.poll(cx));
+        if let Some((attributes, annotations)) = this.span.take() {
+            this.kind.annotate(&attributes, annotations.get());
+        }
+        Poll::Ready(out)
+    }
+}
+
 fn into_bytes(id: trace_context::Id, size: usize) -> Result<Vec<u8>, IdLengthError> {
     let bytes: Vec<u8> = id.into();
     if bytes.len() == size {
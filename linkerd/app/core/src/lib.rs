@@ -11,6 +11,7 @@
 
 pub use linkerd_addr::{self as addr, Addr, NameAddr};
 pub use linkerd_cache as cache;
+pub use linkerd_concurrency_limit as concurrency_limit;
 pub use linkerd_conditional::Conditional;
 pub use linkerd_detect as detect;
 pub use linkerd_dns;
@@ -18,6 +19,7 @@ pub use linkerd_drain as drain;
 pub use linkerd_error::{Error, Never, Recover};
 pub use linkerd_exp_backoff as exp_backoff;
 pub use linkerd_http_metrics as http_metrics;
+pub use linkerd_http_outlier as outlier;
 pub use linkerd_identity as identity;
 pub use linkerd_io as io;
 pub use linkerd_opencensus as opencensus;
@@ -29,8 +31,10 @@ pub use linkerd_tls as tls;
 pub use linkerd_tracing as trace;
 pub use linkerd_transport_header as transport_header;
 
+pub mod access_log;
 mod addr_match;
 pub mod admin;
+pub mod annotate;
 pub mod classify;
 pub mod config;
 pub mod control;
@@ -61,6 +65,8 @@ pub struct ProxyRuntime {
     pub metrics: metrics::Proxy,
     pub tap: proxy::tap::Registry,
     pub span_sink: http_tracing::OpenCensusSink,
+    pub trace_sampler: http_tracing::Sampler,
+    pub access_log: Option<access_log::AccessLog>,
     pub drain: drain::Watch,
 }
 
@@ -1,4 +1,5 @@
 pub use crate::{
+    access_log,
     classify::{Class, SuccessOrFailure},
     control, dst, errors, http_metrics, http_metrics as metrics, opencensus, proxy,
     proxy::identity,
@@ -21,10 +22,20 @@ pub type ControlHttp = http_metrics::Requests<ControlLabels, Class>;
 
 pub type HttpEndpoint = http_metrics::Requests<EndpointLabels, Class>;
 
+pub type HttpEndpointEjections = http_metrics::Ejections<EndpointLabels>;
+
+pub type HttpEndpointLimits = http_metrics::Limits<EndpointLabels>;
+
 pub type HttpRoute = http_metrics::Requests<RouteLabels, Class>;
 
 pub type HttpRouteRetry = http_metrics::Retries<RouteLabels>;
 
+pub type HttpRouteMirror = http_metrics::Mirrors<RouteLabels>;
+
+pub type HttpRateLimits = http_metrics::RateLimits<RateLimitLabels>;
+
+pub type HttpAuthz = http_metrics::Authz<AuthzLabels>;
+
 pub type Stack = stack_metrics::Registry<StackLabels>;
 
 #[derive(Clone, Debug)]
@@ -32,7 +43,12 @@ pub struct Proxy {
     pub http_route: HttpRoute,
     pub http_route_actual: HttpRoute,
     pub http_route_retry: HttpRouteRetry,
+    pub http_route_mirror: HttpRouteMirror,
     pub http_endpoint: HttpEndpoint,
+    pub http_endpoint_ejections: HttpEndpointEjections,
+    pub http_endpoint_limits: HttpEndpointLimits,
+    pub http_rate_limits: HttpRateLimits,
+    pub http_authz: HttpAuthz,
     pub http_errors: errors::MetricsLayer,
     pub stack: Stack,
     pub transport: transport::Metrics,
@@ -44,6 +60,7 @@ pub struct Metrics {
     pub outbound: Proxy,
     pub control: ControlHttp,
     pub opencensus: opencensus::metrics::Registry,
+    pub access_log: access_log::Metrics,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -87,6 +104,25 @@ pub struct RouteLabels {
     labels: Option<String>,
 }
 
+/// Identifies how the inbound proxy's rate limits are keyed.
+///
+/// Dropped requests are counted per scope rather than per key, since keys
+/// (e.g. client IPs) are unbounded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitLabels {
+    ClientId,
+    SourceIp,
+    Route,
+}
+
+/// Identifies the requests evaluated by an inbound authorization policy.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AuthzLabels {
+    pub target_addr: SocketAddr,
+    pub route: Option<String>,
+    pub tls: tls::ConditionalServerTls,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     In,
@@ -129,6 +165,33 @@ impl Metrics {
             (m, r)
         };
 
+        let (http_endpoint_ejections, ejections_report) = {
+            let m = metrics::Ejections::<EndpointLabels>::default();
+            let r = m.clone().into_report(retain_idle).with_prefix("endpoint");
+            (m, r)
+        };
+
+        let (http_endpoint_limits, limits_report) = {
+            let m = metrics::Limits::<EndpointLabels>::default();
+            let r = m.clone().into_report(retain_idle).with_prefix("endpoint");
+            (m, r)
+        };
+
+        let (http_rate_limits, rate_limits_report) = {
+            let m = metrics::RateLimits::<RateLimitLabels>::default();
+            let r = m.clone().into_report(retain_idle);
+            (m, r)
+        };
+
+        let (http_authz, authz_report) = {
+            let m = metrics::Authz::<AuthzLabels>::default();
+            let r = m
+                .clone()
+                .into_report(retain_idle)
+                .with_prefix("inbound_http");
+            (m, r)
+        };
+
         let (http_route, route_report) = {
             let m = metrics::Requests::<RouteLabels, Class>::default();
             let r = m.clone().into_report(retain_idle).with_prefix("route");
@@ -141,6 +204,12 @@ impl Metrics {
             (m, r)
         };
 
+        let (http_route_mirror, mirror_report) = {
+            let m = metrics::Mirrors::<RouteLabels>::default();
+            let r = m.clone().into_report(retain_idle).with_prefix("route");
+            (m, r)
+        };
+
         let (http_route_actual, actual_report) = {
             let m = metrics::Requests::<RouteLabels, Class>::default();
             let r = m
@@ -158,20 +227,32 @@ impl Metrics {
 
         let (opencensus, opencensus_report) = opencensus::metrics::new();
 
+        let (access_log, access_log_report) = access_log::metrics();
+
         let metrics = Metrics {
             inbound: Proxy {
                 http_endpoint: http_endpoint.clone(),
+                http_endpoint_ejections: http_endpoint_ejections.clone(),
+                http_endpoint_limits: http_endpoint_limits.clone(),
+                http_rate_limits: http_rate_limits.clone(),
+                http_authz: http_authz.clone(),
                 http_route: http_route.clone(),
                 http_route_actual: http_route_actual.clone(),
                 http_route_retry: http_route_retry.clone(),
+                http_route_mirror: http_route_mirror.clone(),
                 http_errors: http_errors.inbound(),
                 stack: stack.clone(),
                 transport: transport.clone(),
             },
             outbound: Proxy {
                 http_endpoint,
+                http_endpoint_ejections,
+                http_endpoint_limits,
+                http_rate_limits,
+                http_authz,
                 http_route,
                 http_route_retry,
+                http_route_mirror,
                 http_route_actual,
                 http_errors: http_errors.outbound(),
                 stack: stack.clone(),
@@ -179,16 +260,23 @@ impl Metrics {
             },
             control,
             opencensus,
+            access_log,
         };
 
         let report = (http_errors.report())
             .and_then(endpoint_report)
+            .and_then(ejections_report)
+            .and_then(limits_report)
+            .and_then(rate_limits_report)
+            .and_then(authz_report)
             .and_then(route_report)
             .and_then(retry_report)
+            .and_then(mirror_report)
             .and_then(actual_report)
             .and_then(control_report)
             .and_then(transport_report)
             .and_then(opencensus_report)
+            .and_then(access_log_report)
             .and_then(stack)
             .and_then(process)
             .and_then(build_info);
@@ -242,6 +330,34 @@ impl FmtLabels for RouteLabels {
     }
 }
 
+// === impl RateLimitLabels ===
+
+impl FmtLabels for RateLimitLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scope = match self {
+            Self::ClientId => "client_id",
+            Self::SourceIp => "source_ip",
+            Self::Route => "route",
+        };
+        Direction::In.fmt_labels(f)?;
+        write!(f, ",scope=\"{}\"", scope)
+    }
+}
+
+// === impl AuthzLabels ===
+
+impl FmtLabels for AuthzLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "target_addr=\"{}\",", self.target_addr)?;
+
+        if let Some(route) = self.route.as_ref() {
+            write!(f, "route=\"{}\",", route)?;
+        }
+
+        TlsAccept::from(&self.tls).fmt_labels(f)
+    }
+}
+
 // === impl EndpointLabels ===
 
 impl From<InboundEndpointLabels> for EndpointLabels {
@@ -1,24 +1,94 @@
 use super::classify;
 use super::dst::Route;
+use super::errors;
 // use super::handle_time;
 use super::http_metrics::retries::Handle;
-use super::metrics::HttpRouteRetry;
-use crate::profiles;
-use futures::future;
+use super::metrics::{HttpRoute, HttpRouteRetry, RouteLabels};
+use crate::{profiles, proxy::http::BoxBody, Error};
+use futures::{future, TryFutureExt};
 use hyper::body::HttpBody;
+use linkerd_exp_backoff::ExponentialBackoff;
 use linkerd_http_classify::{Classify, ClassifyEos, ClassifyResponse};
-use linkerd_retry::NewRetryLayer;
-use linkerd_stack::Param;
+use linkerd_http_retry::ReplayBody;
+use linkerd_retry::{hedge, NewRetryLayer, PerTryTimeout, PolicyTimeout};
+use linkerd_stack::{layer, NewService, Param, Proxy};
+use linkerd_trace_context::SpanAttributes;
+use pin_project::pin_project;
+use std::collections::VecDeque;
+use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time;
 use tower::retry::budget::Budget;
 
-pub fn layer(metrics: HttpRouteRetry) -> NewRetryLayer<NewRetry> {
-    NewRetryLayer::new(NewRetry::new(metrics))
+/// The number of responses a route must record before its latency
+/// distribution is used to determine when requests are hedged.
+const HEDGE_MIN_LATENCY_SAMPLES: u64 = 100;
+
+/// The most response body data that is read to classify a response to a
+/// hedged request by its trailers.
+const HEDGE_MAX_CLASSIFY_BYTES: usize = 64 * 1024;
+
+pub fn layer(metrics: HttpRouteRetry) -> NewRetryLayer<NewRetry<ReplayBodies>> {
+    NewRetryLayer::new(NewRetry::new(metrics).clone_requests_via())
+}
+
+/// Buffers request bodies of up to `max_bytes` on retryable routes so that
+/// their requests may be retried.
+///
+/// Must be applied outside of the retry layer.
+pub fn replay_layer<N>(
+    max_bytes: usize,
+) -> impl tower::layer::Layer<N, Service = NewReplayBody<N>> + Clone {
+    layer::mk(move |inner| NewReplayBody { inner, max_bytes })
+}
+
+/// Hedges requests on retryable routes that configure hedging. Route
+/// latencies are read from `latencies` when a route hedges by latency quantile.
+///
+/// Must be applied outside of the retry layer, so that the original request
+/// and its hedge are each retried rather than each retry being hedged. Only
+/// requests without bodies are hedged, since a body may only be read by one
+/// request.
+pub fn hedge_layer<N>(
+    metrics: HttpRouteRetry,
+    latencies: HttpRoute,
+) -> impl tower::layer::Layer<N, Service = hedge::NewHedge<NewHedge, NewBoxResponse<N>>> + Clone {
+    let hedge = hedge::NewHedgeLayer::new(NewHedge {
+        metrics,
+        latencies,
+        _clone_request: PhantomData,
+    });
+    layer::mk(move |inner| tower::layer::Layer::layer(&hedge, NewBoxResponse(inner)))
 }
 
 pub trait CloneRequest<Req> {
     fn clone_request(req: &Req) -> Option<Req>;
+
+    /// Returns false if a clone of `req` may no longer be sent, e.g. because
+    /// its body could not be buffered.
+    fn can_replay(_: &Req) -> bool {
+        true
+    }
+}
+
+/// Clones requests whose bodies are buffered by a [`ReplayBody`].
+#[derive(Clone, Debug)]
+pub struct ReplayBodies(());
+
+#[derive(Clone, Debug)]
+pub struct NewReplayBody<N> {
+    inner: N,
+    max_bytes: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Replay<P> {
+    inner: P,
+    max_bytes: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -31,9 +101,64 @@ pub struct Retry<C = ()> {
     metrics: Handle,
     budget: Arc<Budget>,
     response_classes: profiles::http::ResponseClasses,
+    per_try_timeout: Option<Duration>,
+    backoff: Option<ExponentialBackoff>,
+    /// The number of times the request has been retried.
+    retries: u32,
+    _clone_request: PhantomData<C>,
+}
+
+/// Waits for a retry policy's backoff before a request is retried.
+#[pin_project]
+pub struct Backoff<C> {
+    #[pin]
+    sleep: Option<time::Sleep>,
+    policy: Option<Retry<C>>,
+}
+
+/// Boxes the bodies of responses to hedged requests so that a response's
+/// body may be read to classify it and then replayed.
+#[derive(Clone, Debug)]
+pub struct NewBoxResponse<N>(N);
+
+#[derive(Clone, Debug)]
+pub struct BoxResponse<P>(P);
+
+/// Replays the part of a response body that was read to classify the
+/// response before reading the rest of the body.
+#[derive(Default)]
+struct PeekedBody {
+    data: VecDeque<<BoxBody as HttpBody>::Data>,
+    error: Option<Error>,
+    trailers: Option<http::HeaderMap>,
+    rest: Option<BoxBody>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewHedge<C = ()> {
+    metrics: HttpRouteRetry,
+    latencies: HttpRoute,
+    _clone_request: PhantomData<C>,
+}
+
+pub struct Hedge<C = ()> {
+    metrics: Handle,
+    budget: Arc<Budget>,
+    delay: HedgeDelay,
+    response_classes: profiles::http::ResponseClasses,
     _clone_request: PhantomData<C>,
 }
 
+#[derive(Clone)]
+enum HedgeDelay {
+    After(Duration),
+    LatencyQuantile {
+        latencies: HttpRoute,
+        labels: RouteLabels,
+        quantile: f64,
+    },
+}
+
 impl NewRetry {
     pub fn new(metrics: HttpRouteRetry) -> Self {
         Self {
@@ -61,24 +186,36 @@ impl<C> linkerd_retry::NewPolicy<Route> for NewRetry<C> {
             metrics,
             budget: retries.budget().clone(),
             response_classes: route.route.response_classes().clone(),
+            per_try_timeout: route.route.per_try_timeout(),
+            backoff: route.route.retry_backoff().cloned(),
+            retries: 0,
             _clone_request: self._clone_request,
         })
     }
 }
 
-impl<C, A, B, E> linkerd_retry::Policy<http::Request<A>, http::Response<B>, E> for Retry<C>
+impl<C, A, B> linkerd_retry::Policy<http::Request<A>, http::Response<B>, Error> for Retry<C>
 where
     C: CloneRequest<http::Request<A>>,
 {
-    type Future = future::Ready<Self>;
+    type Future = Backoff<C>;
 
     fn retry(
         &self,
         req: &http::Request<A>,
-        result: Result<&http::Response<B>, &E>,
+        result: Result<&http::Response<B>, &Error>,
     ) -> Option<Self::Future> {
         let retryable = match result {
-            Err(_) => false,
+            // Attempts that exceed the per-try timeout may be retried, so long
+            // as the request as a whole has not timed out.
+            Err(e) if e.is::<PerTryTimeout>() => {
+                self.metrics.incr_per_try_timeout();
+                true
+            }
+            // Errors that indicate the request was never processed are safe to
+            // retry. Each attempt is dispatched through the balancer, so it is
+            // likely to be sent to a different endpoint.
+            Err(e) => errors::is_safe_to_retry(&**e),
             Ok(rsp) => classify::Request::from(self.response_classes.clone())
                 .classify(req)
                 .start(rsp)
@@ -91,13 +228,29 @@ where
             return None;
         }
 
+        if !C::can_replay(req) {
+            tracing::debug!("Request body could not be buffered; not retrying");
+            return None;
+        }
+
         let withdrew = self.budget.withdraw().is_ok();
         self.metrics.incr_retryable(withdrew);
         if !withdrew {
             return None;
         }
 
-        Some(future::ready(self.clone()))
+        let mut policy = self.clone();
+        policy.retries = self.retries.saturating_add(1);
+        if let Some(span) = req.extensions().get::<SpanAttributes>() {
+            span.insert("http.retries", policy.retries.to_string());
+        }
+        let sleep = self
+            .backoff
+            .map(|backoff| time::sleep(backoff.delay(self.retries)));
+        Some(Backoff {
+            sleep,
+            policy: Some(policy),
+        })
     }
 
     fn clone_request(&self, req: &http::Request<A>) -> Option<http::Request<A>> {
@@ -105,12 +258,181 @@ where
     }
 }
 
+impl<C> PolicyTimeout for Retry<C> {
+    fn per_try_timeout(&self) -> Option<Duration> {
+        self.per_try_timeout
+    }
+}
+
 impl<C> Clone for Retry<C> {
     fn clone(&self) -> Self {
         Self {
             metrics: self.metrics.clone(),
             budget: self.budget.clone(),
             response_classes: self.response_classes.clone(),
+            per_try_timeout: self.per_try_timeout,
+            backoff: self.backoff,
+            retries: self.retries,
+            _clone_request: self._clone_request,
+        }
+    }
+}
+
+// === impl Backoff ===
+
+impl<C> Future for Backoff<C> {
+    type Output = Retry<C>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Some(sleep) = this.sleep.as_pin_mut() {
+            futures::ready!(sleep.poll(cx));
+        }
+        Poll::Ready(this.policy.take().expect("polled after ready"))
+    }
+}
+
+// === impl NewHedge ===
+
+impl<C> linkerd_retry::NewPolicy<Route> for NewHedge<C> {
+    type Policy = Hedge<C>;
+
+    fn new_policy(&self, route: &Route) -> Option<Self::Policy> {
+        // Hedged requests are withdrawn from the retry budget, so only
+        // retryable routes may be hedged.
+        let retries = route.route.retries()?;
+        let delay = match route.route.hedge()? {
+            profiles::http::Hedge::After(delay) => HedgeDelay::After(*delay),
+            profiles::http::Hedge::LatencyQuantile(quantile) => HedgeDelay::LatencyQuantile {
+                latencies: self.latencies.clone(),
+                labels: route.param(),
+                quantile: *quantile,
+            },
+        };
+
+        let metrics = self.metrics.get_handle(route.param());
+        Some(Hedge {
+            metrics,
+            budget: retries.budget().clone(),
+            delay,
+            response_classes: route.route.response_classes().clone(),
+            _clone_request: self._clone_request,
+        })
+    }
+}
+
+// === impl Hedge ===
+
+impl<C, A> hedge::Policy<http::Request<A>, http::Response<BoxBody>> for Hedge<C>
+where
+    C: CloneRequest<http::Request<A>>,
+{
+    type Classify = Pin<Box<dyn Future<Output = (http::Response<BoxBody>, bool)> + Send + 'static>>;
+
+    fn delay(&self, _: &http::Request<A>) -> Option<Duration> {
+        match self.delay {
+            HedgeDelay::After(delay) => Some(delay),
+            HedgeDelay::LatencyQuantile {
+                ref latencies,
+                ref labels,
+                quantile,
+            } => latencies.latency_quantile(labels, quantile, HEDGE_MIN_LATENCY_SAMPLES),
+        }
+    }
+
+    fn clone_request(&self, req: &http::Request<A>) -> Option<http::Request<A>> {
+        C::clone_request(req)
+    }
+
+    fn can_hedge(&self) -> bool {
+        let withdrew = self.budget.withdraw().is_ok();
+        self.metrics.incr_hedge(withdrew);
+        withdrew
+    }
+
+    fn classify(&self, rsp: http::Response<BoxBody>) -> Self::Classify {
+        let classify = if !self.response_classes.is_empty() {
+            classify::Response::Profile(self.response_classes.clone())
+        } else if is_grpc(rsp.headers()) {
+            classify::Response::Grpc
+        } else {
+            classify::Response::Default
+        };
+
+        let eos = classify.start(&rsp);
+        if eos.awaits_trailers() {
+            return Box::pin(classify_trailers(eos, rsp));
+        }
+        let is_success = !eos.eos(None).is_failure();
+        Box::pin(future::ready((rsp, is_success)))
+    }
+}
+
+fn is_grpc(headers: &http::HeaderMap) -> bool {
+    headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/grpc"))
+        .unwrap_or(false)
+}
+
+/// Reads a response's body so that the response may be classified by its
+/// trailers. The response is returned with a body that replays what was read.
+///
+/// Responses whose bodies exceed `HEDGE_MAX_CLASSIFY_BYTES` are considered
+/// successful, since they are streaming data.
+async fn classify_trailers(
+    eos: classify::Eos,
+    rsp: http::Response<BoxBody>,
+) -> (http::Response<BoxBody>, bool) {
+    let (head, mut body) = rsp.into_parts();
+    let mut peeked = PeekedBody::default();
+    let mut len = 0;
+    while let Some(res) = body.data().await {
+        match res {
+            Ok(data) => {
+                len += bytes::Buf::remaining(&data);
+                peeked.data.push_back(data);
+                if len > HEDGE_MAX_CLASSIFY_BYTES {
+                    tracing::trace!("Response body is too large to classify");
+                    peeked.rest = Some(body);
+                    return (http::Response::from_parts(head, BoxBody::new(peeked)), true);
+                }
+            }
+            Err(error) => {
+                peeked.error = Some(error);
+                return (
+                    http::Response::from_parts(head, BoxBody::new(peeked)),
+                    false,
+                );
+            }
+        }
+    }
+
+    let is_success = match body.trailers().await {
+        Ok(trailers) => {
+            let is_success = !eos.eos(trailers.as_ref()).is_failure();
+            peeked.trailers = trailers;
+            is_success
+        }
+        Err(error) => {
+            peeked.error = Some(error);
+            false
+        }
+    };
+    (
+        http::Response::from_parts(head, BoxBody::new(peeked)),
+        is_success,
+    )
+}
+
+impl<C> Clone for Hedge<C> {
+    fn clone(&self) -> Self {
+        Self {
+            metrics: self.metrics.clone(),
+            budget: self.budget.clone(),
+            delay: self.delay.clone(),
+            response_classes: self.response_classes.clone(),
             _clone_request: self._clone_request,
         }
     }
@@ -128,6 +450,11 @@ impl<B: Default + HttpBody> CloneRequest<http::Request<B>> for () {
         *clone.headers_mut() = req.headers().clone();
         *clone.version_mut() = req.version();
 
+        // Retries are described on the request's span.
+        if let Some(span) = req.extensions().get::<SpanAttributes>() {
+            clone.extensions_mut().insert(span.clone());
+        }
+
         // // Count retries toward the request's total handle time.
         // if let Some(ext) = req.extensions().get::<handle_time::Tracker>() {
         //     clone.extensions_mut().insert(ext.clone());
@@ -136,3 +463,174 @@ impl<B: Default + HttpBody> CloneRequest<http::Request<B>> for () {
         Some(clone)
     }
 }
+
+impl<B> CloneRequest<http::Request<ReplayBody<B>>> for ReplayBodies {
+    fn clone_request(req: &http::Request<ReplayBody<B>>) -> Option<http::Request<ReplayBody<B>>> {
+        if req.body().is_capped() {
+            return None;
+        }
+
+        let mut clone = http::Request::new(req.body().clone());
+        *clone.method_mut() = req.method().clone();
+        *clone.uri_mut() = req.uri().clone();
+        *clone.headers_mut() = req.headers().clone();
+        *clone.version_mut() = req.version();
+
+        // Retries are described on the request's span.
+        if let Some(span) = req.extensions().get::<SpanAttributes>() {
+            clone.extensions_mut().insert(span.clone());
+        }
+
+        Some(clone)
+    }
+
+    fn can_replay(req: &http::Request<ReplayBody<B>>) -> bool {
+        !req.body().is_capped()
+    }
+}
+
+// === impl NewBoxResponse ===
+
+impl<T, N: NewService<T>> NewService<T> for NewBoxResponse<N> {
+    type Service = BoxResponse<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        BoxResponse(self.0.new_service(target))
+    }
+}
+
+// === impl BoxResponse ===
+
+impl<Req, P, S, B> Proxy<Req, S> for BoxResponse<P>
+where
+    P: Proxy<Req, S, Response = http::Response<B>>,
+    S: tower::Service<P::Request>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send + 'static,
+    B::Error: Into<Error>,
+{
+    type Request = P::Request;
+    type Response = http::Response<BoxBody>;
+    type Error = P::Error;
+    type Future = future::MapOk<P::Future, fn(http::Response<B>) -> http::Response<BoxBody>>;
+
+    fn proxy(&self, svc: &mut S, req: Req) -> Self::Future {
+        self.0.proxy(svc, req).map_ok(|rsp| rsp.map(BoxBody::new))
+    }
+}
+
+// === impl PeekedBody ===
+
+impl HttpBody for PeekedBody {
+    type Data = <BoxBody as HttpBody>::Data;
+    type Error = Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.data.is_empty()
+            && self.error.is_none()
+            && self.trailers.is_none()
+            && self
+                .rest
+                .as_ref()
+                .map(HttpBody::is_end_stream)
+                .unwrap_or(true)
+    }
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let this = self.get_mut();
+        if let Some(data) = this.data.pop_front() {
+            return Poll::Ready(Some(Ok(data)));
+        }
+        if let Some(error) = this.error.take() {
+            return Poll::Ready(Some(Err(error)));
+        }
+        match this.rest.as_mut() {
+            Some(rest) => Pin::new(rest).poll_data(cx),
+            None => Poll::Ready(None),
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        let this = self.get_mut();
+        match this.rest.as_mut() {
+            Some(rest) => Pin::new(rest).poll_trailers(cx),
+            None => Poll::Ready(Ok(this.trailers.take())),
+        }
+    }
+}
+
+// === impl NewReplayBody ===
+
+impl<N: NewService<Route>> NewService<Route> for NewReplayBody<N> {
+    type Service = Replay<N::Service>;
+
+    fn new_service(&mut self, route: Route) -> Self::Service {
+        // Bodies are only buffered when the route may be retried.
+        let max_bytes = if route.route.retries().is_some() {
+            self.max_bytes
+        } else {
+            0
+        };
+        let inner = self.inner.new_service(route);
+        Replay { inner, max_bytes }
+    }
+}
+
+// === impl Replay ===
+
+impl<B, P, S> Proxy<http::Request<B>, S> for Replay<P>
+where
+    B: HttpBody,
+    P: Proxy<http::Request<ReplayBody<B>>, S>,
+    S: tower::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = P::Error;
+    type Future = P::Future;
+
+    fn proxy(&self, svc: &mut S, req: http::Request<B>) -> Self::Future {
+        let max_bytes = self.max_bytes;
+        self.inner
+            .proxy(svc, req.map(|body| ReplayBody::new(body, max_bytes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn classifies_grpc_responses_by_trailers() {
+        let (mut tx, body) = hyper::Body::channel();
+        let rsp = http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "application/grpc")
+            .body(BoxBody::new(body))
+            .unwrap();
+        let eos = classify::Response::Grpc.start(&rsp);
+        assert!(eos.awaits_trailers());
+
+        tx.send_data("hello".into()).await.unwrap();
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", "14".parse().unwrap());
+        let send = tokio::spawn(async move { tx.send_trailers(trailers).await });
+        let (rsp, is_success) = classify_trailers(eos, rsp).await;
+        send.await.unwrap().unwrap();
+        assert!(!is_success, "UNAVAILABLE must be a failure");
+
+        // The body that was read to classify the response is replayed.
+        let mut body = rsp.into_body();
+        let data = body.data().await.unwrap().unwrap();
+        assert_eq!(bytes::Buf::chunk(&data), b"hello");
+        assert!(body.data().await.is_none());
+        let trailers = body.trailers().await.unwrap().unwrap();
+        assert_eq!(trailers["grpc-status"], "14");
+        assert!(body.is_end_stream());
+    }
+}
@@ -0,0 +1,574 @@
+//! Authorizes inbound connections and requests.
+//!
+//! Each inbound port may have a policy listing the clients that may connect
+//! to it. An authorization matches clients by their source network and either
+//! permits unauthenticated clients or requires a client identity that matches
+//! one of its patterns. HTTP routes (as named by the `route` label of a service
+//! profile route) may be further restricted with their own authorizations.
+//! Once a port restricts any routes, requests whose route can't be determined
+//! are denied.
+//!
+//! Ports without authorizations are not restricted. Authorization policies are
+//! part of each port's [`ServerPolicy`](crate::policy::ServerPolicy), so they
+//! may be updated while the proxy runs.
+
+use crate::{
+    policy,
+    target::{HttpAccept, TcpAccept},
+};
+use futures::{future, TryFutureExt};
+use linkerd_app_core::{
+    dst,
+    errors::{HttpError, UnauthorizedConnection},
+    metrics, svc, tls, Conditional, Error, IpMatch,
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    task::{Context, Poll},
+};
+use tracing::debug;
+
+#[derive(Clone, Debug, Default)]
+pub struct PortPolicy {
+    /// Authorizations for all connections to the port.
+    pub authorizations: Vec<Authorization>,
+
+    /// Additional authorizations for HTTP requests, keyed by route name.
+    pub routes: HashMap<String, Vec<Authorization>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Authorization {
+    pub networks: IpMatch,
+    pub clients: Clients,
+}
+
+#[derive(Clone, Debug)]
+pub enum Clients {
+    Unauthenticated,
+    Authenticated(Vec<IdentityMatch>),
+}
+
+/// Matches client identity names.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdentityMatch {
+    /// Matches any client identity.
+    Any,
+    Exact(String),
+    /// Matches names ending with `.{suffix}`.
+    Suffix(String),
+}
+
+/// Refuses opaque TCP connections that are not permitted by the port's policy.
+#[derive(Clone, Debug)]
+pub struct AuthorizeTcp(policy::Store);
+
+#[derive(Clone, Debug)]
+pub struct NewAuthorizeHttp<N> {
+    policies: policy::Store,
+    metrics: metrics::HttpAuthz,
+    inner: N,
+}
+
+/// Authorizes HTTP requests by their connection's port policy.
+#[derive(Clone, Debug)]
+pub struct AuthorizeHttp<S> {
+    inner: S,
+    policy: policy::Receiver,
+    accept: HttpAccept,
+    metrics: metrics::HttpAuthz,
+    handle: Option<metrics::http_metrics::authz::Handle>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewAuthorizeRoute<N> {
+    metrics: metrics::HttpAuthz,
+    inner: N,
+}
+
+/// Authorizes HTTP requests by their route's policy.
+#[derive(Clone, Debug)]
+pub struct AuthorizeRoute<P> {
+    inner: P,
+    policy: Option<RoutePolicy>,
+}
+
+#[derive(Clone, Debug)]
+struct RoutePolicy {
+    name: String,
+    metrics: metrics::HttpAuthz,
+}
+
+/// Describes a client for authorization.
+struct Client<'t> {
+    ip: IpAddr,
+    tls: &'t tls::ConditionalServerTls,
+}
+
+// === impl PortPolicy ===
+
+impl PortPolicy {
+    /// Returns whether the policy permits the client to use the port, or
+    /// `None` if the port is not restricted.
+    fn check_port(&self, client: &Client<'_>) -> Option<bool> {
+        if self.authorizations.is_empty() {
+            return None;
+        }
+        Some(permits(&self.authorizations, client))
+    }
+
+    /// Returns whether the policy permits the client to use the route, or
+    /// `None` if the route is not restricted.
+    fn check_route(&self, route: &str, client: &Client<'_>) -> Option<bool> {
+        let authzs = self.routes.get(route)?;
+        Some(permits(authzs, client))
+    }
+}
+
+fn permits(authzs: &[Authorization], client: &Client<'_>) -> bool {
+    authzs.iter().any(|a| a.permits(client))
+}
+
+// === impl AuthorizeTcp ===
+
+impl From<policy::Store> for AuthorizeTcp {
+    fn from(policies: policy::Store) -> Self {
+        Self(policies)
+    }
+}
+
+impl svc::stack::Predicate<TcpAccept> for AuthorizeTcp {
+    type Request = TcpAccept;
+
+    fn check(&mut self, accept: TcpAccept) -> Result<TcpAccept, Error> {
+        let port = accept.target_addr.port();
+        let client = Client {
+            ip: accept.client_addr.ip(),
+            tls: &accept.tls,
+        };
+        let permitted = self.0.get(port).borrow().authz.check_port(&client);
+        match permitted {
+            Some(false) => {
+                debug!(%port, tls = ?accept.tls, client.addr = %accept.client_addr, "Connection not authorized");
+                Err(UnauthorizedConnection { port }.into())
+            }
+            _ => Ok(accept),
+        }
+    }
+}
+
+// === impl Authorization ===
+
+impl Authorization {
+    fn permits(&self, client: &Client<'_>) -> bool {
+        if !self.networks.matches(client.ip) {
+            return false;
+        }
+
+        match self.clients {
+            Clients::Unauthenticated => true,
+            Clients::Authenticated(ref ids) => match client.tls {
+                Conditional::Some(tls::ServerTls::Established {
+                    client_id: Some(ref id),
+                    ..
+                }) => ids.iter().any(|m| m.matches(id.0.as_ref())),
+                _ => false,
+            },
+        }
+    }
+}
+
+// === impl IdentityMatch ===
+
+impl IdentityMatch {
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('.');
+        match self {
+            Self::Any => true,
+            Self::Exact(n) => n.trim_end_matches('.').eq_ignore_ascii_case(name),
+            Self::Suffix(sfx) => {
+                let sfx = sfx.trim_end_matches('.');
+                name.len() > sfx.len() + 1 && {
+                    let (hd, tl) = name.split_at(name.len() - sfx.len());
+                    hd.ends_with('.') && tl.eq_ignore_ascii_case(sfx)
+                }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for IdentityMatch {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        if s == "*" {
+            return Ok(Self::Any);
+        }
+        if let Some(sfx) = s.strip_prefix("*.") {
+            if sfx.is_empty() || sfx.contains('*') {
+                return Err(());
+            }
+            return Ok(Self::Suffix(sfx.to_string()));
+        }
+        if s.is_empty() || s.contains('*') {
+            return Err(());
+        }
+        Ok(Self::Exact(s.to_string()))
+    }
+}
+
+// === impl NewAuthorizeHttp ===
+
+impl<N> NewAuthorizeHttp<N> {
+    pub fn layer(
+        policies: policy::Store,
+        metrics: metrics::HttpAuthz,
+    ) -> impl svc::layer::Layer<N, Service = Self> + Clone {
+        svc::layer::mk(move |inner| Self {
+            policies: policies.clone(),
+            metrics: metrics.clone(),
+            inner,
+        })
+    }
+}
+
+impl<N> svc::NewService<HttpAccept> for NewAuthorizeHttp<N>
+where
+    N: svc::NewService<HttpAccept>,
+{
+    type Service = AuthorizeHttp<N::Service>;
+
+    fn new_service(&mut self, accept: HttpAccept) -> Self::Service {
+        AuthorizeHttp {
+            policy: self.policies.get(accept.tcp.target_addr.port()),
+            metrics: self.metrics.clone(),
+            handle: None,
+            inner: self.inner.new_service(accept.clone()),
+            accept,
+        }
+    }
+}
+
+// === impl AuthorizeHttp ===
+
+impl<S> AuthorizeHttp<S> {
+    /// Checks the connection against the port's current policy.
+    fn check(&mut self) -> Result<(), Error> {
+        let client = Client {
+            ip: self.accept.tcp.client_addr.ip(),
+            tls: &self.accept.tcp.tls,
+        };
+        let permitted = match self.policy.borrow().authz.check_port(&client) {
+            Some(permitted) => permitted,
+            None => return Ok(()),
+        };
+
+        let (accept, metrics) = (&self.accept, &self.metrics);
+        let handle = self.handle.get_or_insert_with(|| {
+            metrics.get_handle(metrics::AuthzLabels {
+                target_addr: accept.tcp.target_addr,
+                route: None,
+                tls: accept.tcp.tls.clone(),
+            })
+        });
+        if !permitted {
+            handle.incr_deny();
+            debug!("Request not authorized");
+            return Err(HttpError::unauthorized("unauthorized connection").into());
+        }
+        handle.incr_allow();
+        Ok(())
+    }
+}
+
+impl<S, B> svc::Service<http::Request<B>> for AuthorizeHttp<S>
+where
+    S: svc::Service<http::Request<B>>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::ErrInto<S::Future, Error>,
+        future::Ready<Result<S::Response, Error>>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        if let Err(e) = self.check() {
+            return future::Either::Right(future::err(e));
+        }
+        // Shares the connection's policy with route authorization, so that the
+        // policy is not looked up for each request.
+        req.extensions_mut().insert(self.policy.clone());
+        future::Either::Left(self.inner.call(req).err_into())
+    }
+}
+
+// === impl NewAuthorizeRoute ===
+
+impl<N> NewAuthorizeRoute<N> {
+    pub fn layer(metrics: metrics::HttpAuthz) -> impl svc::layer::Layer<N, Service = Self> + Clone {
+        svc::layer::mk(move |inner| Self {
+            metrics: metrics.clone(),
+            inner,
+        })
+    }
+}
+
+impl<N> svc::NewService<dst::Route> for NewAuthorizeRoute<N>
+where
+    N: svc::NewService<dst::Route>,
+{
+    type Service = AuthorizeRoute<N::Service>;
+
+    fn new_service(&mut self, route: dst::Route) -> Self::Service {
+        let policy = route.route.labels().get("route").map(|name| RoutePolicy {
+            name: name.clone(),
+            metrics: self.metrics.clone(),
+        });
+        AuthorizeRoute {
+            inner: self.inner.new_service(route),
+            policy,
+        }
+    }
+}
+
+// === impl AuthorizeRoute ===
+
+impl<P> AuthorizeRoute<P> {
+    /// Checks the request against its route's policy.
+    ///
+    /// Once the port restricts routes, requests are denied unless their route
+    /// and client are known, so that they can't bypass the restriction (e.g.
+    /// when the profile that names the route is unavailable).
+    fn check<B>(&self, req: &http::Request<B>) -> Result<(), Error> {
+        // The connection's policy is set on each request by `AuthorizeHttp`.
+        let port_policy = match req.extensions().get::<policy::Receiver>() {
+            Some(port_policy) => port_policy.borrow(),
+            None => {
+                debug!("Request has no port policy");
+                return Err(HttpError::unauthorized("unauthorized route").into());
+            }
+        };
+        if port_policy.authz.routes.is_empty() {
+            return Ok(());
+        }
+
+        // The connection's target is set on each request by the server stack.
+        let (policy, accept) = match (self.policy.as_ref(), req.extensions().get::<HttpAccept>()) {
+            (Some(policy), Some(accept)) => (policy, accept),
+            (policy, _) => {
+                debug!(
+                    route = ?policy.map(|p| &p.name),
+                    "Request not authorized on a port with route policies"
+                );
+                return Err(HttpError::unauthorized("unauthorized route").into());
+            }
+        };
+
+        let client = Client {
+            ip: accept.tcp.client_addr.ip(),
+            tls: &accept.tcp.tls,
+        };
+        let target_addr: SocketAddr = accept.tcp.target_addr;
+        let permitted = match port_policy.authz.check_route(&policy.name, &client) {
+            Some(permitted) => permitted,
+            None => return Ok(()),
+        };
+
+        let metrics = policy.metrics.get_handle(metrics::AuthzLabels {
+            target_addr,
+            route: Some(policy.name.clone()),
+            tls: accept.tcp.tls.clone(),
+        });
+        if !permitted {
+            metrics.incr_deny();
+            debug!(route = %policy.name, "Request not authorized");
+            return Err(HttpError::unauthorized("unauthorized route").into());
+        }
+        metrics.incr_allow();
+        Ok(())
+    }
+}
+
+impl<P, S, B> svc::stack::Proxy<http::Request<B>, S> for AuthorizeRoute<P>
+where
+    P: svc::stack::Proxy<http::Request<B>, S>,
+    S: svc::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::ErrInto<P::Future, Error>,
+        future::Ready<Result<P::Response, Error>>,
+    >;
+
+    fn proxy(&self, svc: &mut S, req: http::Request<B>) -> Self::Future {
+        if let Err(e) = self.check(&req) {
+            return future::Either::Right(future::err(e));
+        }
+        future::Either::Left(self.inner.proxy(svc, req).err_into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_app_core::tls::ClientId;
+    use std::str::FromStr;
+
+    fn authenticated(id: &str) -> tls::ConditionalServerTls {
+        Conditional::Some(tls::ServerTls::Established {
+            client_id: Some(ClientId::from_str(id).unwrap()),
+            negotiated_protocol: None,
+        })
+    }
+
+    fn all_networks() -> IpMatch {
+        IpMatch::new(vec!["0.0.0.0/0".parse().unwrap(), "::/0".parse().unwrap()])
+    }
+
+    #[test]
+    fn identity_patterns() {
+        let sfx =
+            IdentityMatch::from_str("*.ns.serviceaccount.identity.linkerd.cluster.local").unwrap();
+        assert!(sfx.matches("web.ns.serviceaccount.identity.linkerd.cluster.local"));
+        assert!(!sfx.matches("ns.serviceaccount.identity.linkerd.cluster.local"));
+        assert!(!sfx.matches("web.other.serviceaccount.identity.linkerd.cluster.local"));
+
+        let exact = IdentityMatch::from_str("web.ns.serviceaccount.identity.linkerd.cluster.local")
+            .unwrap();
+        assert!(exact.matches("web.ns.serviceaccount.identity.linkerd.cluster.local"));
+        assert!(!exact.matches("api.ns.serviceaccount.identity.linkerd.cluster.local"));
+
+        assert!(IdentityMatch::from_str("*").unwrap().matches("anything"));
+        assert!(IdentityMatch::from_str("web.*.local").is_err());
+    }
+
+    #[test]
+    fn port_and_route_policies() {
+        let web = IdentityMatch::from_str("web.ns.serviceaccount.identity.linkerd.cluster.local")
+            .unwrap();
+        let mut routes = HashMap::new();
+        routes.insert(
+            "admin".to_string(),
+            vec![Authorization {
+                networks: IpMatch::new(vec!["10.0.0.0/8".parse().unwrap()]),
+                clients: Clients::Authenticated(vec![IdentityMatch::Any]),
+            }],
+        );
+        let policy = PortPolicy {
+            authorizations: vec![Authorization {
+                networks: all_networks(),
+                clients: Clients::Authenticated(vec![web]),
+            }],
+            routes,
+        };
+
+        let web_tls = authenticated("web.ns.serviceaccount.identity.linkerd.cluster.local");
+        let web = Client {
+            ip: [10, 1, 2, 3].into(),
+            tls: &web_tls,
+        };
+        let api_tls = authenticated("api.ns.serviceaccount.identity.linkerd.cluster.local");
+        let api = Client {
+            ip: [10, 1, 2, 4].into(),
+            tls: &api_tls,
+        };
+        let no_tls = Conditional::None(tls::NoServerTls::NoClientHello);
+        let plain = Client {
+            ip: [10, 1, 2, 5].into(),
+            tls: &no_tls,
+        };
+
+        assert_eq!(policy.check_port(&web), Some(true));
+        assert_eq!(policy.check_port(&api), Some(false));
+        assert_eq!(policy.check_port(&plain), Some(false));
+        assert_eq!(PortPolicy::default().check_port(&plain), None);
+
+        assert_eq!(policy.check_route("admin", &web), Some(true));
+        assert_eq!(policy.check_route("admin", &plain), Some(false));
+        let outside = Client {
+            ip: [192, 168, 0, 1].into(),
+            tls: &web_tls,
+        };
+        assert_eq!(policy.check_route("admin", &outside), Some(false));
+        assert_eq!(policy.check_route("other", &web), None);
+    }
+
+    #[test]
+    fn route_policies_deny_unknown_routes() {
+        let mut routes = HashMap::new();
+        routes.insert(
+            "admin".to_string(),
+            vec![Authorization {
+                networks: all_networks(),
+                clients: Clients::Unauthenticated,
+            }],
+        );
+        let restricted = policy::Store::new(
+            policy::ServerPolicy {
+                authz: PortPolicy {
+                    authorizations: vec![],
+                    routes,
+                },
+                ..policy::ServerPolicy::default()
+            },
+            None,
+        );
+        let unrestricted = policy::Store::default();
+        let accept = HttpAccept {
+            tcp: TcpAccept {
+                target_addr: ([127, 0, 0, 1], 8080).into(),
+                client_addr: ([10, 1, 2, 3], 40000).into(),
+                tls: Conditional::None(tls::NoServerTls::NoClientHello),
+            },
+            version: linkerd_app_core::proxy::http::Version::Http1,
+        };
+        let req = |accept: Option<&HttpAccept>, policy: Option<policy::Receiver>| {
+            let mut req = http::Request::new(());
+            if let Some(accept) = accept {
+                req.extensions_mut().insert(accept.clone());
+            }
+            if let Some(policy) = policy {
+                req.extensions_mut().insert(policy);
+            }
+            req
+        };
+
+        let admin = AuthorizeRoute {
+            inner: (),
+            policy: Some(RoutePolicy {
+                name: "admin".to_string(),
+                metrics: metrics::HttpAuthz::default(),
+            }),
+        };
+        let unnamed = AuthorizeRoute {
+            inner: (),
+            policy: None,
+        };
+
+        assert!(admin
+            .check(&req(Some(&accept), Some(restricted.get(8080))))
+            .is_ok());
+        // Requests that can't be attributed to a route or a client are denied
+        // once the port restricts routes.
+        assert!(unnamed
+            .check(&req(Some(&accept), Some(restricted.get(8080))))
+            .is_err());
+        assert!(admin.check(&req(None, Some(restricted.get(8080)))).is_err());
+        // Requests without the connection's policy are always denied.
+        assert!(admin.check(&req(Some(&accept), None)).is_err());
+        assert!(unnamed.check(&req(None, None)).is_err());
+
+        assert!(unnamed
+            .check(&req(None, Some(unrestricted.get(8080))))
+            .is_ok());
+    }
+}
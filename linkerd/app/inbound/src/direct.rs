@@ -1,6 +1,6 @@
 use crate::{target::TcpEndpoint, Inbound};
 use linkerd_app_core::{
-    io,
+    access_log, io,
     proxy::identity::LocalCrtKey,
     svc::{self, stack::Param},
     tls,
@@ -155,6 +155,7 @@ impl<T> Inbound<T> {
                     .into_inner(),
             )
             .check_new_service::<ClientInfo, SensorIo<tls::server::Io<I>>>()
+            .push(access_log::NewTcpLog::layer(rt.access_log.clone()))
             .push(rt.metrics.transport.layer_accept())
             .instrument(|_: &ClientInfo| debug_span!("direct"))
             // Build a ClientInfo target for each accepted connection. Refuse the
@@ -236,6 +237,12 @@ impl svc::stack::Param<tls::server::Config> for WithTransportHeaderAlpn {
     }
 }
 
+impl svc::stack::Param<transport::metrics::Handshakes> for WithTransportHeaderAlpn {
+    fn param(&self) -> transport::metrics::Handshakes {
+        self.0.param()
+    }
+}
+
 impl svc::stack::Param<tls::LocalId> for WithTransportHeaderAlpn {
     fn param(&self) -> tls::LocalId {
         self.0.id().clone()
@@ -1,5 +1,7 @@
 use crate::{
     allow_discovery::AllowProfile,
+    authz,
+    rate_limit::{self, RateLimits},
     target::{self, HttpAccept, HttpEndpoint, Logical, RequestTarget, Target, TcpEndpoint},
     Inbound,
 };
@@ -8,9 +10,9 @@ pub use linkerd_app_core::proxy::http::{
     Version,
 };
 use linkerd_app_core::{
-    classify,
+    access_log, annotate, classify,
     config::{ProxyConfig, ServerConfig},
-    dst, errors, http_tracing, io, profiles,
+    dst, errors, http_tracing, io, metrics, profiles,
     proxy::{http, tap},
     reconnect,
     svc::{self, stack::Param},
@@ -75,7 +77,16 @@ impl<H> Inbound<H> {
                     .push(rt.metrics.http_errors.clone())
                     // Synthesizes responses for proxy errors.
                     .push(errors::layer())
-                    .push(http_tracing::server(rt.span_sink.clone(), trace_labels()))
+                    .push(http_tracing::server(
+                        rt.span_sink.clone(),
+                        rt.trace_sampler.clone(),
+                        trace_labels(),
+                    ))
+                    // Writes an access log record for each request, if enabled.
+                    .push(access_log::server(
+                        rt.access_log.clone(),
+                        metrics::Direction::In,
+                    ))
                     // Record when an HTTP/1 URI was in absolute form
                     .push(http::normalize_uri::MarkAbsoluteForm::layer())
                     .push(http::BoxRequest::layer())
@@ -125,6 +136,9 @@ where
             stack: connect,
         } = self;
 
+        // Token buckets shared by all connections, keyed by client or by route.
+        let rate_limits = RateLimits::new(config.rate_limit, rt.metrics.http_rate_limits.clone());
+
         // Creates HTTP clients for each inbound port & HTTP settings.
         let endpoint = connect
             .push(rt.metrics.transport.layer_connect())
@@ -163,11 +177,20 @@ where
                     // Sets the route as a request extension so that it can be used
                     // by tap.
                     .push_http_insert_target::<dst::Route>()
+                    // Describes the route in each request's access log record
+                    // and spans.
+                    .push(annotate::NewAnnotate::layer())
                     // Records per-route metrics.
                     .push(rt.metrics.http_route.to_layer::<classify::Response, _>())
                     // Sets the per-route response classifier as a request
                     // extension.
                     .push(classify::NewClassify::layer())
+                    // Limits the rate of requests to each route, if configured.
+                    .push(rate_limits.layer::<metrics::RouteLabels, _>())
+                    // Enforces route-specific authorization policies.
+                    .push(authz::NewAuthorizeRoute::layer(
+                        rt.metrics.http_authz.clone(),
+                    ))
                     .check_new_clone::<dst::Route>()
                     .push_map_target(target::route)
                     .into_inner(),
@@ -223,7 +246,18 @@ where
             .instrument_from_target()
             .push(svc::NewRouter::layer(RequestTarget::from))
             // Used by tap.
-            .push_http_insert_target::<HttpAccept>();
+            .push_http_insert_target::<HttpAccept>()
+            // Describes the client in each request's access log record and
+            // spans.
+            .push(annotate::NewAnnotate::layer())
+            // Limits the rate of requests from each client, if configured.
+            .push(rate_limits.layer::<rate_limit::Key, _>())
+            // Denies requests on connections that the port's authorization
+            // policy does not permit.
+            .push(authz::NewAuthorizeHttp::layer(
+                config.policies.clone(),
+                rt.metrics.http_authz.clone(),
+            ));
 
         Inbound {
             config,
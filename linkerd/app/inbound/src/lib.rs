@@ -6,9 +6,12 @@
 #![deny(warnings, rust_2018_idioms)]
 
 mod allow_discovery;
+pub mod authz;
 pub mod direct;
 pub mod http;
+pub mod policy;
 mod prevent_loop;
+pub mod rate_limit;
 mod require_identity;
 pub mod target;
 #[cfg(test)]
@@ -21,6 +24,7 @@ use self::{
     target::{HttpAccept, TcpAccept},
 };
 use linkerd_app_core::{
+    access_log,
     config::{ConnectConfig, ProxyConfig},
     detect, drain, io, metrics, profiles,
     proxy::tcp,
@@ -36,13 +40,13 @@ use tracing::debug_span;
 pub struct Config {
     pub allow_discovery: NameMatch,
     pub proxy: ProxyConfig,
-    pub require_identity_for_inbound_ports: RequireIdentityForPorts,
-    pub disable_protocol_detection_for_ports: SkipByPort,
+    pub policies: policy::Store,
     pub profile_idle_timeout: Duration,
+    pub rate_limit: Option<rate_limit::Config>,
 }
 
 #[derive(Clone, Debug)]
-pub struct SkipByPort(std::sync::Arc<indexmap::IndexSet<u16>>);
+pub struct SkipByPort(policy::Store);
 
 #[derive(Clone, Debug)]
 pub struct Inbound<S> {
@@ -192,8 +196,9 @@ where
         P::Error: Send,
         P::Future: Send,
     {
-        let disable_detect = self.config.disable_protocol_detection_for_ports.clone();
-        let require_id = self.config.require_identity_for_inbound_ports.clone();
+        let disable_detect = SkipByPort::from(self.config.policies.clone());
+        let require_id = RequireIdentityForPorts::from(self.config.policies.clone());
+        let authz = authz::AuthorizeTcp::from(self.config.policies.clone());
         let config = self.config.proxy.clone();
         self.clone()
             .push_http_router(profiles)
@@ -207,6 +212,7 @@ where
                     .push_tcp_forward(server_port)
                     .stack
                     .push_map_target(TcpEndpoint::from)
+                    .push_request_filter(authz.clone())
                     .into_inner(),
             ))
             .push_cache(config.cache_max_idle_age)
@@ -215,6 +221,10 @@ where
                 http::DetectHttp::default(),
             ))
             .push_request_filter(require_id)
+            // Writes an access log record for each connection, if enabled.
+            .push(access_log::NewTcpLog::layer(
+                self.runtime.access_log.clone(),
+            ))
             .push(self.runtime.metrics.transport.layer_accept())
             .push_map_target(TcpAccept::from)
             .push(tls::NewDetectTls::layer(
@@ -228,6 +238,10 @@ where
                     .push_tcp_forward(server_port)
                     .stack
                     .push_map_target(TcpEndpoint::from)
+                    .push_request_filter(authz)
+                    .push(access_log::NewTcpLog::layer(
+                        self.runtime.access_log.clone(),
+                    ))
                     .push(self.runtime.metrics.transport.layer_accept())
                     .push_map_target(TcpAccept::port_skipped)
                     .check_new_service::<listen::Addrs, I>()
@@ -249,9 +263,9 @@ where
 
 // === impl SkipByPort ===
 
-impl From<indexmap::IndexSet<u16>> for SkipByPort {
-    fn from(ports: indexmap::IndexSet<u16>) -> Self {
-        SkipByPort(ports.into())
+impl From<policy::Store> for SkipByPort {
+    fn from(policies: policy::Store) -> Self {
+        SkipByPort(policies)
     }
 }
 
@@ -259,7 +273,8 @@ impl svc::Predicate<listen::Addrs> for SkipByPort {
     type Request = svc::Either<listen::Addrs, listen::Addrs>;
 
     fn check(&mut self, t: listen::Addrs) -> Result<Self::Request, Error> {
-        if !self.0.contains(&t.target_addr().port()) {
+        let protocol = self.0.get(t.target_addr().port()).borrow().protocol;
+        if protocol == policy::Protocol::Detect {
             Ok(svc::Either::A(t))
         } else {
             Ok(svc::Either::B(t))
@@ -0,0 +1,161 @@
+//! Inbound port policies.
+//!
+//! Each inbound port has a [`ServerPolicy`] that determines whether protocol
+//! detection is performed on its connections, whether its clients must have
+//! an identity, and which clients are authorized to use it. Policies are
+//! published on watches so that they may be changed while the proxy runs:
+//! connections observe their port's policy when they are accepted, and HTTP
+//! requests are authorized against the policy as it is when they are received.
+
+use crate::authz;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::watch;
+
+/// Configures how an inbound port's connections are handled.
+#[derive(Clone, Debug, Default)]
+pub struct ServerPolicy {
+    pub protocol: Protocol,
+    pub require_identity: bool,
+    pub authz: authz::PortPolicy,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// The connection's protocol is detected.
+    Detect,
+    /// The connection is forwarded without protocol detection.
+    Opaque,
+}
+
+pub type Receiver = watch::Receiver<ServerPolicy>;
+
+/// Publishes the policy for each inbound port.
+///
+/// The set of ports is fixed when the store is created, so that policies can
+/// be looked up without locking. Other ports always use the default policy.
+#[derive(Clone, Debug)]
+pub struct Store {
+    default: Receiver,
+    ports: Arc<HashMap<u16, Port>>,
+}
+
+#[derive(Debug)]
+struct Port {
+    tx: watch::Sender<ServerPolicy>,
+    rx: Receiver,
+}
+
+// === impl Protocol ===
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::Detect
+    }
+}
+
+// === impl Store ===
+
+impl Store {
+    pub fn new(
+        default: ServerPolicy,
+        ports: impl IntoIterator<Item = (u16, ServerPolicy)>,
+    ) -> Self {
+        let ports = ports
+            .into_iter()
+            .map(|(port, policy)| {
+                let (tx, rx) = watch::channel(policy);
+                (port, Port { tx, rx })
+            })
+            .collect();
+        // The default policy is never updated, so its sender is dropped.
+        let (_, default) = watch::channel(default);
+        Self {
+            default,
+            ports: Arc::new(ports),
+        }
+    }
+
+    /// Returns the ports that have their own policies.
+    pub fn ports(&self) -> impl Iterator<Item = u16> + '_ {
+        self.ports.keys().copied()
+    }
+
+    /// Returns a watch on the port's policy.
+    ///
+    /// Callers should hold the returned watch for the lifetime of a connection
+    /// rather than looking up the policy for each request.
+    pub fn get(&self, port: u16) -> Receiver {
+        match self.ports.get(&port) {
+            Some(Port { rx, .. }) => rx.clone(),
+            None => self.default.clone(),
+        }
+    }
+
+    /// Sets the port's policy, notifying all of the port's watches.
+    ///
+    /// Updates to ports that the store does not know about are ignored.
+    pub fn update(&self, port: u16, policy: ServerPolicy) {
+        match self.ports.get(&port) {
+            Some(Port { tx, .. }) => {
+                tracing::debug!(%port, ?policy, "Updating inbound policy");
+                // The store holds a receiver, so the send cannot fail.
+                let _ = tx.send(policy);
+            }
+            None => tracing::warn!(%port, "Ignoring policy for unknown port"),
+        }
+    }
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self::new(ServerPolicy::default(), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_are_observed() {
+        let store = Store::new(
+            ServerPolicy::default(),
+            vec![(
+                8080,
+                ServerPolicy {
+                    protocol: Protocol::Opaque,
+                    ..ServerPolicy::default()
+                },
+            )],
+        );
+
+        let port = store.get(8080);
+        assert_eq!(port.borrow().protocol, Protocol::Opaque);
+        assert!(!port.borrow().require_identity);
+        let default = store.get(9090);
+        assert_eq!(default.borrow().protocol, Protocol::Detect);
+
+        store.update(
+            8080,
+            ServerPolicy {
+                require_identity: true,
+                ..ServerPolicy::default()
+            },
+        );
+        assert!(port.borrow().require_identity);
+        assert_eq!(port.borrow().protocol, Protocol::Detect);
+        assert!(store.get(8080).borrow().require_identity);
+
+        // Ports without their own policies are not updated.
+        store.update(
+            9090,
+            ServerPolicy {
+                require_identity: true,
+                ..ServerPolicy::default()
+            },
+        );
+        assert!(!default.borrow().require_identity);
+        assert!(!store.get(9090).borrow().require_identity);
+        assert_eq!(store.ports().collect::<Vec<_>>(), vec![8080]);
+    }
+}
@@ -0,0 +1,421 @@
+//! Limits the rate of inbound requests with token buckets.
+//!
+//! Each bucket is shared by all requests with the same key: the client's
+//! identity, its source IP, or the route it targets, depending on the
+//! configured [`Scope`]. A bucket holds up to `burst` tokens and is refilled
+//! at `requests_per_second`. Requests that find their bucket empty fail with a
+//! 429 (or a gRPC `RESOURCE_EXHAUSTED`) and are counted in the
+//! `rate_limit_dropped_total` metric, which is labeled by scope.
+
+use crate::target::HttpAccept;
+use futures::{future, TryFutureExt};
+use linkerd_app_core::{
+    errors::HttpError, metrics, svc, svc::stack::Param, tls, Conditional, Error,
+};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Configures inbound rate limiting.
+#[derive(Copy, Clone, Debug)]
+pub struct Config {
+    pub scope: Scope,
+    pub requests_per_second: u32,
+    pub burst: u32,
+}
+
+/// Determines which requests share a rate limit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// Requests are limited per client identity. Clients without an identity
+    /// are limited by their source IP.
+    ClientId,
+    SourceIp,
+    Route,
+}
+
+/// The values a target may provide to key rate limits.
+///
+/// Routes provide their `RouteLabels`, which convert into a `Key`.
+#[derive(Clone, Debug, Default)]
+pub struct Key {
+    client_id: Option<tls::ClientId>,
+    source_ip: Option<IpAddr>,
+    route: Option<metrics::RouteLabels>,
+}
+
+/// The token buckets shared by all rate-limited services.
+///
+/// Each service looks up its key's bucket when it is built, so requests only
+/// contend with other requests that share their key.
+#[derive(Clone, Debug)]
+pub struct RateLimits {
+    config: Option<Config>,
+    buckets: Arc<Mutex<Buckets>>,
+    dropped: Option<metrics::http_metrics::rate_limits::Handle>,
+}
+
+/// Builds a rate-limited service for each target, keyed by the target's
+/// `K`-typed parameter.
+#[derive(Debug)]
+pub struct NewRateLimit<K, N> {
+    limits: RateLimits,
+    inner: N,
+    _marker: PhantomData<fn() -> K>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RateLimit<S> {
+    inner: S,
+    bucket: Option<Arc<Bucket>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum BucketKey {
+    ClientId(tls::ClientId),
+    SourceIp(IpAddr),
+    Route(metrics::RouteLabels),
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<BucketKey, Arc<Bucket>>,
+    last_purge: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    key: BucketKey,
+    rate: f64,
+    burst: f64,
+    tokens: Mutex<Tokens>,
+    dropped: Option<metrics::http_metrics::rate_limits::Handle>,
+}
+
+#[derive(Debug)]
+struct Tokens {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+// === impl Config ===
+
+impl Config {
+    /// Returns how long an empty bucket takes to refill completely.
+    fn refill_time(&self) -> Duration {
+        Duration::from_secs_f64(f64::from(self.burst) / f64::from(self.requests_per_second))
+    }
+}
+
+// === impl Key ===
+
+impl Key {
+    fn bucket_key(&self, scope: Scope) -> Option<BucketKey> {
+        match scope {
+            Scope::ClientId => self
+                .client_id
+                .clone()
+                .map(BucketKey::ClientId)
+                .or_else(|| self.source_ip.map(BucketKey::SourceIp)),
+            Scope::SourceIp => self.source_ip.map(BucketKey::SourceIp),
+            Scope::Route => self.route.clone().map(BucketKey::Route),
+        }
+    }
+}
+
+impl Param<Key> for HttpAccept {
+    fn param(&self) -> Key {
+        let client_id = match self.tcp.tls {
+            Conditional::Some(tls::ServerTls::Established {
+                client_id: Some(ref id),
+                ..
+            }) => Some(id.clone()),
+            _ => None,
+        };
+        Key {
+            client_id,
+            source_ip: Some(self.tcp.client_addr.ip()),
+            route: None,
+        }
+    }
+}
+
+impl From<metrics::RouteLabels> for Key {
+    fn from(route: metrics::RouteLabels) -> Self {
+        Self {
+            route: Some(route),
+            ..Self::default()
+        }
+    }
+}
+
+// === impl RateLimits ===
+
+impl RateLimits {
+    pub fn new(config: Option<Config>, metrics: metrics::HttpRateLimits) -> Self {
+        let config = config.filter(|c| c.requests_per_second > 0);
+        let dropped = config.map(|c| metrics.get_handle(c.scope.into()));
+        Self {
+            config,
+            buckets: Default::default(),
+            dropped,
+        }
+    }
+
+    pub fn layer<K, N>(&self) -> impl svc::layer::Layer<N, Service = NewRateLimit<K, N>> + Clone {
+        let limits = self.clone();
+        svc::layer::mk(move |inner| NewRateLimit {
+            limits: limits.clone(),
+            inner,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the key's bucket, creating it if no other service holds it.
+    fn bucket(&self, config: Config, key: BucketKey) -> Arc<Bucket> {
+        let burst = f64::from(config.burst.max(1));
+        let rate = f64::from(config.requests_per_second);
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        buckets.purge(now, config.refill_time());
+
+        let dropped = &self.dropped;
+        buckets
+            .buckets
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(Bucket {
+                    key,
+                    rate,
+                    burst,
+                    tokens: Mutex::new(Tokens {
+                        tokens: burst,
+                        last_refill: now,
+                    }),
+                    dropped: dropped.clone(),
+                })
+            })
+            .clone()
+    }
+}
+
+impl From<Scope> for metrics::RateLimitLabels {
+    fn from(scope: Scope) -> Self {
+        match scope {
+            Scope::ClientId => Self::ClientId,
+            Scope::SourceIp => Self::SourceIp,
+            Scope::Route => Self::Route,
+        }
+    }
+}
+
+// === impl Buckets ===
+
+impl Buckets {
+    /// Drops buckets that are no longer held by any service and that would be
+    /// full, since they are indistinguishable from new buckets. This bounds
+    /// the number of buckets to the keys that are in use or have been seen
+    /// recently.
+    fn purge(&mut self, now: Instant, refill_time: Duration) {
+        if let Some(last) = self.last_purge {
+            if now.saturating_duration_since(last) < refill_time {
+                return;
+            }
+        }
+        self.last_purge = Some(now);
+        self.buckets
+            .retain(|_, b| Arc::strong_count(b) > 1 || !b.is_full(now));
+    }
+}
+
+// === impl Bucket ===
+
+impl Bucket {
+    /// Takes a token from the bucket, returning false if the bucket is empty.
+    fn acquire(&self) -> bool {
+        let mut tokens = self.tokens.lock().expect("rate limit bucket poisoned");
+        tokens.refill(Instant::now(), self.rate, self.burst);
+        if tokens.tokens < 1.0 {
+            drop(tokens);
+            if let Some(dropped) = self.dropped.as_ref() {
+                dropped.incr_dropped();
+            }
+            return false;
+        }
+        tokens.tokens -= 1.0;
+        true
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let mut tokens = self.tokens.lock().expect("rate limit bucket poisoned");
+        tokens.refill(now, self.rate, self.burst);
+        tokens.tokens >= self.burst
+    }
+}
+
+// === impl Tokens ===
+
+impl Tokens {
+    fn refill(&mut self, now: Instant, rate: f64, burst: f64) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        self.last_refill = now;
+    }
+}
+
+// === impl NewRateLimit ===
+
+impl<K, N: Clone> Clone for NewRateLimit<K, N> {
+    fn clone(&self) -> Self {
+        Self {
+            limits: self.limits.clone(),
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, K, N> svc::NewService<T> for NewRateLimit<K, N>
+where
+    T: Param<K>,
+    K: Into<Key>,
+    N: svc::NewService<T>,
+{
+    type Service = RateLimit<N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let limits = &self.limits;
+        let bucket = limits.config.and_then(|c| {
+            let key = target.param().into().bucket_key(c.scope)?;
+            Some(limits.bucket(c, key))
+        });
+        RateLimit {
+            inner: self.inner.new_service(target),
+            bucket,
+        }
+    }
+}
+
+// === impl RateLimit ===
+
+impl<S> RateLimit<S> {
+    fn check(&self) -> Result<(), Error> {
+        if let Some(bucket) = self.bucket.as_ref() {
+            if !bucket.acquire() {
+                debug!(key = ?bucket.key, "Rate limit exceeded");
+                return Err(HttpError::rate_limited().into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S, Req> svc::Service<Req> for RateLimit<S>
+where
+    S: svc::Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::ErrInto<S::Future, Error>,
+        future::Ready<Result<S::Response, Error>>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        if let Err(e) = self.check() {
+            return future::Either::Right(future::err(e));
+        }
+        future::Either::Left(self.inner.call(req).err_into())
+    }
+}
+
+impl<P, S, Req> svc::stack::Proxy<Req, S> for RateLimit<P>
+where
+    P: svc::stack::Proxy<Req, S>,
+    S: svc::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::ErrInto<P::Future, Error>,
+        future::Ready<Result<P::Response, Error>>,
+    >;
+
+    fn proxy(&self, svc: &mut S, req: Req) -> Self::Future {
+        if let Err(e) = self.check() {
+            return future::Either::Right(future::err(e));
+        }
+        future::Either::Left(self.inner.proxy(svc, req).err_into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time;
+
+    fn limits(burst: u32) -> (RateLimits, Config) {
+        let config = Config {
+            scope: Scope::SourceIp,
+            requests_per_second: 10,
+            burst,
+        };
+        (RateLimits::new(Some(config), Default::default()), config)
+    }
+
+    fn source(ip: [u8; 4]) -> BucketKey {
+        BucketKey::SourceIp(ip.into())
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn drops_requests_over_burst() {
+        time::pause();
+        let (limits, config) = limits(2);
+        let client = limits.bucket(config, source([10, 0, 0, 1]));
+        assert!(client.acquire());
+        assert!(client.acquire());
+        assert!(!client.acquire());
+
+        // Services for the same client share its bucket.
+        assert!(!limits.bucket(config, source([10, 0, 0, 1])).acquire());
+
+        // Other clients have their own buckets.
+        assert!(limits.bucket(config, source([10, 0, 0, 2])).acquire());
+
+        // Tokens are replenished at the configured rate.
+        time::advance(Duration::from_millis(100)).await;
+        assert!(client.acquire());
+        assert!(!client.acquire());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn purges_full_buckets() {
+        time::pause();
+        let (limits, config) = limits(2);
+        let _held = limits.bucket(config, source([10, 0, 0, 1]));
+        assert!(limits.bucket(config, source([10, 0, 0, 2])).acquire());
+        assert_eq!(limits.buckets.lock().unwrap().buckets.len(), 2);
+
+        // Full buckets are only dropped once no service holds them.
+        time::advance(Duration::from_secs(1)).await;
+        let _ = limits.bucket(config, source([10, 0, 0, 3]));
+        let buckets = limits.buckets.lock().unwrap();
+        assert!(buckets.buckets.contains_key(&source([10, 0, 0, 1])));
+        assert!(!buckets.buckets.contains_key(&source([10, 0, 0, 2])));
+        assert_eq!(buckets.buckets.len(), 2);
+    }
+}
@@ -1,13 +1,11 @@
-use crate::target::TcpAccept;
-use indexmap::IndexSet;
+use crate::{policy, target::TcpAccept};
 use linkerd_app_core::{svc::stack::Predicate, tls, Conditional, Error};
-use std::sync::Arc;
 
 /// A connection policy that fails connections that don't have a client identity
-/// if they target one of the configured local ports.
+/// if they target a port whose policy requires identity.
 #[derive(Clone, Debug)]
 pub struct RequireIdentityForPorts {
-    ports: Arc<IndexSet<u16>>,
+    policies: policy::Store,
 }
 
 #[derive(Debug)]
@@ -15,11 +13,9 @@ pub struct IdentityRequired(());
 
 // === impl RequireIdentityForPorts ===
 
-impl<T: IntoIterator<Item = u16>> From<T> for RequireIdentityForPorts {
-    fn from(ports: T) -> Self {
-        Self {
-            ports: Arc::new(ports.into_iter().collect()),
-        }
+impl From<policy::Store> for RequireIdentityForPorts {
+    fn from(policies: policy::Store) -> Self {
+        Self { policies }
     }
 }
 
@@ -28,7 +24,7 @@ impl Predicate<TcpAccept> for RequireIdentityForPorts {
 
     fn check(&mut self, meta: TcpAccept) -> Result<TcpAccept, Error> {
         let port = meta.target_addr.port();
-        let id_required = self.ports.contains(&port);
+        let id_required = self.policies.get(port).borrow().require_identity;
 
         tracing::debug!(%port, tls = ?meta.tls, %id_required);
         if id_required {
@@ -1,6 +1,6 @@
 use indexmap::IndexMap;
 use linkerd_app_core::{
-    classify, dst, http_request_authority_addr, http_request_host_addr,
+    annotate, classify, dst, http_request_authority_addr, http_request_host_addr,
     http_request_l5d_override_dst_addr, metrics, profiles,
     proxy::{http, tap},
     stack_tracing,
@@ -118,6 +118,17 @@ impl Param<http::normalize_uri::DefaultAuthority> for HttpAccept {
     }
 }
 
+impl Param<annotate::Annotation> for HttpAccept {
+    fn param(&self) -> annotate::Annotation {
+        annotate::Annotation {
+            target_addr: Some(self.tcp.target_addr),
+            client_addr: Some(self.tcp.client_addr),
+            client_tls: Some(self.tcp.tls.clone()),
+            ..Default::default()
+        }
+    }
+}
+
 // === impl HttpEndpoint ===
 
 impl Param<http::client::Settings> for HttpEndpoint {
@@ -1,4 +1,4 @@
-use crate::{Config, RequireIdentityForPorts, SkipByPort};
+use crate::Config;
 pub use futures::prelude::*;
 use linkerd_app_core::{
     config,
@@ -49,8 +49,8 @@ pub fn default_config(orig_dst: SocketAddr) -> Config {
             max_in_flight_requests: 10_000,
             detect_protocol_timeout: Duration::from_secs(10),
         },
-        require_identity_for_inbound_ports: RequireIdentityForPorts::from(None),
-        disable_protocol_detection_for_ports: SkipByPort::from(indexmap::IndexSet::default()),
+        policies: Default::default(),
+        rate_limit: None,
         profile_idle_timeout: Duration::from_millis(500),
     }
 }
@@ -64,6 +64,8 @@ pub fn runtime() -> (ProxyRuntime, drain::Signal) {
         metrics: metrics.outbound,
         tap,
         span_sink: None,
+        trace_sampler: Default::default(),
+        access_log: None,
         drain,
     };
     (runtime, drain_tx)
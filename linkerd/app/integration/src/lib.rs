@@ -158,6 +158,7 @@ pub mod proxy;
 pub mod server;
 pub mod tap;
 pub mod tcp;
+pub mod trace_collector;
 
 trait Io: AsyncRead + AsyncWrite {}
 impl<T: AsyncRead + AsyncWrite> Io for T {}
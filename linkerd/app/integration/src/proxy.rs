@@ -10,6 +10,7 @@ pub fn new() -> Proxy {
 pub struct Proxy {
     controller: Option<controller::Listening>,
     identity: Option<controller::Listening>,
+    trace_collector: Option<controller::Listening>,
 
     /// Inbound/outbound addresses helpful for mocking connections that do not
     /// implement `server::Listener`.
@@ -37,6 +38,7 @@ pub struct Listening {
 
     controller: controller::Listening,
     identity: Option<controller::Listening>,
+    trace_collector: Option<controller::Listening>,
 
     shutdown: Shutdown,
     terminated: oneshot::Receiver<()>,
@@ -63,6 +65,12 @@ impl Proxy {
         self
     }
 
+    /// Exports spans to a support OTLP trace collector.
+    pub fn trace_collector(mut self, c: controller::Listening) -> Self {
+        self.trace_collector = Some(c);
+        self
+    }
+
     pub fn inbound(mut self, s: server::Listening) -> Self {
         self.inbound = Some(s.addr);
         self.inbound_server = Some(s);
@@ -135,6 +143,7 @@ impl Listening {
             outbound_server,
             controller,
             identity,
+            trace_collector,
             thread,
             shutdown,
             terminated,
@@ -170,10 +179,17 @@ impl Listening {
             }
         };
 
+        let trace_collector = async move {
+            if let Some(srv) = trace_collector {
+                srv.join().await;
+            }
+        };
+
         tokio::join! {
             inbound,
             outbound,
             identity,
+            trace_collector,
             controller.join(),
         };
     }
@@ -191,6 +207,7 @@ async fn run(proxy: Proxy, mut env: TestEnv, random_ports: bool) -> Listening {
     let inbound = proxy.inbound;
     let outbound = proxy.outbound;
     let identity = proxy.identity;
+    let trace_collector = proxy.trace_collector;
 
     env.put(
         "LINKERD2_PROXY_DESTINATION_SVC_ADDR",
@@ -239,6 +256,17 @@ async fn run(proxy: Proxy, mut env: TestEnv, random_ports: bool) -> Listening {
         None
     };
 
+    if let Some(ref trace_collector) = trace_collector {
+        env.put(
+            "LINKERD2_PROXY_TRACE_COLLECTOR_SVC_ADDR",
+            trace_collector.addr.to_string(),
+        );
+        env.put(
+            app::env::ENV_TRACE_COLLECTOR_PROTOCOL,
+            "opentelemetry".to_owned(),
+        );
+    }
+
     if let Some(ports) = proxy.inbound_disable_ports_protocol_detection {
         let ports = ports
             .into_iter()
@@ -372,6 +400,7 @@ async fn run(proxy: Proxy, mut env: TestEnv, random_ports: bool) -> Listening {
 
         controller,
         identity,
+        trace_collector,
 
         shutdown: tx,
         terminated: term_rx,
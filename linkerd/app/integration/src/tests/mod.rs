@@ -6,4 +6,5 @@ mod profiles;
 mod shutdown;
 mod tap;
 mod telemetry;
+mod trace_collector;
 mod transparency;
@@ -345,3 +345,95 @@ async fn timeout() {
         }
     }
 }
+
+#[tokio::test]
+async fn mirrors_configured_route() {
+    let _trace = trace_init();
+
+    let host = "profiles.test.svc.cluster.local";
+    let srv = server::http1()
+        .route_fn("/load-profile", |_| {
+            Response::builder().status(201).body("".into()).unwrap()
+        })
+        .route_fn("/books", |_| {
+            Response::builder()
+                .status(200)
+                .body("books".into())
+                .unwrap()
+        })
+        .run()
+        .await;
+
+    // The mirror's responses are discarded, so its failures must not affect
+    // the primary responses.
+    let mirrored = Arc::new(AtomicUsize::new(0));
+    let counter = mirrored.clone();
+    let mirror = server::http1()
+        .route_fn("/books", move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Response::builder().status(500).body("".into()).unwrap()
+        })
+        .run()
+        .await;
+
+    let ctrl = controller::new_unordered();
+    let dst = format!("{}:{}", host, srv.addr.port());
+    ctrl.destination_tx(&dst).send_addr(srv.addr);
+    let mirror_dst = format!("mirror.test.svc.cluster.local:{}", mirror.addr.port());
+    ctrl.destination_tx(&mirror_dst).send_addr(mirror.addr);
+
+    // The profile is looked up by the server's IP address, so the mirror is
+    // configured for the profile's name.
+    let profile_tx = ctrl.profile_tx(srv.addr.to_string());
+    profile_tx.send(controller::profile(
+        vec![
+            controller::route()
+                .request_path("/load-profile")
+                .label("load_profile", "test"),
+            controller::route()
+                .request_path("/books")
+                .label("route", "books"),
+        ],
+        None,
+        vec![],
+        host,
+    ));
+
+    let mut env = TestEnv::default();
+    env.put(
+        app::env::ENV_DESTINATION_PROFILE_ROUTE_MIRRORS,
+        format!("{}#books={}", dst, mirror_dst),
+    );
+    let proxy = proxy::new()
+        .controller(ctrl.run().await)
+        .outbound(srv)
+        .run_with_test_env(env)
+        .await;
+    let client = client::http1(proxy.outbound, host);
+    let metrics = client::http1(proxy.metrics, "localhost");
+
+    // Poll metrics until we recognize the profile is loaded...
+    loop {
+        assert_eq!(client.get("/load-profile").await, "");
+        let m = metrics.get("/metrics").await;
+        if m.contains("rt_load_profile=\"test\"") {
+            break;
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+
+    let n = 10;
+    for _ in 0..n {
+        assert_eq!(client.get("/books").await, "books");
+    }
+
+    // Mirrored requests are dispatched in the background.
+    for _ in 0i32..50 {
+        if mirrored.load(Ordering::SeqCst) == n {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(mirrored.load(Ordering::SeqCst), n);
+}
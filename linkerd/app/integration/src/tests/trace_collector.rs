@@ -0,0 +1,161 @@
+use crate::*;
+use opentelemetry_proto::{common::v1 as common, trace::v1 as trace};
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_ID: &str = "00f067aa0ba902b7";
+
+#[tokio::test]
+async fn exports_spans_via_otlp() {
+    let _trace = trace_init();
+
+    let (collector, mut exports) = trace_collector::new();
+    // The collector is initially unavailable, so the batch must be retried.
+    let collector = collector
+        .fail(grpc::Status::unavailable("collector is starting"))
+        .run()
+        .await;
+
+    let srv = server::http1().route("/hello", "hello").run().await;
+    let proxy = proxy::new()
+        .inbound(srv)
+        .trace_collector(collector)
+        .run()
+        .await;
+    let client = client::http1(proxy.inbound, "tracing.test.svc.cluster.local");
+
+    let rsp = client
+        .request(
+            client
+                .request_builder("/hello")
+                .header("x-b3-traceid", TRACE_ID)
+                .header("x-b3-spanid", PARENT_ID)
+                .header("x-b3-sampled", "1"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(rsp.status(), 200);
+
+    let export = exports.next().await;
+    assert_eq!(export.resource_spans.len(), 1);
+    let resource_spans = &export.resource_spans[0];
+
+    let resource = resource_spans
+        .resource
+        .as_ref()
+        .expect("resource must be set");
+    assert!(resource.attributes.contains(&common::KeyValue {
+        key: "service.name".to_string(),
+        value: Some(common::AnyValue {
+            value: Some(common::any_value::Value::StringValue(
+                "linkerd-proxy".to_string()
+            )),
+        }),
+    }));
+
+    let spans = resource_spans
+        .instrumentation_library_spans
+        .iter()
+        .flat_map(|ils| ils.spans.iter())
+        .collect::<Vec<_>>();
+    // The inbound proxy records a span for the request that it serves and
+    // another for the request that it sends to the application.
+    assert_eq!(spans.len(), 2);
+    let span = spans
+        .iter()
+        .find(|s| s.kind == trace::span::SpanKind::Server as i32)
+        .expect("must have a server span");
+    let client = spans
+        .iter()
+        .find(|s| s.kind == trace::span::SpanKind::Client as i32)
+        .expect("must have a client span");
+    assert_eq!(client.parent_span_id, span.span_id);
+    assert_eq!(span.name, "/hello");
+    assert_eq!(hex(&span.trace_id), TRACE_ID);
+    assert_eq!(hex(&span.parent_span_id), PARENT_ID);
+    assert_eq!(span.span_id.len(), 8);
+    assert!(span.start_time_unix_nano <= span.end_time_unix_nano);
+    assert!(span.attributes.contains(&common::KeyValue {
+        key: "http.status_code".to_string(),
+        value: Some(common::AnyValue {
+            value: Some(common::any_value::Value::StringValue("200".to_string())),
+        }),
+    }));
+    // The span describes the client that sent the request.
+    assert!(span.attributes.iter().any(|kv| kv.key == "peer.addr"));
+    assert!(span.attributes.iter().any(|kv| kv.key == "peer.tls"));
+
+    // ensure panics from the server are propagated
+    proxy.join_servers().await;
+}
+
+#[tokio::test]
+async fn samples_new_traces() {
+    let _trace = trace_init();
+
+    let (collector, mut exports) = trace_collector::new();
+    let collector = collector.run().await;
+
+    // The proxy's sampling decision is propagated to the application.
+    let srv = server::http1()
+        .route_fn("/hello", |req| {
+            let sampled = req
+                .headers()
+                .get("traceparent")
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.ends_with("-01"))
+                .unwrap_or(false);
+            let status = if sampled {
+                StatusCode::OK
+            } else {
+                StatusCode::BAD_REQUEST
+            };
+            Response::builder()
+                .status(status)
+                .body(Default::default())
+                .unwrap()
+        })
+        .run()
+        .await;
+    let mut env = TestEnv::default();
+    env.put(app::env::ENV_TRACE_SAMPLING_PROBABILITY, "1".to_owned());
+    let proxy = proxy::new()
+        .inbound(srv)
+        .trace_collector(collector)
+        .run_with_test_env(env)
+        .await;
+    let client = client::http1(proxy.inbound, "tracing.test.svc.cluster.local");
+
+    let rsp = client
+        .request(client.request_builder("/hello"))
+        .await
+        .unwrap();
+    assert_eq!(rsp.status(), 200);
+
+    let export = exports.next().await;
+    let spans = export
+        .resource_spans
+        .iter()
+        .flat_map(|rs| rs.instrumentation_library_spans.iter())
+        .flat_map(|ils| ils.spans.iter())
+        .collect::<Vec<_>>();
+    assert_eq!(spans.len(), 2);
+    let span = spans
+        .iter()
+        .find(|s| s.kind == trace::span::SpanKind::Server as i32)
+        .expect("must have a server span");
+    assert_eq!(span.name, "/hello");
+    assert_eq!(span.trace_id.len(), 16);
+    assert_eq!(span.span_id.len(), 8);
+    // The proxy started the trace, so its server span is the root span.
+    assert!(span.parent_span_id.is_empty());
+    assert!(spans
+        .iter()
+        .any(|s| s.trace_id == span.trace_id && s.parent_span_id == span.span_id));
+
+    // ensure panics from the server are propagated
+    proxy.join_servers().await;
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
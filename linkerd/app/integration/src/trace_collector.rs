@@ -0,0 +1,71 @@
+use super::*;
+use opentelemetry_proto::collector::trace::v1 as pb;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tonic as grpc;
+
+/// Returns a support OTLP trace collector and a stream of the export requests
+/// that it receives.
+pub fn new() -> (Collector, Exports) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let collector = Collector {
+        failures: Arc::new(Mutex::new(VecDeque::new())),
+        tx,
+    };
+    (collector, Exports(rx))
+}
+
+#[derive(Clone, Debug)]
+pub struct Collector {
+    failures: Arc<Mutex<VecDeque<grpc::Status>>>,
+    tx: mpsc::UnboundedSender<pb::ExportTraceServiceRequest>,
+}
+
+#[derive(Debug)]
+pub struct Exports(mpsc::UnboundedReceiver<pb::ExportTraceServiceRequest>);
+
+impl Collector {
+    /// Fails the next export request with `status`.
+    pub fn fail(self, status: grpc::Status) -> Self {
+        self.failures.lock().unwrap().push_back(status);
+        self
+    }
+
+    pub async fn run(self) -> controller::Listening {
+        tracing::debug!("running support trace collector");
+        controller::run(
+            pb::trace_service_server::TraceServiceServer::new(self),
+            "support trace collector",
+            None,
+        )
+        .await
+    }
+}
+
+#[tonic::async_trait]
+impl pb::trace_service_server::TraceService for Collector {
+    async fn export(
+        &self,
+        req: grpc::Request<pb::ExportTraceServiceRequest>,
+    ) -> Result<grpc::Response<pb::ExportTraceServiceResponse>, grpc::Status> {
+        if let Some(status) = self.failures.lock().unwrap().pop_front() {
+            tracing::debug!(%status, "failing export");
+            return Err(status);
+        }
+
+        let _ = self.tx.send(req.into_inner());
+        Ok(grpc::Response::new(pb::ExportTraceServiceResponse {}))
+    }
+}
+
+impl Exports {
+    /// Waits for the collector to receive an export request.
+    pub async fn next(&mut self) -> pb::ExportTraceServiceRequest {
+        // Spans are batched for several seconds before they are exported.
+        tokio::time::timeout(Duration::from_secs(30), self.0.recv())
+            .await
+            .expect("timed out waiting for spans to be exported")
+            .expect("trace collector stopped")
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{tcp, Outbound};
 use linkerd_app_core::{
-    discovery_rejected, io, profiles, svc,
+    access_log, discovery_rejected, io, profiles, svc,
     transport::{listen, metrics::SensorIo},
     Error, IpMatch,
 };
@@ -55,6 +55,8 @@ impl<N> Outbound<N> {
                     .push_spawn_buffer(config.proxy.buffer_capacity),
             )
             .check_new_service::<tcp::Accept, SensorIo<I>>()
+            // Writes an access log record for each connection, if enabled.
+            .push(access_log::NewTcpLog::layer(rt.access_log.clone()))
             .push(rt.metrics.transport.layer_accept())
             .push_cache(config.proxy.cache_max_idle_age)
             .check_new_service::<tcp::Accept, I>()
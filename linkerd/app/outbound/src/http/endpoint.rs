@@ -1,7 +1,7 @@
 use super::{require_identity_on_endpoint::NewRequireIdentity, Endpoint};
 use crate::Outbound;
 use linkerd_app_core::{
-    classify, config, http_tracing,
+    annotate, classify, config, http_tracing,
     proxy::{http, tap},
     reconnect, svc, Error, CANONICAL_DST_HEADER, L5D_REQUIRE_ID,
 };
@@ -58,6 +58,9 @@ where
             .check_new::<Endpoint>()
             .push(tap::NewTapHttp::layer(rt.tap.clone()))
             .push(rt.metrics.http_endpoint.to_layer::<classify::Response, _>())
+            // Describes the endpoint in each request's access log record and
+            // spans.
+            .push(annotate::NewAnnotate::layer())
             .push_on_response(http_tracing::client(
                 rt.span_sink.clone(),
                 crate::trace_labels(),
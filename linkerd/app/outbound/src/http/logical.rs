@@ -1,7 +1,7 @@
-use super::{Concrete, Endpoint, Logical};
+use super::{mirror, Concrete, Endpoint, Logical};
 use crate::{resolve, stack_labels, Outbound};
 use linkerd_app_core::{
-    classify, config, profiles,
+    annotate, classify, concurrency_limit, config, outlier, profiles,
     proxy::{api_resolve::Metadata, core::Resolve, http},
     retry, svc, tls, Error, CANONICAL_DST_HEADER, DST_OVERRIDE_HEADER,
 };
@@ -48,8 +48,25 @@ impl<E> Outbound<E> {
         } = config.proxy;
         let watchdog = cache_max_idle_age * 2;
 
-        let stack = endpoint
+        let concrete = endpoint
             .clone()
+            // Ejects endpoints from the balancer while their responses
+            // indicate that they are failing.
+            .push(outlier::NewEject::<classify::Response, _, _>::layer(
+                config.outlier_detection,
+                rt.metrics.http_endpoint_ejections.clone(),
+            ))
+            // Stops sending requests to each endpoint while it is at its
+            // adaptive concurrency limit, if one is configured, so that the
+            // balancer prefers other endpoints.
+            .push(concurrency_limit::adaptive::NewAdaptiveLimit::<
+                classify::Response,
+                _,
+                _,
+            >::layer(
+                config.endpoint_concurrency_limit,
+                rt.metrics.http_endpoint_limits.clone(),
+            ))
             .push_on_response(
                 svc::layers()
                     .push(http::BoxRequest::layer())
@@ -67,17 +84,19 @@ impl<E> Outbound<E> {
             // If the balancer has been empty/unavailable, eagerly fail requests.
             // When the balancer is in failfast, spawn the service in a background
             // task so it becomes ready without new requests.
+            //
+            // Profiles may configure the balancer to consistently hash requests
+            // onto endpoints instead of balancing by load.
             .push(resolve::layer(resolve, watchdog))
+            .push(http::balance::MakeBalance::layer(
+                crate::EWMA_DEFAULT_RTT,
+                crate::EWMA_DECAY,
+            ))
             .push_on_response(
                 svc::layers()
-                    .push(http::balance::layer(
-                        crate::EWMA_DEFAULT_RTT,
-                        crate::EWMA_DECAY,
-                    ))
                     .push(rt.metrics.stack.layer(stack_labels("http", "balancer")))
                     .push(svc::layer::mk(svc::SpawnReady::new))
-                    .push(svc::FailFast::layer("HTTP Balancer", dispatch_timeout))
-                    .push(http::BoxResponse::layer()),
+                    .push(svc::FailFast::layer("HTTP Balancer", dispatch_timeout)),
             )
             .push(svc::MapErrLayer::new(Into::into))
             // Drives the initial resolution via the service's readiness.
@@ -100,7 +119,23 @@ impl<E> Outbound<E> {
                         tls::NoClientTls::NotProvidedByServiceDiscovery,
                     ))
                     .into_inner(),
-            ))
+            ));
+
+        // Mirrored requests are dispatched directly to a concrete service,
+        // bypassing the traffic split. The service is buffered so that it may
+        // be shared by the tasks that dispatch mirrored requests.
+        let mirror = concrete
+            .clone()
+            .push_on_response(
+                svc::layers()
+                    .push(svc::layer::mk(svc::SpawnReady::new))
+                    .push(rt.metrics.stack.layer(stack_labels("http", "mirror")))
+                    .push(svc::FailFast::layer("HTTP Mirror", dispatch_timeout))
+                    .push_spawn_buffer(buffer_capacity),
+            )
+            .into_inner();
+
+        let stack = concrete
             // Distribute requests over a distribution of balancers via a
             // traffic split.
             //
@@ -126,6 +161,18 @@ impl<E> Outbound<E> {
                     )
                     // Sets an optional retry policy.
                     .push(retry::layer(rt.metrics.http_route_retry.clone()))
+                    // Sets an optional hedging policy. The original request and
+                    // its hedge are retried independently. Both are dispatched
+                    // through the balancer, which tends to send the hedge to
+                    // another endpoint, since the original request adds to its
+                    // endpoint's load.
+                    .push(retry::hedge_layer(
+                        rt.metrics.http_route_retry.clone(),
+                        rt.metrics.http_route.clone(),
+                    ))
+                    // Buffers request bodies on retryable routes so that they
+                    // may be replayed.
+                    .push(retry::replay_layer(crate::RETRY_MAX_BODY_BYTES))
                     // Sets an optional request timeout.
                     .push(http::MakeTimeoutLayer::default())
                     // Records per-route metrics.
@@ -133,7 +180,17 @@ impl<E> Outbound<E> {
                     // Sets the per-route response classifier as a request
                     // extension.
                     .push(classify::NewClassify::layer())
+                    // Describes the route in each request's access log record
+                    // and spans.
+                    .push(annotate::NewAnnotate::layer())
                     .push_map_target(Logical::mk_route)
+                    // Sends a copy of a sample of the route's requests to the
+                    // route's mirror, if one is configured.
+                    .push(mirror::NewMirror::layer(
+                        mirror,
+                        rt.metrics.http_route_mirror.clone(),
+                        crate::MIRROR_MAX_BODY_BYTES,
+                    ))
                     .into_inner(),
             ))
             // Strips headers that may be set by this proxy and add an outbound
@@ -0,0 +1,389 @@
+//! Mirrors a sample of a route's requests to another destination.
+//!
+//! Mirrored requests are dispatched on a background task once the original
+//! request's body has been fully read, and their responses are discarded. The
+//! original request is never delayed or modified by mirroring: its body is
+//! streamed through unchanged while a copy is buffered. Requests with bodies
+//! that exceed the buffer limit are not mirrored.
+
+use super::Logical;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::ready;
+use linkerd_app_core::{
+    http_metrics::mirrors::Handle,
+    metrics::HttpRouteMirror,
+    profiles,
+    proxy::{
+        api_resolve::ConcreteAddr,
+        http::{self, HttpBody},
+    },
+    svc::{self, stack::Param},
+    Error,
+};
+use pin_project::pin_project;
+use rand::Rng;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::sync::oneshot;
+use tracing::{debug, trace, Instrument};
+
+#[derive(Clone, Debug)]
+pub struct NewMirror<N, M> {
+    inner: N,
+    new_mirror: M,
+    metrics: HttpRouteMirror,
+    max_body_bytes: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Mirror<P, M> {
+    inner: P,
+    shadow: Option<Shadow<M>>,
+}
+
+#[derive(Clone, Debug)]
+struct Shadow<M> {
+    service: M,
+    ratio: f32,
+    max_body_bytes: usize,
+    metrics: Handle,
+}
+
+/// Streams the inner body while buffering a copy of it for a mirrored request.
+#[pin_project]
+struct TeeBody<B> {
+    #[pin]
+    inner: B,
+    tee: Option<Tee>,
+}
+
+struct Tee {
+    buf: BytesMut,
+    max_body_bytes: usize,
+    tx: oneshot::Sender<Bytes>,
+}
+
+// === impl NewMirror ===
+
+impl<N, M: Clone> NewMirror<N, M> {
+    pub fn layer(
+        new_mirror: M,
+        metrics: HttpRouteMirror,
+        max_body_bytes: usize,
+    ) -> impl svc::layer::Layer<N, Service = Self> + Clone {
+        svc::layer::mk(move |inner| Self {
+            inner,
+            new_mirror: new_mirror.clone(),
+            metrics: metrics.clone(),
+            max_body_bytes,
+        })
+    }
+}
+
+impl<N, M> svc::NewService<(profiles::http::Route, Logical)> for NewMirror<N, M>
+where
+    N: svc::NewService<(profiles::http::Route, Logical)>,
+    M: svc::NewService<(Option<ConcreteAddr>, Logical)>,
+{
+    type Service = Mirror<N::Service, M::Service>;
+
+    fn new_service(&mut self, (route, logical): (profiles::http::Route, Logical)) -> Self::Service {
+        let shadow = route.mirror().map(|mirror| {
+            debug!(addr = %mirror.addr(), ratio = %mirror.ratio(), "Mirroring route");
+            let labels = Logical::mk_route((route.clone(), logical.clone())).param();
+            let service = self
+                .new_mirror
+                .new_service((Some(ConcreteAddr(mirror.addr().clone())), logical.clone()));
+            Shadow {
+                service,
+                ratio: mirror.ratio(),
+                max_body_bytes: self.max_body_bytes,
+                metrics: self.metrics.get_handle(labels),
+            }
+        });
+
+        let inner = self.inner.new_service((route, logical));
+        Mirror { inner, shadow }
+    }
+}
+
+// === impl Mirror ===
+
+impl<B, P, M, S> svc::stack::Proxy<http::Request<B>, S> for Mirror<P, M>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send + 'static,
+    B::Error: Into<Error>,
+    P: svc::stack::Proxy<http::Request<http::BoxBody>, S>,
+    S: svc::Service<P::Request>,
+    M: svc::Service<http::Request<http::BoxBody>, Response = http::Response<http::BoxBody>>,
+    M: Clone + Send + 'static,
+    M::Error: Into<Error>,
+    M::Future: Send,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = P::Error;
+    type Future = P::Future;
+
+    fn proxy(&self, svc: &mut S, req: http::Request<B>) -> Self::Future {
+        let req = match self.shadow.as_ref() {
+            Some(shadow) if shadow.sample() => shadow.mirror(req),
+            _ => req.map(http::BoxBody::new),
+        };
+        self.inner.proxy(svc, req)
+    }
+}
+
+// === impl Shadow ===
+
+impl<M> Shadow<M>
+where
+    M: svc::Service<http::Request<http::BoxBody>, Response = http::Response<http::BoxBody>>,
+    M: Clone + Send + 'static,
+    M::Error: Into<Error>,
+    M::Future: Send,
+{
+    fn sample(&self) -> bool {
+        self.ratio > 0.0 && rand::thread_rng().gen::<f32>() < self.ratio
+    }
+
+    /// Spawns a task that mirrors `req` once its body has been buffered,
+    /// returning the original request with its body instrumented to feed the
+    /// mirror.
+    fn mirror<B>(&self, req: http::Request<B>) -> http::Request<http::BoxBody>
+    where
+        B: HttpBody + Send + 'static,
+        B::Data: Send + 'static,
+        B::Error: Into<Error>,
+    {
+        if req.body().size_hint().lower() > self.max_body_bytes as u64 {
+            debug!("Request body too large to mirror");
+            self.metrics.incr_skipped();
+            return req.map(http::BoxBody::new);
+        }
+
+        let mut head = http::Request::new(());
+        *head.method_mut() = req.method().clone();
+        *head.uri_mut() = req.uri().clone();
+        *head.headers_mut() = req.headers().clone();
+        *head.version_mut() = req.version();
+
+        let (tx, rx) = oneshot::channel();
+        let req = if req.body().is_end_stream() {
+            let _ = tx.send(Bytes::new());
+            req.map(http::BoxBody::new)
+        } else {
+            let tee = Tee {
+                tx,
+                buf: BytesMut::new(),
+                max_body_bytes: self.max_body_bytes,
+            };
+            req.map(|inner| {
+                http::BoxBody::new(TeeBody {
+                    inner,
+                    tee: Some(tee),
+                })
+            })
+        };
+
+        let service = self.service.clone();
+        let metrics = self.metrics.clone();
+        tokio::spawn(
+            async move {
+                let body = match rx.await {
+                    Ok(body) => body,
+                    Err(_) => {
+                        debug!("Request body could not be buffered; skipping mirror");
+                        metrics.incr_skipped();
+                        return;
+                    }
+                };
+
+                trace!(body.len = body.len(), "Dispatching mirrored request");
+                let req = head.map(|()| http::BoxBody::new(hyper::Body::from(body)));
+                let rsp = match svc::ServiceExt::oneshot(service, req).await {
+                    Ok(rsp) => rsp,
+                    Err(e) => {
+                        let error: Error = e.into();
+                        debug!(%error, "Mirrored request failed");
+                        metrics.incr_mirrored(false);
+                        return;
+                    }
+                };
+
+                debug!(status = %rsp.status(), "Mirrored request completed");
+                metrics.incr_mirrored(!rsp.status().is_server_error());
+
+                // Drain the response so that the connection may be reused.
+                let mut body = rsp.into_body();
+                while let Some(Ok(_)) = body.data().await {}
+            }
+            .in_current_span(),
+        );
+
+        req
+    }
+}
+
+// === impl TeeBody ===
+
+impl<B> HttpBody for TeeBody<B>
+where
+    B: HttpBody,
+    B::Error: Into<Error>,
+{
+    type Data = Bytes;
+    type Error = Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let mut this = self.project();
+        match ready!(this.inner.as_mut().poll_data(cx)) {
+            Some(Ok(mut data)) => {
+                let bytes = data.copy_to_bytes(data.remaining());
+                if let Some(mut tee) = this.tee.take() {
+                    if tee.buf.len() + bytes.len() > tee.max_body_bytes {
+                        // Dropping the tee cancels the mirrored request.
+                        trace!("Request body exceeds the mirror buffer");
+                    } else {
+                        tee.buf.extend_from_slice(&bytes);
+                        if this.inner.is_end_stream() {
+                            tee.complete();
+                        } else {
+                            *this.tee = Some(tee);
+                        }
+                    }
+                }
+                Poll::Ready(Some(Ok(bytes)))
+            }
+            Some(Err(e)) => {
+                this.tee.take();
+                Poll::Ready(Some(Err(e.into())))
+            }
+            None => {
+                if let Some(tee) = this.tee.take() {
+                    tee.complete();
+                }
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::header::HeaderMap>, Self::Error>> {
+        self.project().inner.poll_trailers(cx).map_err(Into::into)
+    }
+
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// === impl Tee ===
+
+impl Tee {
+    fn complete(self) {
+        let _ = self.tx.send(self.buf.freeze());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_app_core::http_metrics::Mirrors;
+    use tokio::sync::mpsc;
+
+    type Mirrored = (http::Request<()>, Bytes);
+
+    fn shadow(
+        max_body_bytes: usize,
+    ) -> (
+        Shadow<
+            impl svc::Service<
+                    http::Request<http::BoxBody>,
+                    Response = http::Response<http::BoxBody>,
+                    Error = Error,
+                    Future = impl Send,
+                > + Clone
+                + Send
+                + 'static,
+        >,
+        mpsc::UnboundedReceiver<Mirrored>,
+    ) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let service = svc::mk(move |req: http::Request<http::BoxBody>| {
+            let tx = tx.clone();
+            async move {
+                let (parts, body) = req.into_parts();
+                let body = hyper::body::to_bytes(body).await?;
+                let _ = tx.send((http::Request::from_parts(parts, ()), body));
+                Ok::<_, Error>(http::Response::new(http::BoxBody::default()))
+            }
+        });
+        let shadow = Shadow {
+            service,
+            ratio: 1.0,
+            max_body_bytes,
+            metrics: Mirrors::<()>::default().get_handle(()),
+        };
+        (shadow, rx)
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn mirrors_buffered_body() {
+        let (shadow, mut mirrored) = shadow(8);
+
+        let req = http::Request::post("http://foo.example.com/bar")
+            .body(hyper::Body::from("hello"))
+            .unwrap();
+        let req = shadow.mirror(req);
+        drop(shadow);
+
+        // The original request is unchanged.
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(body, "hello");
+
+        let (head, body) = mirrored.recv().await.expect("request must be mirrored");
+        assert_eq!(head.method(), ::http::Method::POST);
+        assert_eq!(head.uri(), "http://foo.example.com/bar");
+        assert_eq!(body, "hello");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn does_not_mirror_bodies_over_limit() {
+        let (shadow, mut mirrored) = shadow(8);
+
+        let (mut tx, body) = hyper::Body::channel();
+        let req = shadow.mirror(
+            http::Request::post("http://foo.example.com")
+                .body(body)
+                .unwrap(),
+        );
+        drop(shadow);
+
+        tokio::spawn(async move {
+            tx.send_data(Bytes::from_static(b"hello")).await.unwrap();
+            tx.send_data(Bytes::from_static(b"world")).await.unwrap();
+        });
+
+        // The original request is unchanged.
+        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+        assert_eq!(body, "helloworld");
+
+        assert!(
+            mirrored.recv().await.is_none(),
+            "request must not be mirrored"
+        );
+    }
+}
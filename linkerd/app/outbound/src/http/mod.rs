@@ -1,6 +1,7 @@
 mod detect;
 mod endpoint;
 pub mod logical;
+mod mirror;
 mod require_identity_on_endpoint;
 mod server;
 
@@ -1,5 +1,5 @@
 use crate::{http, trace_labels, Outbound};
-use linkerd_app_core::{config, errors, http_tracing, svc, Error};
+use linkerd_app_core::{access_log, config, errors, http_tracing, metrics, svc, Error};
 use tracing::debug_span;
 
 impl<H, HSvc> Outbound<H>
@@ -54,7 +54,16 @@ where
                     // Synthesizes responses for proxy errors.
                     .push(errors::layer())
                     // Initiates OpenCensus tracing.
-                    .push(http_tracing::server(rt.span_sink.clone(), trace_labels()))
+                    .push(http_tracing::server(
+                        rt.span_sink.clone(),
+                        rt.trace_sampler.clone(),
+                        trace_labels(),
+                    ))
+                    // Writes an access log record for each request, if enabled.
+                    .push(access_log::server(
+                        rt.access_log.clone(),
+                        metrics::Direction::Out,
+                    ))
                     .push(http::BoxResponse::layer()),
             )
             // Convert origin form HTTP/1 URIs to absolute form for Hyper's
@@ -1,8 +1,9 @@
 use crate::{http, stack_labels, tcp, trace_labels, Config, Outbound};
 use linkerd_app_core::{
+    access_log,
     config::{ProxyConfig, ServerConfig},
     detect, discovery_rejected, drain, errors, http_request_l5d_override_dst_addr, http_tracing,
-    io, profiles, svc, tls,
+    io, metrics, profiles, svc, tls,
     transport::{self, listen},
     Addr, AddrMatch, Error,
 };
@@ -108,8 +109,15 @@ impl Outbound<()> {
                     .push(errors::layer())
                     .push(http_tracing::server(
                         self.runtime.span_sink.clone(),
+                        self.runtime.trace_sampler.clone(),
                         trace_labels(),
                     ))
+                    // Writes an access log record for each request, if enabled.
+                    .push(access_log::server(
+                        self.runtime.access_log.clone(),
+                        metrics::Direction::Out,
+                    ))
+                    .push(http::BoxRequest::layer())
                     .push(http::BoxResponse::layer()),
             )
             .check_new_service::<http::Accept, http::Request<_>>()
@@ -129,6 +137,9 @@ impl Outbound<()> {
                 http::DetectHttp::default(),
             ))
             .check_new_service::<tcp::Accept, transport::metrics::SensorIo<I>>()
+            .push(access_log::NewTcpLog::layer(
+                self.runtime.access_log.clone(),
+            ))
             .push(self.runtime.metrics.transport.layer_accept())
             .push_map_target(tcp::Accept::from)
             .check_new_service::<listen::Addrs, I>()
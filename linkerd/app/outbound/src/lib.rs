@@ -13,10 +13,12 @@ pub mod target;
 pub mod tcp;
 #[cfg(test)]
 pub(crate) mod test_util;
+pub mod tls_origination;
 
 use linkerd_app_core::{
+    concurrency_limit,
     config::ProxyConfig,
-    io, metrics, profiles,
+    io, metrics, outlier, profiles,
     proxy::{api_resolve::Metadata, core::Resolve},
     svc, tls,
     transport::listen,
@@ -27,10 +29,21 @@ use std::{collections::HashMap, time::Duration};
 const EWMA_DEFAULT_RTT: Duration = Duration::from_millis(30);
 const EWMA_DECAY: Duration = Duration::from_secs(10);
 
+/// The largest request body that is buffered so that it may be mirrored.
+const MIRROR_MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// The largest request body that is buffered so that it may be retried.
+const RETRY_MAX_BODY_BYTES: usize = 64 * 1024;
+
 #[derive(Clone, Debug)]
 pub struct Config {
     pub proxy: ProxyConfig,
     pub allow_discovery: AddrMatch,
+    pub outlier_detection: outlier::Config,
+    pub endpoint_concurrency_limit: concurrency_limit::adaptive::Config,
+
+    /// Configures the destinations to which TLS is originated, if any.
+    pub tls_origination: Option<tls_origination::Config>,
 }
 
 #[derive(Clone, Debug)]
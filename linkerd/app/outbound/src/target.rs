@@ -1,13 +1,14 @@
 use linkerd_app_core::{
-    metrics, profiles,
+    annotate, metrics, profiles,
     proxy::{
         api_resolve::{ConcreteAddr, Metadata},
+        http::balance::hash,
         resolve::map_endpoint::MapEndpoint,
     },
     svc::{self, stack::Param},
     tls, transport, transport_header, Addr, Conditional, Error,
 };
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 
 #[derive(Copy, Clone)]
 pub struct EndpointFromMetadata;
@@ -182,6 +183,32 @@ impl<P> Param<ConcreteAddr> for Concrete<P> {
     }
 }
 
+/// Selects the balancer's consistent hashing configuration, if any, from the
+/// logical target's profile at the time the balancer is built.
+impl<P> Param<Option<hash::Config>> for Concrete<P> {
+    fn param(&self) -> Option<hash::Config> {
+        self.logical
+            .profile
+            .as_ref()
+            .and_then(|p| p.borrow().consistent_hash.as_ref().map(hash_config))
+    }
+}
+
+fn hash_config(config: &profiles::consistent_hash::Config) -> hash::Config {
+    use profiles::consistent_hash::{Key, Table};
+    hash::Config {
+        key: match config.key {
+            Key::Header(ref name) => hash::Key::Header(name.clone()),
+            Key::Cookie(ref name) => hash::Key::Cookie(name.clone()),
+            Key::SourceIp => hash::Key::SourceIp,
+        },
+        table: match config.table {
+            Table::Ring => hash::Table::Ring,
+            Table::Maglev => hash::Table::Maglev,
+        },
+    }
+}
+
 // === impl Endpoint ===
 
 impl<P> Endpoint<P> {
@@ -257,6 +284,18 @@ impl<P> Param<metrics::EndpointLabels> for Endpoint<P> {
     }
 }
 
+impl<P> Param<annotate::Annotation> for Endpoint<P> {
+    fn param(&self) -> annotate::Annotation {
+        annotate::Annotation {
+            target_addr: Some(self.target_addr),
+            endpoint_addr: Some(self.addr),
+            endpoint_tls: Some(self.tls.clone()),
+            endpoint_labels: Some(Arc::new(self.metadata.labels().clone())),
+            ..Default::default()
+        }
+    }
+}
+
 impl<P: std::hash::Hash> std::hash::Hash for Endpoint<P> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.addr.hash(state);
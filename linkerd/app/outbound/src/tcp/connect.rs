@@ -1,7 +1,10 @@
 use super::opaque_transport::OpaqueTransport;
-use crate::{target::Endpoint, Outbound};
+use crate::{target::Endpoint, tls_origination::Originate, Outbound};
 use linkerd_app_core::{
-    io, svc, tls, transport::ConnectTcp, transport_header::SessionProtocol, Error,
+    io, svc, tls,
+    transport::{metrics::Handshakes, ConnectTcp},
+    transport_header::SessionProtocol,
+    Error,
 };
 use tracing::debug_span;
 
@@ -36,10 +39,28 @@ impl<C> Outbound<C> {
             stack: connect,
         } = self;
         let identity_disabled = rt.identity.is_none();
+        let tls_origination = config.tls_origination.clone();
+        // Handshakes are recorded in the identity's registered metrics, since
+        // they are not labeled by the client that initiated them.
+        let handshakes = rt
+            .identity
+            .as_ref()
+            .map(svc::stack::Param::<Handshakes>::param)
+            .unwrap_or_default();
 
         // TODO: We should prevent connections on the loopback interface; but
         // this is currently required by tests.
         let stack = connect
+            .push_map_target(|o: Originate<Endpoint<P>>| o.target)
+            // Originates TLS (verified with web PKI roots) if the target is not
+            // meshed and is configured for TLS origination.
+            .push(tls::Client::layer(
+                config
+                    .tls_origination
+                    .as_ref()
+                    .map(|c| c.client(handshakes)),
+            ))
+            .push_map_target(move |e: Endpoint<P>| Originate::new(tls_origination.as_ref(), e))
             // Initiates mTLS if the target is configured with identity. The
             // endpoint configures ALPN when there is an opaque transport hint OR
             // when an authority override is present (indicating the target is a
@@ -7,10 +7,10 @@ use crate::{
         },
         *,
     },
-    Config, Outbound,
+    tls_origination, Config, Outbound,
 };
 use linkerd_app_core::{
-    io, svc, svc::NewService, tls, transport::listen, Conditional, Error, IpMatch,
+    identity, io, svc, svc::NewService, tls, transport::listen, Conditional, Error, IpMatch,
 };
 use std::{
     future::Future,
@@ -151,6 +151,69 @@ async fn tls_when_hinted() {
     tokio::try_join!(plain, tls).expect("neither connection should fail");
 }
 
+#[tokio::test]
+async fn originates_tls_to_configured_targets() {
+    use tokio::io::AsyncReadExt;
+
+    let _trace = support::trace_init();
+
+    // The upstream "server" only reads the client's hello, so the handshake is
+    // never completed.
+    let listener = tokio::net::TcpListener::bind(SocketAddr::new([127, 0, 0, 1].into(), 0))
+        .await
+        .expect("listener must bind");
+    let target_addr = listener.local_addr().unwrap();
+
+    let server_id = tls::ServerId::from_str("api.example.com").expect("hostname is valid");
+    let mut cfg = default_config(target_addr);
+    cfg.tls_origination = Some(tls_origination::Config {
+        targets: Some((target_addr.into(), server_id.clone()))
+            .into_iter()
+            .collect(),
+        roots: identity::TrustAnchors::empty(),
+    });
+
+    // The endpoint is not meshed, so it is connected in plaintext unless TLS is
+    // originated.
+    let endpoint = Endpoint {
+        addr: target_addr,
+        target_addr,
+        tls: Conditional::None(tls::NoClientTls::NotProvidedByServiceDiscovery),
+        metadata: Default::default(),
+        logical: Logical {
+            orig_dst: target_addr,
+            profile: None,
+            protocol: (),
+        },
+    };
+    let (rt, _) = runtime();
+    let connect = Outbound::new(cfg, rt)
+        .to_tcp_connect()
+        .push_tcp_endpoint()
+        .into_inner()
+        .oneshot(endpoint);
+
+    let server = async move {
+        let (mut io, _) = listener.accept().await.expect("proxy must connect");
+        let mut header = [0u8; 5];
+        io.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0], 22, "a TLS handshake record must be sent");
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let mut hello = vec![0u8; len];
+        io.read_exact(&mut hello).await.unwrap();
+        hello
+    };
+
+    // The connection fails once the server hangs up.
+    let (conn, hello) = tokio::join!(connect, server);
+    assert!(conn.is_err(), "handshake must not complete");
+    let sni = server_id.to_string();
+    assert!(
+        hello.windows(sni.len()).any(|w| w == sni.as_bytes()),
+        "the client hello must include the configured server name"
+    );
+}
+
 #[tokio::test]
 async fn resolutions_are_reused() {
     let _trace = support::trace_init();
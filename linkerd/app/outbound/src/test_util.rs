@@ -2,7 +2,7 @@ use crate::Config;
 pub use futures::prelude::*;
 pub use ipnet::IpNet;
 use linkerd_app_core::{
-    config, drain, exp_backoff, metrics,
+    concurrency_limit, config, drain, exp_backoff, metrics, outlier,
     proxy::{
         http::{h1, h2},
         tap,
@@ -45,6 +45,9 @@ pub fn default_config(orig_dst: SocketAddr) -> Config {
             max_in_flight_requests: 10_000,
             detect_protocol_timeout: Duration::from_secs(3),
         },
+        outlier_detection: outlier::Config::default(),
+        endpoint_concurrency_limit: concurrency_limit::adaptive::Config::default(),
+        tls_origination: None,
     }
 }
 
@@ -57,6 +60,8 @@ pub fn runtime() -> (ProxyRuntime, drain::Signal) {
         metrics: metrics.outbound,
         tap,
         span_sink: None,
+        trace_sampler: Default::default(),
+        access_log: None,
         drain,
     };
     (runtime, drain_tx)
@@ -0,0 +1,256 @@
+//! Originates TLS to destinations outside of the mesh.
+//!
+//! Applications may send plaintext to a configured destination so that the
+//! outbound proxy establishes the TLS connection, verifying the server's
+//! certificate against web PKI roots. This lets the proxy observe (and report
+//! L7 metrics for) traffic to external HTTPS services.
+//!
+//! Destinations are configured either by address, in `Config::targets`, or by
+//! setting a server name in the destination's profile.
+
+use crate::target::Endpoint;
+use linkerd_app_core::{
+    identity, svc::stack::Param, tls, transport::metrics::Handshakes,
+    transport_header::SessionProtocol, Addr, Conditional,
+};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Maps each destination--either a logical name or an IP address, with a
+    /// port--to the server name that is sent via SNI and against which the
+    /// server's certificate is verified.
+    pub targets: HashMap<Addr, tls::ServerId>,
+
+    /// The roots that servers' certificates must be issued by, e.g. the
+    /// system's CA bundle.
+    pub roots: identity::TrustAnchors,
+}
+
+/// Configures a `tls::Client` to originate TLS without presenting a client
+/// certificate.
+#[derive(Clone, Debug)]
+pub(crate) struct Client {
+    config: tls::client::Config,
+    handshakes: Handshakes,
+}
+
+/// A target that is connected via a `tls::Client` that originates TLS.
+#[derive(Clone, Debug)]
+pub(crate) struct Originate<T> {
+    pub target: T,
+    tls: tls::ConditionalClientTls,
+}
+
+// === impl Config ===
+
+impl Config {
+    /// Builds a client that records handshakes in the given (registered)
+    /// metrics.
+    pub(crate) fn client(&self, handshakes: Handshakes) -> Client {
+        Client {
+            config: self.roots.web_pki_client_config(),
+            handshakes,
+        }
+    }
+
+    /// Returns the server name for the endpoint, if TLS should be originated
+    /// to it.
+    ///
+    /// Targets that are configured by address take precedence over the server
+    /// name in the endpoint's profile. Endpoints that are discovered with an
+    /// identity are always connected via mTLS instead.
+    fn server_id<P>(&self, endpoint: &Endpoint<P>) -> Option<tls::ServerId> {
+        if endpoint.tls.is_some() {
+            return None;
+        }
+
+        self.targets
+            .get(&endpoint.logical.addr())
+            .or_else(|| self.targets.get(&Addr::from(endpoint.target_addr)))
+            .cloned()
+            .or_else(|| {
+                let profile = endpoint.logical.profile.as_ref()?.borrow();
+                let name = profile.tls_server_name.clone()?;
+                Some(tls::ServerId(name.into()))
+            })
+    }
+}
+
+// === impl Client ===
+
+impl Param<tls::client::Config> for Client {
+    fn param(&self) -> tls::client::Config {
+        self.config.clone()
+    }
+}
+
+impl Param<tls::SharedDenylist> for Client {
+    fn param(&self) -> tls::SharedDenylist {
+        tls::SharedDenylist::default()
+    }
+}
+
+impl Param<Handshakes> for Client {
+    fn param(&self) -> Handshakes {
+        self.handshakes.clone()
+    }
+}
+
+// === impl Originate ===
+
+impl<P> Originate<Endpoint<P>>
+where
+    Endpoint<P>: Param<Option<SessionProtocol>>,
+{
+    pub(crate) fn new(config: Option<&Config>, endpoint: Endpoint<P>) -> Self {
+        let tls = match config.and_then(|c| c.server_id(&endpoint)) {
+            Some(server_id) => {
+                // Servers outside of the mesh negotiate the application
+                // protocol via ALPN, so we advertise only the protocol that
+                // the connection is used for. Opaque connections don't
+                // advertise one.
+                let protocol: Option<SessionProtocol> = endpoint.param();
+                let alpn = protocol.map(|protocol| {
+                    let protocol: &[u8] = match protocol {
+                        SessionProtocol::Http1 => b"http/1.1",
+                        SessionProtocol::Http2 => b"h2",
+                    };
+                    tls::client::AlpnProtocols(vec![protocol.into()])
+                });
+                Conditional::Some(tls::ClientTls { server_id, alpn })
+            }
+            // If the endpoint is meshed, its connection is secured by mTLS.
+            None => Conditional::None(
+                endpoint
+                    .tls
+                    .reason()
+                    .unwrap_or(tls::NoClientTls::NotProvidedByServiceDiscovery),
+            ),
+        };
+        Self {
+            target: endpoint,
+            tls,
+        }
+    }
+}
+
+impl<T> Param<tls::ConditionalClientTls> for Originate<T> {
+    fn param(&self) -> tls::ConditionalClientTls {
+        self.tls.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{http, target::Logical, test_util::support::profile};
+    use linkerd_app_core::profiles;
+    use std::{net::SocketAddr, str::FromStr};
+
+    fn config(addr: SocketAddr, server_id: &tls::ServerId) -> Config {
+        Config {
+            targets: Some((addr.into(), server_id.clone())).into_iter().collect(),
+            roots: identity::TrustAnchors::empty(),
+        }
+    }
+
+    fn endpoint<P>(addr: SocketAddr, tls: tls::ConditionalClientTls, protocol: P) -> Endpoint<P> {
+        Endpoint {
+            addr,
+            target_addr: addr,
+            tls,
+            metadata: Default::default(),
+            logical: Logical {
+                orig_dst: addr,
+                profile: None,
+                protocol,
+            },
+        }
+    }
+
+    fn profiled(addr: SocketAddr, tls_server_name: &str) -> Endpoint<()> {
+        let (_, rx) = profile::channel(profiles::Profile {
+            tls_server_name: Some(profiles::Name::from_str(tls_server_name).unwrap()),
+            ..Default::default()
+        });
+        let mut endpoint = endpoint(addr, UNMESHED, ());
+        endpoint.logical.profile = Some(rx);
+        endpoint
+    }
+
+    fn originated(server_id: &tls::ServerId, alpn: Option<&[u8]>) -> tls::ConditionalClientTls {
+        Conditional::Some(tls::ClientTls {
+            server_id: server_id.clone(),
+            alpn: alpn.map(|p| tls::client::AlpnProtocols(vec![p.to_vec()])),
+        })
+    }
+
+    const UNMESHED: tls::ConditionalClientTls =
+        Conditional::None(tls::NoClientTls::NotProvidedByServiceDiscovery);
+
+    #[test]
+    fn originates_to_configured_targets() {
+        let addr = SocketAddr::new([10, 1, 2, 3].into(), 443);
+        let server_id = tls::ServerId::from_str("api.example.com").unwrap();
+        let config = config(addr, &server_id);
+
+        let opaque = Originate::new(Some(&config), endpoint(addr, UNMESHED, ()));
+        assert_eq!(opaque.param(), originated(&server_id, None));
+
+        let h1 = Originate::new(
+            Some(&config),
+            endpoint(addr, UNMESHED, http::Version::Http1),
+        );
+        assert_eq!(h1.param(), originated(&server_id, Some(&b"http/1.1"[..])));
+
+        let h2 = Originate::new(Some(&config), endpoint(addr, UNMESHED, http::Version::H2));
+        assert_eq!(h2.param(), originated(&server_id, Some(&b"h2"[..])));
+    }
+
+    #[test]
+    fn originates_to_profiled_targets() {
+        let addr = SocketAddr::new([10, 1, 2, 3].into(), 443);
+        let server_id = tls::ServerId::from_str("api.example.com").unwrap();
+        let config = Config {
+            targets: Default::default(),
+            roots: identity::TrustAnchors::empty(),
+        };
+
+        let from_profile = Originate::new(Some(&config), profiled(addr, "api.example.com"));
+        assert_eq!(from_profile.param(), originated(&server_id, None));
+
+        // Targets that are configured by address take precedence.
+        let configured_id = tls::ServerId::from_str("web.example.com").unwrap();
+        let config = self::config(addr, &configured_id);
+        let configured = Originate::new(Some(&config), profiled(addr, "api.example.com"));
+        assert_eq!(configured.param(), originated(&configured_id, None));
+    }
+
+    #[test]
+    fn does_not_originate_to_meshed_or_unconfigured_targets() {
+        let addr = SocketAddr::new([10, 1, 2, 3].into(), 443);
+        let server_id = tls::ServerId::from_str("api.example.com").unwrap();
+        let config = config(addr, &server_id);
+
+        // Meshed endpoints are secured by mTLS, even if they are configured.
+        let mesh_id =
+            tls::ServerId::from_str("foo.ns1.serviceaccount.identity.linkerd.cluster.local")
+                .unwrap();
+        let meshed = Originate::new(
+            Some(&config),
+            endpoint(addr, Conditional::Some(mesh_id.into()), ()),
+        );
+        assert_eq!(
+            meshed.param(),
+            Conditional::None(tls::NoClientTls::NotProvidedByServiceDiscovery)
+        );
+
+        let other = SocketAddr::new([10, 1, 2, 4].into(), 443);
+        let unconfigured = Originate::new(Some(&config), endpoint(other, UNMESHED, ()));
+        assert_eq!(unconfigured.param(), UNMESHED);
+
+        let disabled = Originate::new(None, endpoint(addr, UNMESHED, ()));
+        assert_eq!(disabled.param(), UNMESHED);
+    }
+}
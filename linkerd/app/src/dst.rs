@@ -11,6 +11,9 @@ use tonic::body::BoxBody;
 pub struct Config {
     pub control: control::Config,
     pub context: String,
+
+    /// Route policies that are applied to discovered profiles.
+    pub profile_overrides: profiles::Overrides,
 }
 
 /// Handles to destination service clients.
@@ -44,7 +47,8 @@ impl Config {
 
         Ok(Dst {
             addr,
-            profiles: profiles::Client::new(svc.clone(), backoff, self.context.clone()),
+            profiles: profiles::Client::new(svc.clone(), backoff, self.context.clone())
+                .with_overrides(self.profile_overrides),
             resolve: recover::Resolve::new(backoff, api::Resolve::new(svc, self.context)),
         })
     }
@@ -1,11 +1,12 @@
 use crate::core::{
-    addr,
+    access_log, addr, concurrency_limit,
     config::*,
     control::{Config as ControlConfig, ControlAddr},
+    http_tracing, outlier, profiles,
     proxy::http::{h1, h2},
     tls,
     transport::BindTcp,
-    Addr, AddrMatch, Conditional, NameMatch,
+    Addr, AddrMatch, Conditional, IpMatch, NameMatch,
 };
 use crate::{dns, gateway, identity, inbound, oc_collector, outbound};
 use indexmap::IndexSet;
@@ -44,6 +45,16 @@ pub enum ParseError {
     NameError,
     InvalidTokenSource,
     InvalidTrustAnchors,
+    NotARateLimitKey,
+    InvalidAuthz,
+    InvalidKeyAlgorithm,
+    NotAnAccessLogOutput,
+    InvalidAccessLogField,
+    NotATraceCollectorProtocol,
+    NotAProbability,
+    NotARoutePolicy,
+    NotAConsistentHash,
+    NotABackoff,
 }
 
 // Environment variables to look at when loading the configuration
@@ -92,8 +103,82 @@ const ENV_OUTBOUND_MAX_IDLE_CONNS_PER_ENDPOINT: &str =
 pub const ENV_INBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_INBOUND_MAX_IN_FLIGHT";
 pub const ENV_OUTBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_OUTBOUND_MAX_IN_FLIGHT";
 
+/// Limits the rate of inbound requests to this many requests per second for
+/// each key. Disabled when unset or zero.
+const ENV_INBOUND_RATE_LIMIT_RPS: &str = "LINKERD2_PROXY_INBOUND_RATE_LIMIT_RPS";
+/// The number of requests that may be sent at once before the rate limit
+/// applies. Defaults to the per-second rate.
+const ENV_INBOUND_RATE_LIMIT_BURST: &str = "LINKERD2_PROXY_INBOUND_RATE_LIMIT_BURST";
+/// Determines which requests share a rate limit: `client-id`, `source-ip`, or
+/// `route`. Defaults to `client-id`.
+const ENV_INBOUND_RATE_LIMIT_KEY: &str = "LINKERD2_PROXY_INBOUND_RATE_LIMIT_KEY";
+
+/// Ejects an outbound endpoint from its load balancer after it fails this many
+/// consecutive requests. Disabled when unset or zero.
+const ENV_OUTBOUND_OUTLIER_CONSECUTIVE_FAILURES: &str =
+    "LINKERD2_PROXY_OUTBOUND_OUTLIER_CONSECUTIVE_FAILURES";
+
+/// Ejects an outbound endpoint from its load balancer when at least this ratio
+/// (between 0 and 1) of its recent requests fail. Disabled when unset.
+const ENV_OUTBOUND_OUTLIER_FAILURE_RATE: &str = "LINKERD2_PROXY_OUTBOUND_OUTLIER_FAILURE_RATE";
+const ENV_OUTBOUND_OUTLIER_FAILURE_RATE_MIN_REQUESTS: &str =
+    "LINKERD2_PROXY_OUTBOUND_OUTLIER_FAILURE_RATE_MIN_REQUESTS";
+const ENV_OUTBOUND_OUTLIER_FAILURE_RATE_WINDOW: &str =
+    "LINKERD2_PROXY_OUTBOUND_OUTLIER_FAILURE_RATE_WINDOW";
+
+/// The maximum percentage (between 0 and 100) of a load balancer's endpoints
+/// that may be ejected at once. An endpoint may always be ejected while fewer
+/// than this share of its balancer's endpoints are ejected. Defaults to 10.
+const ENV_OUTBOUND_OUTLIER_MAX_EJECTION_PERCENT: &str =
+    "LINKERD2_PROXY_OUTBOUND_OUTLIER_MAX_EJECTION_PERCENT";
+
+/// Limits the number of concurrent requests to each outbound endpoint, adapting
+/// the limit (up to this maximum) as endpoints fail or slow down. Disabled when
+/// unset or zero.
+const ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MAX: &str =
+    "LINKERD2_PROXY_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MAX";
+const ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MIN: &str =
+    "LINKERD2_PROXY_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MIN";
+const ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_INITIAL: &str =
+    "LINKERD2_PROXY_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_INITIAL";
+/// Requests that take longer than this decrease an endpoint's concurrency limit.
+const ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_LATENCY_THRESHOLD: &str =
+    "LINKERD2_PROXY_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_LATENCY_THRESHOLD";
+/// The ratio (greater than 0 and less than 1) by which an endpoint's
+/// concurrency limit is multiplied when a request fails or is slow.
+const ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_BACKOFF_RATIO: &str =
+    "LINKERD2_PROXY_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_BACKOFF_RATIO";
+
+/// Configures the outbound proxy to originate TLS to destinations that are not
+/// meshed, so that applications may send plaintext to them.
+///
+/// The value is a comma-separated list of destinations, each either a
+/// `NAME:PORT` or an `IP:PORT=NAME`, where `NAME` is the server name that is
+/// sent via SNI and that the server's certificate must be valid for. A
+/// `NAME:PORT` destination only matches when the destination is discovered
+/// with that name. Destinations may also be configured by their profiles with
+/// `LINKERD2_PROXY_DESTINATION_PROFILE_TLS_ORIGINATION`.
+const ENV_OUTBOUND_TLS_ORIGINATION_TARGETS: &str =
+    "LINKERD2_PROXY_OUTBOUND_TLS_ORIGINATION_TARGETS";
+/// A path to a PEM-encoded bundle of the roots that TLS origination targets'
+/// certificates must be issued by. Defaults to the system's CA bundle.
+const ENV_OUTBOUND_TLS_ORIGINATION_ROOTS_FILE: &str =
+    "LINKERD2_PROXY_OUTBOUND_TLS_ORIGINATION_ROOTS_FILE";
+
 pub const ENV_TRACE_ATTRIBUTES_PATH: &str = "LINKERD2_PROXY_TRACE_ATTRIBUTES_PATH";
 
+/// Enables access logging for inbound and outbound HTTP requests and TCP
+/// connections.
+///
+/// The value is either `stdout` or the path of a file to which records are
+/// appended. If unspecified, no access log is written. Note that the proxy's
+/// diagnostic logs are also written to stdout, so records written there are
+/// interleaved with them; a file should be used when the access log is parsed.
+const ENV_ACCESS_LOG: &str = "LINKERD2_PROXY_ACCESS_LOG";
+/// A comma-separated list of the fields written in each access log record.
+/// Defaults to all fields.
+const ENV_ACCESS_LOG_FORMAT: &str = "LINKERD2_PROXY_ACCESS_LOG_FORMAT";
+
 /// Constrains which destination names may be used for profile/route discovery.
 ///
 /// The value is a comma-separated list of domain name suffixes that may be
@@ -115,6 +200,87 @@ pub const ENV_DESTINATION_PROFILE_SUFFIXES: &str = "LINKERD2_PROXY_DESTINATION_P
 /// If unspecified, a default value is used.
 pub const ENV_DESTINATION_PROFILE_NETWORKS: &str = "LINKERD2_PROXY_DESTINATION_PROFILE_NETWORKS";
 
+/// Mirrors a sample of the requests on a destination's profile routes to
+/// another destination.
+///
+/// The value is a comma-separated list of `DST[#ROUTE]=MIRROR[@RATIO]`
+/// entries. `DST` is the destination's logical address, or the IP address and
+/// port with which its profile is looked up. `ROUTE`, if specified, selects
+/// the route whose `route` label has the given value; otherwise the entry
+/// applies to all of the destination's routes. `MIRROR` is the logical address
+/// to which requests are copied and `RATIO` is the fraction of requests that
+/// are mirrored, which defaults to 1.0. For example:
+///
+/// `web.ns.svc.cluster.local:8080#GET /books=web-v2.ns.svc.cluster.local:8080@0.1`
+pub const ENV_DESTINATION_PROFILE_ROUTE_MIRRORS: &str =
+    "LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_MIRRORS";
+
+/// Hedges requests on a destination's profile routes: when a request has not
+/// completed after a delay, a duplicate is sent and the first successful
+/// response is used. Only retryable routes are hedged, since hedges are
+/// withdrawn from the route's retry budget.
+///
+/// The value is a comma-separated list of `DST[#ROUTE]=DELAY` entries, where
+/// `DST` and `ROUTE` are as in
+/// `LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_MIRRORS`. `DELAY` is either a
+/// duration or a percentile of the route's observed latency (e.g. `p95`). For
+/// example:
+///
+/// `web.ns.svc.cluster.local:8080#GET /books=p95,10.1.2.3:80=50ms`
+pub const ENV_DESTINATION_PROFILE_ROUTE_HEDGES: &str =
+    "LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_HEDGES";
+
+/// Bounds the time each attempt of a retryable request on a destination's
+/// profile routes may take, so that slow attempts are retried.
+///
+/// The value is a comma-separated list of `DST[#ROUTE]=DURATION` entries,
+/// where `DST` and `ROUTE` are as in
+/// `LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_MIRRORS`. For example:
+///
+/// `web.ns.svc.cluster.local:8080#GET /books=500ms`
+pub const ENV_DESTINATION_PROFILE_ROUTE_PER_TRY_TIMEOUTS: &str =
+    "LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_PER_TRY_TIMEOUTS";
+
+/// Waits for a jittered, exponentially increasing delay before retrying
+/// requests on a destination's profile routes.
+///
+/// The value is a comma-separated list of `DST[#ROUTE]=MIN-MAX[@JITTER]`
+/// entries, where `DST` and `ROUTE` are as in
+/// `LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_MIRRORS`. `MIN` and `MAX` are
+/// durations and `JITTER` is a ratio between 0.0 (the default) and 100.0. For
+/// example:
+///
+/// `web.ns.svc.cluster.local:8080=25ms-1s@0.5`
+pub const ENV_DESTINATION_PROFILE_ROUTE_RETRY_BACKOFFS: &str =
+    "LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_RETRY_BACKOFFS";
+
+/// Balances a destination's requests over its endpoints by consistently
+/// hashing a request key rather than by load.
+///
+/// The value is a comma-separated list of `DST=KEY[@TABLE]` entries, where
+/// `DST` identifies a destination as in
+/// `LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_MIRRORS`. `KEY` is one of
+/// `header:NAME`, `cookie:NAME`, or `source-ip`, and `TABLE` is either `ring`
+/// (the default) or `maglev`. For example:
+///
+/// `web.ns.svc.cluster.local:8080=header:x-user-id@maglev`
+pub const ENV_DESTINATION_PROFILE_CONSISTENT_HASH: &str =
+    "LINKERD2_PROXY_DESTINATION_PROFILE_CONSISTENT_HASH";
+
+/// Originates TLS to a destination's endpoints that are not meshed, so that
+/// applications may send plaintext to them.
+///
+/// The value is a comma-separated list of `DST=NAME` entries, where `DST`
+/// identifies a destination as in
+/// `LINKERD2_PROXY_DESTINATION_PROFILE_ROUTE_MIRRORS` and `NAME` is the server
+/// name that is sent via SNI and that the server's certificate must be valid
+/// for. Servers' certificates are verified against the roots in
+/// `LINKERD2_PROXY_OUTBOUND_TLS_ORIGINATION_ROOTS_FILE`. For example:
+///
+/// `api.example.com:443=api.example.com`
+pub const ENV_DESTINATION_PROFILE_TLS_ORIGINATION: &str =
+    "LINKERD2_PROXY_DESTINATION_PROFILE_TLS_ORIGINATION";
+
 /// Constrains which destination names are permitted.
 ///
 /// If unspecified or empty, no inbound gateway is configured.
@@ -128,9 +294,39 @@ pub const ENV_INBOUND_PORTS_DISABLE_PROTOCOL_DETECTION: &str =
 pub const ENV_INBOUND_PORTS_REQUIRE_IDENTITY: &str =
     "LINKERD2_PROXY_INBOUND_PORTS_REQUIRE_IDENTITY";
 
+/// Authorizes clients of inbound ports, as a comma-separated list of
+/// `PORT[/ROUTE]=CLIENTS[@NETWORKS]` entries. `CLIENTS` is either
+/// `unauthenticated` or a `|`-separated list of client identity patterns
+/// (`*`, `*.SUFFIX`, or an exact name); `NETWORKS` is a `|`-separated list of
+/// CIDRs, defaulting to all networks. Ports without entries are not
+/// restricted, and `ROUTE` entries further restrict HTTP requests for the
+/// named service profile route.
+pub const ENV_INBOUND_AUTHZ: &str = "LINKERD2_PROXY_INBOUND_AUTHZ";
+
 pub const ENV_IDENTITY_DISABLED: &str = "LINKERD2_PROXY_IDENTITY_DISABLED";
 pub const ENV_IDENTITY_DIR: &str = "LINKERD2_PROXY_IDENTITY_DIR";
+
+/// The algorithm of the private key in `LINKERD2_PROXY_IDENTITY_DIR`: one of
+/// `ecdsa-p256` (the default), `ecdsa-p384`, or `ed25519`.
+pub const ENV_IDENTITY_KEY_ALGORITHM: &str = "LINKERD2_PROXY_IDENTITY_KEY_ALGORITHM";
+
+/// When true and `LINKERD2_PROXY_IDENTITY_DIR` has no `key.p8`, a key is
+/// generated with `LINKERD2_PROXY_IDENTITY_KEY_ALGORITHM`, along with a CSR for
+/// the local identity. Otherwise, a missing key is an error.
+pub const ENV_IDENTITY_GENERATE_KEY: &str = "LINKERD2_PROXY_IDENTITY_GENERATE_KEY";
+
 pub const ENV_IDENTITY_TRUST_ANCHORS: &str = "LINKERD2_PROXY_IDENTITY_TRUST_ANCHORS";
+
+/// A path to a file of PEM-encoded trust anchors, used instead of
+/// `LINKERD2_PROXY_IDENTITY_TRUST_ANCHORS`. The file is read periodically and
+/// its anchors replace the current ones whenever its contents change, so that
+/// the CA may be rotated without restarting the proxy. During a rotation, the
+/// file should hold both the old and new anchors.
+pub const ENV_IDENTITY_TRUST_ANCHORS_FILE: &str = "LINKERD2_PROXY_IDENTITY_TRUST_ANCHORS_FILE";
+
+/// How often the trust anchors file is checked for changes.
+pub const ENV_IDENTITY_TRUST_ANCHORS_RELOAD_INTERVAL: &str =
+    "LINKERD2_PROXY_IDENTITY_TRUST_ANCHORS_RELOAD_INTERVAL";
 pub const ENV_IDENTITY_IDENTITY_LOCAL_NAME: &str = "LINKERD2_PROXY_IDENTITY_LOCAL_NAME";
 pub const ENV_IDENTITY_TOKEN_FILE: &str = "LINKERD2_PROXY_IDENTITY_TOKEN_FILE";
 pub const ENV_IDENTITY_MIN_REFRESH: &str = "LINKERD2_PROXY_IDENTITY_MIN_REFRESH";
@@ -138,12 +334,57 @@ pub const ENV_IDENTITY_MAX_REFRESH: &str = "LINKERD2_PROXY_IDENTITY_MAX_REFRESH"
 
 pub const ENV_IDENTITY_SVC_BASE: &str = "LINKERD2_PROXY_IDENTITY_SVC";
 
+/// Paths to a PEM-encoded certificate chain and PKCS#8 private key for the
+/// local identity. When set, the identity is loaded from these files instead
+/// of being certified by the Identity controller. The files are read
+/// periodically so that rotated certificates are used without a restart.
+pub const ENV_IDENTITY_CRT_FILE: &str = "LINKERD2_PROXY_IDENTITY_CRT_FILE";
+pub const ENV_IDENTITY_KEY_FILE: &str = "LINKERD2_PROXY_IDENTITY_KEY_FILE";
+
+/// How often the certificate and key files are checked for changes.
+pub const ENV_IDENTITY_FILE_RELOAD_INTERVAL: &str = "LINKERD2_PROXY_IDENTITY_FILE_RELOAD_INTERVAL";
+
+/// A path to the UNIX socket of a SPIFFE Workload API (e.g. a SPIRE agent).
+/// When set, the local identity and its trust anchors are obtained from the
+/// Workload API instead of the Identity controller.
+pub const ENV_IDENTITY_SPIFFE_WORKLOAD_API_SOCKET: &str =
+    "LINKERD2_PROXY_IDENTITY_SPIFFE_WORKLOAD_API_SOCKET";
+
+/// A path to a file listing certificates that peers may not present, even if
+/// they were issued by a trust anchor. Each line holds either `serial:`
+/// followed by a hex-encoded certificate serial number, or an identity name.
+/// The file is read periodically so that certificates may be revoked without
+/// restarting the proxy.
+pub const ENV_IDENTITY_DENYLIST_FILE: &str = "LINKERD2_PROXY_IDENTITY_DENYLIST_FILE";
+
+/// How often the denylist file is checked for changes.
+pub const ENV_IDENTITY_DENYLIST_RELOAD_INTERVAL: &str =
+    "LINKERD2_PROXY_IDENTITY_DENYLIST_RELOAD_INTERVAL";
+
 pub const ENV_DESTINATION_SVC_BASE: &str = "LINKERD2_PROXY_DESTINATION_SVC";
 
 pub const ENV_HOSTNAME: &str = "HOSTNAME";
 
 pub const ENV_TRACE_COLLECTOR_SVC_BASE: &str = "LINKERD2_PROXY_TRACE_COLLECTOR_SVC";
 
+/// The protocol used to export spans to the trace collector: either
+/// `opencensus` or `opentelemetry` (OTLP). Defaults to `opencensus`.
+pub const ENV_TRACE_COLLECTOR_PROTOCOL: &str = "LINKERD2_PROXY_TRACE_COLLECTOR_PROTOCOL";
+
+/// The probability (between 0 and 1) that the proxy samples a trace that it
+/// starts or that it receives unsampled. Defaults to 1 if a sampling rate
+/// limit is set, and to 0 otherwise.
+pub const ENV_TRACE_SAMPLING_PROBABILITY: &str = "LINKERD2_PROXY_TRACE_SAMPLING_PROBABILITY";
+/// Limits the number of traces that the proxy samples each second. Traces that
+/// are received sampled do not count against this limit.
+pub const ENV_TRACE_SAMPLING_MAX_PER_SECOND: &str = "LINKERD2_PROXY_TRACE_SAMPLING_MAX_PER_SECOND";
+/// If true, spans are recorded for requests that fail with a 5XX or gRPC error
+/// status, even if their trace is not sampled. Defaults to false.
+///
+/// Only the response's headers are inspected, so gRPC errors that are reported
+/// in trailers (i.e. after a response body) are not recorded.
+pub const ENV_TRACE_SAMPLING_ERRORS: &str = "LINKERD2_PROXY_TRACE_SAMPLING_ERRORS";
+
 pub const ENV_DESTINATION_CONTEXT: &str = "LINKERD2_PROXY_DESTINATION_CONTEXT";
 pub const ENV_DESTINATION_PROFILE_INITIAL_TIMEOUT: &str =
     "LINKERD2_PROXY_DESTINATION_PROFILE_INITIAL_TIMEOUT";
@@ -189,6 +430,21 @@ const DEFAULT_OUTBOUND_CONNECT_BACKOFF: ExponentialBackoff = ExponentialBackoff
     max: Duration::from_millis(500),
     jitter: 0.1,
 };
+// Ejected endpoints are readmitted after a backoff that doubles each time an
+// endpoint is ejected again without having served a successful response.
+const DEFAULT_OUTBOUND_OUTLIER_BACKOFF: ExponentialBackoff = ExponentialBackoff {
+    min: Duration::from_secs(5),
+    max: Duration::from_secs(60),
+    jitter: 0.1,
+};
+const DEFAULT_OUTBOUND_OUTLIER_FAILURE_RATE_MIN_REQUESTS: u32 = 20;
+const DEFAULT_OUTBOUND_OUTLIER_FAILURE_RATE_WINDOW: Duration = Duration::from_secs(10);
+const DEFAULT_OUTBOUND_OUTLIER_MAX_EJECTION_PERCENT: u32 = 10;
+const DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MIN: usize = 1;
+const DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_INITIAL: usize = 20;
+const DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_LATENCY_THRESHOLD: Duration =
+    Duration::from_secs(1);
+const DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_BACKOFF_RATIO: f64 = 0.9;
 const DEFAULT_RESOLV_CONF: &str = "/etc/resolv.conf";
 
 const DEFAULT_INITIAL_STREAM_WINDOW_SIZE: u32 = 65_535; // Protocol default
@@ -235,10 +491,17 @@ const DEFAULT_DESTINATION_PROFILE_SUFFIXES: &str = "svc.cluster.local.";
 const DEFAULT_DESTINATION_PROFILE_IDLE_TIMEOUT: Duration = Duration::from_millis(500);
 
 const DEFAULT_IDENTITY_MIN_REFRESH: Duration = Duration::from_secs(10);
+const DEFAULT_IDENTITY_TRUST_ANCHORS_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
 const DEFAULT_IDENTITY_MAX_REFRESH: Duration = Duration::from_secs(60 * 60 * 24);
+const DEFAULT_IDENTITY_FILE_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const DEFAULT_IDENTITY_SPIFFE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_IDENTITY_DENYLIST_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+const DEFAULT_OUTBOUND_TLS_ORIGINATION_ROOTS_FILE: &str = "/etc/ssl/certs/ca-certificates.crt";
 
 const INBOUND_CONNECT_BASE: &str = "INBOUND_CONNECT";
 const OUTBOUND_CONNECT_BASE: &str = "OUTBOUND_CONNECT";
+const OUTBOUND_OUTLIER_BASE: &str = "OUTBOUND_OUTLIER";
 
 /// Load a `App` by reading ENV variables.
 pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError> {
@@ -295,6 +558,13 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
     let inbound_max_in_flight = parse(strings, ENV_INBOUND_MAX_IN_FLIGHT, parse_number);
     let outbound_max_in_flight = parse(strings, ENV_OUTBOUND_MAX_IN_FLIGHT, parse_number);
 
+    let inbound_rate_limit = parse_rate_limit_config(strings);
+
+    let outbound_outlier_detection = parse_outlier_config(strings);
+    let outbound_endpoint_concurrency_limit = parse_concurrency_limit_config(strings);
+    let outbound_tls_origination = parse_tls_origination(strings);
+    let access_log = parse_access_log(strings);
+
     let metrics_retain_idle = parse(strings, ENV_METRICS_RETAIN_IDLE, parse_duration);
 
     // DNS
@@ -305,6 +575,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
     let dns_max_ttl = parse(strings, ENV_DNS_MAX_TTL, parse_duration);
 
     let identity_config = parse_identity_config(strings);
+    let identity_denylist = parse_identity_denylist(strings);
 
     let id_disabled = identity_config
         .as_ref()
@@ -326,8 +597,25 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
     } else {
         parse_control_addr(strings, ENV_TRACE_COLLECTOR_SVC_BASE)
     };
+    let trace_collector_protocol = parse(
+        strings,
+        ENV_TRACE_COLLECTOR_PROTOCOL,
+        parse_trace_collector_protocol,
+    );
+    let trace_sampling_probability = parse(
+        strings,
+        ENV_TRACE_SAMPLING_PROBABILITY,
+        parse_sampling_probability,
+    );
+    let trace_sampling_max_per_second = parse(
+        strings,
+        ENV_TRACE_SAMPLING_MAX_PER_SECOND,
+        parse_number::<u32>,
+    );
+    let trace_sampling_errors = parse(strings, ENV_TRACE_SAMPLING_ERRORS, parse_bool);
 
     let dst_token = strings.get(ENV_DESTINATION_CONTEXT);
+    let dst_profile_overrides = parse_profile_overrides(strings);
 
     let gateway_suffixes = parse(strings, ENV_INBOUND_GATEWAY_SUFFIXES, parse_dns_suffixes);
     let dst_profile_idle_timeout = parse(
@@ -440,6 +728,9 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                     .unwrap_or(DEFAULT_OUTBOUND_MAX_IN_FLIGHT),
                 detect_protocol_timeout,
             },
+            outlier_detection: outbound_outlier_detection?,
+            endpoint_concurrency_limit: outbound_endpoint_concurrency_limit?,
+            tls_origination: outbound_tls_origination?,
         }
     };
 
@@ -511,6 +802,8 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             require_identity_for_inbound_ports.insert(inbound_port);
         }
 
+        let authz = parse(strings, ENV_INBOUND_AUTHZ, parse_authz)?.unwrap_or_default();
+
         // Ensure that the inbound port does not disable protocol detection, as
         // is required for opaque transport.
         let inbound_opaque_ports = inbound_disable_ports?.unwrap_or_default();
@@ -522,6 +815,29 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             return Err(EnvError::InvalidEnvVar);
         }
 
+        // Each port's policy is determined by the environment.
+        let policies = {
+            use inbound::policy::{Protocol, ServerPolicy};
+
+            let mut ports = authz
+                .into_iter()
+                .map(|(port, authz)| {
+                    let policy = ServerPolicy {
+                        authz,
+                        ..ServerPolicy::default()
+                    };
+                    (port, policy)
+                })
+                .collect::<HashMap<_, _>>();
+            for port in require_identity_for_inbound_ports {
+                ports.entry(port).or_default().require_identity = true;
+            }
+            for port in inbound_opaque_ports {
+                ports.entry(port).or_default().protocol = Protocol::Opaque;
+            }
+            inbound::policy::Store::new(ServerPolicy::default(), ports)
+        };
+
         inbound::Config {
             allow_discovery: NameMatch::new(dst_profile_suffixes),
             proxy: ProxyConfig {
@@ -534,10 +850,10 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                     .unwrap_or(DEFAULT_INBOUND_MAX_IN_FLIGHT),
                 detect_protocol_timeout,
             },
-            require_identity_for_inbound_ports: require_identity_for_inbound_ports.into(),
+            policies,
             profile_idle_timeout: dst_profile_idle_timeout?
                 .unwrap_or(DEFAULT_DESTINATION_PROFILE_IDLE_TIMEOUT),
-            disable_protocol_detection_for_ports: inbound_opaque_ports.into(),
+            rate_limit: inbound_rate_limit?,
         }
     };
 
@@ -550,6 +866,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         };
         super::dst::Config {
             context: dst_token?.unwrap_or_default(),
+            profile_overrides: dst_profile_overrides?,
             control: ControlConfig {
                 addr,
                 connect,
@@ -594,9 +911,26 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                 })
                 .unwrap_or_default();
 
+            // If only a rate limit is set, traces are sampled up to that limit.
+            let max_per_second = trace_sampling_max_per_second?;
+            let probability = trace_sampling_probability?.unwrap_or_else(|| {
+                if max_per_second.is_some() {
+                    1.0
+                } else {
+                    0.0
+                }
+            });
+            let sampler = http_tracing::Sampler::new(
+                probability,
+                max_per_second,
+                trace_sampling_errors?.unwrap_or(false),
+            );
+
             oc_collector::Config::Enabled(Box::new(oc_collector::EnabledConfig {
                 attributes,
                 hostname: hostname?,
+                protocol: trace_collector_protocol?.unwrap_or_default(),
+                sampler,
                 control: ControlConfig {
                     addr,
                     connect,
@@ -617,21 +951,29 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         .unwrap_or(super::tap::Config::Disabled);
 
     let identity = identity_config?
-        .map(|(addr, certify)| {
-            // If the address doesn't have a server identity, then we're on localhost.
-            let connect = if addr.identity.is_none() {
-                inbound.proxy.connect.clone()
-            } else {
-                outbound.proxy.connect.clone()
-            };
-            identity::Config::Enabled {
-                certify,
-                control: ControlConfig {
-                    addr,
-                    connect,
-                    buffer_capacity: 1,
-                },
+        .map(|source| match source {
+            IdentitySource::Service(addr, certify) => {
+                // If the address doesn't have a server identity, then we're on localhost.
+                let connect = if addr.identity.is_none() {
+                    inbound.proxy.connect.clone()
+                } else {
+                    outbound.proxy.connect.clone()
+                };
+                identity::Config::Enabled {
+                    certify,
+                    control: ControlConfig {
+                        addr,
+                        connect,
+                        buffer_capacity: 1,
+                    },
+                }
             }
+            IdentitySource::File(file) => identity::Config::File(file),
+            IdentitySource::Spiffe { socket, certify } => identity::Config::Spiffe {
+                socket,
+                backoff: outbound.proxy.connect.backoff,
+                certify,
+            },
         })
         .unwrap_or(identity::Config::Disabled);
 
@@ -641,7 +983,9 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         dst,
         tap,
         oc_collector,
+        access_log: access_log?,
         identity,
+        identity_denylist: identity_denylist?,
         outbound,
         gateway,
         inbound,
@@ -781,6 +1125,13 @@ fn parse_addr(s: &str) -> Result<Addr, ParseError> {
     })
 }
 
+fn parse_dns_name(s: &str) -> Result<profiles::Name, ParseError> {
+    profiles::Name::from_str(s).map_err(|_| {
+        error!("Not a valid DNS name: {}", s);
+        ParseError::NameError
+    })
+}
+
 fn parse_port_set(s: &str) -> Result<IndexSet<u16>, ParseError> {
     let mut set = IndexSet::new();
     for num in s.split(',') {
@@ -796,6 +1147,13 @@ pub(super) fn parse_identity(s: &str) -> Result<identity::Name, ParseError> {
     })
 }
 
+fn parse_key_algorithm(s: &str) -> Result<identity::KeyAlgorithm, ParseError> {
+    identity::KeyAlgorithm::from_str(s).map_err(|identity::InvalidKeyAlgorithm| {
+        error!("Not a supported key algorithm: {}", s);
+        ParseError::InvalidKeyAlgorithm
+    })
+}
+
 pub(super) fn parse<T, Parse>(
     strings: &dyn Strings,
     name: &str,
@@ -839,6 +1197,68 @@ where
     }
 }
 
+fn parse_authz(list: &str) -> Result<HashMap<u16, inbound::authz::PortPolicy>, ParseError> {
+    use inbound::authz::{Authorization, Clients, IdentityMatch, PortPolicy};
+
+    let mut ports = HashMap::<u16, PortPolicy>::new();
+    for entry in list.split(',') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        let eq = entry.find('=').ok_or(ParseError::InvalidAuthz)?;
+        let (target, authz) = (&entry[..eq], &entry[eq + 1..]);
+        let (port, route) = match target.find('/') {
+            Some(idx) => (&target[..idx], Some(&target[idx + 1..])),
+            None => (target, None),
+        };
+        let port = parse_number::<u16>(port.trim())?;
+
+        let (clients, networks) = match authz.find('@') {
+            Some(idx) => (&authz[..idx], Some(&authz[idx + 1..])),
+            None => (authz, None),
+        };
+        let clients = match clients.trim() {
+            "unauthenticated" => Clients::Unauthenticated,
+            ids => Clients::Authenticated(
+                ids.split('|')
+                    .map(|id| IdentityMatch::from_str(id.trim()))
+                    .collect::<Result<Vec<_>, ()>>()
+                    .map_err(|()| {
+                        error!(%entry, "Invalid client identity pattern");
+                        ParseError::InvalidAuthz
+                    })?,
+            ),
+        };
+        let networks = match networks {
+            Some(nets) => parse_networks(&nets.replace('|', ","))?,
+            None => vec![
+                ipnet::IpNet::from_str("0.0.0.0/0").unwrap(),
+                ipnet::IpNet::from_str("::/0").unwrap(),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let authz = Authorization {
+            networks: IpMatch::new(networks),
+            clients,
+        };
+
+        let policy = ports.entry(port).or_default();
+        match route {
+            None => policy.authorizations.push(authz),
+            Some(route) => policy
+                .routes
+                .entry(route.trim().to_string())
+                .or_default()
+                .push(authz),
+        }
+    }
+
+    Ok(ports)
+}
+
 fn parse_dns_suffixes(list: &str) -> Result<IndexSet<dns::Suffix>, ParseError> {
     let mut suffixes = IndexSet::new();
     for item in list.split(',') {
@@ -902,6 +1322,386 @@ pub fn parse_backoff<S: Strings>(
     }
 }
 
+pub fn parse_outlier_config<S: Strings>(strings: &S) -> Result<outlier::Config, EnvError> {
+    let consecutive_failures = parse(
+        strings,
+        ENV_OUTBOUND_OUTLIER_CONSECUTIVE_FAILURES,
+        parse_number::<u32>,
+    );
+    let ratio = parse(
+        strings,
+        ENV_OUTBOUND_OUTLIER_FAILURE_RATE,
+        parse_number::<f64>,
+    );
+    let min_requests = parse(
+        strings,
+        ENV_OUTBOUND_OUTLIER_FAILURE_RATE_MIN_REQUESTS,
+        parse_number::<u32>,
+    );
+    let window = parse(
+        strings,
+        ENV_OUTBOUND_OUTLIER_FAILURE_RATE_WINDOW,
+        parse_duration,
+    );
+    let max_ejection_percent = parse(
+        strings,
+        ENV_OUTBOUND_OUTLIER_MAX_EJECTION_PERCENT,
+        parse_number::<u32>,
+    );
+    let backoff = parse_backoff(
+        strings,
+        OUTBOUND_OUTLIER_BASE,
+        DEFAULT_OUTBOUND_OUTLIER_BACKOFF,
+    );
+
+    let failure_rate = match ratio? {
+        None => None,
+        Some(ratio) if ratio > 0.0 && ratio <= 1.0 => Some(outlier::FailureRate {
+            ratio,
+            min_requests: min_requests?
+                .unwrap_or(DEFAULT_OUTBOUND_OUTLIER_FAILURE_RATE_MIN_REQUESTS),
+            window: window?.unwrap_or(DEFAULT_OUTBOUND_OUTLIER_FAILURE_RATE_WINDOW),
+        }),
+        Some(ratio) => {
+            error!(
+                "{}={} must be greater than 0 and no greater than 1",
+                ENV_OUTBOUND_OUTLIER_FAILURE_RATE, ratio
+            );
+            return Err(EnvError::InvalidEnvVar);
+        }
+    };
+
+    let max_ejection_percent =
+        max_ejection_percent?.unwrap_or(DEFAULT_OUTBOUND_OUTLIER_MAX_EJECTION_PERCENT);
+    if max_ejection_percent > 100 {
+        error!(
+            "{}={} must be no greater than 100",
+            ENV_OUTBOUND_OUTLIER_MAX_EJECTION_PERCENT, max_ejection_percent
+        );
+        return Err(EnvError::InvalidEnvVar);
+    }
+
+    Ok(outlier::Config {
+        consecutive_failures: consecutive_failures?.unwrap_or(0),
+        failure_rate,
+        backoff: backoff?,
+        max_ejection_percent: Some(max_ejection_percent),
+    })
+}
+
+pub fn parse_rate_limit_config<S: Strings>(
+    strings: &S,
+) -> Result<Option<inbound::rate_limit::Config>, EnvError> {
+    let rps = parse(strings, ENV_INBOUND_RATE_LIMIT_RPS, parse_number::<u32>);
+    let burst = parse(strings, ENV_INBOUND_RATE_LIMIT_BURST, parse_number::<u32>);
+    let scope = parse(strings, ENV_INBOUND_RATE_LIMIT_KEY, parse_rate_limit_scope);
+
+    let requests_per_second = match rps? {
+        Some(rps) if rps > 0 => rps,
+        _ => return Ok(None),
+    };
+    let burst = match burst? {
+        Some(0) => {
+            error!("{} must be greater than 0", ENV_INBOUND_RATE_LIMIT_BURST);
+            return Err(EnvError::InvalidEnvVar);
+        }
+        Some(burst) => burst,
+        None => requests_per_second,
+    };
+
+    Ok(Some(inbound::rate_limit::Config {
+        scope: scope?.unwrap_or(inbound::rate_limit::Scope::ClientId),
+        requests_per_second,
+        burst,
+    }))
+}
+
+fn parse_tls_origination<S: Strings>(
+    strings: &S,
+) -> Result<Option<outbound::tls_origination::Config>, EnvError> {
+    let targets = parse(
+        strings,
+        ENV_OUTBOUND_TLS_ORIGINATION_TARGETS,
+        parse_tls_origination_targets,
+    )?;
+    let profiled = strings.get(ENV_DESTINATION_PROFILE_TLS_ORIGINATION)?;
+    let roots_path = strings.get(ENV_OUTBOUND_TLS_ORIGINATION_ROOTS_FILE)?;
+
+    // The roots are loaded if TLS is originated to any destination, whether
+    // it's configured by address or by its profile.
+    let targets = targets.unwrap_or_default();
+    if targets.is_empty() && profiled.map_or(true, |s| s.trim().is_empty()) {
+        return Ok(None);
+    }
+
+    let path =
+        roots_path.unwrap_or_else(|| DEFAULT_OUTBOUND_TLS_ORIGINATION_ROOTS_FILE.to_string());
+    let pem = fs::read_to_string(&path).map_err(|e| {
+        error!("Failed to read TLS origination roots from {}: {}", path, e);
+        EnvError::InvalidEnvVar
+    })?;
+    let roots = identity::TrustAnchors::from_pem(&pem).ok_or_else(|| {
+        error!("No valid TLS origination roots in {}", path);
+        EnvError::InvalidEnvVar
+    })?;
+
+    Ok(Some(outbound::tls_origination::Config { targets, roots }))
+}
+
+fn parse_tls_origination_targets(list: &str) -> Result<HashMap<Addr, tls::ServerId>, ParseError> {
+    let mut targets = HashMap::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+
+        let (addr, name) = match item.find('=') {
+            Some(i) => (&item[..i], Some(&item[i + 1..])),
+            None => (item, None),
+        };
+        let addr = parse_addr(addr.trim())?;
+        let name = match (name, &addr) {
+            (Some(name), _) => parse_identity(name.trim())?,
+            (None, Addr::Name(n)) => parse_identity(n.name().as_ref())?,
+            (None, Addr::Socket(_)) => {
+                error!("A server name must be specified for {}", addr);
+                return Err(ParseError::NameError);
+            }
+        };
+        targets.insert(addr, tls::ServerId(name));
+    }
+
+    Ok(targets)
+}
+
+fn parse_rate_limit_scope(s: &str) -> Result<inbound::rate_limit::Scope, ParseError> {
+    match s {
+        "client-id" => Ok(inbound::rate_limit::Scope::ClientId),
+        "source-ip" => Ok(inbound::rate_limit::Scope::SourceIp),
+        "route" => Ok(inbound::rate_limit::Scope::Route),
+        _ => Err(ParseError::NotARateLimitKey),
+    }
+}
+
+fn parse_profile_overrides<S: Strings>(strings: &S) -> Result<profiles::Overrides, EnvError> {
+    let mirrors = parse(strings, ENV_DESTINATION_PROFILE_ROUTE_MIRRORS, |s| {
+        parse_route_policies(s, parse_mirror)
+    })?;
+
+    let hedges = parse(strings, ENV_DESTINATION_PROFILE_ROUTE_HEDGES, |s| {
+        parse_route_policies(s, parse_hedge)
+    })?;
+
+    let per_try_timeouts = parse(
+        strings,
+        ENV_DESTINATION_PROFILE_ROUTE_PER_TRY_TIMEOUTS,
+        |s| parse_route_policies(s, parse_duration),
+    )?;
+
+    let retry_backoffs = parse(strings, ENV_DESTINATION_PROFILE_ROUTE_RETRY_BACKOFFS, |s| {
+        parse_route_policies(s, parse_retry_backoff)
+    })?;
+
+    let consistent_hashes = parse(strings, ENV_DESTINATION_PROFILE_CONSISTENT_HASH, |s| {
+        parse_route_policies(s, parse_consistent_hash)
+    })?;
+
+    let tls_server_names = parse(strings, ENV_DESTINATION_PROFILE_TLS_ORIGINATION, |s| {
+        parse_route_policies(s, parse_dns_name)
+    })?;
+
+    let mut overrides = profiles::Overrides::default();
+    for (dst, route, (addr, ratio)) in mirrors.unwrap_or_default() {
+        overrides.set_mirror(dst, route, addr, ratio);
+    }
+    for (dst, route, hedge) in hedges.unwrap_or_default() {
+        overrides.set_hedge(dst, route, hedge);
+    }
+    for (dst, route, timeout) in per_try_timeouts.unwrap_or_default() {
+        overrides.set_per_try_timeout(dst, route, timeout);
+    }
+    for (dst, route, backoff) in retry_backoffs.unwrap_or_default() {
+        overrides.set_retry_backoff(dst, route, backoff);
+    }
+    for (dst, route, config) in consistent_hashes.unwrap_or_default() {
+        if route.is_some() {
+            error!("Consistent hashing applies to all of a destination's routes");
+            return Err(ParseError::NotAConsistentHash.into());
+        }
+        overrides.set_consistent_hash(dst, config);
+    }
+    for (dst, route, name) in tls_server_names.unwrap_or_default() {
+        if route.is_some() {
+            error!("TLS origination applies to all of a destination's routes");
+            return Err(ParseError::NameError.into());
+        }
+        overrides.set_tls_server_name(dst, name);
+    }
+    Ok(overrides)
+}
+
+/// Parses a comma-separated list of `DST[#ROUTE]=VALUE` route policies.
+fn parse_route_policies<T>(
+    list: &str,
+    parse_value: impl Fn(&str) -> Result<T, ParseError>,
+) -> Result<Vec<(Addr, Option<String>, T)>, ParseError> {
+    let mut policies = Vec::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+
+        let (dst, value) = match item.find('=') {
+            Some(i) => (&item[..i], &item[i + 1..]),
+            None => {
+                error!("A value must be specified for {}", item);
+                return Err(ParseError::NotARoutePolicy);
+            }
+        };
+        let (dst, route) = match dst.find('#') {
+            Some(i) => (&dst[..i], Some(dst[i + 1..].trim().to_string())),
+            None => (dst, None),
+        };
+        let dst = parse_addr(dst.trim())?;
+        policies.push((dst, route, parse_value(value.trim())?));
+    }
+
+    Ok(policies)
+}
+
+fn parse_mirror(s: &str) -> Result<(Addr, f32), ParseError> {
+    let (addr, ratio) = match s.rfind('@') {
+        Some(i) => (&s[..i], parse_number::<f32>(s[i + 1..].trim())?),
+        None => (s, 1.0),
+    };
+    if !(0.0..=1.0).contains(&ratio) {
+        return Err(ParseError::NotAProbability);
+    }
+    Ok((parse_addr(addr.trim())?, ratio))
+}
+
+fn parse_hedge(s: &str) -> Result<profiles::http::Hedge, ParseError> {
+    if let Some(percentile) = s.strip_prefix('p') {
+        let quantile = parse_number::<f64>(percentile)? / 100.0;
+        if quantile.is_nan() || quantile <= 0.0 || quantile > 1.0 {
+            return Err(ParseError::NotAProbability);
+        }
+        return Ok(profiles::http::Hedge::LatencyQuantile(quantile));
+    }
+    parse_duration(s).map(profiles::http::Hedge::After)
+}
+
+fn parse_retry_backoff(s: &str) -> Result<ExponentialBackoff, ParseError> {
+    let (range, jitter) = match s.rfind('@') {
+        Some(i) => (&s[..i], parse_number::<f64>(s[i + 1..].trim())?),
+        None => (s, 0.0),
+    };
+    let (min, max) = match range.find('-') {
+        Some(i) => (
+            parse_duration(&range[..i])?,
+            parse_duration(&range[i + 1..])?,
+        ),
+        None => return Err(ParseError::NotABackoff),
+    };
+    ExponentialBackoff::new(min, max, jitter).map_err(|error| {
+        error!(%error, "Invalid backoff");
+        ParseError::NotABackoff
+    })
+}
+
+fn parse_consistent_hash(s: &str) -> Result<profiles::consistent_hash::Config, ParseError> {
+    use profiles::consistent_hash::{Config, Key, Table};
+
+    let (key, table) = match s.rfind('@') {
+        Some(i) => (&s[..i], &s[i + 1..]),
+        None => (s, "ring"),
+    };
+    let table = match table.trim() {
+        "ring" => Table::Ring,
+        "maglev" => Table::Maglev,
+        _ => return Err(ParseError::NotAConsistentHash),
+    };
+    let key = key.trim();
+    let key = if key == "source-ip" {
+        Key::SourceIp
+    } else if let Some(name) = key.strip_prefix("header:") {
+        Key::Header(name.parse().map_err(|_| ParseError::NotAConsistentHash)?)
+    } else if let Some(name) = key.strip_prefix("cookie:") {
+        if name.is_empty() {
+            return Err(ParseError::NotAConsistentHash);
+        }
+        Key::Cookie(name.to_string())
+    } else {
+        return Err(ParseError::NotAConsistentHash);
+    };
+    Ok(Config { key, table })
+}
+
+pub fn parse_concurrency_limit_config<S: Strings>(
+    strings: &S,
+) -> Result<concurrency_limit::adaptive::Config, EnvError> {
+    let max_limit = parse(
+        strings,
+        ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MAX,
+        parse_number::<usize>,
+    );
+    let min_limit = parse(
+        strings,
+        ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MIN,
+        parse_number::<usize>,
+    );
+    let initial_limit = parse(
+        strings,
+        ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_INITIAL,
+        parse_number::<usize>,
+    );
+    let latency_threshold = parse(
+        strings,
+        ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_LATENCY_THRESHOLD,
+        parse_duration,
+    );
+    let backoff_ratio = parse(
+        strings,
+        ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_BACKOFF_RATIO,
+        parse_number::<f64>,
+    );
+
+    let max_limit = max_limit?.unwrap_or(0);
+    let min_limit = min_limit?.unwrap_or(DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MIN);
+    if max_limit > 0 && min_limit > max_limit {
+        error!(
+            "{}={} must not be greater than {}={}",
+            ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MIN,
+            min_limit,
+            ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_MAX,
+            max_limit
+        );
+        return Err(EnvError::InvalidEnvVar);
+    }
+
+    let backoff_ratio =
+        backoff_ratio?.unwrap_or(DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_BACKOFF_RATIO);
+    if !(backoff_ratio > 0.0 && backoff_ratio < 1.0) {
+        error!(
+            "{}={} must be greater than 0 and less than 1",
+            ENV_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_BACKOFF_RATIO, backoff_ratio
+        );
+        return Err(EnvError::InvalidEnvVar);
+    }
+
+    Ok(concurrency_limit::adaptive::Config {
+        max_limit,
+        min_limit,
+        initial_limit: initial_limit?
+            .unwrap_or(DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_INITIAL),
+        latency_threshold: latency_threshold?
+            .unwrap_or(DEFAULT_OUTBOUND_ENDPOINT_CONCURRENCY_LIMIT_LATENCY_THRESHOLD),
+        backoff_ratio,
+    })
+}
+
 pub fn parse_control_addr<S: Strings>(
     strings: &S,
     base: &str,
@@ -940,14 +1740,26 @@ pub fn parse_control_addr_disable_identity<S: Strings>(
     Ok(a.map(|addr| ControlAddr { addr, identity }))
 }
 
-pub fn parse_identity_config<S: Strings>(
-    strings: &S,
-) -> Result<Option<(ControlAddr, identity::certify::Config)>, EnvError> {
+/// Describes how the local identity is obtained.
+#[derive(Clone, Debug)]
+pub enum IdentitySource {
+    /// Certified by the Identity controller.
+    Service(ControlAddr, identity::certify::Config),
+    /// Loaded from local files.
+    File(identity::file::Config),
+    /// Obtained from a SPIFFE Workload API.
+    Spiffe {
+        socket: PathBuf,
+        certify: identity::spiffe::Config,
+    },
+}
+
+pub fn parse_identity_config<S: Strings>(strings: &S) -> Result<Option<IdentitySource>, EnvError> {
     let control = parse_control_addr(strings, ENV_IDENTITY_SVC_BASE);
-    let ta = parse(strings, ENV_IDENTITY_TRUST_ANCHORS, |ref s| {
-        identity::TrustAnchors::from_pem(s).ok_or(ParseError::InvalidTrustAnchors)
-    });
+    let ta = parse_trust_anchors(strings);
     let dir = parse(strings, ENV_IDENTITY_DIR, |ref s| Ok(PathBuf::from(s)));
+    let key_algorithm = parse(strings, ENV_IDENTITY_KEY_ALGORITHM, parse_key_algorithm);
+    let generate_key = parse(strings, ENV_IDENTITY_GENERATE_KEY, parse_bool);
     let tok = parse(strings, ENV_IDENTITY_TOKEN_FILE, |ref s| {
         identity::TokenSource::if_nonempty_file(s.to_string()).map_err(|e| {
             error!("Could not read {}: {}", ENV_IDENTITY_TOKEN_FILE, e);
@@ -963,6 +1775,108 @@ pub fn parse_identity_config<S: Strings>(
         .map(|d| !d.is_empty())
         .unwrap_or(false);
 
+    let crt = parse(strings, ENV_IDENTITY_CRT_FILE, |s| Ok(PathBuf::from(s)))?;
+    let key = parse(strings, ENV_IDENTITY_KEY_FILE, |s| Ok(PathBuf::from(s)))?;
+    let socket = parse(strings, ENV_IDENTITY_SPIFFE_WORKLOAD_API_SOCKET, |s| {
+        Ok(PathBuf::from(s))
+    })?;
+    if crt.is_some() || key.is_some() || socket.is_some() {
+        // The local identity is not certified by the Identity controller, so
+        // none of its configuration may be set.
+        let mut valid = !disabled;
+        let svc_addr = format!("{}_ADDR", ENV_IDENTITY_SVC_BASE);
+        for (set, name) in &[
+            (control?.is_some(), svc_addr.as_str()),
+            (dir?.is_some(), ENV_IDENTITY_DIR),
+            (key_algorithm?.is_some(), ENV_IDENTITY_KEY_ALGORITHM),
+            (generate_key?.is_some(), ENV_IDENTITY_GENERATE_KEY),
+            (tok?.is_some(), ENV_IDENTITY_TOKEN_FILE),
+            (min_refresh?.is_some(), ENV_IDENTITY_MIN_REFRESH),
+            (max_refresh?.is_some(), ENV_IDENTITY_MAX_REFRESH),
+        ] {
+            if *set {
+                error!(
+                    "{} must be unset when the identity is loaded from a file or the SPIFFE Workload API.",
+                    name
+                );
+                valid = false;
+            }
+        }
+        if disabled {
+            error!(
+                "{} must be unset when other identity variables are set.",
+                ENV_IDENTITY_DISABLED,
+            );
+        }
+        let local_id = li?.map(tls::LocalId);
+        if local_id.is_none() {
+            error!(
+                "{} must be set when other identity variables are set.",
+                ENV_IDENTITY_IDENTITY_LOCAL_NAME,
+            );
+        }
+
+        return match (crt, key, socket, local_id) {
+            (Some(crt), Some(key), None, Some(local_id)) if valid => {
+                let (trust_anchors, trust_bundle) = ta?.ok_or_else(|| {
+                    error!(
+                        "{} or {} must be set when {} is set.",
+                        ENV_IDENTITY_TRUST_ANCHORS,
+                        ENV_IDENTITY_TRUST_ANCHORS_FILE,
+                        ENV_IDENTITY_CRT_FILE
+                    );
+                    EnvError::InvalidEnvVar
+                })?;
+                let reload_interval =
+                    parse(strings, ENV_IDENTITY_FILE_RELOAD_INTERVAL, parse_duration)?
+                        .unwrap_or(DEFAULT_IDENTITY_FILE_RELOAD_INTERVAL);
+                Ok(Some(IdentitySource::File(identity::file::Config {
+                    local_id,
+                    trust_anchors,
+                    trust_bundle,
+                    crt,
+                    key,
+                    reload_interval,
+                })))
+            }
+            (None, None, Some(socket), Some(local_id)) if valid => {
+                if ta?.is_some() {
+                    // The Workload API provides the trust anchors.
+                    error!(
+                        "{} and {} must be unset when {} is set.",
+                        ENV_IDENTITY_TRUST_ANCHORS,
+                        ENV_IDENTITY_TRUST_ANCHORS_FILE,
+                        ENV_IDENTITY_SPIFFE_WORKLOAD_API_SOCKET
+                    );
+                    return Err(EnvError::InvalidEnvVar);
+                }
+                Ok(Some(IdentitySource::Spiffe {
+                    socket,
+                    certify: identity::spiffe::Config {
+                        local_id,
+                        retry_interval: DEFAULT_IDENTITY_SPIFFE_RETRY_INTERVAL,
+                    },
+                }))
+            }
+            (crt, key, socket, _) => {
+                if socket.is_some() && (crt.is_some() || key.is_some()) {
+                    error!(
+                        "{} must not be set with {} or {}.",
+                        ENV_IDENTITY_SPIFFE_WORKLOAD_API_SOCKET,
+                        ENV_IDENTITY_CRT_FILE,
+                        ENV_IDENTITY_KEY_FILE
+                    );
+                } else if crt.is_some() != key.is_some() {
+                    error!(
+                        "{} and {} must be set together.",
+                        ENV_IDENTITY_CRT_FILE, ENV_IDENTITY_KEY_FILE
+                    );
+                }
+                Err(EnvError::InvalidEnvVar)
+            }
+        };
+    }
+
     match (
         disabled,
         control?,
@@ -987,59 +1901,92 @@ pub fn parse_identity_config<S: Strings>(
         (
             false,
             Some(control),
-            Some(trust_anchors),
+            Some((trust_anchors, trust_bundle)),
             Some(dir),
             Some(local_name),
             Some(token),
             min_refresh,
             max_refresh,
         ) => {
-            let key = {
+            let algorithm = key_algorithm?.unwrap_or_default();
+            let generate_key = generate_key?.unwrap_or(false);
+
+            // If the directory has no key, one may be generated.
+            let (key, generated) = {
                 let mut p = dir.clone();
                 p.push("key");
                 p.set_extension("p8");
 
-                fs::read(p)
-                    .map_err(|e| {
-                        error!("Failed to read key: {}", e);
-                        EnvError::InvalidEnvVar
-                    })
-                    .and_then(|b| {
-                        identity::Key::from_pkcs8(&b).map_err(|e| {
-                            error!("Invalid key: {}", e);
+                match fs::read(p) {
+                    Ok(b) => identity::Key::from_pkcs8_as(algorithm, &b)
+                        .map(|k| (k, false))
+                        .map_err(|e| {
+                            error!("Invalid {} key: {}", algorithm, e);
                             EnvError::InvalidEnvVar
-                        })
-                    })
-            };
+                        }),
+                    Err(e) if generate_key && e.kind() == std::io::ErrorKind::NotFound => {
+                        debug!(%algorithm, "Generating key");
+                        identity::Key::generate(algorithm)
+                            .map(|k| (k, true))
+                            .map_err(|_| {
+                                error!("Failed to generate {} key", algorithm);
+                                EnvError::InvalidEnvVar
+                            })
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                        error!(
+                            "No key found; set {} to generate one",
+                            ENV_IDENTITY_GENERATE_KEY
+                        );
+                        Err(EnvError::InvalidEnvVar)
+                    }
+                    Err(e) => {
+                        error!("Failed to read key: {}", e);
+                        Err(EnvError::InvalidEnvVar)
+                    }
+                }
+            }?;
 
+            // A generated key always needs a new CSR. Otherwise, a CSR is only
+            // generated if key generation is enabled and there is no CSR.
             let csr = {
                 let mut p = dir;
                 p.push("csr");
                 p.set_extension("der");
 
-                fs::read(p)
-                    .map_err(|e| {
-                        error!("Failed to read Csr: {}", e);
+                match fs::read(p) {
+                    Ok(b) if !generated => identity::Csr::from_der(b).ok_or_else(|| {
+                        error!("No CSR found");
                         EnvError::InvalidEnvVar
-                    })
-                    .and_then(|b| {
-                        identity::Csr::from_der(b).ok_or_else(|| {
-                            error!("No CSR found");
+                    }),
+                    Err(e)
+                        if !generated
+                            && !(generate_key && e.kind() == std::io::ErrorKind::NotFound) =>
+                    {
+                        error!("Failed to read Csr: {}", e);
+                        Err(EnvError::InvalidEnvVar)
+                    }
+                    _ => {
+                        debug!("Generating CSR");
+                        identity::Csr::generate(&key, &local_name).map_err(|_| {
+                            error!("Failed to sign CSR");
                             EnvError::InvalidEnvVar
                         })
-                    })
+                    }
+                }
             };
 
-            Ok(Some((
+            Ok(Some(IdentitySource::Service(
                 control,
                 identity::certify::Config {
                     local_id: tls::LocalId(local_name),
                     token,
                     trust_anchors,
                     csr: csr?,
-                    key: key?,
+                    key,
                     min_refresh: min_refresh.unwrap_or(DEFAULT_IDENTITY_MIN_REFRESH),
                     max_refresh: max_refresh.unwrap_or(DEFAULT_IDENTITY_MAX_REFRESH),
+                    trust_bundle,
                 },
             )))
         }
@@ -1071,6 +2018,133 @@ pub fn parse_identity_config<S: Strings>(
     }
 }
 
+fn parse_trace_collector_protocol(s: &str) -> Result<oc_collector::Protocol, ParseError> {
+    match s.trim() {
+        "opencensus" => Ok(oc_collector::Protocol::OpenCensus),
+        "opentelemetry" => Ok(oc_collector::Protocol::OpenTelemetry),
+        _ => Err(ParseError::NotATraceCollectorProtocol),
+    }
+}
+
+fn parse_sampling_probability(s: &str) -> Result<f64, ParseError> {
+    match parse_number::<f64>(s)? {
+        p if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(ParseError::NotAProbability),
+    }
+}
+
+fn parse_access_log<S: Strings>(strings: &S) -> Result<Option<access_log::Config>, EnvError> {
+    let output = parse(strings, ENV_ACCESS_LOG, parse_access_log_output)?;
+    let format = parse(strings, ENV_ACCESS_LOG_FORMAT, |s| {
+        s.parse::<access_log::Format>().map_err(|e| {
+            error!("{}", e);
+            ParseError::InvalidAccessLogField
+        })
+    })?;
+    Ok(output.map(|output| access_log::Config {
+        output,
+        format: format.unwrap_or_default(),
+    }))
+}
+
+fn parse_access_log_output(s: &str) -> Result<access_log::Output, ParseError> {
+    match s.trim() {
+        "" => Err(ParseError::NotAnAccessLogOutput),
+        "stdout" => Ok(access_log::Output::Stdout),
+        path => Ok(access_log::Output::File(PathBuf::from(path))),
+    }
+}
+
+/// Reads the denylist file, if one is configured, so that an invalid denylist
+/// prevents the proxy from starting.
+fn parse_identity_denylist<S: Strings>(
+    strings: &S,
+) -> Result<Option<identity::certify::DenylistFile>, EnvError> {
+    let path = parse(strings, ENV_IDENTITY_DENYLIST_FILE, |s| {
+        Ok(PathBuf::from(s))
+    })?;
+    let reload_interval = parse(
+        strings,
+        ENV_IDENTITY_DENYLIST_RELOAD_INTERVAL,
+        parse_duration,
+    )?;
+
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let denylist = fs::read_to_string(&path)
+        .map_err(|e| {
+            error!("Failed to read {}: {}", ENV_IDENTITY_DENYLIST_FILE, e);
+            EnvError::InvalidEnvVar
+        })?
+        .parse::<identity::Denylist>()
+        .map_err(|e| {
+            error!("{} is invalid: {}", ENV_IDENTITY_DENYLIST_FILE, e);
+            EnvError::InvalidEnvVar
+        })?;
+    Ok(Some(identity::certify::DenylistFile {
+        path,
+        denylist,
+        reload_interval: reload_interval.unwrap_or(DEFAULT_IDENTITY_DENYLIST_RELOAD_INTERVAL),
+    }))
+}
+
+/// Reads trust anchors from either the environment or a file. When a file is
+/// used, it is also configured to be reloaded.
+fn parse_trust_anchors<S: Strings>(
+    strings: &S,
+) -> Result<
+    Option<(
+        identity::TrustAnchors,
+        Option<identity::certify::TrustBundle>,
+    )>,
+    EnvError,
+> {
+    let ta = parse(strings, ENV_IDENTITY_TRUST_ANCHORS, |ref s| {
+        identity::TrustAnchors::from_pem(s).ok_or(ParseError::InvalidTrustAnchors)
+    });
+    let path = parse(strings, ENV_IDENTITY_TRUST_ANCHORS_FILE, |s| {
+        Ok(PathBuf::from(s))
+    });
+    let reload_interval = parse(
+        strings,
+        ENV_IDENTITY_TRUST_ANCHORS_RELOAD_INTERVAL,
+        parse_duration,
+    );
+
+    match (ta?, path?) {
+        (Some(_), Some(_)) => {
+            error!(
+                "{} and {} must not both be set",
+                ENV_IDENTITY_TRUST_ANCHORS, ENV_IDENTITY_TRUST_ANCHORS_FILE
+            );
+            Err(EnvError::InvalidEnvVar)
+        }
+        (Some(ta), None) => Ok(Some((ta, None))),
+        (None, Some(path)) => {
+            let pem = fs::read_to_string(&path).map_err(|e| {
+                error!("Failed to read {}: {}", ENV_IDENTITY_TRUST_ANCHORS_FILE, e);
+                EnvError::InvalidEnvVar
+            })?;
+            let ta = identity::TrustAnchors::from_pem(&pem).ok_or_else(|| {
+                error!(
+                    "{} has no valid trust anchors",
+                    ENV_IDENTITY_TRUST_ANCHORS_FILE
+                );
+                EnvError::InvalidEnvVar
+            })?;
+            let bundle = identity::certify::TrustBundle {
+                path,
+                reload_interval: reload_interval?
+                    .unwrap_or(DEFAULT_IDENTITY_TRUST_ANCHORS_RELOAD_INTERVAL),
+            };
+            Ok(Some((ta, Some(bundle))))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
 impl fmt::Display for EnvError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -1202,6 +2276,242 @@ mod tests {
         );
     }
 
+    #[test]
+    fn authz() {
+        assert!(parse_authz("").is_ok());
+        assert!(parse_authz(
+            "8080=*.ns.serviceaccount.identity.linkerd.cluster.local|admin.identity.local, \
+             8080/GET /healthz=unauthenticated@10.0.0.0/8|fd00::/8, \
+             9090=*"
+        )
+        .is_ok());
+        assert_eq!(parse_authz("8080").err(), Some(ParseError::InvalidAuthz));
+        assert_eq!(parse_authz("http=*").err(), Some(ParseError::NotANumber));
+        assert_eq!(
+            parse_authz("8080=a.*.local").err(),
+            Some(ParseError::InvalidAuthz)
+        );
+        assert_eq!(
+            parse_authz("8080=unauthenticated@10.0.0.0").err(),
+            Some(ParseError::NotANetwork)
+        );
+    }
+
+    #[test]
+    fn tls_origination_targets() {
+        let targets =
+            parse_tls_origination_targets(" api.example.com:443, 10.1.2.3:443=web.example.com,")
+                .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(
+            targets.get(&Addr::from_str("api.example.com:443").unwrap()),
+            Some(&tls::ServerId::from_str("api.example.com").unwrap())
+        );
+        assert_eq!(
+            targets.get(&Addr::from_str("10.1.2.3:443").unwrap()),
+            Some(&tls::ServerId::from_str("web.example.com").unwrap())
+        );
+
+        assert!(parse_tls_origination_targets("").unwrap().is_empty());
+        assert_eq!(
+            parse_tls_origination_targets("10.1.2.3:443").err(),
+            Some(ParseError::NameError)
+        );
+        assert_eq!(
+            parse_tls_origination_targets("api.example.com:443=not a name").err(),
+            Some(ParseError::NameError)
+        );
+    }
+
+    #[test]
+    fn profile_tls_origination() {
+        let names = parse_route_policies(
+            "api.example.com:443=api.example.com, 10.1.2.3:443 = web.example.com",
+            parse_dns_name,
+        )
+        .unwrap();
+        assert_eq!(
+            names,
+            vec![
+                (
+                    Addr::from_str("api.example.com:443").unwrap(),
+                    None,
+                    profiles::Name::from_str("api.example.com").unwrap()
+                ),
+                (
+                    Addr::from_str("10.1.2.3:443").unwrap(),
+                    None,
+                    profiles::Name::from_str("web.example.com").unwrap()
+                ),
+            ]
+        );
+        assert_eq!(
+            parse_route_policies("api.example.com:443=not a name", parse_dns_name).err(),
+            Some(ParseError::NameError)
+        );
+    }
+
+    #[test]
+    fn route_mirrors() {
+        let mirrors = parse_route_policies(
+            "web.ns.svc.cluster.local:8080#GET /books = web-v2.ns.svc.cluster.local:8080@0.1, \
+             10.1.2.3:80=10.1.2.4:80,",
+            parse_mirror,
+        )
+        .unwrap();
+        assert_eq!(
+            mirrors,
+            vec![
+                (
+                    Addr::from_str("web.ns.svc.cluster.local:8080").unwrap(),
+                    Some("GET /books".to_string()),
+                    (
+                        Addr::from_str("web-v2.ns.svc.cluster.local:8080").unwrap(),
+                        0.1
+                    ),
+                ),
+                (
+                    Addr::from_str("10.1.2.3:80").unwrap(),
+                    None,
+                    (Addr::from_str("10.1.2.4:80").unwrap(), 1.0),
+                ),
+            ]
+        );
+
+        assert_eq!(
+            parse_route_policies("web.ns.svc.cluster.local:8080", parse_mirror).err(),
+            Some(ParseError::NotARoutePolicy)
+        );
+        assert_eq!(
+            parse_route_policies("10.1.2.3:80=10.1.2.4:80@2", parse_mirror).err(),
+            Some(ParseError::NotAProbability)
+        );
+    }
+
+    #[test]
+    fn route_hedges() {
+        use profiles::http::Hedge;
+
+        assert_eq!(parse_hedge("p95"), Ok(Hedge::LatencyQuantile(0.95)));
+        assert_eq!(
+            parse_hedge("50ms"),
+            Ok(Hedge::After(Duration::from_millis(50)))
+        );
+        assert_eq!(parse_hedge("p0"), Err(ParseError::NotAProbability));
+        assert_eq!(parse_hedge("p101"), Err(ParseError::NotAProbability));
+        assert_eq!(parse_hedge("soon"), Err(ParseError::NotADuration));
+    }
+
+    #[test]
+    fn route_retry_backoffs() {
+        let backoff = parse_retry_backoff("25ms-1s@0.5").unwrap();
+        assert_eq!(backoff.min, Duration::from_millis(25));
+        assert_eq!(backoff.max, Duration::from_secs(1));
+        assert_eq!(backoff.jitter, 0.5);
+        assert_eq!(parse_retry_backoff("25ms-1s").unwrap().jitter, 0.0);
+
+        assert_eq!(
+            parse_retry_backoff("25ms").err(),
+            Some(ParseError::NotABackoff)
+        );
+        assert_eq!(
+            parse_retry_backoff("1s-25ms").err(),
+            Some(ParseError::NotABackoff)
+        );
+    }
+
+    #[test]
+    fn consistent_hashes() {
+        use profiles::consistent_hash::{Config, Key, Table};
+
+        assert_eq!(
+            parse_consistent_hash("header:x-user-id@maglev"),
+            Ok(Config {
+                key: Key::Header("x-user-id".parse().unwrap()),
+                table: Table::Maglev,
+            })
+        );
+        assert_eq!(
+            parse_consistent_hash("cookie:session"),
+            Ok(Config {
+                key: Key::Cookie("session".to_string()),
+                table: Table::Ring,
+            })
+        );
+        assert_eq!(
+            parse_consistent_hash("source-ip@ring"),
+            Ok(Config {
+                key: Key::SourceIp,
+                table: Table::Ring,
+            })
+        );
+        for invalid in &["header:", "cookie:", "source-ip@other", "user"] {
+            assert_eq!(
+                parse_consistent_hash(invalid),
+                Err(ParseError::NotAConsistentHash),
+                "{} must not parse",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn trace_collector_protocol() {
+        assert_eq!(
+            parse_trace_collector_protocol("opencensus"),
+            Ok(oc_collector::Protocol::OpenCensus)
+        );
+        assert_eq!(
+            parse_trace_collector_protocol(" opentelemetry "),
+            Ok(oc_collector::Protocol::OpenTelemetry)
+        );
+        assert_eq!(
+            parse_trace_collector_protocol("zipkin"),
+            Err(ParseError::NotATraceCollectorProtocol)
+        );
+    }
+
+    #[test]
+    fn sampling_probability() {
+        assert_eq!(parse_sampling_probability("0"), Ok(0.0));
+        assert_eq!(parse_sampling_probability("0.25"), Ok(0.25));
+        assert_eq!(parse_sampling_probability("1"), Ok(1.0));
+        assert_eq!(
+            parse_sampling_probability("1.5"),
+            Err(ParseError::NotAProbability)
+        );
+        assert_eq!(
+            parse_sampling_probability("-0.1"),
+            Err(ParseError::NotAProbability)
+        );
+        assert_eq!(
+            parse_sampling_probability("NaN"),
+            Err(ParseError::NotAProbability)
+        );
+        assert_eq!(
+            parse_sampling_probability("often"),
+            Err(ParseError::NotANumber)
+        );
+    }
+
+    #[test]
+    fn access_log_output() {
+        assert_eq!(
+            parse_access_log_output("stdout"),
+            Ok(access_log::Output::Stdout)
+        );
+        assert_eq!(
+            parse_access_log_output(" /var/log/linkerd/access.log "),
+            Ok(access_log::Output::File(PathBuf::from(
+                "/var/log/linkerd/access.log"
+            )))
+        );
+        assert_eq!(
+            parse_access_log_output(" "),
+            Err(ParseError::NotAnAccessLogOutput)
+        );
+    }
+
     #[test]
     fn dns_suffixes() {
         fn p(s: &str) -> Result<Vec<String>, ParseError> {
@@ -1,15 +1,22 @@
 pub use linkerd_app_core::identity::{
-    Crt, CrtKey, Csr, InvalidName, Key, Name, TokenSource, TrustAnchors,
+    Crt, CrtKey, Csr, Denylist, InvalidDenylist, InvalidKeyAlgorithm, InvalidName, Key,
+    KeyAlgorithm, Name, TokenSource, TrustAnchors,
 };
-pub use linkerd_app_core::proxy::identity::{certify, metrics, LocalCrtKey};
+pub use linkerd_app_core::proxy::identity::{certify, file, metrics, spiffe, LocalCrtKey};
 use linkerd_app_core::{
     control, dns,
     exp_backoff::{ExponentialBackoff, ExponentialBackoffStream},
     metrics::ControlHttp as Metrics,
+    proxy::http,
+    reconnect,
+    svc::{self, NewService},
     Error,
 };
 use std::future::Future;
+use std::path::PathBuf;
 use std::pin::Pin;
+use tokio::net::UnixStream;
+use tower::ServiceExt;
 use tracing::debug;
 
 // The Disabled case is extraordinarily rare.
@@ -21,6 +28,15 @@ pub enum Config {
         control: control::Config,
         certify: certify::Config,
     },
+    /// Loads the local identity's certificate and key from files.
+    File(file::Config),
+    /// Obtains the local identity's certificates from a SPIFFE Workload API
+    /// served on a UNIX socket.
+    Spiffe {
+        socket: PathBuf,
+        backoff: ExponentialBackoff,
+        certify: spiffe::Config,
+    },
 }
 
 // The Disabled case is extraordinarily rare.
@@ -28,7 +44,9 @@ pub enum Config {
 pub enum Identity {
     Disabled,
     Enabled {
-        addr: control::ControlAddr,
+        /// The Identity controller's address, if the local identity is
+        /// certified by the control plane.
+        addr: Option<control::ControlAddr>,
         local: LocalCrtKey,
         task: Task,
     },
@@ -40,7 +58,29 @@ struct Recover(ExponentialBackoff);
 pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
 
 impl Config {
-    pub fn build(self, dns: dns::Resolver, metrics: Metrics) -> Result<Identity, Error> {
+    /// Builds the local identity. If a denylist is configured, the identity
+    /// rejects peers with denied certificates and its task reloads the
+    /// denylist.
+    pub fn build(
+        self,
+        denylist: Option<certify::DenylistFile>,
+        dns: dns::Resolver,
+        metrics: Metrics,
+    ) -> Result<Identity, Error> {
+        let identity = self.build_local(dns, metrics)?;
+        match (identity, denylist) {
+            (Identity::Enabled { addr, local, task }, Some(denylist)) => {
+                let (local, daemon) = local.with_denylist(denylist);
+                let task = Box::pin(async move {
+                    futures::join!(task, daemon.run());
+                });
+                Ok(Identity::Enabled { addr, local, task })
+            }
+            (identity, _) => Ok(identity),
+        }
+    }
+
+    fn build_local(self, dns: dns::Resolver, metrics: Metrics) -> Result<Identity, Error> {
         match self {
             Config::Disabled => Ok(Identity::Disabled),
             Config::Enabled { control, certify } => {
@@ -58,7 +98,56 @@ impl Config {
                     })
                 };
 
-                Ok(Identity::Enabled { addr, local, task })
+                Ok(Identity::Enabled {
+                    addr: Some(addr),
+                    local,
+                    task,
+                })
+            }
+            Config::File(file) => {
+                let (local, daemon) = file.build();
+                let task = Box::pin(daemon.run());
+                Ok(Identity::Enabled {
+                    addr: None,
+                    local,
+                    task,
+                })
+            }
+            Config::Spiffe {
+                socket,
+                backoff,
+                certify,
+            } => {
+                let (local, daemon) = certify.build();
+
+                let connect = http::h2::Connect::new(
+                    tower::service_fn(|path: PathBuf| UnixStream::connect(path)),
+                    Default::default(),
+                );
+                let svc = svc::stack(connect)
+                    .push(reconnect::layer(Recover(backoff)))
+                    .new_service(socket.clone())
+                    // The Workload API is served locally, so requests need
+                    // not be routed by authority; but HTTP/2 requires an
+                    // absolute URI.
+                    .map_request(|mut req: http::Request<tonic::body::BoxBody>| {
+                        let mut uri = req.uri().clone().into_parts();
+                        uri.scheme = Some(http::uri::Scheme::HTTP);
+                        uri.authority = Some(http::uri::Authority::from_static("localhost"));
+                        *req.uri_mut() =
+                            http::uri::Uri::from_parts(uri).expect("URI must be valid");
+                        req
+                    });
+
+                let task = Box::pin(async move {
+                    debug!(?socket, "running");
+                    daemon.run(svc).await
+                });
+                Ok(Identity::Enabled {
+                    addr: None,
+                    local,
+                    task,
+                })
             }
         }
     }
@@ -13,7 +13,7 @@ pub use self::metrics::Metrics;
 use futures::{future, FutureExt, TryFutureExt};
 pub use linkerd_app_core::{self as core, metrics, trace};
 use linkerd_app_core::{
-    control::ControlAddr, dns, drain, proxy::http, serve, svc, Error, ProxyRuntime,
+    access_log, control::ControlAddr, dns, drain, proxy::http, serve, svc, Error, ProxyRuntime,
 };
 use linkerd_app_gateway as gateway;
 use linkerd_app_inbound::{self as inbound, Inbound};
@@ -49,10 +49,12 @@ pub struct Config {
 
     pub dns: dns::Config,
     pub identity: identity::Config,
+    pub identity_denylist: Option<identity::certify::DenylistFile>,
     pub dst: dst::Config,
     pub admin: admin::Config,
     pub tap: tap::Config,
     pub oc_collector: oc_collector::Config,
+    pub access_log: Option<access_log::Config>,
 }
 
 pub struct App {
@@ -62,6 +64,7 @@ pub struct App {
     identity: identity::Identity,
     inbound_addr: SocketAddr,
     oc_collector: oc_collector::OcCollector,
+    access_log: Option<access_log::Task>,
     outbound_addr: SocketAddr,
     start_proxy: Pin<Box<dyn std::future::Future<Output = ()> + Send + 'static>>,
     tap: tap::Tap,
@@ -88,8 +91,10 @@ impl Config {
             dns,
             dst,
             identity,
+            identity_denylist,
             inbound,
             oc_collector,
+            access_log,
             outbound,
             gateway,
             tap,
@@ -100,8 +105,13 @@ impl Config {
 
         let dns = dns.build();
 
-        let identity = info_span!("identity")
-            .in_scope(|| identity.build(dns.resolver.clone(), metrics.control.clone()))?;
+        let identity = info_span!("identity").in_scope(|| {
+            identity.build(
+                identity_denylist,
+                dns.resolver.clone(),
+                metrics.control.clone(),
+            )
+        })?;
         let report = identity.metrics().and_then(report);
 
         let (drain_tx, drain_rx) = drain::channel();
@@ -122,6 +132,15 @@ impl Config {
                 .in_scope(|| oc_collector.build(identity, dns, metrics, client_metrics))
         }?;
 
+        let (access_log, access_log_task) = match access_log {
+            Some(config) => {
+                let metrics = metrics.access_log;
+                let (log, task) = info_span!("access_log").in_scope(|| config.build(metrics))?;
+                (Some(log), Some(task))
+            }
+            None => (None, None),
+        };
+
         let admin = {
             let identity = identity.local();
             let drain = drain_rx.clone();
@@ -141,6 +160,8 @@ impl Config {
                 metrics: metrics.inbound,
                 tap: tap.registry(),
                 span_sink: oc_collector.span_sink(),
+                trace_sampler: oc_collector.sampler(),
+                access_log: access_log.clone(),
                 drain: drain_rx.clone(),
             },
         );
@@ -153,6 +174,8 @@ impl Config {
                 metrics: metrics.outbound,
                 tap: tap.registry(),
                 span_sink: oc_collector.span_sink(),
+                trace_sampler: oc_collector.sampler(),
+                access_log,
                 drain: drain_rx.clone(),
             },
         );
@@ -227,6 +250,7 @@ impl Config {
             identity,
             inbound_addr,
             oc_collector,
+            access_log: access_log_task,
             outbound_addr,
             start_proxy,
             tap,
@@ -268,7 +292,7 @@ impl App {
     pub fn identity_addr(&self) -> Option<&ControlAddr> {
         match self.identity {
             identity::Identity::Disabled => None,
-            identity::Identity::Enabled { ref addr, .. } => Some(addr),
+            identity::Identity::Enabled { ref addr, .. } => addr.as_ref(),
         }
     }
 
@@ -285,6 +309,7 @@ impl App {
             drain,
             identity,
             oc_collector,
+            access_log,
             start_proxy,
             tap,
             ..
@@ -361,6 +386,10 @@ impl App {
                             tokio::spawn(oc.task.instrument(info_span!("opencensus")));
                         }
 
+                        if let Some(task) = access_log {
+                            tokio::spawn(task.instrument(info_span!("access_log")));
+                        }
+
                         // we don't care if the admin shutdown channel is
                         // dropped or actually triggered.
                         let _ = admin_shutdown_rx.await;
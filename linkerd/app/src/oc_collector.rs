@@ -1,7 +1,11 @@
 use crate::{dns, identity::LocalCrtKey};
-use linkerd_app_core::{control, metrics::ControlHttp as HttpMetrics, Error};
+use futures::StreamExt;
+use linkerd_app_core::{
+    control, http_tracing::Sampler, metrics::ControlHttp as HttpMetrics, Error,
+};
 use linkerd_channel::into_stream::IntoStream;
 use linkerd_opencensus::{self as opencensus, metrics, proto};
+use linkerd_opentelemetry as opentelemetry;
 use std::future::Future;
 use std::pin::Pin;
 use std::{collections::HashMap, time::SystemTime};
@@ -19,6 +23,17 @@ pub struct EnabledConfig {
     pub control: control::Config,
     pub attributes: HashMap<String, String>,
     pub hostname: Option<String>,
+    pub protocol: Protocol,
+    pub sampler: Sampler,
+}
+
+/// The protocol used to export spans to the collector.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// The OpenCensus agent protocol.
+    OpenCensus,
+    /// OTLP over gRPC.
+    OpenTelemetry,
 }
 
 pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
@@ -33,6 +48,7 @@ pub enum OcCollector {
 pub struct EnabledCollector {
     pub addr: control::ControlAddr,
     pub span_sink: SpanSink,
+    pub sampler: Sampler,
     pub task: Task,
 }
 
@@ -51,44 +67,102 @@ impl Config {
             Config::Disabled => Ok(OcCollector::Disabled),
             Config::Enabled(inner) => {
                 let addr = inner.control.addr.clone();
+                let sampler = inner.sampler;
+                let backoff = inner.control.connect.backoff;
                 let svc = inner.control.build(dns, client_metrics, identity);
 
                 let (span_sink, spans_rx) = mpsc::channel(Self::SPAN_BUFFER_CAPACITY);
                 let spans_rx = spans_rx.into_stream();
 
-                let task = {
-                    use self::proto::agent::common::v1 as oc;
-
-                    let node = oc::Node {
-                        identifier: Some(oc::ProcessIdentifier {
-                            host_name: inner.hostname.unwrap_or_default(),
-                            pid: std::process::id(),
-                            start_timestamp: Some(SystemTime::now().into()),
-                        }),
-                        service_info: Some(oc::ServiceInfo {
-                            name: Self::SERVICE_NAME.to_string(),
-                        }),
-                        attributes: inner.attributes,
-                        ..oc::Node::default()
-                    };
-
-                    let addr = addr.clone();
-                    Box::pin(async move {
-                        debug!(peer.addr = ?addr, "Running");
-                        opencensus::export_spans(svc, node, spans_rx, metrics).await
-                    })
+                let task: Task = match inner.protocol {
+                    Protocol::OpenCensus => {
+                        use self::proto::agent::common::v1 as oc;
+
+                        let node = oc::Node {
+                            identifier: Some(oc::ProcessIdentifier {
+                                host_name: inner.hostname.unwrap_or_default(),
+                                pid: std::process::id(),
+                                start_timestamp: Some(SystemTime::now().into()),
+                            }),
+                            service_info: Some(oc::ServiceInfo {
+                                name: Self::SERVICE_NAME.to_string(),
+                            }),
+                            attributes: inner.attributes,
+                            ..oc::Node::default()
+                        };
+
+                        let addr = addr.clone();
+                        Box::pin(async move {
+                            debug!(peer.addr = ?addr, "Running");
+                            opencensus::export_spans(svc, node, spans_rx, metrics).await
+                        })
+                    }
+
+                    Protocol::OpenTelemetry => {
+                        use opentelemetry::proto::{
+                            common::v1 as common, resource::v1 as resource,
+                        };
+
+                        let attr = |key: &str, value| common::KeyValue {
+                            key: key.to_string(),
+                            value: Some(common::AnyValue { value: Some(value) }),
+                        };
+                        let mut attributes = vec![
+                            attr(
+                                "service.name",
+                                common::any_value::Value::StringValue(
+                                    Self::SERVICE_NAME.to_string(),
+                                ),
+                            ),
+                            attr(
+                                "process.pid",
+                                common::any_value::Value::IntValue(std::process::id().into()),
+                            ),
+                        ];
+                        if let Some(hostname) = inner.hostname {
+                            attributes.push(attr(
+                                "host.name",
+                                common::any_value::Value::StringValue(hostname),
+                            ));
+                        }
+                        for (k, v) in inner.attributes {
+                            attributes.push(attr(&k, common::any_value::Value::StringValue(v)));
+                        }
+                        let resource = resource::Resource {
+                            attributes,
+                            dropped_attributes_count: 0,
+                        };
+
+                        // The proxy records OpenCensus spans, so they're
+                        // converted as they are exported.
+                        let spans = spans_rx.map(opentelemetry::from_opencensus);
+
+                        let addr = addr.clone();
+                        Box::pin(async move {
+                            debug!(peer.addr = ?addr, "Running");
+                            opentelemetry::export_spans(svc, resource, spans, backoff, metrics)
+                                .await
+                        })
+                    }
                 };
 
                 Ok(OcCollector::Enabled(Box::new(EnabledCollector {
                     addr,
                     task,
                     span_sink,
+                    sampler,
                 })))
             }
         }
     }
 }
 
+impl Default for Protocol {
+    fn default() -> Self {
+        Self::OpenCensus
+    }
+}
+
 impl OcCollector {
     pub fn span_sink(&self) -> Option<SpanSink> {
         match self {
@@ -96,4 +170,11 @@ impl OcCollector {
             OcCollector::Enabled(inner) => Some(inner.span_sink.clone()),
         }
     }
+
+    pub fn sampler(&self) -> Sampler {
+        match self {
+            OcCollector::Disabled => Sampler::default(),
+            OcCollector::Enabled(inner) => inner.sampler.clone(),
+        }
+    }
 }
@@ -0,0 +1,683 @@
+//! A concurrency limit that adapts to the observed behavior of the inner
+//! service.
+//!
+//! The limit is adjusted by additive-increase/multiplicative-decrease (AIMD):
+//! it grows by one as requests complete quickly while at least half of the
+//! limit is in use, and it shrinks by a ratio whenever a request fails or takes
+//! longer than a latency threshold. Responses are classified with the
+//! classifier found in each request's extensions, so failures reported in a
+//! response's status or trailers also shrink the limit.
+//!
+//! A service at its limit is not ready, so that a load balancer routes requests
+//! to other endpoints rather than queueing them behind a saturated one.
+
+use futures::{ready, TryFuture};
+use http_body::Body;
+use linkerd_error::Error;
+use linkerd_http_classify::{ClassifyEos, ClassifyResponse, IsFailure};
+use linkerd_http_metrics::limits::{Handle, Limits};
+use linkerd_stack::{layer, NewService, Param};
+use pin_project::{pin_project, pinned_drop};
+use std::{
+    future::Future,
+    hash::Hash,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tower::Service;
+use tracing::{debug, trace};
+
+/// Configures an adaptive concurrency limit.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Config {
+    /// The limit before any requests have been observed.
+    pub initial_limit: usize,
+
+    /// The limit never decreases below this value.
+    pub min_limit: usize,
+
+    /// The limit never increases above this value. Zero disables the limit.
+    pub max_limit: usize,
+
+    /// Requests that take longer than this to complete decrease the limit.
+    pub latency_threshold: Duration,
+
+    /// The ratio (between 0.0 and 1.0) by which the limit is multiplied when a
+    /// request fails or exceeds the latency threshold.
+    pub backoff_ratio: f64,
+}
+
+/// Builds an adaptive concurrency limit for each target.
+#[derive(Debug)]
+pub struct NewAdaptiveLimit<C, K: Hash + Eq, N> {
+    config: Config,
+    metrics: Limits<K>,
+    inner: N,
+    _classify: PhantomData<fn() -> C>,
+}
+
+/// Enforces an adaptive limit on the number of concurrent requests to the inner
+/// service.
+///
+/// Responses are classified with the `C`-typed classifier found in each
+/// request's extensions. Requests without a classifier only fail when the
+/// inner service or the response stream fails.
+#[derive(Debug)]
+pub struct AdaptiveLimit<C, S> {
+    inner: S,
+    limiter: Option<Arc<Limiter>>,
+    // A permit acquired by `poll_ready` and consumed by `call`.
+    permit: Option<Permit>,
+    // Set while `poll_ready` waits for a permit.
+    waiting_since: Option<Instant>,
+    _classify: PhantomData<fn() -> C>,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseFuture<F, C> {
+    #[pin]
+    inner: F,
+    classify: Option<C>,
+    permit: Option<Permit>,
+}
+
+/// Holds a request's permit until its response stream completes.
+#[pin_project(PinnedDrop)]
+#[derive(Debug)]
+pub struct ResponseBody<B, C: ClassifyEos>
+where
+    C::Class: IsFailure,
+{
+    #[pin]
+    inner: B,
+    classify: Option<C>,
+    permit: Option<Permit>,
+}
+
+#[derive(Debug)]
+struct Limiter {
+    config: Config,
+    state: Mutex<State>,
+    metrics: Handle,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: f64,
+    in_flight: usize,
+    /// Tasks waiting for a permit to become available.
+    waiters: Vec<Waker>,
+}
+
+/// Permits a request to be in flight. The limit is updated from the request's
+/// outcome once it completes.
+#[derive(Debug)]
+struct Permit {
+    limiter: Arc<Limiter>,
+    start: Instant,
+    /// The time the request took to receive response headers.
+    latency: Option<Duration>,
+    released: bool,
+}
+
+// === impl Config ===
+
+impl Config {
+    pub fn is_enabled(&self) -> bool {
+        self.max_limit > 0
+    }
+}
+
+// === impl NewAdaptiveLimit ===
+
+impl<C, K: Hash + Eq, N> NewAdaptiveLimit<C, K, N> {
+    pub fn new(config: Config, metrics: Limits<K>, inner: N) -> Self {
+        Self {
+            config,
+            metrics,
+            inner,
+            _classify: PhantomData,
+        }
+    }
+
+    pub fn layer(
+        config: Config,
+        metrics: Limits<K>,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self::new(config, metrics.clone(), inner))
+    }
+}
+
+impl<C, K: Hash + Eq, N: Clone> Clone for NewAdaptiveLimit<C, K, N> {
+    fn clone(&self) -> Self {
+        Self::new(self.config, self.metrics.clone(), self.inner.clone())
+    }
+}
+
+impl<T, C, K, N> NewService<T> for NewAdaptiveLimit<C, K, N>
+where
+    T: Param<K>,
+    K: Hash + Eq,
+    N: NewService<T>,
+{
+    type Service = AdaptiveLimit<C, N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let limiter = if self.config.is_enabled() {
+            let metrics = self.metrics.get_handle(target.param());
+            Some(Arc::new(Limiter::new(self.config, metrics)))
+        } else {
+            None
+        };
+        let inner = self.inner.new_service(target);
+        AdaptiveLimit {
+            inner,
+            limiter,
+            permit: None,
+            waiting_since: None,
+            _classify: PhantomData,
+        }
+    }
+}
+
+// === impl AdaptiveLimit ===
+
+impl<C, S, A, B> Service<http::Request<A>> for AdaptiveLimit<C, S>
+where
+    S: Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+    C: ClassifyResponse + Clone + Send + Sync + 'static,
+    C::Class: IsFailure,
+{
+    type Response = http::Response<ResponseBody<B, C::ClassifyEos>>;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future, C>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if let Some(limiter) = self.limiter.as_ref() {
+            if self.permit.is_none() {
+                match Limiter::poll_acquire(limiter, cx) {
+                    Poll::Ready(permit) => {
+                        if let Some(since) = self.waiting_since.take() {
+                            limiter.metrics.record_wait(since.elapsed());
+                        }
+                        self.permit = Some(permit);
+                    }
+                    Poll::Pending => {
+                        self.waiting_since.get_or_insert_with(Instant::now);
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<A>) -> Self::Future {
+        let permit = match self.limiter {
+            None => None,
+            Some(_) => {
+                let mut permit = self
+                    .permit
+                    .take()
+                    .expect("poll_ready must be called before call");
+                permit.start = Instant::now();
+                Some(permit)
+            }
+        };
+        let classify = permit
+            .as_ref()
+            .and_then(|_| req.extensions().get::<C>().cloned());
+
+        ResponseFuture {
+            inner: self.inner.call(req),
+            classify,
+            permit,
+        }
+    }
+}
+
+impl<C, S: Clone> Clone for AdaptiveLimit<C, S> {
+    fn clone(&self) -> Self {
+        // Permits are not shared between clones.
+        Self {
+            inner: self.inner.clone(),
+            limiter: self.limiter.clone(),
+            permit: None,
+            waiting_since: None,
+            _classify: PhantomData,
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B, C> Future for ResponseFuture<F, C>
+where
+    F: TryFuture<Ok = http::Response<B>>,
+    F::Error: Into<Error>,
+    C: ClassifyResponse,
+    C::Class: IsFailure,
+{
+    type Output = Result<http::Response<ResponseBody<B, C::ClassifyEos>>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let rsp = ready!(this.inner.try_poll(cx));
+
+        let classify = this.classify.take();
+        let mut permit = this.permit.take();
+        if let Some(permit) = permit.as_mut() {
+            permit.latency = Some(permit.start.elapsed());
+        }
+        Poll::Ready(match rsp {
+            Ok(rsp) => {
+                // Without a classifier, the response succeeds unless its
+                // stream fails.
+                let classify = classify.map(|c| c.start(&rsp));
+                Ok(rsp.map(|inner| ResponseBody {
+                    inner,
+                    classify,
+                    permit,
+                }))
+            }
+            Err(e) => {
+                let e = e.into();
+                if let Some(permit) = permit {
+                    let failed = classify.map(|c| c.error(&e).is_failure());
+                    permit.release(failed.unwrap_or(true));
+                }
+                Err(e)
+            }
+        })
+    }
+}
+
+// === impl ResponseBody ===
+
+impl<B, C> ResponseBody<B, C>
+where
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    /// Releases the request's permit once its response stream completes.
+    /// Unclassified responses fail only if `stream_failed`.
+    fn release(self: Pin<&mut Self>, class: impl FnOnce(C) -> C::Class, stream_failed: bool) {
+        let this = self.project();
+        if let Some(permit) = this.permit.take() {
+            let failed = match this.classify.take() {
+                Some(classify) => class(classify).is_failure(),
+                None => stream_failed,
+            };
+            permit.release(failed);
+        }
+    }
+}
+
+impl<B, C> Body for ResponseBody<B, C>
+where
+    B: Body,
+    B::Error: Into<Error>,
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    type Data = B::Data;
+    type Error = Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let frame = ready!(self.as_mut().project().inner.poll_data(cx));
+        Poll::Ready(frame.map(|res| {
+            res.map_err(|e| {
+                let e = e.into();
+                self.as_mut().release(|c| c.error(&e), true);
+                e
+            })
+        }))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        let trailers = ready!(self.as_mut().project().inner.poll_trailers(cx)).map_err(|e| {
+            let e = e.into();
+            self.as_mut().release(|c| c.error(&e), true);
+            e
+        })?;
+        self.as_mut().release(|c| c.eos(trailers.as_ref()), false);
+        Poll::Ready(Ok(trailers))
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[pinned_drop]
+impl<B, C> PinnedDrop for ResponseBody<B, C>
+where
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    fn drop(self: Pin<&mut Self>) {
+        self.release(|c| c.eos(None), false);
+    }
+}
+
+// === impl Limiter ===
+
+impl Limiter {
+    fn new(config: Config, metrics: Handle) -> Self {
+        let min = config.min_limit.max(1);
+        let limit = config.initial_limit.max(min).min(config.max_limit.max(min));
+        metrics.set_limit(limit);
+        Self {
+            config: Config {
+                min_limit: min,
+                ..config
+            },
+            state: Mutex::new(State {
+                limit: limit as f64,
+                in_flight: 0,
+                waiters: Vec::new(),
+            }),
+            metrics,
+        }
+    }
+
+    /// Acquires a permit, or registers the task to be notified when one may
+    /// be available.
+    fn poll_acquire(this: &Arc<Self>, cx: &mut Context<'_>) -> Poll<Permit> {
+        let mut state = this.state.lock().expect("limiter state poisoned");
+        if state.in_flight >= state.limit as usize {
+            if !state.waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                trace!(in_flight = %state.in_flight, "Concurrency limit reached");
+                this.metrics.incr_saturated();
+                state.waiters.push(cx.waker().clone());
+            }
+            return Poll::Pending;
+        }
+
+        state.in_flight += 1;
+        trace!(in_flight = %state.in_flight, limit = %state.limit as usize, "Acquired permit");
+        Poll::Ready(Permit {
+            limiter: this.clone(),
+            start: Instant::now(),
+            latency: None,
+            released: false,
+        })
+    }
+
+    /// Releases a permit, updating the limit from the request's outcome if it
+    /// completed.
+    fn release(&self, outcome: Option<(Duration, bool)>) {
+        let mut state = self.state.lock().expect("limiter state poisoned");
+        let in_flight = state.in_flight;
+        state.in_flight = in_flight.saturating_sub(1);
+        for waiter in state.waiters.drain(..) {
+            waiter.wake();
+        }
+
+        let (latency, failed) = match outcome {
+            Some(outcome) => outcome,
+            None => return,
+        };
+        let prior = state.limit as usize;
+        if failed || latency > self.config.latency_threshold {
+            state.limit =
+                (state.limit * self.config.backoff_ratio).max(self.config.min_limit as f64);
+        } else if in_flight * 2 >= prior {
+            state.limit = (state.limit + 1.0).min(self.config.max_limit as f64);
+        }
+
+        let limit = state.limit as usize;
+        if limit != prior {
+            debug!(%prior, %limit, ?latency, %failed, "Updated concurrency limit");
+        }
+        self.metrics.set_limit(limit);
+    }
+}
+
+// === impl Permit ===
+
+impl Permit {
+    fn release(mut self, failed: bool) {
+        self.released = true;
+        let latency = self.latency.unwrap_or_else(|| self.start.elapsed());
+        self.limiter.release(Some((latency, failed)));
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        // Canceled requests release their permits without updating the limit.
+        if !self.released {
+            self.limiter.release(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker_ref;
+    use tokio::time;
+    use tokio_test::{assert_pending, assert_ready_ok};
+    use tower_test::mock;
+
+    const THRESHOLD: Duration = Duration::from_millis(100);
+
+    fn config(initial_limit: usize) -> Config {
+        Config {
+            initial_limit,
+            min_limit: 1,
+            max_limit: 4,
+            latency_threshold: THRESHOLD,
+            backoff_ratio: 0.5,
+        }
+    }
+
+    fn limiter(initial_limit: usize) -> Arc<Limiter> {
+        let metrics = Limits::<()>::default().get_handle(());
+        Arc::new(Limiter::new(config(initial_limit), metrics))
+    }
+
+    fn acquire(limiter: &Arc<Limiter>) -> Option<Permit> {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match Limiter::poll_acquire(limiter, &mut cx) {
+            Poll::Ready(permit) => Some(permit),
+            Poll::Pending => None,
+        }
+    }
+
+    fn limit(limiter: &Limiter) -> usize {
+        limiter.state.lock().unwrap().limit as usize
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn waits_for_permits_over_limit() {
+        let limiter = limiter(2);
+        let p0 = acquire(&limiter).expect("must acquire");
+        let _p1 = acquire(&limiter).expect("must acquire");
+        assert!(acquire(&limiter).is_none());
+
+        // Canceled requests do not change the limit.
+        drop(p0);
+        assert_eq!(limit(&limiter), 2);
+        assert!(acquire(&limiter).is_some());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn increases_while_saturated() {
+        let limiter = limiter(2);
+        for expected in 3..=4 {
+            let p0 = acquire(&limiter).unwrap();
+            let _p1 = acquire(&limiter).unwrap();
+            p0.release(false);
+            assert_eq!(limit(&limiter), expected);
+        }
+
+        // The limit does not exceed the maximum.
+        let permits = (0..4)
+            .map(|_| acquire(&limiter).unwrap())
+            .collect::<Vec<_>>();
+        for p in permits.into_iter() {
+            p.release(false);
+        }
+        assert_eq!(limit(&limiter), 4);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn decreases_on_failures_and_slow_responses() {
+        time::pause();
+        let limiter = limiter(4);
+
+        acquire(&limiter).unwrap().release(true);
+        assert_eq!(limit(&limiter), 2);
+
+        let permit = acquire(&limiter).unwrap();
+        time::advance(THRESHOLD * 2).await;
+        permit.release(false);
+        assert_eq!(limit(&limiter), 1);
+
+        // The limit does not drop below the minimum.
+        acquire(&limiter).unwrap().release(true);
+        assert_eq!(limit(&limiter), 1);
+    }
+
+    /// Classifies responses by their status and trailers, treating 5XX
+    /// statuses and `grpc-status` trailers other than 0 as failures.
+    #[derive(Clone, Debug, Default)]
+    struct Classify;
+
+    #[derive(Clone, Debug)]
+    struct Class(bool);
+
+    impl ClassifyResponse for Classify {
+        type Class = Class;
+        type ClassifyEos = Class;
+
+        fn start<B>(self, rsp: &http::Response<B>) -> Class {
+            Class(rsp.status().is_server_error())
+        }
+
+        fn error(self, _: &Error) -> Class {
+            Class(true)
+        }
+    }
+
+    impl ClassifyEos for Class {
+        type Class = Class;
+
+        fn eos(self, trailers: Option<&http::HeaderMap>) -> Class {
+            let grpc_failure = trailers
+                .and_then(|t| t.get("grpc-status"))
+                .map(|s| s != "0")
+                .unwrap_or(false);
+            Class(self.0 || grpc_failure)
+        }
+
+        fn error(self, _: &Error) -> Class {
+            Class(true)
+        }
+    }
+
+    impl IsFailure for Class {
+        fn is_failure(&self) -> bool {
+            self.0
+        }
+    }
+
+    type Svc = AdaptiveLimit<Classify, mock::Mock<http::Request<()>, http::Response<()>>>;
+
+    fn adaptive_limit(
+        initial_limit: usize,
+    ) -> (
+        mock::Spawn<Svc>,
+        mock::Handle<http::Request<()>, http::Response<()>>,
+    ) {
+        let (inner, handle) = mock::pair();
+        let mut new_limit = NewAdaptiveLimit::<Classify, (), _>::new(
+            config(initial_limit),
+            Limits::default(),
+            move |()| inner.clone(),
+        );
+        (mock::Spawn::new(new_limit.new_service(())), handle)
+    }
+
+    fn classified() -> http::Request<()> {
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(Classify);
+        req
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn not_ready_at_limit() {
+        let (mut svc, mut handle) = adaptive_limit(1);
+        handle.allow(2);
+
+        assert_ready_ok!(svc.poll_ready());
+        let rsp = svc.call(classified());
+        let (_, tx) = handle.next_request().await.expect("request must be sent");
+
+        // The balancer must route around the endpoint while it is saturated.
+        assert_pending!(svc.poll_ready());
+
+        tx.send_response(http::Response::new(()));
+        drop(rsp.await.expect("response must succeed"));
+        assert!(svc.is_woken());
+        assert_ready_ok!(svc.poll_ready());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn holds_unclassified_permits_until_end_of_stream() {
+        let (mut svc, mut handle) = adaptive_limit(1);
+        handle.allow(2);
+
+        assert_ready_ok!(svc.poll_ready());
+        let rsp = svc.call(http::Request::new(()));
+        let (_, tx) = handle.next_request().await.expect("request must be sent");
+        tx.send_response(http::Response::new(()));
+        let rsp = rsp.await.expect("response must succeed");
+
+        // The response body is still streaming.
+        assert_pending!(svc.poll_ready());
+
+        drop(rsp);
+        assert!(svc.is_woken());
+        assert_ready_ok!(svc.poll_ready());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn classifies_failures_in_trailers() {
+        let (mut svc, mut handle) = adaptive_limit(4);
+        handle.allow(1);
+        assert_ready_ok!(svc.poll_ready());
+        let rsp = svc.call(classified());
+        let (_, tx) = handle.next_request().await.expect("request must be sent");
+        tx.send_response(http::Response::new(()));
+        let rsp = rsp.await.expect("response must succeed");
+
+        // The limit is only updated once the response is classified.
+        let limiter = svc.get_ref().limiter.clone().unwrap();
+        assert_eq!(limit(&limiter), 4);
+
+        let mut body = rsp.into_body();
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", http::HeaderValue::from_static("14"));
+        Pin::new(&mut body).release(|c| c.eos(Some(&trailers)), false);
+        assert_eq!(limit(&limiter), 2);
+    }
+}
@@ -72,6 +72,12 @@ impl ExponentialBackoff {
         Ok(ExponentialBackoff { min, max, jitter })
     }
 
+    /// Returns a jittered duration to wait after `iterations` prior attempts.
+    pub fn delay(&self, iterations: u32) -> Duration {
+        let base = self.base(iterations);
+        base + self.jitter(base, &mut thread_rng())
+    }
+
     fn base(&self, iterations: u32) -> Duration {
         debug_assert!(
             self.min <= self.max,
@@ -52,6 +52,11 @@ pub trait ClassifyEos {
     fn error(self, error: &Error) -> Self::Class;
 }
 
+/// Determines whether a response classification indicates a failure.
+pub trait IsFailure {
+    fn is_failure(&self) -> bool;
+}
+
 // Used for stack targets that can produce a `Classify` implementation.
 pub trait CanClassify {
     type Classify: Classify;
@@ -0,0 +1,147 @@
+use super::{LastUpdate, Prefixed, Registry, Report};
+use linkerd_metrics::{Counter, FmtLabels, FmtMetric, FmtMetrics, Metric};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::trace;
+
+#[derive(Debug)]
+pub struct Authz<T>(Arc<Mutex<Registry<T, Metrics>>>)
+where
+    T: Hash + Eq;
+
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Mutex<Metrics>>);
+
+#[derive(Debug)]
+pub struct Metrics {
+    last_update: Instant,
+    allow: Counter,
+    deny: Counter,
+}
+
+// === impl Authz ===
+
+impl<T: Hash + Eq> Default for Authz<T> {
+    fn default() -> Self {
+        Authz(Arc::new(Mutex::new(Registry::default())))
+    }
+}
+
+impl<T: Hash + Eq> Authz<T> {
+    pub fn into_report(self, retain_idle: Duration) -> Report<T, Metrics> {
+        Report::new(retain_idle, self.0)
+    }
+
+    pub fn get_handle(&self, target: T) -> Handle {
+        let mut reg = self.0.lock().expect("authz metrics registry poisoned");
+        Handle(reg.entry(target).or_default().clone())
+    }
+}
+
+impl<T: Hash + Eq> Clone for Authz<T> {
+    fn clone(&self) -> Self {
+        Authz(self.0.clone())
+    }
+}
+
+// === impl Handle ===
+
+impl Handle {
+    /// Records that a request was permitted by an authorization policy.
+    pub fn incr_allow(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.allow.incr();
+        }
+    }
+
+    /// Records that a request was denied by an authorization policy.
+    pub fn incr_deny(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.deny.incr();
+        }
+    }
+}
+
+// === impl Metrics ===
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            last_update: Instant::now(),
+            allow: Counter::default(),
+            deny: Counter::default(),
+        }
+    }
+}
+
+impl LastUpdate for Metrics {
+    fn last_update(&self) -> Instant {
+        self.last_update
+    }
+}
+
+// === impl Report ===
+
+impl<T> Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn authz_allow_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("authz_allow_total"),
+            "Total count of requests permitted by an authorization policy.",
+        )
+    }
+
+    fn authz_deny_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("authz_deny_total"),
+            "Total count of requests denied by an authorization policy.",
+        )
+    }
+}
+
+impl<T> FmtMetrics for Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut registry = match self.registry.lock() {
+            Err(_) => return Ok(()),
+            Ok(r) => r,
+        };
+        trace!(
+            prefix = %self.prefix,
+            targets = %registry.len(),
+            "Formatting authorization metrics",
+        );
+
+        if registry.is_empty() {
+            return Ok(());
+        }
+
+        let metric = self.authz_allow_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.allow.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        let metric = self.authz_deny_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.deny.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        registry.retain_since(Instant::now() - self.retain_idle);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use super::{LastUpdate, Prefixed, Registry, Report};
+use linkerd_metrics::{Counter, FmtLabels, FmtMetric, FmtMetrics, Gauge, Metric};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::trace;
+
+#[derive(Debug)]
+pub struct Ejections<T>(Arc<Mutex<Registry<T, Metrics>>>)
+where
+    T: Hash + Eq;
+
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Mutex<Metrics>>);
+
+#[derive(Debug)]
+pub struct Metrics {
+    last_update: Instant,
+    consecutive_failures: Counter,
+    failure_rate: Counter,
+    ejected: Gauge,
+}
+
+/// Describes why an endpoint was ejected.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    ConsecutiveFailures,
+    FailureRate,
+}
+
+struct ReasonLabel(Reason);
+
+// === impl Ejections ===
+
+impl<T: Hash + Eq> Default for Ejections<T> {
+    fn default() -> Self {
+        Ejections(Arc::new(Mutex::new(Registry::default())))
+    }
+}
+
+impl<T: Hash + Eq> Ejections<T> {
+    pub fn into_report(self, retain_idle: Duration) -> Report<T, Metrics> {
+        Report::new(retain_idle, self.0)
+    }
+
+    pub fn get_handle(&self, target: T) -> Handle {
+        let mut reg = self.0.lock().expect("ejection metrics registry poisoned");
+        Handle(reg.entry(target).or_default().clone())
+    }
+}
+
+impl<T: Hash + Eq> Clone for Ejections<T> {
+    fn clone(&self) -> Self {
+        Ejections(self.0.clone())
+    }
+}
+
+// === impl Handle ===
+
+impl Handle {
+    /// Records that an endpoint was ejected from its load balancer.
+    pub fn ejected(&self, reason: Reason) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.ejected.incr();
+            match reason {
+                Reason::ConsecutiveFailures => m.consecutive_failures.incr(),
+                Reason::FailureRate => m.failure_rate.incr(),
+            }
+        }
+    }
+
+    /// Records that a previously-ejected endpoint is no longer ejected.
+    pub fn readmitted(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.ejected.decr();
+        }
+    }
+}
+
+// === impl Metrics ===
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            last_update: Instant::now(),
+            consecutive_failures: Counter::default(),
+            failure_rate: Counter::default(),
+            ejected: Gauge::default(),
+        }
+    }
+}
+
+impl LastUpdate for Metrics {
+    fn last_update(&self) -> Instant {
+        self.last_update
+    }
+}
+
+// === impl Report ===
+
+impl<T> Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn ejections_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("ejections_total"),
+            "Total count of times an endpoint was ejected from its load balancer.",
+        )
+    }
+
+    fn ejected(&self) -> Metric<'_, Prefixed<'_, &'static str>, Gauge> {
+        Metric::new(
+            self.prefix_key("ejected"),
+            "The number of load balancers from which an endpoint is currently ejected.",
+        )
+    }
+}
+
+impl<T> FmtMetrics for Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut registry = match self.registry.lock() {
+            Err(_) => return Ok(()),
+            Ok(r) => r,
+        };
+        trace!(
+            prefix = %self.prefix,
+            targets = %registry.len(),
+            "Formatting HTTP ejection metrics",
+        );
+
+        if registry.is_empty() {
+            return Ok(());
+        }
+
+        let metric = self.ejections_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.consecutive_failures.fmt_metric_labeled(
+                    f,
+                    &metric.name,
+                    (tgt, ReasonLabel(Reason::ConsecutiveFailures)),
+                )?;
+                m.failure_rate.fmt_metric_labeled(
+                    f,
+                    &metric.name,
+                    (tgt, ReasonLabel(Reason::FailureRate)),
+                )?;
+            }
+        }
+
+        let metric = self.ejected();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.ejected.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        registry.retain_since(Instant::now() - self.retain_idle);
+
+        Ok(())
+    }
+}
+
+// === impl Reason ===
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConsecutiveFailures => write!(f, "consecutive_failures"),
+            Self::FailureRate => write!(f, "failure_rate"),
+        }
+    }
+}
+
+impl FmtLabels for ReasonLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reason=\"{}\"", self.0)
+    }
+}
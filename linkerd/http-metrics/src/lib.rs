@@ -1,12 +1,20 @@
 #![deny(warnings, rust_2018_idioms)]
 
-pub use self::{requests::Requests, retries::Retries};
+pub use self::{
+    authz::Authz, ejections::Ejections, limits::Limits, mirrors::Mirrors, rate_limits::RateLimits,
+    requests::Requests, retries::Retries,
+};
 use linkerd_metrics::{LastUpdate, Store};
 use std::fmt;
 use std::hash::Hash;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+pub mod authz;
+pub mod ejections;
+pub mod limits;
+pub mod mirrors;
+pub mod rate_limits;
 pub mod requests;
 pub mod retries;
 
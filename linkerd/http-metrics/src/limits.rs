@@ -0,0 +1,178 @@
+use super::{LastUpdate, Prefixed, Registry, Report};
+use linkerd_metrics::{
+    latency, Counter, FmtLabels, FmtMetric, FmtMetrics, Gauge, Histogram, Metric,
+};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::trace;
+
+#[derive(Debug)]
+pub struct Limits<T>(Arc<Mutex<Registry<T, Metrics>>>)
+where
+    T: Hash + Eq;
+
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Mutex<Metrics>>);
+
+#[derive(Debug)]
+pub struct Metrics {
+    last_update: Instant,
+    limit: Gauge,
+    saturated: Counter,
+    wait: Histogram<latency::Ms>,
+}
+
+// === impl Limits ===
+
+impl<T: Hash + Eq> Default for Limits<T> {
+    fn default() -> Self {
+        Limits(Arc::new(Mutex::new(Registry::default())))
+    }
+}
+
+impl<T: Hash + Eq> Limits<T> {
+    pub fn into_report(self, retain_idle: Duration) -> Report<T, Metrics> {
+        Report::new(retain_idle, self.0)
+    }
+
+    pub fn get_handle(&self, target: T) -> Handle {
+        let mut reg = self.0.lock().expect("limit metrics registry poisoned");
+        Handle(reg.entry(target).or_default().clone())
+    }
+}
+
+impl<T: Hash + Eq> Clone for Limits<T> {
+    fn clone(&self) -> Self {
+        Limits(self.0.clone())
+    }
+}
+
+// === impl Handle ===
+
+impl Handle {
+    /// Records the current concurrency limit.
+    pub fn set_limit(&self, limit: usize) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.limit = Gauge::from(limit as u64);
+        }
+    }
+
+    /// Records that the service stopped accepting requests because the
+    /// concurrency limit was reached.
+    pub fn incr_saturated(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.saturated.incr();
+        }
+    }
+
+    /// Records how long the service waited at its concurrency limit before it
+    /// could accept a request.
+    pub fn record_wait(&self, wait: Duration) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.wait.add(wait);
+        }
+    }
+}
+
+// === impl Metrics ===
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            last_update: Instant::now(),
+            limit: Gauge::default(),
+            saturated: Counter::default(),
+            wait: Histogram::default(),
+        }
+    }
+}
+
+impl LastUpdate for Metrics {
+    fn last_update(&self) -> Instant {
+        self.last_update
+    }
+}
+
+// === impl Report ===
+
+impl<T> Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn concurrency_limit(&self) -> Metric<'_, Prefixed<'_, &'static str>, Gauge> {
+        Metric::new(
+            self.prefix_key("concurrency_limit"),
+            "The current adaptive limit on the number of in-flight requests.",
+        )
+    }
+
+    fn concurrency_limit_saturated_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("concurrency_limit_saturated_total"),
+            "Total count of times the service stopped accepting requests because the concurrency limit was reached.",
+        )
+    }
+
+    fn concurrency_limit_wait_duration_ms(
+        &self,
+    ) -> Metric<'_, Prefixed<'_, &'static str>, Histogram<latency::Ms>> {
+        Metric::new(
+            self.prefix_key("concurrency_limit_wait_duration_ms"),
+            "Time the service spent at its concurrency limit before it could accept another request.",
+        )
+    }
+}
+
+impl<T> FmtMetrics for Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut registry = match self.registry.lock() {
+            Err(_) => return Ok(()),
+            Ok(r) => r,
+        };
+        trace!(
+            prefix = %self.prefix,
+            targets = %registry.len(),
+            "Formatting concurrency limit metrics",
+        );
+
+        if registry.is_empty() {
+            return Ok(());
+        }
+
+        let metric = self.concurrency_limit();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.limit.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        let metric = self.concurrency_limit_saturated_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.saturated.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        let metric = self.concurrency_limit_wait_duration_ms();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.wait.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        registry.retain_since(Instant::now() - self.retain_idle);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use super::{LastUpdate, Prefixed, Registry, Report};
+use linkerd_metrics::{Counter, FmtLabels, FmtMetric, FmtMetrics, Metric};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::trace;
+
+#[derive(Debug)]
+pub struct Mirrors<T>(Arc<Mutex<Registry<T, Metrics>>>)
+where
+    T: Hash + Eq;
+
+#[derive(Clone, Debug)]
+pub struct Handle(Arc<Mutex<Metrics>>);
+
+#[derive(Debug)]
+pub struct Metrics {
+    last_update: Instant,
+    success: Counter,
+    failure: Counter,
+    skipped: Counter,
+}
+
+struct ClassLabel(&'static str);
+
+// === impl Mirrors ===
+
+impl<T: Hash + Eq> Default for Mirrors<T> {
+    fn default() -> Self {
+        Mirrors(Arc::new(Mutex::new(Registry::default())))
+    }
+}
+
+impl<T: Hash + Eq> Mirrors<T> {
+    pub fn into_report(self, retain_idle: Duration) -> Report<T, Metrics> {
+        Report::new(retain_idle, self.0)
+    }
+
+    pub fn get_handle(&self, target: T) -> Handle {
+        let mut reg = self.0.lock().expect("mirror metrics registry poisoned");
+        Handle(reg.entry(target).or_default().clone())
+    }
+}
+
+impl<T: Hash + Eq> Clone for Mirrors<T> {
+    fn clone(&self) -> Self {
+        Mirrors(self.0.clone())
+    }
+}
+
+// === impl Handle ===
+
+impl Handle {
+    /// Records the outcome of a mirrored request.
+    pub fn incr_mirrored(&self, is_success: bool) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            if is_success {
+                m.success.incr();
+            } else {
+                m.failure.incr();
+            }
+        }
+    }
+
+    /// Records that a request was selected for mirroring but could not be
+    /// mirrored.
+    pub fn incr_skipped(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.skipped.incr();
+        }
+    }
+}
+
+// === impl Metrics ===
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            last_update: Instant::now(),
+            success: Counter::default(),
+            failure: Counter::default(),
+            skipped: Counter::default(),
+        }
+    }
+}
+
+impl LastUpdate for Metrics {
+    fn last_update(&self) -> Instant {
+        self.last_update
+    }
+}
+
+// === impl Report ===
+
+impl<T> Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn mirror_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("mirror_total"),
+            "Total count of mirrored HTTP requests.",
+        )
+    }
+
+    fn mirror_skipped_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("mirror_skipped_total"),
+            "Total count of HTTP requests selected for mirroring that could not be mirrored.",
+        )
+    }
+}
+
+impl<T> FmtMetrics for Report<T, Metrics>
+where
+    T: FmtLabels + Hash + Eq,
+{
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut registry = match self.registry.lock() {
+            Err(_) => return Ok(()),
+            Ok(r) => r,
+        };
+        trace!(
+            prefix = %self.prefix,
+            targets = %registry.len(),
+            "Formatting HTTP mirror metrics",
+        );
+
+        if registry.is_empty() {
+            return Ok(());
+        }
+
+        let metric = self.mirror_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.success
+                    .fmt_metric_labeled(f, &metric.name, (tgt, ClassLabel("success")))?;
+                m.failure
+                    .fmt_metric_labeled(f, &metric.name, (tgt, ClassLabel("failure")))?;
+            }
+        }
+
+        let metric = self.mirror_skipped_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.skipped.fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
+        registry.retain_since(Instant::now() - self.retain_idle);
+
+        Ok(())
+    }
+}
+
+impl FmtLabels for ClassLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "classification=\"{}\"", self.0)
+    }
+}
@@ -4,7 +4,7 @@ mod service;
 use super::{LastUpdate, Registry, Report};
 use indexmap::IndexMap;
 use linkerd_http_classify::ClassifyResponse;
-use linkerd_metrics::{latency, Counter, FmtMetrics, Histogram};
+use linkerd_metrics::{latency, Bucket, Counter, FmtMetrics, Histogram};
 use linkerd_stack::layer;
 use std::{
     fmt::Debug,
@@ -70,6 +70,51 @@ impl<T: Hash + Eq, C: Hash + Eq> Requests<T, C> {
         let reg = self.0.clone();
         layer::mk(move |inner| NewHttpMetrics::new(reg.clone(), inner))
     }
+
+    /// Estimates the `quantile` (between 0.0 and 1.0) of a target's response
+    /// latency as the upper bound of the latency bucket that contains it.
+    ///
+    /// Returns `None` if fewer than `min_samples` responses have been recorded
+    /// for the target or if the quantile falls in the unbounded bucket.
+    pub fn latency_quantile(
+        &self,
+        target: &T,
+        quantile: f64,
+        min_samples: u64,
+    ) -> Option<Duration> {
+        let reg = self.0.lock().ok()?;
+        let metrics = reg.get(target)?.lock().ok()?;
+
+        // Sum the bucket counts across all response statuses.
+        let mut counts = Vec::<(Bucket, u64)>::new();
+        for status in metrics.by_status.values() {
+            for (i, (bucket, count)) in status.latency.into_iter().enumerate() {
+                let count: u64 = count.into();
+                match counts.get_mut(i) {
+                    Some((_, total)) => *total += count,
+                    None => counts.push((*bucket, count)),
+                }
+            }
+        }
+
+        let total = counts.iter().map(|(_, c)| c).sum::<u64>();
+        if total == 0 || total < min_samples {
+            return None;
+        }
+
+        let rank = (quantile * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in counts {
+            seen += count;
+            if seen >= rank {
+                return match bucket {
+                    Bucket::Le(ms) => Some(Duration::from_micros((ms * 1_000.0) as u64)),
+                    Bucket::Inf => None,
+                };
+            }
+        }
+        None
+    }
 }
 
 impl<T: Hash + Eq, C: Hash + Eq> Clone for Requests<T, C> {
@@ -177,4 +222,45 @@ mod tests {
 
         drop((registry, report));
     }
+
+    #[test]
+    fn latency_quantile() {
+        use std::time::Duration;
+
+        let r = super::Requests::<usize, ()>::default();
+        assert_eq!(r.latency_quantile(&1, 0.5, 0), None);
+        {
+            let mut registry = r.0.lock().unwrap();
+            let metrics = registry.entry(1).or_insert_with(Default::default);
+            let mut metrics = metrics.lock().unwrap();
+            let ok = metrics
+                .by_status
+                .entry(Some(http::StatusCode::OK))
+                .or_default();
+            for ms in 1..=90 {
+                ok.latency.add(Duration::from_millis(ms));
+            }
+            let err = metrics
+                .by_status
+                .entry(Some(http::StatusCode::INTERNAL_SERVER_ERROR))
+                .or_default();
+            for _ in 0..10 {
+                err.latency.add(Duration::from_secs(10));
+            }
+        }
+
+        assert_eq!(r.latency_quantile(&1, 0.5, 101), None);
+        assert_eq!(
+            r.latency_quantile(&1, 0.5, 100),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(
+            r.latency_quantile(&1, 0.9, 100),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            r.latency_quantile(&1, 0.95, 100),
+            Some(Duration::from_secs(10))
+        );
+    }
 }
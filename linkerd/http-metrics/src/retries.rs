@@ -19,6 +19,9 @@ pub struct Metrics {
     last_update: Instant,
     retryable: Counter,
     no_budget: Counter,
+    hedges: Counter,
+    hedges_no_budget: Counter,
+    per_try_timeouts: Counter,
 }
 
 struct NoBudgetLabel;
@@ -60,6 +63,25 @@ impl Handle {
             }
         }
     }
+
+    /// Records that a request was outstanding long enough to be hedged.
+    pub fn incr_hedge(&self, has_budget: bool) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.hedges.incr();
+            if !has_budget {
+                m.hedges_no_budget.incr();
+            }
+        }
+    }
+
+    /// Records that a request attempt exceeded its per-try timeout.
+    pub fn incr_per_try_timeout(&self) {
+        if let Ok(mut m) = self.0.lock() {
+            m.last_update = Instant::now();
+            m.per_try_timeouts.incr();
+        }
+    }
 }
 
 // === impl Metrics ===
@@ -70,6 +92,9 @@ impl Default for Metrics {
             last_update: Instant::now(),
             retryable: Counter::default(),
             no_budget: Counter::default(),
+            hedges: Counter::default(),
+            hedges_no_budget: Counter::default(),
+            per_try_timeouts: Counter::default(),
         }
     }
 }
@@ -92,6 +117,20 @@ where
             "Total count of retryable HTTP responses.",
         )
     }
+
+    fn hedges_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("hedges_total"),
+            "Total count of HTTP requests outstanding long enough to be hedged.",
+        )
+    }
+
+    fn per_try_timeouts_total(&self) -> Metric<'_, Prefixed<'_, &'static str>, Counter> {
+        Metric::new(
+            self.prefix_key("per_try_timeouts_total"),
+            "Total count of HTTP request attempts that exceeded a per-try timeout.",
+        )
+    }
 }
 
 impl<T> FmtMetrics for Report<T, Metrics>
@@ -123,6 +162,25 @@ where
             }
         }
 
+        let metric = self.hedges_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.hedges.fmt_metric_labeled(f, &metric.name, tgt)?;
+                m.hedges_no_budget
+                    .fmt_metric_labeled(f, &metric.name, (tgt, NoBudgetLabel))?;
+            }
+        }
+
+        let metric = self.per_try_timeouts_total();
+        metric.fmt_help(f)?;
+        for (tgt, tm) in registry.iter() {
+            if let Ok(m) = tm.lock() {
+                m.per_try_timeouts
+                    .fmt_metric_labeled(f, &metric.name, tgt)?;
+            }
+        }
+
         registry.retain_since(Instant::now() - self.retain_idle);
 
         Ok(())
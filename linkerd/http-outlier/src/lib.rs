@@ -0,0 +1,922 @@
+//! Outlier detection for load balanced endpoints.
+//!
+//! Each endpoint's responses are classified as they complete. When an
+//! endpoint fails too many requests in a row, or too large a share of its
+//! requests, it is ejected: it stops advertising readiness so the balancer
+//! routes around it. Ejected endpoints are readmitted after an exponential
+//! backoff that grows each time the endpoint is ejected again before it has
+//! served a successful response.
+//!
+//! The share of a balancer's endpoints that may be ejected at once may be
+//! capped, so that a balancer is not emptied when all of its endpoints fail
+//! (e.g. because of a bad deploy of the callers' shared dependency).
+
+#![deny(warnings, rust_2018_idioms)]
+
+use futures::{ready, StreamExt, TryFuture};
+use http_body::Body;
+use linkerd_error::Error;
+use linkerd_exp_backoff::{ExponentialBackoff, ExponentialBackoffStream};
+pub use linkerd_http_classify::IsFailure;
+use linkerd_http_classify::{ClassifyEos, ClassifyResponse};
+use linkerd_http_metrics::ejections::{Ejections, Handle, Reason};
+use linkerd_stack::{layer, NewService, Param};
+use pin_project::{pin_project, pinned_drop};
+use std::{
+    future::Future,
+    hash::Hash,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::{debug, trace};
+
+/// Configures when endpoints are ejected from a load balancer.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Config {
+    /// Ejects an endpoint after this many consecutive failures. Zero disables
+    /// consecutive failure detection.
+    pub consecutive_failures: u32,
+
+    /// Ejects an endpoint when its failure rate exceeds a threshold.
+    pub failure_rate: Option<FailureRate>,
+
+    /// Determines how long an endpoint remains ejected.
+    pub backoff: ExponentialBackoff,
+
+    /// The maximum percentage of a balancer's endpoints that may be ejected at
+    /// once. As in Envoy, an endpoint may be ejected whenever fewer than this
+    /// share of its balancer's endpoints are ejected, so at least one endpoint
+    /// may always be ejected. Unlimited when unset.
+    pub max_ejection_percent: Option<u32>,
+}
+
+/// Configures failure rate based ejection.
+#[derive(Copy, Clone, Debug)]
+pub struct FailureRate {
+    /// The ratio of failed responses to all responses at or above which an
+    /// endpoint is ejected.
+    pub ratio: f64,
+
+    /// The minimum number of responses that must be observed in a window
+    /// before its failure rate is considered.
+    pub min_requests: u32,
+
+    /// The interval over which failure rates are computed.
+    pub window: Duration,
+}
+
+/// Builds an `Eject` service for each of a balancer's endpoints.
+///
+/// The stack clones a `NewEject` for each balancer it builds, so each clone
+/// tracks the ejections of its own set of endpoints.
+#[derive(Debug)]
+pub struct NewEject<C, K: Hash + Eq, N> {
+    config: Config,
+    metrics: Ejections<K>,
+    group: Arc<Mutex<Group>>,
+    inner: N,
+    _classify: PhantomData<fn() -> C>,
+}
+
+/// Ejects the inner service when its responses indicate that it is failing.
+///
+/// Responses are classified with the `C`-typed classifier found in each
+/// request's extensions. Requests without a classifier are not considered.
+#[derive(Debug)]
+pub struct Eject<C, S> {
+    inner: S,
+    config: Config,
+    outcomes: Option<Arc<Mutex<Outcomes>>>,
+    group: Arc<Mutex<Group>>,
+    state: State,
+    backoff: ExponentialBackoffStream,
+    metrics: Handle,
+    _classify: PhantomData<fn() -> C>,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct ResponseFuture<F, C> {
+    #[pin]
+    inner: F,
+    classify: Option<C>,
+    outcomes: Option<Arc<Mutex<Outcomes>>>,
+    generation: u64,
+    config: Config,
+}
+
+#[pin_project(PinnedDrop)]
+#[derive(Debug)]
+pub struct ResponseBody<B, C: ClassifyEos>
+where
+    C::Class: IsFailure,
+{
+    #[pin]
+    inner: B,
+    classify: Option<C>,
+    outcomes: Option<Arc<Mutex<Outcomes>>>,
+    generation: u64,
+    config: Config,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    Admitted,
+    /// The endpoint has been ejected and is waiting for its backoff to
+    /// expire.
+    Ejected,
+}
+
+/// Response outcomes shared between a service and its pending responses.
+#[derive(Debug)]
+pub struct Outcomes {
+    /// Incremented each time the outcomes are reset, so that responses to
+    /// requests dispatched before an endpoint was ejected or readmitted are
+    /// not recorded.
+    generation: u64,
+    consecutive_failures: u32,
+    window_start: Instant,
+    requests: u32,
+    failures: u32,
+    /// Set when a response succeeds so that the service can reset its backoff.
+    recovered: bool,
+}
+
+/// Counts a balancer's endpoints and how many of them are ejected.
+#[derive(Debug, Default)]
+struct Group {
+    endpoints: usize,
+    ejected: usize,
+}
+
+// === impl Config ===
+
+impl Config {
+    fn is_enabled(&self) -> bool {
+        self.consecutive_failures > 0 || self.failure_rate.is_some()
+    }
+}
+
+// === impl NewEject ===
+
+impl<C, K: Hash + Eq, N> NewEject<C, K, N> {
+    pub fn new(config: Config, metrics: Ejections<K>, inner: N) -> Self {
+        Self {
+            config,
+            metrics,
+            group: Arc::new(Mutex::new(Group::default())),
+            inner,
+            _classify: PhantomData,
+        }
+    }
+
+    pub fn layer(
+        config: Config,
+        metrics: Ejections<K>,
+    ) -> impl layer::Layer<N, Service = Self> + Clone {
+        layer::mk(move |inner| Self::new(config, metrics.clone(), inner))
+    }
+}
+
+impl<C, K: Hash + Eq, N: Clone> Clone for NewEject<C, K, N> {
+    fn clone(&self) -> Self {
+        Self::new(self.config, self.metrics.clone(), self.inner.clone())
+    }
+}
+
+impl<T, C, K, N> NewService<T> for NewEject<C, K, N>
+where
+    T: Param<K>,
+    K: Hash + Eq,
+    N: NewService<T>,
+{
+    type Service = Eject<C, N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        let metrics = self.metrics.get_handle(target.param());
+        let inner = self.inner.new_service(target);
+        Eject::grouped(self.config, metrics, self.group.clone(), inner)
+    }
+}
+
+// === impl Eject ===
+
+impl<C, S> Eject<C, S> {
+    pub fn new(config: Config, metrics: Handle, inner: S) -> Self {
+        Self::grouped(
+            config,
+            metrics,
+            Arc::new(Mutex::new(Group::default())),
+            inner,
+        )
+    }
+
+    fn grouped(config: Config, metrics: Handle, group: Arc<Mutex<Group>>, inner: S) -> Self {
+        let outcomes = if config.is_enabled() {
+            Some(Arc::new(Mutex::new(Outcomes::new(Instant::now()))))
+        } else {
+            None
+        };
+        if let Ok(mut group) = group.lock() {
+            group.endpoints += 1;
+        }
+        Self {
+            inner,
+            config,
+            outcomes,
+            group,
+            state: State::Admitted,
+            backoff: config.backoff.stream(),
+            metrics,
+            _classify: PhantomData,
+        }
+    }
+
+    /// Ejects the endpoint if its recent responses warrant it.
+    fn update(&mut self) {
+        let outcomes = match self.outcomes.as_ref() {
+            Some(outcomes) => outcomes,
+            None => return,
+        };
+        let mut outcomes = match outcomes.lock() {
+            Ok(outcomes) => outcomes,
+            Err(_) => return,
+        };
+
+        // Once a readmitted endpoint serves a successful response, it is no
+        // longer penalized for having been ejected previously.
+        if std::mem::take(&mut outcomes.recovered) {
+            trace!("Resetting ejection backoff");
+            self.backoff = self.config.backoff.stream();
+        }
+
+        if let Some(reason) = outcomes.check(&self.config) {
+            let ejected = match self.group.lock() {
+                Ok(mut group) => group.eject(self.config.max_ejection_percent),
+                Err(_) => false,
+            };
+            if !ejected {
+                trace!(%reason, "Too many endpoints are ejected");
+                return;
+            }
+            debug!(%reason, "Ejecting endpoint");
+            outcomes.reset(Instant::now());
+            self.metrics.ejected(reason);
+            self.state = State::Ejected;
+        }
+    }
+
+    fn readmit(&mut self) {
+        if let Some(outcomes) = self.outcomes.as_ref() {
+            if let Ok(mut outcomes) = outcomes.lock() {
+                outcomes.reset(Instant::now());
+            }
+        }
+        if let Ok(mut group) = self.group.lock() {
+            group.ejected = group.ejected.saturating_sub(1);
+        }
+        self.metrics.readmitted();
+        self.state = State::Admitted;
+    }
+}
+
+impl<C, S, A, B> tower::Service<http::Request<A>> for Eject<C, S>
+where
+    S: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+    C: ClassifyResponse + Clone + Send + Sync + 'static,
+    C::Class: IsFailure,
+{
+    type Response = http::Response<ResponseBody<B, C::ClassifyEos>>;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future, C>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.state == State::Admitted {
+            self.update();
+        }
+
+        if self.state == State::Ejected {
+            // The backoff stream only ends after `u32::MAX` iterations, at
+            // which point the endpoint is readmitted immediately.
+            let _ = ready!(self.backoff.poll_next_unpin(cx));
+            debug!("Readmitting endpoint");
+            self.readmit();
+        }
+
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<A>) -> Self::Future {
+        let (classify, outcomes, generation) = match self.outcomes.as_ref() {
+            Some(outcomes) => match req.extensions().get::<C>().cloned() {
+                Some(classify) => {
+                    let generation = outcomes.lock().map(|o| o.generation).unwrap_or(0);
+                    (Some(classify), Some(outcomes.clone()), generation)
+                }
+                None => (None, None, 0),
+            },
+            None => (None, None, 0),
+        };
+
+        ResponseFuture {
+            inner: self.inner.call(req),
+            classify,
+            outcomes,
+            generation,
+            config: self.config,
+        }
+    }
+}
+
+impl<C, S> Drop for Eject<C, S> {
+    fn drop(&mut self) {
+        if let Ok(mut group) = self.group.lock() {
+            group.endpoints = group.endpoints.saturating_sub(1);
+            if self.state == State::Ejected {
+                group.ejected = group.ejected.saturating_sub(1);
+            }
+        }
+        if self.state == State::Ejected {
+            self.metrics.readmitted();
+        }
+    }
+}
+
+// === impl Group ===
+
+impl Group {
+    /// Marks an endpoint as ejected, unless too many of the group's endpoints
+    /// are already ejected.
+    fn eject(&mut self, max_percent: Option<u32>) -> bool {
+        if let Some(max) = max_percent {
+            if self.ejected as u64 * 100 >= u64::from(max) * self.endpoints as u64 {
+                return false;
+            }
+        }
+        self.ejected += 1;
+        true
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B, C> Future for ResponseFuture<F, C>
+where
+    F: TryFuture<Ok = http::Response<B>>,
+    F::Error: Into<Error>,
+    C: ClassifyResponse,
+    C::Class: IsFailure,
+{
+    type Output = Result<http::Response<ResponseBody<B, C::ClassifyEos>>, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let rsp = ready!(this.inner.try_poll(cx));
+
+        let classify = this.classify.take();
+        let outcomes = this.outcomes.take();
+        let generation = *this.generation;
+        let config = *this.config;
+        Poll::Ready(match rsp {
+            Ok(rsp) => {
+                let classify = classify.map(|c| c.start(&rsp));
+                Ok(rsp.map(|inner| ResponseBody {
+                    inner,
+                    classify,
+                    outcomes,
+                    generation,
+                    config,
+                }))
+            }
+            Err(e) => {
+                let e = e.into();
+                if let (Some(classify), Some(outcomes)) = (classify, outcomes) {
+                    record(&outcomes, generation, &config, classify.error(&e));
+                }
+                Err(e)
+            }
+        })
+    }
+}
+
+// === impl ResponseBody ===
+
+impl<B, C> ResponseBody<B, C>
+where
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    fn record(self: Pin<&mut Self>, class: impl FnOnce(C) -> C::Class) {
+        let this = self.project();
+        if let (Some(classify), Some(outcomes)) = (this.classify.take(), this.outcomes.take()) {
+            record(&outcomes, *this.generation, &*this.config, class(classify));
+        }
+    }
+}
+
+impl<B, C> Body for ResponseBody<B, C>
+where
+    B: Body,
+    B::Error: Into<Error>,
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    type Data = B::Data;
+    type Error = Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let frame = ready!(self.as_mut().project().inner.poll_data(cx));
+        Poll::Ready(frame.map(|res| {
+            res.map_err(|e| {
+                let e = e.into();
+                self.as_mut().record(|c| c.error(&e));
+                e
+            })
+        }))
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        let trailers = ready!(self.as_mut().project().inner.poll_trailers(cx)).map_err(|e| {
+            let e = e.into();
+            self.as_mut().record(|c| c.error(&e));
+            e
+        })?;
+        self.as_mut().record(|c| c.eos(trailers.as_ref()));
+        Poll::Ready(Ok(trailers))
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[pinned_drop]
+impl<B, C> PinnedDrop for ResponseBody<B, C>
+where
+    C: ClassifyEos,
+    C::Class: IsFailure,
+{
+    fn drop(self: Pin<&mut Self>) {
+        self.record(|c| c.eos(None));
+    }
+}
+
+fn record(outcomes: &Mutex<Outcomes>, generation: u64, config: &Config, class: impl IsFailure) {
+    if let Ok(mut outcomes) = outcomes.lock() {
+        // Responses to requests dispatched before the endpoint was last
+        // ejected or readmitted don't describe its current state.
+        if outcomes.generation != generation {
+            trace!("Ignoring a stale response");
+            return;
+        }
+        outcomes.record(config, class.is_failure(), Instant::now());
+    }
+}
+
+// === impl Outcomes ===
+
+impl Outcomes {
+    fn new(now: Instant) -> Self {
+        Self {
+            generation: 0,
+            consecutive_failures: 0,
+            window_start: now,
+            requests: 0,
+            failures: 0,
+            recovered: false,
+        }
+    }
+
+    fn reset(&mut self, now: Instant) {
+        *self = Self {
+            generation: self.generation.wrapping_add(1),
+            ..Self::new(now)
+        };
+    }
+
+    fn record(&mut self, config: &Config, is_failure: bool, now: Instant) {
+        if is_failure {
+            self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        } else {
+            self.consecutive_failures = 0;
+            self.recovered = true;
+        }
+
+        if let Some(FailureRate { window, .. }) = config.failure_rate {
+            if now.saturating_duration_since(self.window_start) >= window {
+                self.window_start = now;
+                self.requests = 0;
+                self.failures = 0;
+            }
+            self.requests = self.requests.saturating_add(1);
+            if is_failure {
+                self.failures = self.failures.saturating_add(1);
+            }
+        }
+    }
+
+    fn check(&self, config: &Config) -> Option<Reason> {
+        if config.consecutive_failures > 0
+            && self.consecutive_failures >= config.consecutive_failures
+        {
+            return Some(Reason::ConsecutiveFailures);
+        }
+
+        if let Some(FailureRate {
+            ratio,
+            min_requests,
+            ..
+        }) = config.failure_rate
+        {
+            if self.requests > 0
+                && self.requests >= min_requests
+                && f64::from(self.failures) >= ratio * f64::from(self.requests)
+            {
+                return Some(Reason::FailureRate);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_http_metrics::Ejections;
+    use tokio_test::{assert_pending, assert_ready_ok};
+    use tower_test::mock;
+
+    /// Classifies responses by their status, treating 5XX as failures.
+    #[derive(Clone, Debug, Default)]
+    struct Classify;
+
+    #[derive(Clone, Debug)]
+    struct Class(bool);
+
+    impl ClassifyResponse for Classify {
+        type Class = Class;
+        type ClassifyEos = Class;
+
+        fn start<B>(self, rsp: &http::Response<B>) -> Class {
+            Class(rsp.status().is_server_error())
+        }
+
+        fn error(self, _: &Error) -> Class {
+            Class(true)
+        }
+    }
+
+    impl ClassifyEos for Class {
+        type Class = Class;
+
+        fn eos(self, _: Option<&http::HeaderMap>) -> Class {
+            self
+        }
+
+        fn error(self, _: &Error) -> Class {
+            Class(true)
+        }
+    }
+
+    impl IsFailure for Class {
+        fn is_failure(&self) -> bool {
+            self.0
+        }
+    }
+
+    type Svc = Eject<Classify, mock::Mock<http::Request<()>, http::Response<()>>>;
+
+    const BACKOFF: ExponentialBackoff = ExponentialBackoff {
+        min: Duration::from_secs(1),
+        max: Duration::from_secs(10),
+        jitter: 0.0,
+    };
+
+    fn eject(
+        config: Config,
+    ) -> (
+        mock::Spawn<Svc>,
+        mock::Handle<http::Request<()>, http::Response<()>>,
+    ) {
+        let (inner, handle) = mock::pair();
+        let metrics = Ejections::<()>::default().get_handle(());
+        let svc = mock::Spawn::new(Eject::new(config, metrics, inner));
+        (svc, handle)
+    }
+
+    /// Advances the paused clock past a timer's resolution.
+    async fn advance(d: Duration) {
+        tokio::time::advance(d + Duration::from_millis(1)).await;
+    }
+
+    /// Dispatches a classified request and completes it with `status`.
+    async fn send(
+        svc: &mut mock::Spawn<Svc>,
+        handle: &mut mock::Handle<http::Request<()>, http::Response<()>>,
+        status: http::StatusCode,
+    ) {
+        handle.allow(1);
+        assert_ready_ok!(svc.poll_ready());
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(Classify);
+        let rsp = svc.call(req);
+        let (_, tx) = handle.next_request().await.expect("request must be sent");
+        tx.send_response(http::Response::builder().status(status).body(()).unwrap());
+        // Dropping the response body completes the stream.
+        drop(rsp.await.expect("response must succeed"));
+    }
+
+    #[tokio::test]
+    async fn ejects_after_consecutive_failures() {
+        tokio::time::pause();
+        let (mut svc, mut handle) = eject(Config {
+            consecutive_failures: 2,
+            failure_rate: None,
+            backoff: BACKOFF,
+            max_ejection_percent: None,
+        });
+
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        send(&mut svc, &mut handle, http::StatusCode::OK).await;
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc.poll_ready());
+    }
+
+    #[tokio::test]
+    async fn ejects_on_failure_rate() {
+        tokio::time::pause();
+        let (mut svc, mut handle) = eject(Config {
+            consecutive_failures: 0,
+            failure_rate: Some(FailureRate {
+                ratio: 0.5,
+                min_requests: 4,
+                window: Duration::from_secs(10),
+            }),
+            backoff: BACKOFF,
+            max_ejection_percent: None,
+        });
+
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        send(&mut svc, &mut handle, http::StatusCode::OK).await;
+        send(&mut svc, &mut handle, http::StatusCode::OK).await;
+        handle.allow(1);
+        assert_ready_ok!(svc.poll_ready(), "too few requests to eject");
+
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+    }
+
+    #[tokio::test]
+    async fn failure_rate_window_expires() {
+        tokio::time::pause();
+        let window = Duration::from_secs(10);
+        let (mut svc, mut handle) = eject(Config {
+            consecutive_failures: 0,
+            failure_rate: Some(FailureRate {
+                ratio: 0.5,
+                min_requests: 2,
+                window,
+            }),
+            backoff: BACKOFF,
+            max_ejection_percent: None,
+        });
+
+        send(&mut svc, &mut handle, http::StatusCode::OK).await;
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc.poll_ready());
+
+        // After the window expires, earlier successes are forgotten.
+        send(&mut svc, &mut handle, http::StatusCode::OK).await;
+        advance(window).await;
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_ready_ok!(svc.poll_ready(), "too few requests to eject");
+    }
+
+    #[tokio::test]
+    async fn backoff_grows_until_success() {
+        tokio::time::pause();
+        let (mut svc, mut handle) = eject(Config {
+            consecutive_failures: 1,
+            failure_rate: None,
+            backoff: BACKOFF,
+            max_ejection_percent: None,
+        });
+
+        // The first ejection lasts for the minimum backoff.
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc.poll_ready());
+
+        // Failing again immediately doubles the backoff.
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc.poll_ready());
+
+        // A success resets the backoff.
+        send(&mut svc, &mut handle, http::StatusCode::OK).await;
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc.poll_ready());
+    }
+
+    #[tokio::test]
+    async fn ignores_unclassified_requests() {
+        tokio::time::pause();
+        let (mut svc, mut handle) = eject(Config {
+            consecutive_failures: 1,
+            failure_rate: None,
+            backoff: BACKOFF,
+            max_ejection_percent: None,
+        });
+
+        handle.allow(1);
+        assert_ready_ok!(svc.poll_ready());
+        let rsp = svc.call(http::Request::new(()));
+        let (_, tx) = handle.next_request().await.expect("request must be sent");
+        tx.send_response(
+            http::Response::builder()
+                .status(http::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(())
+                .unwrap(),
+        );
+        drop(rsp.await.expect("response must succeed"));
+
+        handle.allow(1);
+        assert_ready_ok!(svc.poll_ready());
+    }
+
+    #[tokio::test]
+    async fn caps_ejected_endpoints() {
+        tokio::time::pause();
+        let config = Config {
+            consecutive_failures: 1,
+            failure_rate: None,
+            backoff: BACKOFF,
+            max_ejection_percent: Some(50),
+        };
+        let group = Arc::new(Mutex::new(Group::default()));
+        let endpoint = || {
+            let (inner, handle) = mock::pair();
+            let metrics = Ejections::<()>::default().get_handle(());
+            let svc = Eject::grouped(config, metrics, group.clone(), inner);
+            (mock::Spawn::new(svc), handle)
+        };
+        let (mut svc0, mut handle0) = endpoint();
+        let (mut svc1, mut handle1) = endpoint();
+
+        send(
+            &mut svc0,
+            &mut handle0,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle0.allow(1);
+        assert_pending!(svc0.poll_ready());
+
+        // Half of the endpoints are already ejected.
+        send(
+            &mut svc1,
+            &mut handle1,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle1.allow(1);
+        assert_ready_ok!(svc1.poll_ready());
+
+        // Once the first endpoint is readmitted, the second may be ejected.
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc0.poll_ready());
+        assert_pending!(svc1.poll_ready());
+    }
+
+    #[tokio::test]
+    async fn ignores_responses_dispatched_before_readmission() {
+        tokio::time::pause();
+        let (mut svc, mut handle) = eject(Config {
+            consecutive_failures: 1,
+            failure_rate: None,
+            backoff: BACKOFF,
+            max_ejection_percent: None,
+        });
+
+        handle.allow(1);
+        assert_ready_ok!(svc.poll_ready());
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(Classify);
+        let slow = svc.call(req);
+        let (_, slow_tx) = handle.next_request().await.expect("request must be sent");
+
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc.poll_ready());
+
+        // A success that was dispatched before the endpoint was ejected does
+        // not reset its backoff.
+        slow_tx.send_response(http::Response::new(()));
+        drop(slow.await.expect("response must succeed"));
+        send(
+            &mut svc,
+            &mut handle,
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .await;
+        handle.allow(1);
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_pending!(svc.poll_ready());
+        advance(BACKOFF.min).await;
+        assert_ready_ok!(svc.poll_ready());
+    }
+}
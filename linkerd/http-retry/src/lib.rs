@@ -0,0 +1,386 @@
+//! Buffers HTTP request bodies so that requests may be retried.
+//!
+//! A [`ReplayBody`] streams its inner body to the first attempt while
+//! buffering the data it yields. Clones of the body replay the buffered data
+//! before continuing to read from the inner body. Once the body exceeds its
+//! buffer limit, the buffer is discarded and the body can no longer be
+//! replayed.
+
+#![deny(warnings, rust_2018_idioms)]
+
+use bytes::{Buf, Bytes};
+use http::HeaderMap;
+use http_body::{Body, SizeHint};
+use linkerd_error::Error;
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+};
+use tracing::trace;
+
+/// A request body that may be cloned and replayed, so long as it does not
+/// exceed `max_bytes`.
+pub struct ReplayBody<B> {
+    /// The shared body state, while it is held by this clone.
+    ///
+    /// Only one clone may read the body at a time. The state is taken from the
+    /// shared slot when this clone is first polled and returned when it is
+    /// dropped.
+    state: Option<BodyState<B>>,
+
+    shared: Arc<Shared<B>>,
+
+    /// The number of buffered chunks this clone has read.
+    replayed: usize,
+}
+
+/// Returned when a body is polled while it cannot be replayed, either because
+/// it was too large to buffer or because another clone is still reading it.
+#[derive(Debug)]
+pub struct Unreplayable(());
+
+struct Shared<B> {
+    body: Mutex<Option<BodyState<B>>>,
+    is_capped: AtomicBool,
+    is_end_stream: bool,
+    size_hint: SizeHint,
+}
+
+struct BodyState<B> {
+    buf: Vec<Bytes>,
+    buf_len: usize,
+    max_bytes: usize,
+    is_capped: bool,
+
+    /// The inner body, until its trailers have been read.
+    rest: Option<B>,
+    data_eos: bool,
+    trailers: Option<HeaderMap>,
+}
+
+// === impl ReplayBody ===
+
+impl<B: Body> ReplayBody<B> {
+    pub fn new(body: B, max_bytes: usize) -> Self {
+        // Bodies that are known to exceed the limit are never buffered.
+        let is_capped = body.size_hint().lower() > max_bytes as u64;
+        let state = BodyState {
+            buf: Vec::new(),
+            buf_len: 0,
+            max_bytes,
+            is_capped,
+            data_eos: body.is_end_stream(),
+            rest: Some(body),
+            trailers: None,
+        };
+        let shared = Shared {
+            is_end_stream: state.data_eos,
+            size_hint: state.rest.as_ref().map(Body::size_hint).unwrap_or_default(),
+            body: Mutex::new(None),
+            is_capped: AtomicBool::new(is_capped),
+        };
+        Self {
+            state: Some(state),
+            shared: Arc::new(shared),
+            replayed: 0,
+        }
+    }
+}
+
+impl<B> ReplayBody<B> {
+    /// Returns true if the body has exceeded its buffer limit, so that clones
+    /// of it may not be sent.
+    pub fn is_capped(&self) -> bool {
+        self.shared.is_capped.load(Ordering::Acquire)
+    }
+
+    /// Takes the shared body state if this clone does not yet hold it.
+    ///
+    /// Returns `None` if another clone holds the state or if the body was
+    /// capped before this clone could read it.
+    fn acquire_state(&mut self) -> Option<&mut BodyState<B>> {
+        if self.state.is_none() {
+            let mut body = self.shared.body.lock().ok()?;
+            if body.as_ref()?.is_capped {
+                return None;
+            }
+            self.state = body.take();
+        }
+        self.state.as_mut()
+    }
+}
+
+impl<B> Body for ReplayBody<B>
+where
+    B: Body + Unpin,
+    B::Error: Into<Error>,
+{
+    type Data = Bytes;
+    type Error = Error;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Error>>> {
+        let this = self.get_mut();
+        let replayed = this.replayed;
+        let shared = this.shared.clone();
+        let state = match this.acquire_state() {
+            Some(state) => state,
+            None => return Poll::Ready(Some(Err(Unreplayable(()).into()))),
+        };
+
+        // Replay any data that was buffered by a prior clone.
+        if let Some(chunk) = state.buf.get(replayed) {
+            let chunk = chunk.clone();
+            this.replayed += 1;
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+        if state.data_eos {
+            return Poll::Ready(None);
+        }
+        let rest = match state.rest.as_mut() {
+            Some(rest) => rest,
+            None => return Poll::Ready(None),
+        };
+        let mut data = match Pin::new(rest).poll_data(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(None) => {
+                state.data_eos = true;
+                return Poll::Ready(None);
+            }
+            Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(Some(Ok(data))) => data,
+        };
+        let chunk = data.copy_to_bytes(data.remaining());
+
+        if !state.is_capped {
+            if state.buf_len + chunk.len() > state.max_bytes {
+                trace!(
+                    max_bytes = state.max_bytes,
+                    "Request body exceeds buffer limit; it may not be replayed"
+                );
+                state.is_capped = true;
+                state.buf = Vec::new();
+                state.buf_len = 0;
+                shared.is_capped.store(true, Ordering::Release);
+            } else {
+                state.buf_len += chunk.len();
+                state.buf.push(chunk.clone());
+                this.replayed += 1;
+            }
+        }
+
+        Poll::Ready(Some(Ok(chunk)))
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Error>> {
+        let this = self.get_mut();
+        let state = match this.acquire_state() {
+            Some(state) => state,
+            None => return Poll::Ready(Err(Unreplayable(()).into())),
+        };
+
+        if let Some(rest) = state.rest.as_mut() {
+            let trailers = match Pin::new(rest).poll_trailers(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Ready(Ok(trailers)) => trailers,
+            };
+            state.trailers = trailers;
+            state.rest = None;
+        }
+
+        Poll::Ready(Ok(state.trailers.clone()))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        match self.state.as_ref() {
+            Some(state) => {
+                self.replayed >= state.buf.len()
+                    && match state.rest.as_ref() {
+                        Some(rest) => rest.is_end_stream(),
+                        None => state.trailers.is_none(),
+                    }
+            }
+            None => self.shared.is_end_stream,
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.shared.size_hint.clone()
+    }
+}
+
+impl<B> Clone for ReplayBody<B> {
+    fn clone(&self) -> Self {
+        Self {
+            state: None,
+            shared: self.shared.clone(),
+            replayed: 0,
+        }
+    }
+}
+
+impl<B> Drop for ReplayBody<B> {
+    fn drop(&mut self) {
+        // Return the body state so that another clone may replay it.
+        if let Some(state) = self.state.take() {
+            if let Ok(mut body) = self.shared.body.lock() {
+                *body = Some(state);
+            }
+        }
+    }
+}
+
+impl<B: Body + Default> Default for ReplayBody<B> {
+    fn default() -> Self {
+        Self::new(B::default(), 0)
+    }
+}
+
+// === impl Unreplayable ===
+
+impl std::fmt::Display for Unreplayable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request body may not be replayed")
+    }
+}
+
+impl std::error::Error for Unreplayable {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+    use std::collections::VecDeque;
+
+    /// A body that yields a fixed set of chunks and trailers.
+    #[derive(Default)]
+    struct TestBody {
+        chunks: VecDeque<Bytes>,
+        trailers: Option<HeaderMap>,
+    }
+
+    impl TestBody {
+        fn new(chunks: &[&'static str], trailers: Option<HeaderMap>) -> Self {
+            Self {
+                chunks: chunks
+                    .iter()
+                    .map(|c| Bytes::from_static(c.as_bytes()))
+                    .collect(),
+                trailers,
+            }
+        }
+    }
+
+    impl Body for TestBody {
+        type Data = Bytes;
+        type Error = Error;
+
+        fn poll_data(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Option<Result<Bytes, Error>>> {
+            Poll::Ready(self.chunks.pop_front().map(Ok))
+        }
+
+        fn poll_trailers(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Option<HeaderMap>, Error>> {
+            Poll::Ready(Ok(self.trailers.take()))
+        }
+
+        fn is_end_stream(&self) -> bool {
+            self.chunks.is_empty() && self.trailers.is_none()
+        }
+    }
+
+    async fn read_to_end<B>(body: &mut B) -> Result<(Vec<u8>, Option<HeaderMap>), Error>
+    where
+        B: Body<Data = Bytes, Error = Error> + Unpin,
+    {
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk?);
+        }
+        let trailers = body.trailers().await?;
+        Ok((data, trailers))
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn replays_buffered_body() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("x-trailer", HeaderValue::from_static("yes"));
+        let body = TestBody::new(&["hello ", "world"], Some(trailers.clone()));
+        let mut initial = ReplayBody::new(body, 64 * 1024);
+        let mut replay = initial.clone();
+
+        let (data, t) = read_to_end(&mut initial).await.unwrap();
+        assert_eq!(data, b"hello world");
+        assert_eq!(t.as_ref(), Some(&trailers));
+        assert!(!initial.is_capped());
+        drop(initial);
+
+        let (data, t) = read_to_end(&mut replay).await.unwrap();
+        assert_eq!(data, b"hello world");
+        assert_eq!(t.as_ref(), Some(&trailers));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn replays_partially_read_body() {
+        let body = TestBody::new(&["hello ", "world"], None);
+        let mut initial = ReplayBody::new(body, 64 * 1024);
+        let mut replay = initial.clone();
+
+        // The first attempt fails before the body is complete.
+        assert_eq!(initial.data().await.unwrap().unwrap(), "hello ");
+        drop(initial);
+
+        let (data, t) = read_to_end(&mut replay).await.unwrap();
+        assert_eq!(data, b"hello world");
+        assert_eq!(t, None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn streams_bodies_over_limit_without_replay() {
+        let body = TestBody::new(&["hello ", "world"], None);
+        let mut initial = ReplayBody::new(body, 8);
+        let mut replay = initial.clone();
+
+        // The first attempt still receives the whole body...
+        let (data, _) = read_to_end(&mut initial).await.unwrap();
+        assert_eq!(data, b"hello world");
+        assert!(initial.is_capped());
+        assert!(replay.is_capped());
+        drop(initial);
+
+        // ...but it may not be replayed.
+        let err = replay.data().await.unwrap().unwrap_err();
+        assert!(err.is::<Unreplayable>());
+    }
+
+    #[test]
+    fn empty_bodies_end_stream() {
+        let initial = ReplayBody::new(TestBody::default(), 64 * 1024);
+        let replay = initial.clone();
+        assert!(initial.is_end_stream());
+        assert!(replay.is_end_stream());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn clones_may_not_read_concurrently() {
+        let body = TestBody::new(&["hello ", "world"], None);
+        let mut initial = ReplayBody::new(body, 64 * 1024);
+        let mut replay = initial.clone();
+
+        assert_eq!(initial.data().await.unwrap().unwrap(), "hello ");
+        let err = replay.data().await.unwrap().unwrap_err();
+        assert!(err.is::<Unreplayable>());
+    }
+}
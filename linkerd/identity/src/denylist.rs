@@ -0,0 +1,592 @@
+use crate::{der, CrtKey, Name, TrustAnchors};
+use std::{
+    collections::HashSet,
+    convert::TryInto,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+use tracing::info;
+
+/// Certificates that are rejected even though they were issued by a trust
+/// anchor, e.g. because their keys were compromised before they expired.
+///
+/// A denylist is parsed from a document with one entry per line: either
+/// `serial:` followed by a hex-encoded certificate serial number (which may be
+/// separated by colons), or an identity name, which denies every certificate
+/// that is valid for it. Blank lines and lines starting with `#` are ignored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Denylist(Arc<Entries>);
+
+#[derive(Debug, Default, PartialEq)]
+struct Entries {
+    serials: HashSet<Vec<u8>>,
+    names: HashSet<Name>,
+}
+
+/// A denylist that is shared by a local identity's TLS configurations and
+/// updated as it is reloaded.
+///
+/// Peers' certificates are checked when they are verified during the
+/// handshake. A resumed session isn't verified again, so sessions are scoped
+/// to the denylist they were established under: updating the denylist
+/// prevents all prior sessions from being resumed.
+///
+/// The default denylist is empty.
+#[derive(Clone, Debug, Default)]
+pub struct SharedDenylist(Arc<Shared>);
+
+#[derive(Debug, Default)]
+struct Shared {
+    denylist: RwLock<Denylist>,
+    generation: AtomicU64,
+    rejected_clients: AtomicU64,
+    rejected_servers: AtomicU64,
+}
+
+/// Verifies clients' certificates and then checks them against a denylist.
+struct DenyClients {
+    denylist: SharedDenylist,
+    inner: Arc<dyn rustls::ClientCertVerifier>,
+}
+
+/// Verifies servers' certificates and then checks them against a denylist.
+struct DenyServers {
+    denylist: SharedDenylist,
+    inner: Arc<dyn rustls::ServerCertVerifier>,
+}
+
+/// Scopes a server's cached sessions to the denylist's generation.
+struct ServerSessions {
+    denylist: SharedDenylist,
+    inner: Arc<dyn rustls::StoresServerSessions>,
+}
+
+/// Scopes a server's session tickets to the denylist's generation.
+struct Tickets {
+    denylist: SharedDenylist,
+    inner: Arc<dyn rustls::ProducesTickets>,
+}
+
+/// Scopes a client's cached sessions to the denylist's generation.
+struct ClientSessions {
+    denylist: SharedDenylist,
+    inner: Arc<dyn rustls::StoresClientSessions>,
+}
+
+/// Indicates that a peer presented a certificate that is on the denylist.
+#[derive(Clone, Debug)]
+pub struct DeniedCertificate(Denied);
+
+#[derive(Clone, Debug)]
+enum Denied {
+    Serial(Vec<u8>),
+    Name(Name),
+}
+
+#[derive(Clone, Debug)]
+pub struct InvalidDenylist {
+    line: usize,
+}
+
+const SERIAL_PREFIX: &str = "serial:";
+
+// === impl Denylist ===
+
+impl Denylist {
+    pub fn len(&self) -> usize {
+        self.0.serials.len() + self.0.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks a DER-encoded X.509 certificate against the denylist.
+    pub fn check(&self, crt: &[u8]) -> Result<(), DeniedCertificate> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(serial) = der::serial(crt) {
+            if self.0.serials.contains(serial) {
+                return Err(DeniedCertificate(Denied::Serial(serial.to_vec())));
+            }
+        }
+
+        let sans = der::dns_sans(crt)
+            .into_iter()
+            .chain(der::uri_sans(crt))
+            .flatten();
+        for san in sans {
+            if let Ok(name) = Name::from_str(san) {
+                if self.0.names.contains(&name) {
+                    return Err(DeniedCertificate(Denied::Name(name)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Denylist {
+    type Err = InvalidDenylist;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut entries = Entries::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = InvalidDenylist { line: i + 1 };
+            if let Some(serial) = line.strip_prefix(SERIAL_PREFIX) {
+                let serial = parse_serial(serial).ok_or(invalid)?;
+                entries.serials.insert(serial);
+            } else {
+                let name = Name::from_str(line).map_err(|_| invalid)?;
+                entries.names.insert(name);
+            }
+        }
+        Ok(Denylist(Arc::new(entries)))
+    }
+}
+
+// === impl SharedDenylist ===
+
+impl SharedDenylist {
+    /// Replaces the denylist, returning false if it is unchanged.
+    ///
+    /// Sessions established under the prior denylist can't be resumed.
+    pub fn update(&self, denylist: Denylist) -> bool {
+        let mut current = self.0.denylist.write().expect("denylist lock poisoned");
+        if *current == denylist {
+            return false;
+        }
+        *current = denylist;
+        self.0.generation.fetch_add(1, Ordering::AcqRel);
+        true
+    }
+
+    /// Returns the current number of denylist entries.
+    pub fn entries(&self) -> usize {
+        self.0.denylist.read().map(|d| d.len()).unwrap_or(0)
+    }
+
+    /// Returns the number of clients whose certificates were rejected.
+    pub fn rejected_clients(&self) -> u64 {
+        self.0.rejected_clients.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of servers whose certificates were rejected.
+    pub fn rejected_servers(&self) -> u64 {
+        self.0.rejected_servers.load(Ordering::Acquire)
+    }
+
+    /// Configures a client to reject servers whose certificates are denied,
+    /// once they have been verified by `verifier`.
+    pub(crate) fn verify_servers(
+        &self,
+        config: &mut rustls::ClientConfig,
+        verifier: Arc<dyn rustls::ServerCertVerifier>,
+    ) {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(DenyServers {
+                denylist: self.clone(),
+                inner: verifier,
+            }));
+    }
+
+    /// Configures a client to reject denied servers and to only resume
+    /// sessions established under the current denylist.
+    fn deny_servers(&self, config: &mut rustls::ClientConfig) {
+        self.verify_servers(config, Arc::new(rustls::WebPKIVerifier::new()));
+        config.session_persistence = Arc::new(ClientSessions {
+            denylist: self.clone(),
+            inner: config.session_persistence.clone(),
+        });
+    }
+
+    /// Configures a server to reject denied clients, once they have been
+    /// verified by `verifier`, and to only resume sessions established under
+    /// the current denylist.
+    fn deny_clients(
+        &self,
+        config: &mut rustls::ServerConfig,
+        verifier: Arc<dyn rustls::ClientCertVerifier>,
+    ) {
+        config.set_client_certificate_verifier(Arc::new(DenyClients {
+            denylist: self.clone(),
+            inner: verifier,
+        }));
+        config.session_storage = Arc::new(ServerSessions {
+            denylist: self.clone(),
+            inner: config.session_storage.clone(),
+        });
+        config.ticketer = Arc::new(Tickets {
+            denylist: self.clone(),
+            inner: config.ticketer.clone(),
+        });
+    }
+
+    fn check(
+        &self,
+        chain: &[rustls::Certificate],
+        rejected: impl Fn(&Shared) -> &AtomicU64,
+    ) -> Result<(), rustls::TLSError> {
+        let crt = match chain.first() {
+            Some(crt) => crt,
+            None => return Ok(()),
+        };
+        let res = match self.0.denylist.read() {
+            Ok(denylist) => denylist.check(crt.as_ref()),
+            Err(_) => return Err(rustls::TLSError::General("denylist lock poisoned".into())),
+        };
+        res.map_err(|denied| {
+            info!(%denied, "Rejecting TLS connection");
+            rejected(&self.0).fetch_add(1, Ordering::Release);
+            rustls::TLSError::General(denied.to_string())
+        })
+    }
+
+    /// Prefixes a session's key (or a ticket's plaintext) with the current
+    /// generation.
+    fn scope(&self, bytes: &[u8]) -> Vec<u8> {
+        let generation = self.0.generation.load(Ordering::Acquire);
+        let mut scoped = Vec::with_capacity(8 + bytes.len());
+        scoped.extend_from_slice(&generation.to_le_bytes());
+        scoped.extend_from_slice(bytes);
+        scoped
+    }
+
+    /// Strips the generation from a ticket's plaintext, if it is current.
+    fn unscope(&self, mut scoped: Vec<u8>) -> Option<Vec<u8>> {
+        if scoped.len() < 8 {
+            return None;
+        }
+        let generation = u64::from_le_bytes(scoped[..8].try_into().ok()?);
+        if generation != self.0.generation.load(Ordering::Acquire) {
+            return None;
+        }
+        scoped.drain(..8);
+        Some(scoped)
+    }
+}
+
+// === impl TrustAnchors ===
+
+impl TrustAnchors {
+    /// Returns anchors whose client configuration rejects servers that
+    /// present denied certificates.
+    pub fn with_denylist(&self, denylist: &SharedDenylist) -> Self {
+        let mut config = self.config.as_ref().clone();
+        denylist.deny_servers(&mut config);
+        Self {
+            config: Arc::new(config),
+            fingerprint: self.fingerprint.clone(),
+        }
+    }
+}
+
+// === impl CrtKey ===
+
+impl CrtKey {
+    /// Returns a certificate whose client and server configurations reject
+    /// peers that present denied certificates.
+    pub fn with_denylist(&self, denylist: &SharedDenylist) -> Self {
+        let mut client = self.client_config.as_ref().clone();
+        denylist.deny_servers(&mut client);
+        let mut server = self.server_config.as_ref().clone();
+        denylist.deny_clients(&mut server, self.client_verifier.clone());
+        Self {
+            client_config: Arc::new(client),
+            server_config: Arc::new(server),
+            ..self.clone()
+        }
+    }
+}
+
+// === impl DenyClients ===
+
+impl rustls::ClientCertVerifier for DenyClients {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self, sni: Option<&webpki::DNSName>) -> Option<bool> {
+        self.inner.client_auth_mandatory(sni)
+    }
+
+    fn client_auth_root_subjects(
+        &self,
+        sni: Option<&webpki::DNSName>,
+    ) -> Option<rustls::DistinguishedNames> {
+        self.inner.client_auth_root_subjects(sni)
+    }
+
+    fn verify_client_cert(
+        &self,
+        chain: &[rustls::Certificate],
+        sni: Option<&webpki::DNSName>,
+    ) -> Result<rustls::ClientCertVerified, rustls::TLSError> {
+        let verified = self.inner.verify_client_cert(chain, sni)?;
+        self.denylist.check(chain, |s| &s.rejected_clients)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        crt: &rustls::Certificate,
+        dss: &rustls::internal::msgs::handshake::DigitallySignedStruct,
+    ) -> Result<rustls::HandshakeSignatureValid, rustls::TLSError> {
+        self.inner.verify_tls12_signature(message, crt, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        crt: &rustls::Certificate,
+        dss: &rustls::internal::msgs::handshake::DigitallySignedStruct,
+    ) -> Result<rustls::HandshakeSignatureValid, rustls::TLSError> {
+        self.inner.verify_tls13_signature(message, crt, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// === impl DenyServers ===
+
+impl rustls::ServerCertVerifier for DenyServers {
+    fn verify_server_cert(
+        &self,
+        roots: &rustls::RootCertStore,
+        chain: &[rustls::Certificate],
+        name: webpki::DNSNameRef<'_>,
+        ocsp: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        let verified = self.inner.verify_server_cert(roots, chain, name, ocsp)?;
+        self.denylist.check(chain, |s| &s.rejected_servers)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        crt: &rustls::Certificate,
+        dss: &rustls::internal::msgs::handshake::DigitallySignedStruct,
+    ) -> Result<rustls::HandshakeSignatureValid, rustls::TLSError> {
+        self.inner.verify_tls12_signature(message, crt, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        crt: &rustls::Certificate,
+        dss: &rustls::internal::msgs::handshake::DigitallySignedStruct,
+    ) -> Result<rustls::HandshakeSignatureValid, rustls::TLSError> {
+        self.inner.verify_tls13_signature(message, crt, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+// === impl ServerSessions ===
+
+impl rustls::StoresServerSessions for ServerSessions {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.inner.put(self.denylist.scope(&key), value)
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.get(&self.denylist.scope(key))
+    }
+
+    fn take(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.take(&self.denylist.scope(key))
+    }
+}
+
+// === impl Tickets ===
+
+impl rustls::ProducesTickets for Tickets {
+    fn enabled(&self) -> bool {
+        self.inner.enabled()
+    }
+
+    fn get_lifetime(&self) -> u32 {
+        self.inner.get_lifetime()
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        self.inner.encrypt(&self.denylist.scope(plain))
+    }
+
+    fn decrypt(&self, cipher: &[u8]) -> Option<Vec<u8>> {
+        self.denylist.unscope(self.inner.decrypt(cipher)?)
+    }
+}
+
+// === impl ClientSessions ===
+
+impl rustls::StoresClientSessions for ClientSessions {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.inner.put(self.denylist.scope(&key), value)
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.get(&self.denylist.scope(key))
+    }
+}
+
+fn parse_serial(s: &str) -> Option<Vec<u8>> {
+    let hex = s.trim().replace(':', "");
+    if hex.is_empty() || hex.len() % 2 != 0 {
+        return None;
+    }
+
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(der::trim_serial(&bytes).to_vec())
+}
+
+// === impl DeniedCertificate ===
+
+impl fmt::Display for DeniedCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Denied::Serial(ref serial) => {
+                write!(f, "certificate serial ")?;
+                for (i, b) in serial.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ":")?;
+                    }
+                    write!(f, "{:02x}", b)?;
+                }
+                write!(f, " is denied")
+            }
+            Denied::Name(ref name) => write!(f, "certificates for {} are denied", name),
+        }
+    }
+}
+
+impl std::error::Error for DeniedCertificate {}
+
+// === impl InvalidDenylist ===
+
+impl fmt::Display for InvalidDenylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid denylist entry on line {}", self.line)
+    }
+}
+
+impl std::error::Error for InvalidDenylist {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+    #[test]
+    fn parses_entries() {
+        let denylist = Denylist::from_str(
+            "# Compromised keys\n\
+             serial:58:1c:43:d1:07:56:53:76:42:79:73:d8:3c:36:c2:ef:5d:49:a6:a8\n\
+             \n\
+             serial:00FF\n\
+             spiffe://cluster.local/ns/ns1/sa/baz\n",
+        )
+        .expect("denylist must be valid");
+        assert_eq!(denylist.len(), 3);
+        assert!(denylist.0.serials.contains(&vec![0xff]));
+
+        for (invalid, line) in &[
+            ("serial:", 1),
+            ("serial:abc", 1),
+            ("serial:xy", 1),
+            ("foo.ns1\nnot a name", 2),
+        ] {
+            let err = Denylist::from_str(invalid).expect_err(invalid);
+            assert_eq!(err.line, *line);
+        }
+    }
+
+    #[test]
+    fn denies_serials_and_names() {
+        let denylist =
+            Denylist::from_str("serial:581c43d107565376427973d83c36c2ef5d49a6a8").unwrap();
+        assert!(denylist.check(FOO_NS1.crt).is_err());
+        assert!(denylist.check(FOO_NS1_P384.crt).is_ok());
+
+        let denylist = Denylist::from_str(FOO_NS1.name).unwrap();
+        assert!(denylist.check(FOO_NS1.crt).is_err());
+        assert!(denylist.check(FOO_NS1_P384.crt).is_err());
+        assert!(denylist.check(BAR_NS1.crt).is_ok());
+
+        let denylist = Denylist::from_str(BAZ_NS1_SVID.name).unwrap();
+        assert!(denylist.check(BAZ_NS1_SVID.crt).is_err());
+        assert!(denylist.check(FOO_NS1.crt).is_ok());
+
+        assert!(Denylist::default().check(FOO_NS1.crt).is_ok());
+    }
+
+    #[test]
+    fn rejects_denied_peers_during_handshakes() {
+        let denylist = SharedDenylist::default();
+        let foo = FOO_NS1.validate().unwrap().with_denylist(&denylist);
+        let bar = BAR_NS1.validate().unwrap().with_denylist(&denylist);
+        handshake(&foo, &bar).expect("peers must not be denied");
+
+        // The session established with bar may not be resumed once bar is
+        // denied.
+        assert!(denylist.update(Denylist::from_str(BAR_NS1.name).unwrap()));
+        assert!(handshake(&foo, &bar).is_err());
+        assert_eq!(denylist.rejected_servers(), 1);
+        assert_eq!(denylist.rejected_clients(), 0);
+
+        assert!(handshake(&bar, &foo).is_err());
+        assert_eq!(denylist.rejected_clients(), 1);
+
+        assert!(!denylist.update(Denylist::from_str(BAR_NS1.name).unwrap()));
+        assert!(denylist.update(Denylist::default()));
+        handshake(&foo, &bar).expect("peers must not be denied");
+    }
+
+    fn handshake(client: &CrtKey, server: &CrtKey) -> Result<(), rustls::TLSError> {
+        use rustls::Session;
+
+        fn transfer(from: &mut dyn Session, to: &mut dyn Session) -> Result<(), rustls::TLSError> {
+            let mut buf = Vec::new();
+            while from.wants_write() {
+                from.write_tls(&mut buf).expect("write must succeed");
+            }
+            let mut rd = &buf[..];
+            while !rd.is_empty() {
+                to.read_tls(&mut rd).expect("read must succeed");
+                to.process_new_packets()?;
+            }
+            Ok(())
+        }
+
+        let name = webpki::DNSNameRef::try_from_ascii_str(server.name().as_ref()).unwrap();
+        let mut client = rustls::ClientSession::new(&client.client_config(), name);
+        let mut server = rustls::ServerSession::new(&server.server_config());
+        while client.is_handshaking() || server.is_handshaking() {
+            transfer(&mut client, &mut server)?;
+            transfer(&mut server, &mut client)?;
+        }
+        Ok(())
+    }
+}
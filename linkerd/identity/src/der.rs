@@ -0,0 +1,428 @@
+//! Just enough DER decoding to split certificate chains and read their serial
+//! numbers, expiration times, and subject alternative names, and enough
+//! encoding to build certificate signing requests.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const UTF8_STRING: u8 = 0x0c;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const EXPLICIT_0: u8 = 0xa0;
+const EXPLICIT_3: u8 = 0xa3;
+/// The `dNSName` choice of a `GeneralName`.
+const DNS_NAME: u8 = 0x82;
+/// The `uniformResourceIdentifier` choice of a `GeneralName`.
+const URI: u8 = 0x86;
+
+/// id-ce-subjectAltName (2.5.29.17)
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+/// id-at-commonName (2.5.4.3)
+const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+/// pkcs-9-at-extensionRequest (1.2.840.113549.1.9.14)
+const EXTENSION_REQUEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e];
+
+/// The name that a certificate signing request asks to be certified for.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum SubjectAltName<'a> {
+    Dns(&'a str),
+    Uri(&'a str),
+}
+
+/// Splits a series of concatenated DER-encoded certificates.
+pub(crate) fn split_certs(mut input: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut certs = Vec::new();
+    while !input.is_empty() {
+        let (_, rest) = expect(SEQUENCE, input)?;
+        let len = input.len() - rest.len();
+        certs.push(input[..len].to_vec());
+        input = rest;
+    }
+    Some(certs)
+}
+
+/// Reads the time after which a DER-encoded X.509 certificate is not valid.
+pub(crate) fn not_after(crt: &[u8]) -> Option<SystemTime> {
+    let (validity, _) = validity(crt)?;
+    let (_, _not_before, validity) = read(validity)?;
+    let (tag, not_after, _) = read(validity)?;
+    parse_time(tag, not_after)
+}
+
+/// Reads the serial number of a DER-encoded X.509 certificate, without any
+/// leading zero bytes.
+pub(crate) fn serial(crt: &[u8]) -> Option<&[u8]> {
+    let (serial, _) = expect(INTEGER, tbs_from_serial(crt)?)?;
+    Some(trim_serial(serial))
+}
+
+/// Strips the leading zero bytes from a serial number, which DER requires
+/// for serials with the high bit set.
+pub(crate) fn trim_serial(serial: &[u8]) -> &[u8] {
+    let zeros = serial.iter().take_while(|&&b| b == 0).count();
+    &serial[zeros..]
+}
+
+/// Reads the DNS subject alternative names of a DER-encoded X.509
+/// certificate.
+pub(crate) fn dns_sans(crt: &[u8]) -> Option<Vec<&str>> {
+    sans(crt, DNS_NAME)
+}
+
+/// Reads the URI subject alternative names of a DER-encoded X.509
+/// certificate.
+pub(crate) fn uri_sans(crt: &[u8]) -> Option<Vec<&str>> {
+    sans(crt, URI)
+}
+
+fn sans(crt: &[u8], name_tag: u8) -> Option<Vec<&str>> {
+    let (_validity, rest) = validity(crt)?;
+    let (_subject, rest) = expect(SEQUENCE, rest)?;
+    let (_spki, mut rest) = expect(SEQUENCE, rest)?;
+
+    let mut sans = Vec::new();
+    while !rest.is_empty() {
+        // Skip the optional unique identifiers that precede the extensions.
+        let (tag, exts, r) = read(rest)?;
+        rest = r;
+        if tag != EXPLICIT_3 {
+            continue;
+        }
+
+        let (mut exts, _) = expect(SEQUENCE, exts)?;
+        while !exts.is_empty() {
+            let (ext, r) = expect(SEQUENCE, exts)?;
+            exts = r;
+            let (oid, ext) = expect(OID, ext)?;
+            if oid != SUBJECT_ALT_NAME {
+                continue;
+            }
+
+            let ext = match read(ext)? {
+                (BOOLEAN, _critical, ext) => ext,
+                _ => ext,
+            };
+            let (value, _) = expect(OCTET_STRING, ext)?;
+            let (mut names, _) = expect(SEQUENCE, value)?;
+            while !names.is_empty() {
+                let (tag, name, r) = read(names)?;
+                names = r;
+                if tag == name_tag {
+                    sans.push(std::str::from_utf8(name).ok()?);
+                }
+            }
+        }
+    }
+    Some(sans)
+}
+
+/// Encodes a PKCS#10 `CertificationRequestInfo` for a public key, identified by
+/// the contents of its `AlgorithmIdentifier`.
+///
+/// DNS names are also set as the subject's common name, since some CAs still
+/// expect it.
+pub(crate) fn csr_info(san: SubjectAltName<'_>, key_id: &[u8], public_key: &[u8]) -> Vec<u8> {
+    let (subject, name) = match san {
+        SubjectAltName::Dns(name) => {
+            let cn = encode(
+                SEQUENCE,
+                &[
+                    &encode(OID, &[COMMON_NAME]),
+                    &encode(UTF8_STRING, &[name.as_bytes()]),
+                ],
+            );
+            let subject = encode(SEQUENCE, &[&encode(SET, &[&cn])]);
+            (subject, encode(DNS_NAME, &[name.as_bytes()]))
+        }
+        SubjectAltName::Uri(uri) => (encode(SEQUENCE, &[]), encode(URI, &[uri.as_bytes()])),
+    };
+
+    let spki = encode(
+        SEQUENCE,
+        &[&encode(SEQUENCE, &[key_id]), &bit_string(public_key)],
+    );
+
+    let san = encode(
+        SEQUENCE,
+        &[
+            &encode(OID, &[SUBJECT_ALT_NAME]),
+            &encode(OCTET_STRING, &[&encode(SEQUENCE, &[&name])]),
+        ],
+    );
+    let extension_request = encode(
+        SEQUENCE,
+        &[
+            &encode(OID, &[EXTENSION_REQUEST]),
+            &encode(SET, &[&encode(SEQUENCE, &[&san])]),
+        ],
+    );
+    // The attributes are an implicitly-tagged `[0] SET`.
+    let attributes = encode(EXPLICIT_0, &[&extension_request]);
+
+    let version = encode(INTEGER, &[&[0]]);
+    encode(SEQUENCE, &[&version, &subject, &spki, &attributes])
+}
+
+/// Encodes a PKCS#10 `CertificationRequest` from its info and signature. The
+/// signature algorithm is identified by the contents of its
+/// `AlgorithmIdentifier`.
+pub(crate) fn csr(info: &[u8], signature_id: &[u8], signature: &[u8]) -> Vec<u8> {
+    encode(
+        SEQUENCE,
+        &[
+            info,
+            &encode(SEQUENCE, &[signature_id]),
+            &bit_string(signature),
+        ],
+    )
+}
+
+/// Encodes a value from the concatenation of `parts`.
+fn encode(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let len = parts.iter().map(|p| p.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(len + 6);
+    out.push(tag);
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+        out.push(0x80 | (bytes.len() - zeros) as u8);
+        out.extend_from_slice(&bytes[zeros..]);
+    }
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+    out
+}
+
+/// Encodes a `BIT STRING` of whole bytes.
+fn bit_string(bytes: &[u8]) -> Vec<u8> {
+    encode(BIT_STRING, &[&[0], bytes])
+}
+
+/// Reads a certificate's validity, returning it and the remainder of the
+/// certificate's `TBSCertificate`.
+fn validity(crt: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (_serial, rest) = expect(INTEGER, tbs_from_serial(crt)?)?;
+    let (_signature, rest) = expect(SEQUENCE, rest)?;
+    let (_issuer, rest) = expect(SEQUENCE, rest)?;
+    expect(SEQUENCE, rest)
+}
+
+/// Reads a certificate's `TBSCertificate`, skipping its version so that it
+/// starts with the serial number.
+fn tbs_from_serial(crt: &[u8]) -> Option<&[u8]> {
+    let (crt, _) = expect(SEQUENCE, crt)?;
+    let (tbs, _) = expect(SEQUENCE, crt)?;
+    if tbs.first() == Some(&EXPLICIT_0) {
+        // Skip the version.
+        return Some(read(tbs)?.2);
+    }
+    Some(tbs)
+}
+
+/// Reads a value from the front of `input`, returning its tag, its contents,
+/// and the input that follows it.
+fn read(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&len, mut rest) = rest.split_first()?;
+    let len = if len < 0x80 {
+        usize::from(len)
+    } else {
+        let n = usize::from(len & 0x7f);
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let (len, r) = rest.split_at(n);
+        rest = r;
+        len.iter().fold(0, |l, &b| (l << 8) | usize::from(b))
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let (contents, rest) = rest.split_at(len);
+    Some((tag, contents, rest))
+}
+
+fn expect(tag: u8, input: &[u8]) -> Option<(&[u8], &[u8])> {
+    match read(input)? {
+        (t, contents, rest) if t == tag => Some((contents, rest)),
+        _ => None,
+    }
+}
+
+/// Parses a `UTCTime` (`YYMMDDHHMMSSZ`) or `GeneralizedTime`
+/// (`YYYYMMDDHHMMSSZ`), as restricted by RFC 5280.
+fn parse_time(tag: u8, time: &[u8]) -> Option<SystemTime> {
+    let (year, rest) = match (tag, time.len()) {
+        (UTC_TIME, 13) => {
+            let yy = digits(&time[..2])?;
+            let year = if yy >= 50 { 1900 + yy } else { 2000 + yy };
+            (year, &time[2..])
+        }
+        (GENERALIZED_TIME, 15) => (digits(&time[..4])?, &time[4..]),
+        _ => return None,
+    };
+    if rest[10] != b'Z' {
+        return None;
+    }
+
+    let month = digits(&rest[0..2])?;
+    let day = digits(&rest[2..4])?;
+    let hour = digits(&rest[4..6])?;
+    let minute = digits(&rest[6..8])?;
+    let second = digits(&rest[8..10])?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let days = days_since_epoch(year, month, day)?;
+    let secs = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn digits(s: &[u8]) -> Option<u64> {
+    s.iter().try_fold(0, |n, &d| {
+        if d.is_ascii_digit() {
+            Some(n * 10 + u64::from(d - b'0'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Counts the days from 1970-01-01 to the given date in the proleptic
+/// Gregorian calendar, returning `None` for dates before the epoch.
+fn days_since_epoch(year: u64, month: u64, day: u64) -> Option<u64> {
+    // Years start in March so that leap days fall at the end of the year.
+    let year = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era).checked_sub(719_468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CRT: &[u8] = include_bytes!("testdata/foo-ns1-ca1/crt.der");
+    static SVID: &[u8] = include_bytes!("testdata/baz-ns1-ca1/crt.der");
+
+    #[test]
+    fn reads_not_after() {
+        assert_eq!(
+            not_after(CRT),
+            Some(UNIX_EPOCH + Duration::from_secs(1_899_965_340))
+        );
+        assert_eq!(not_after(&CRT[..CRT.len() / 2]), None);
+    }
+
+    #[test]
+    fn reads_serials() {
+        assert_eq!(
+            serial(CRT),
+            Some(
+                &[
+                    0x58, 0x1c, 0x43, 0xd1, 0x07, 0x56, 0x53, 0x76, 0x42, 0x79, 0x73, 0xd8, 0x3c,
+                    0x36, 0xc2, 0xef, 0x5d, 0x49, 0xa6, 0xa8
+                ][..]
+            )
+        );
+        assert_eq!(serial(&CRT[..CRT.len() / 2]), None);
+        assert_eq!(trim_serial(&[0, 0x80, 0]), &[0x80, 0]);
+    }
+
+    #[test]
+    fn reads_uri_sans() {
+        assert_eq!(uri_sans(CRT), Some(vec![]));
+        assert_eq!(
+            dns_sans(CRT),
+            Some(vec![
+                "foo.ns1.serviceaccount.identity.linkerd.cluster.local"
+            ])
+        );
+        assert_eq!(
+            uri_sans(SVID),
+            Some(vec!["spiffe://cluster.local/ns/ns1/sa/baz"])
+        );
+        assert_eq!(uri_sans(&SVID[..SVID.len() / 2]), None);
+    }
+
+    #[test]
+    fn parses_times() {
+        let utc = parse_time(UTC_TIME, b"700101000000Z").unwrap();
+        assert_eq!(utc, UNIX_EPOCH);
+        let generalized = parse_time(GENERALIZED_TIME, b"20000301000000Z").unwrap();
+        assert_eq!(generalized, UNIX_EPOCH + Duration::from_secs(951_868_800));
+        assert_eq!(
+            parse_time(UTC_TIME, b"491231235959Z"),
+            parse_time(GENERALIZED_TIME, b"20491231235959Z")
+        );
+        assert!(parse_time(UTC_TIME, b"701301000000Z").is_none());
+        assert!(parse_time(GENERALIZED_TIME, b"19691231235959Z").is_none());
+    }
+
+    #[test]
+    fn encodes_lengths() {
+        for &len in &[0, 0x7f, 0x80, 0xff, 0x100, 0x1_0000] {
+            let value = vec![0; len];
+            let encoded = encode(OCTET_STRING, &[&value]);
+            assert_eq!(read(&encoded), Some((OCTET_STRING, &value[..], &[][..])));
+        }
+    }
+
+    #[test]
+    fn encodes_signed_csrs() {
+        use crate::{Csr, Key, KeyAlgorithm, Name};
+        use ring::signature::{self, VerificationAlgorithm};
+        use std::str::FromStr;
+
+        let name = Name::from_str("foo.ns1.serviceaccount.identity.linkerd.cluster.local").unwrap();
+        let algs: &[(KeyAlgorithm, &dyn VerificationAlgorithm)] = &[
+            (KeyAlgorithm::EcdsaP256, &signature::ECDSA_P256_SHA256_ASN1),
+            (KeyAlgorithm::EcdsaP384, &signature::ECDSA_P384_SHA384_ASN1),
+            (KeyAlgorithm::Ed25519, &signature::ED25519),
+        ];
+        for &(alg, verification) in algs {
+            let key = Key::generate(alg).expect("key must be generated");
+            let der = Csr::generate(&key, &name)
+                .expect("CSR must be signed")
+                .to_vec();
+
+            let (csr, rest) = expect(SEQUENCE, &der).unwrap();
+            assert!(rest.is_empty());
+            let (_, _, rest) = read(csr).unwrap();
+            let info = &csr[..csr.len() - rest.len()];
+            let (signature_id, rest) = expect(SEQUENCE, rest).unwrap();
+            assert_eq!(signature_id, alg.signature_id());
+            let (signature, _) = expect(BIT_STRING, rest).unwrap();
+            signature::UnparsedPublicKey::new(verification, key.public_key())
+                .verify(info, &signature[1..])
+                .unwrap_or_else(|_| panic!("{} CSR signature must be valid", alg));
+        }
+    }
+
+    #[test]
+    fn splits_certs() {
+        let chain = [CRT, CRT].concat();
+        assert_eq!(split_certs(&chain), Some(vec![CRT.to_vec(), CRT.to_vec()]));
+        assert_eq!(split_certs(&chain[..chain.len() - 1]), None);
+    }
+}
@@ -0,0 +1,219 @@
+use ring::{
+    error::{KeyRejected, Unspecified},
+    rand,
+    signature::{self, EcdsaKeyPair, Ed25519KeyPair, KeyPair as _},
+};
+use rustls::internal::msgs::enums::SignatureAlgorithm;
+use std::{fmt, str::FromStr, sync::Arc};
+
+/// The algorithm of the local identity's private key.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum KeyAlgorithm {
+    EcdsaP256,
+    EcdsaP384,
+    Ed25519,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct InvalidKeyAlgorithm;
+
+#[derive(Clone, Debug)]
+pub struct Key(Arc<KeyPair>);
+
+#[derive(Debug)]
+enum KeyPair {
+    Ecdsa(KeyAlgorithm, EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+pub(crate) struct SigningKey(pub(crate) Key);
+struct Signer(Key);
+
+// DER-encoded `AlgorithmIdentifier` contents.
+
+/// id-ecPublicKey (1.2.840.10045.2.1) with prime256v1 (1.2.840.10045.3.1.7)
+static EC_P256_PUBLIC_KEY: &[u8] = &[
+    0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d,
+    0x03, 0x01, 0x07,
+];
+/// id-ecPublicKey (1.2.840.10045.2.1) with secp384r1 (1.3.132.0.34)
+static EC_P384_PUBLIC_KEY: &[u8] = &[
+    0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22,
+];
+/// ecdsa-with-SHA256 (1.2.840.10045.4.3.2)
+static ECDSA_SHA256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+/// ecdsa-with-SHA384 (1.2.840.10045.4.3.3)
+static ECDSA_SHA384: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+/// id-Ed25519 (1.3.101.112), which identifies both keys and signatures.
+static ED25519: &[u8] = &[0x06, 0x03, 0x2b, 0x65, 0x70];
+
+// === impl KeyAlgorithm ===
+
+impl KeyAlgorithm {
+    fn ecdsa_signing(self) -> Option<&'static signature::EcdsaSigningAlgorithm> {
+        match self {
+            Self::EcdsaP256 => Some(&signature::ECDSA_P256_SHA256_ASN1_SIGNING),
+            Self::EcdsaP384 => Some(&signature::ECDSA_P384_SHA384_ASN1_SIGNING),
+            Self::Ed25519 => None,
+        }
+    }
+
+    pub(crate) fn rustls_scheme(self) -> rustls::SignatureScheme {
+        match self {
+            Self::EcdsaP256 => rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
+            Self::EcdsaP384 => rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
+            Self::Ed25519 => rustls::SignatureScheme::ED25519,
+        }
+    }
+
+    pub(crate) fn public_key_id(self) -> &'static [u8] {
+        match self {
+            Self::EcdsaP256 => EC_P256_PUBLIC_KEY,
+            Self::EcdsaP384 => EC_P384_PUBLIC_KEY,
+            Self::Ed25519 => ED25519,
+        }
+    }
+
+    pub(crate) fn signature_id(self) -> &'static [u8] {
+        match self {
+            Self::EcdsaP256 => ECDSA_SHA256,
+            Self::EcdsaP384 => ECDSA_SHA384,
+            Self::Ed25519 => ED25519,
+        }
+    }
+}
+
+impl Default for KeyAlgorithm {
+    fn default() -> Self {
+        Self::EcdsaP256
+    }
+}
+
+impl FromStr for KeyAlgorithm {
+    type Err = InvalidKeyAlgorithm;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ecdsa-p256" => Ok(Self::EcdsaP256),
+            "ecdsa-p384" => Ok(Self::EcdsaP384),
+            "ed25519" => Ok(Self::Ed25519),
+            _ => Err(InvalidKeyAlgorithm),
+        }
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EcdsaP256 => write!(f, "ecdsa-p256"),
+            Self::EcdsaP384 => write!(f, "ecdsa-p384"),
+            Self::Ed25519 => write!(f, "ed25519"),
+        }
+    }
+}
+
+// === impl Key ===
+
+impl Key {
+    /// Generates a new random key.
+    pub fn generate(alg: KeyAlgorithm) -> Result<Self, Unspecified> {
+        let rng = rand::SystemRandom::new();
+        let pkcs8 = match alg.ecdsa_signing() {
+            Some(signing) => EcdsaKeyPair::generate_pkcs8(signing, &rng)?,
+            None => Ed25519KeyPair::generate_pkcs8(&rng)?,
+        };
+        Self::from_pkcs8_as(alg, pkcs8.as_ref()).map_err(|_| Unspecified)
+    }
+
+    /// Parses a PKCS#8-encoded key of any supported algorithm.
+    pub fn from_pkcs8(b: &[u8]) -> Result<Self, KeyRejected> {
+        Self::from_pkcs8_as(KeyAlgorithm::EcdsaP256, b)
+            .or_else(|e| Self::from_pkcs8_as(KeyAlgorithm::EcdsaP384, b).map_err(|_| e))
+            .or_else(|e| Self::from_pkcs8_as(KeyAlgorithm::Ed25519, b).map_err(|_| e))
+    }
+
+    /// Parses a PKCS#8-encoded key, which must use the given algorithm.
+    pub fn from_pkcs8_as(alg: KeyAlgorithm, b: &[u8]) -> Result<Self, KeyRejected> {
+        let pair = match alg.ecdsa_signing() {
+            Some(signing) => KeyPair::Ecdsa(alg, EcdsaKeyPair::from_pkcs8(signing, b)?),
+            // Tools like OpenSSL omit the public key from Ed25519 keys.
+            None => KeyPair::Ed25519(Ed25519KeyPair::from_pkcs8_maybe_unchecked(b)?),
+        };
+        Ok(Key(Arc::new(pair)))
+    }
+
+    /// Parses the first PKCS#8-encoded key in a PEM document.
+    pub fn from_pkcs8_pem(s: &str) -> Result<Self, KeyRejected> {
+        use std::io::Cursor;
+
+        let keys =
+            rustls::internal::pemfile::pkcs8_private_keys(&mut Cursor::new(s)).unwrap_or_default();
+        // If there is no key, an empty key is rejected as invalid.
+        let der = keys.first().map(|k| k.0.as_slice()).unwrap_or(&[]);
+        Self::from_pkcs8(der)
+    }
+
+    pub fn algorithm(&self) -> KeyAlgorithm {
+        match *self.0 {
+            KeyPair::Ecdsa(alg, _) => alg,
+            KeyPair::Ed25519(_) => KeyAlgorithm::Ed25519,
+        }
+    }
+
+    pub(crate) fn public_key(&self) -> &[u8] {
+        match *self.0 {
+            KeyPair::Ecdsa(_, ref k) => k.public_key().as_ref(),
+            KeyPair::Ed25519(ref k) => k.public_key().as_ref(),
+        }
+    }
+
+    pub(crate) fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Unspecified> {
+        let signature = match *self.0 {
+            KeyPair::Ecdsa(_, ref k) => k.sign(&rand::SystemRandom::new(), message)?,
+            KeyPair::Ed25519(ref k) => k.sign(message),
+        };
+        Ok(signature.as_ref().to_owned())
+    }
+}
+
+impl rustls::sign::SigningKey for SigningKey {
+    fn choose_scheme(
+        &self,
+        offered: &[rustls::SignatureScheme],
+    ) -> Option<Box<dyn rustls::sign::Signer>> {
+        if offered.contains(&self.0.algorithm().rustls_scheme()) {
+            Some(Box::new(Signer(self.0.clone())))
+        } else {
+            None
+        }
+    }
+
+    /// Rustls uses this to select TLS 1.2 cipher suites. EdDSA keys are used
+    /// with the ECDHE_ECDSA suites (RFC 8422), but Rustls only considers ECDSA
+    /// schemes usable with them, so all supported keys are reported as ECDSA.
+    fn algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::ECDSA
+    }
+}
+
+impl rustls::sign::Signer for Signer {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::TLSError> {
+        self.0
+            .sign(message)
+            .map_err(|Unspecified| rustls::TLSError::General("Signing Failed".to_owned()))
+    }
+
+    fn get_scheme(&self) -> rustls::SignatureScheme {
+        self.0.algorithm().rustls_scheme()
+    }
+}
+
+// === impl InvalidKeyAlgorithm ===
+
+impl fmt::Display for InvalidKeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid key algorithm")
+    }
+}
+
+impl std::error::Error for InvalidKeyAlgorithm {}
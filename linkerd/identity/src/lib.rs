@@ -1,32 +1,41 @@
 #![deny(warnings, rust_2018_idioms)]
 
-pub use ring::error::KeyRejected;
-use ring::rand;
-use ring::signature::EcdsaKeyPair;
+pub use ring::error::{KeyRejected, Unspecified};
 use std::{convert::TryFrom, error::Error, fmt, fs, io, str::FromStr, sync::Arc, time::SystemTime};
 use tracing::{debug, warn};
 
+mod denylist;
+mod der;
+mod key;
+mod spiffe;
 #[cfg(any(test, feature = "test-util"))]
 pub mod test_util;
 
+pub use self::denylist::{DeniedCertificate, Denylist, InvalidDenylist, SharedDenylist};
+use self::key::SigningKey;
+pub use self::key::{InvalidKeyAlgorithm, Key, KeyAlgorithm};
+pub use self::spiffe::{InvalidSpiffeId, SpiffeId};
 pub use linkerd_dns_name::InvalidName;
 
 /// A DER-encoded X.509 certificate signing request.
 #[derive(Clone, Debug)]
 pub struct Csr(Arc<Vec<u8>>);
 
-/// An endpoint's identity.
+/// An endpoint's identity: either a DNS-like name or a SPIFFE ID.
 #[derive(Clone, Eq, PartialEq, Hash)]
-pub struct Name(Arc<linkerd_dns_name::Name>);
+pub struct Name(Arc<Kind>);
 
-#[derive(Clone, Debug)]
-pub struct Key(Arc<EcdsaKeyPair>);
-
-struct SigningKey(Arc<EcdsaKeyPair>);
-struct Signer(Arc<EcdsaKeyPair>);
+#[derive(Clone, Eq, PartialEq, Hash)]
+enum Kind {
+    Dns(linkerd_dns_name::Name),
+    Spiffe(SpiffeId),
+}
 
 #[derive(Clone)]
-pub struct TrustAnchors(Arc<rustls::ClientConfig>);
+pub struct TrustAnchors {
+    config: Arc<rustls::ClientConfig>,
+    fingerprint: Arc<str>,
+}
 
 #[derive(Clone, Debug)]
 pub struct TokenSource(Arc<String>);
@@ -44,9 +53,10 @@ pub struct CrtKey {
     expiry: SystemTime,
     client_config: Arc<rustls::ClientConfig>,
     server_config: Arc<rustls::ServerConfig>,
+    client_verifier: Arc<dyn rustls::ClientCertVerifier>,
 }
 
-struct CertResolver(rustls::sign::CertifiedKey);
+struct CertResolver(rustls::sign::CertifiedKey, rustls::SignatureScheme);
 
 #[derive(Clone, Debug)]
 pub struct InvalidCrt(rustls::TLSError);
@@ -55,15 +65,19 @@ pub struct InvalidCrt(rustls::TLSError);
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct LocalId(pub Name);
 
-// These must be kept in sync:
-static SIGNATURE_ALG_RING_SIGNING: &ring::signature::EcdsaSigningAlgorithm =
-    &ring::signature::ECDSA_P256_SHA256_ASN1_SIGNING;
-const SIGNATURE_ALG_RUSTLS_SCHEME: rustls::SignatureScheme =
-    rustls::SignatureScheme::ECDSA_NISTP256_SHA256;
-const SIGNATURE_ALG_RUSTLS_ALGORITHM: rustls::internal::msgs::enums::SignatureAlgorithm =
-    rustls::internal::msgs::enums::SignatureAlgorithm::ECDSA;
 const TLS_VERSIONS: &[rustls::ProtocolVersion] = &[rustls::ProtocolVersion::TLSv1_2];
 
+/// The versions that may be negotiated with servers outside of the mesh.
+const WEB_PKI_TLS_VERSIONS: &[rustls::ProtocolVersion] = &[
+    rustls::ProtocolVersion::TLSv1_3,
+    rustls::ProtocolVersion::TLSv1_2,
+];
+
+/// The number of sessions a certified identity retains for resumption, as a
+/// client and as a server, respectively.
+const CLIENT_SESSION_CACHE_CAPACITY: usize = 1024;
+const SERVER_SESSION_CACHE_CAPACITY: usize = 1024;
+
 // === impl Csr ===
 
 impl Csr {
@@ -75,58 +89,62 @@ impl Csr {
         Some(Csr(Arc::new(der)))
     }
 
+    /// Generates a request, signed by `key`, for a certificate that is valid
+    /// for `name`.
+    pub fn generate(key: &Key, name: &Name) -> Result<Self, Unspecified> {
+        let san = match name.spiffe_id() {
+            Some(id) => der::SubjectAltName::Uri(id.as_ref()),
+            None => der::SubjectAltName::Dns(name.as_ref()),
+        };
+        let alg = key.algorithm();
+        let info = der::csr_info(san, alg.public_key_id(), key.public_key());
+        let signature = key.sign(&info)?;
+        let der = der::csr(&info, alg.signature_id(), &signature);
+        Ok(Csr(Arc::new(der)))
+    }
+
     pub fn to_vec(&self) -> Vec<u8> {
         self.0.to_vec()
     }
 }
 
-// === impl Key ===
+// === impl Name ===
 
-impl Key {
-    pub fn from_pkcs8(b: &[u8]) -> Result<Self, KeyRejected> {
-        let k = EcdsaKeyPair::from_pkcs8(SIGNATURE_ALG_RING_SIGNING, b)?;
-        Ok(Key(Arc::new(k)))
+impl Name {
+    pub fn dns_name(&self) -> Option<&linkerd_dns_name::Name> {
+        match *self.0 {
+            Kind::Dns(ref n) => Some(n),
+            Kind::Spiffe(_) => None,
+        }
     }
-}
 
-impl rustls::sign::SigningKey for SigningKey {
-    fn choose_scheme(
-        &self,
-        offered: &[rustls::SignatureScheme],
-    ) -> Option<Box<dyn rustls::sign::Signer>> {
-        if offered.contains(&SIGNATURE_ALG_RUSTLS_SCHEME) {
-            Some(Box::new(Signer(self.0.clone())))
-        } else {
-            None
+    pub fn spiffe_id(&self) -> Option<&SpiffeId> {
+        match *self.0 {
+            Kind::Dns(_) => None,
+            Kind::Spiffe(ref id) => Some(id),
         }
     }
 
-    fn algorithm(&self) -> rustls::internal::msgs::enums::SignatureAlgorithm {
-        SIGNATURE_ALG_RUSTLS_ALGORITHM
+    /// Returns the TLS server name (SNI) that clients use to connect to this
+    /// identity. SPIFFE IDs are not valid server names, so they are encoded as
+    /// DNS names.
+    pub fn server_name(&self) -> &linkerd_dns_name::Name {
+        match *self.0 {
+            Kind::Dns(ref n) => n,
+            Kind::Spiffe(ref id) => id.server_name(),
+        }
     }
 }
 
-impl rustls::sign::Signer for Signer {
-    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::TLSError> {
-        let rng = rand::SystemRandom::new();
-        self.0
-            .sign(&rng, message)
-            .map(|signature| signature.as_ref().to_owned())
-            .map_err(|ring::error::Unspecified| {
-                rustls::TLSError::General("Signing Failed".to_owned())
-            })
-    }
-
-    fn get_scheme(&self) -> rustls::SignatureScheme {
-        SIGNATURE_ALG_RUSTLS_SCHEME
+impl From<linkerd_dns_name::Name> for Name {
+    fn from(n: linkerd_dns_name::Name) -> Self {
+        Name(Arc::new(Kind::Dns(n)))
     }
 }
 
-// === impl Name ===
-
-impl From<linkerd_dns_name::Name> for Name {
-    fn from(n: linkerd_dns_name::Name) -> Self {
-        Name(Arc::new(n))
+impl From<SpiffeId> for Name {
+    fn from(id: SpiffeId) -> Self {
+        Name(Arc::new(Kind::Spiffe(id)))
     }
 }
 
@@ -140,14 +158,21 @@ impl FromStr for Name {
     type Err = InvalidName;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("spiffe:") {
+            return SpiffeId::from_str(s)
+                .map(Name::from)
+                .map_err(|InvalidSpiffeId| InvalidName);
+        }
+
         if s.as_bytes().last() == Some(&b'.') {
             return Err(InvalidName); // SNI hostnames are implicitly absolute.
         }
 
-        linkerd_dns_name::Name::from_str(s).map(|n| Name(Arc::new(n)))
+        linkerd_dns_name::Name::from_str(s).map(Name::from)
     }
 }
 
+/// Parses a TLS server name, which is always a DNS name.
 impl TryFrom<&[u8]> for Name {
     type Error = InvalidName;
 
@@ -156,31 +181,40 @@ impl TryFrom<&[u8]> for Name {
             return Err(InvalidName); // SNI hostnames are implicitly absolute.
         }
 
-        linkerd_dns_name::Name::try_from(s).map(|n| Name(Arc::new(n)))
+        linkerd_dns_name::Name::try_from(s).map(Name::from)
     }
 }
 
 impl<'t> Into<webpki::DNSNameRef<'t>> for &'t Name {
     fn into(self) -> webpki::DNSNameRef<'t> {
-        self.0.as_ref().into()
+        self.server_name().into()
     }
 }
 
 impl AsRef<str> for Name {
     fn as_ref(&self) -> &str {
-        (*self.0).as_ref()
+        match *self.0 {
+            Kind::Dns(ref n) => n.as_ref(),
+            Kind::Spiffe(ref id) => id.as_ref(),
+        }
     }
 }
 
 impl fmt::Debug for Name {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        fmt::Debug::fmt(&self.0, f)
+        match *self.0 {
+            Kind::Dns(ref n) => fmt::Debug::fmt(n, f),
+            Kind::Spiffe(ref id) => fmt::Debug::fmt(id, f),
+        }
     }
 }
 
 impl fmt::Display for Name {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        fmt::Display::fmt(&self.0, f)
+        match *self.0 {
+            Kind::Dns(ref n) => fmt::Display::fmt(n, f),
+            Kind::Spiffe(ref id) => fmt::Display::fmt(id, f),
+        }
     }
 }
 
@@ -206,16 +240,43 @@ impl TokenSource {
 // === impl TrustAnchors ===
 
 impl TrustAnchors {
-    #[cfg(any(test, feature = "test-util"))]
-    fn empty() -> Self {
-        TrustAnchors(Arc::new(rustls::ClientConfig::new()))
+    /// Returns trust anchors that trust no certificates.
+    pub fn empty() -> Self {
+        TrustAnchors {
+            config: Arc::new(rustls::ClientConfig::new()),
+            fingerprint: Arc::from(""),
+        }
     }
 
+    /// Parses a bundle of one or more PEM-encoded trust anchors.
+    ///
+    /// A bundle may hold several anchors so that certificates issued by either
+    /// an old or a new CA are trusted while the CA is rotated.
     pub fn from_pem(s: &str) -> Option<Self> {
         use std::io::Cursor;
 
+        let certs = rustls::internal::pemfile::certs(&mut Cursor::new(s)).ok()?;
+        Self::from_certs(certs)
+    }
+
+    /// Parses a bundle of concatenated DER-encoded trust anchors.
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let certs = der::split_certs(der)?;
+        Self::from_certs(certs.into_iter().map(rustls::Certificate).collect())
+    }
+
+    fn from_certs(certs: Vec<rustls::Certificate>) -> Option<Self> {
         let mut roots = rustls::RootCertStore::empty();
-        let (added, skipped) = roots.add_pem_file(&mut Cursor::new(s)).ok()?;
+        let mut digest = ring::digest::Context::new(&ring::digest::SHA256);
+        let (mut added, mut skipped) = (0, 0);
+        for crt in &certs {
+            if roots.add(crt).is_ok() {
+                digest.update(&crt.0);
+                added += 1;
+            } else {
+                skipped += 1;
+            }
+        }
         if skipped != 0 {
             warn!("skipped {} trust anchors in trust anchors file", skipped);
         }
@@ -232,15 +293,30 @@ impl TrustAnchors {
         // TODO: Change Rustls's API to Avoid needing to clone `root_cert_store`.
         c.root_store = roots;
 
-        // Disable session resumption for the time-being until resumption is
-        // more tested.
+        // Sessions are only resumed once an identity is certified, so that
+        // each certificate has its own session cache (see `certify`).
         c.enable_tickets = false;
 
-        Some(TrustAnchors(Arc::new(c)))
+        let fingerprint = digest
+            .finish()
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
+        Some(TrustAnchors {
+            config: Arc::new(c),
+            fingerprint: fingerprint.into(),
+        })
+    }
+
+    /// Returns the hex-encoded SHA-256 digest of the anchors' DER encodings.
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
     }
 
     pub fn certify(&self, key: Key, crt: Crt) -> Result<CrtKey, InvalidCrt> {
-        let mut client = self.0.as_ref().clone();
+        let mut client = self.config.as_ref().clone();
 
         // Ensure the certificate is valid for the services we terminate for
         // TLS. This assumes that server cert validation does the same or
@@ -255,19 +331,31 @@ impl TrustAnchors {
         //
         // TODO: Restrict accepted signatutre algorithms.
         static NO_OCSP: &[u8] = &[];
-        client
-            .get_verifier()
-            .verify_server_cert(&client.root_store, &crt.chain, (&crt.id).into(), NO_OCSP)
-            .map_err(InvalidCrt)?;
+        match crt.name().spiffe_id() {
+            None => client
+                .get_verifier()
+                .verify_server_cert(&client.root_store, &crt.chain, (&crt.id).into(), NO_OCSP)
+                .map(|_| ())
+                .map_err(InvalidCrt)?,
+            Some(id) => spiffe::verify(&client.root_store, &crt.chain, id).map_err(InvalidCrt)?,
+        }
         debug!("certified {}", crt.id);
 
-        let k = SigningKey(key.0);
+        let scheme = key.algorithm().rustls_scheme();
+        let k = SigningKey(key);
         let key = rustls::sign::CertifiedKey::new(crt.chain, Arc::new(Box::new(k)));
-        let resolver = Arc::new(CertResolver(key));
+        let resolver = Arc::new(CertResolver(key, scheme));
 
         // Enable client authentication.
         client.client_auth_cert_resolver = resolver.clone();
 
+        // Resume sessions with servers to avoid repeating the full mTLS
+        // handshake on every connection. Sessions are cached per-certificate
+        // so that they aren't resumed with a prior certificate.
+        client.enable_tickets = true;
+        client.session_persistence =
+            rustls::ClientSessionMemoryCache::new(CLIENT_SESSION_CACHE_CAPACITY);
+
         // Ask TLS clients for a certificate and accept any certificate issued
         // by our trusted CA(s).
         //
@@ -277,28 +365,50 @@ impl TrustAnchors {
         // TODO: lock down the verification further.
         //
         // TODO: Change Rustls's API to Avoid needing to clone `root_cert_store`.
-        let mut server = rustls::ServerConfig::new(
-            rustls::AllowAnyAnonymousOrAuthenticatedClient::new(self.0.root_store.clone()),
-        );
+        let client_verifier =
+            rustls::AllowAnyAnonymousOrAuthenticatedClient::new(self.config.root_store.clone());
+        let mut server = rustls::ServerConfig::new(client_verifier.clone());
         server.versions = TLS_VERSIONS.to_vec();
         server.cert_resolver = resolver;
 
+        // Let clients resume sessions either by session ID or with a ticket.
+        // A resumed session retains the client's original certificate chain,
+        // so the client's identity is still known.
+        server.session_storage =
+            rustls::ServerSessionMemoryCache::new(SERVER_SESSION_CACHE_CAPACITY);
+        server.ticketer = rustls::Ticketer::new();
+
         Ok(CrtKey {
             id: crt.id,
             expiry: crt.expiry,
             client_config: Arc::new(client),
             server_config: Arc::new(server),
+            client_verifier,
         })
     }
 
     pub fn client_config(&self) -> Arc<rustls::ClientConfig> {
-        self.0.clone()
+        self.config.clone()
+    }
+
+    /// Returns a configuration for connecting to servers outside of the mesh,
+    /// whose certificates are issued by these anchors (e.g. web PKI roots).
+    ///
+    /// Unlike the mesh's configuration, TLS 1.3 may be negotiated, sessions
+    /// are resumed, and a client certificate is never presented.
+    pub fn web_pki_client_config(&self) -> Arc<rustls::ClientConfig> {
+        let mut c = rustls::ClientConfig::new();
+        c.root_store = self.config.root_store.clone();
+        c.versions = WEB_PKI_TLS_VERSIONS.to_vec();
+        Arc::new(c)
     }
 }
 
 impl fmt::Debug for TrustAnchors {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TrustAnchors").finish()
+        f.debug_struct("TrustAnchors")
+            .field("fingerprint", &self.fingerprint)
+            .finish()
     }
 }
 
@@ -318,6 +428,32 @@ impl Crt {
         Self { id, chain, expiry }
     }
 
+    /// Reads a chain of concatenated DER-encoded certificates, leaf first. The
+    /// certificate's expiry is read from the leaf.
+    pub fn from_der(id: LocalId, der: &[u8]) -> Result<Self, InvalidCrt> {
+        let chain = der::split_certs(der).ok_or_else(InvalidCrt::bad_der)?;
+        Self::from_chain(id, chain)
+    }
+
+    /// Reads a chain of PEM-encoded certificates, leaf first. The
+    /// certificate's expiry is read from the leaf.
+    pub fn from_pem(id: LocalId, s: &str) -> Result<Self, InvalidCrt> {
+        use std::io::Cursor;
+
+        let chain = rustls::internal::pemfile::certs(&mut Cursor::new(s))
+            .map_err(|()| InvalidCrt::bad_der())?;
+        Self::from_chain(id, chain.into_iter().map(|c| c.0).collect())
+    }
+
+    fn from_chain(id: LocalId, chain: Vec<Vec<u8>>) -> Result<Self, InvalidCrt> {
+        let expiry = chain
+            .first()
+            .and_then(|leaf| der::not_after(leaf))
+            .ok_or_else(InvalidCrt::bad_der)?;
+        let chain = chain.into_iter().map(rustls::Certificate).collect();
+        Ok(Self { id, chain, expiry })
+    }
+
     pub fn name(&self) -> &Name {
         self.id.as_ref()
     }
@@ -385,7 +521,7 @@ impl CertResolver {
         &self,
         sigschemes: &[rustls::SignatureScheme],
     ) -> Option<rustls::sign::CertifiedKey> {
-        if !sigschemes.contains(&SIGNATURE_ALG_RUSTLS_SCHEME) {
+        if !sigschemes.contains(&self.1) {
             debug!("signature scheme not supported -> no certificate");
             return None;
         }
@@ -402,13 +538,18 @@ impl rustls::ResolvesServerCert for CertResolver {
             return None;
         };
 
-        // Verify that our certificate is valid for the given SNI name.
+        // Verify that our certificate is valid for the given SNI name. A
+        // certificate for a SPIFFE ID is valid for the ID's server name.
         let c = (&self.0.cert)
             .first()
             .map(rustls::Certificate::as_ref)
             .unwrap_or(&[]); // An empty input will fail to parse.
-        if let Err(err) =
-            webpki::EndEntityCert::from(c).and_then(|c| c.verify_is_valid_for_dns_name(server_name))
+        if let Err(err) = webpki::EndEntityCert::from(c)
+            .and_then(|c| c.verify_is_valid_for_dns_name(server_name))
+            .or_else(|err| match SpiffeId::from_crt(c) {
+                Some(id) if *id.server_name() == server_name.to_owned().into() => Ok(()),
+                _ => Err(err),
+            })
         {
             debug!(
                 "our certificate is not valid for the SNI name -> no certificate: {:?}",
@@ -449,6 +590,12 @@ impl fmt::Display for LocalId {
 
 // === impl InvalidCrt ===
 
+impl InvalidCrt {
+    fn bad_der() -> Self {
+        InvalidCrt(rustls::TLSError::WebPKIError(webpki::Error::BadDER))
+    }
+}
+
 impl fmt::Display for InvalidCrt {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.0.fmt(f)
@@ -464,6 +611,7 @@ impl Error for InvalidCrt {
 #[cfg(test)]
 mod tests {
     use super::test_util::*;
+    use super::{Crt, Key, KeyAlgorithm, LocalId, TrustAnchors};
 
     #[test]
     fn can_construct_client_and_server_config_from_valid_settings() {
@@ -479,6 +627,47 @@ mod tests {
         assert!(s.validate().is_err(), "ca2 should not validate foo.ns1");
     }
 
+    #[test]
+    fn bundle_trusts_all_anchors() {
+        let ca1 = std::str::from_utf8(include_bytes!("testdata/ca1.pem")).unwrap();
+        let ca2 = std::str::from_utf8(include_bytes!("testdata/ca2.pem")).unwrap();
+        let bundle = format!("{}\n{}", ca1, ca2);
+        let anchors = TrustAnchors::from_pem(&bundle).expect("bundle must be valid");
+        assert!(anchors.certify(FOO_NS1.key(), FOO_NS1.crt()).is_ok());
+        let foo_ca2 = Identity {
+            crt: include_bytes!("testdata/foo-ns1-ca2/crt.der"),
+            key: include_bytes!("testdata/foo-ns1-ca2/key.p8"),
+            ..FOO_NS1
+        };
+        assert!(anchors.certify(foo_ca2.key(), foo_ca2.crt()).is_ok());
+
+        let ca1 = FOO_NS1.trust_anchors();
+        assert_eq!(ca1.fingerprint().len(), 64);
+        assert_ne!(ca1.fingerprint(), anchors.fingerprint());
+        assert_eq!(
+            ca1.fingerprint(),
+            TrustAnchors::from_pem(std::str::from_utf8(FOO_NS1.trust_anchors).unwrap())
+                .unwrap()
+                .fingerprint()
+        );
+    }
+
+    #[test]
+    fn reads_encoded_chains() {
+        let id = FOO_NS1.crt().name().clone();
+        let crt = Crt::from_der(LocalId(id.clone()), FOO_NS1.crt).expect("must be valid DER");
+        let key = Key::from_pkcs8(FOO_NS1.key).unwrap();
+        let crt_key = FOO_NS1.trust_anchors().certify(key, crt).unwrap();
+        assert_eq!(
+            crt_key.expiry(),
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_899_965_340)
+        );
+
+        let ca1 = std::str::from_utf8(FOO_NS1.trust_anchors).unwrap();
+        assert!(Crt::from_pem(LocalId(id), ca1).is_ok());
+        assert!(Key::from_pkcs8_pem(ca1).is_err());
+    }
+
     #[test]
     fn recognize_cert_is_not_valid_for_identity() {
         let s = Identity {
@@ -489,6 +678,47 @@ mod tests {
         assert!(s.validate().is_err(), "identity should not be valid");
     }
 
+    #[test]
+    fn certifies_spiffe_ids() {
+        let crt_key = BAZ_NS1_SVID.validate().expect("baz.ns1 must be valid");
+        assert_eq!(
+            crt_key.name().spiffe_id().map(|id| id.path()),
+            Some("/ns/ns1/sa/baz")
+        );
+        assert_eq!(
+            crt_key.name().server_name().as_ref(),
+            "baz.sa.ns1.ns.cluster.local"
+        );
+
+        let s = Identity {
+            name: "spiffe://cluster.local/ns/ns1/sa/foo",
+            ..BAZ_NS1_SVID
+        };
+        assert!(s.validate().is_err(), "SPIFFE ID should not be valid");
+        let s = Identity {
+            name: "baz.ns1.serviceaccount.identity.linkerd.cluster.local",
+            ..BAZ_NS1_SVID
+        };
+        assert!(s.validate().is_err(), "DNS name should not be valid");
+    }
+
+    #[test]
+    fn certifies_each_key_algorithm() {
+        for &(id, alg) in &[
+            (&FOO_NS1, KeyAlgorithm::EcdsaP256),
+            (&FOO_NS1_P384, KeyAlgorithm::EcdsaP384),
+            (&FOO_NS1_ED25519, KeyAlgorithm::Ed25519),
+        ] {
+            assert_eq!(id.key().algorithm(), alg);
+            assert!(Key::from_pkcs8_as(alg, id.key).is_ok());
+            id.validate()
+                .unwrap_or_else(|e| panic!("{} must be valid: {}", alg, e));
+        }
+
+        assert!(Key::from_pkcs8_as(KeyAlgorithm::EcdsaP384, FOO_NS1.key).is_err());
+        assert!(Key::from_pkcs8_as(KeyAlgorithm::EcdsaP256, FOO_NS1_ED25519.key).is_err());
+    }
+
     #[test]
     #[ignore] // XXX this doesn't fail because we don't actually check the key against the cert...
     fn recognize_private_key_is_not_valid_for_cert() {
@@ -0,0 +1,364 @@
+use crate::{der, SharedDenylist};
+use linkerd_dns_name as dns;
+use std::{fmt, str::FromStr, sync::Arc, time::SystemTime};
+
+/// A SPIFFE ID, e.g. `spiffe://cluster.local/ns/default/sa/web`.
+///
+/// IDs are validated as described by the [SPIFFE ID specification][spec],
+/// except that the trust domain must also be a valid DNS name so that the ID
+/// may be encoded as a TLS server name.
+///
+/// [spec]: https://github.com/spiffe/spiffe/blob/main/standards/SPIFFE-ID.md
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct SpiffeId {
+    id: String,
+    trust_domain: dns::Name,
+    server_name: dns::Name,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct InvalidSpiffeId;
+
+/// Verifies that servers' certificates are valid for a SPIFFE ID.
+struct Verifier(SpiffeId);
+
+/// Scopes a client's resumable sessions to a SPIFFE ID.
+///
+/// Sessions are otherwise keyed by the TLS server name, which may be shared by
+/// several IDs (see `SpiffeId::server_name`), so a session established with one
+/// ID could be resumed with a server presenting another ID, without its
+/// certificate being verified.
+struct Sessions {
+    id: SpiffeId,
+    inner: Arc<dyn rustls::StoresClientSessions>,
+}
+
+const SCHEME: &str = "spiffe://";
+const MAX_LEN: usize = 2048;
+const MAX_LABEL_LEN: usize = 63;
+
+/// The number of bytes of an ID's hash that are encoded in its server name.
+const HASH_LEN: usize = 16;
+
+static SUPPORTED_SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+// === impl SpiffeId ===
+
+impl SpiffeId {
+    pub fn trust_domain(&self) -> &dns::Name {
+        &self.trust_domain
+    }
+
+    /// Returns the ID's path, which is empty for a trust domain's ID.
+    pub fn path(&self) -> &str {
+        &self.id[SCHEME.len() + self.trust_domain.as_ref().len()..]
+    }
+
+    /// Returns the TLS server name (SNI) that identifies this ID's workload.
+    ///
+    /// The path's segments are prepended to the trust domain in reverse
+    /// order, so `spiffe://cluster.local/ns/default/sa/web` is named
+    /// `web.sa.default.ns.cluster.local`. When a segment is not a lowercase
+    /// DNS label (or the name would be too long), the path is instead encoded
+    /// as a single label holding a hash of the ID.
+    pub fn server_name(&self) -> &dns::Name {
+        &self.server_name
+    }
+
+    /// Reads the SPIFFE ID from a DER-encoded X.509 certificate's URI subject
+    /// alternative names.
+    ///
+    /// An X.509 SVID has exactly one URI SAN, so certificates with more than
+    /// one URI SAN are not valid for any SPIFFE ID.
+    pub fn from_crt(crt: &[u8]) -> Option<Self> {
+        match der::uri_sans(crt)?.as_slice() {
+            [uri] => Self::from_str(uri).ok(),
+            _ => None,
+        }
+    }
+
+    /// Configures a client to verify that servers' certificates are valid for
+    /// this ID, instead of for the TLS server name, and are not denied.
+    pub fn verify_servers(&self, config: &mut rustls::ClientConfig, denylist: &SharedDenylist) {
+        denylist.verify_servers(config, Arc::new(Verifier(self.clone())));
+        config.session_persistence = Arc::new(Sessions {
+            id: self.clone(),
+            inner: config.session_persistence.clone(),
+        });
+    }
+}
+
+impl FromStr for SpiffeId {
+    type Err = InvalidSpiffeId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() > MAX_LEN || !s.starts_with(SCHEME) {
+            return Err(InvalidSpiffeId);
+        }
+
+        let rest = &s[SCHEME.len()..];
+        let (td, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+
+        let valid_td = !td.is_empty()
+            && td.bytes().all(|b| {
+                b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-' || b == b'_'
+            });
+        if !valid_td || td.ends_with('.') {
+            return Err(InvalidSpiffeId);
+        }
+        let trust_domain = dns::Name::from_str(td).map_err(|_| InvalidSpiffeId)?;
+
+        if !path.is_empty() {
+            // The path starts with a `/`, so the first segment is empty.
+            let valid_path = path.split('/').skip(1).all(|seg| {
+                !seg.is_empty()
+                    && seg != "."
+                    && seg != ".."
+                    && seg
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'.' || b == b'-' || b == b'_')
+            });
+            if !valid_path {
+                return Err(InvalidSpiffeId);
+            }
+        }
+
+        let server_name = encode_server_name(s, &trust_domain, path).ok_or(InvalidSpiffeId)?;
+        Ok(Self {
+            id: s.to_string(),
+            trust_domain,
+            server_name,
+        })
+    }
+}
+
+/// Encodes an ID as a DNS name (see `SpiffeId::server_name`).
+fn encode_server_name(id: &str, td: &dns::Name, path: &str) -> Option<dns::Name> {
+    if path.is_empty() {
+        return Some(td.clone());
+    }
+
+    let is_label = |seg: &str| {
+        seg.len() <= MAX_LABEL_LEN
+            && !seg.starts_with('-')
+            && !seg.ends_with('-')
+            && seg
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+    };
+    let segments = path.split('/').skip(1).collect::<Vec<_>>();
+    if segments.iter().all(|seg| is_label(seg)) {
+        let mut labels = segments;
+        labels.reverse();
+        let name = format!("{}.{}", labels.join("."), td.as_ref());
+        if let Ok(name) = dns::Name::from_str(&name) {
+            return Some(name);
+        }
+    }
+
+    let digest = ring::digest::digest(&ring::digest::SHA256, id.as_bytes());
+    let hash = digest.as_ref()[..HASH_LEN]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    dns::Name::from_str(&format!("{}.{}", hash, td.as_ref())).ok()
+}
+
+impl AsRef<str> for SpiffeId {
+    fn as_ref(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Debug for SpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.id, f)
+    }
+}
+
+impl fmt::Display for SpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.id, f)
+    }
+}
+
+// === impl Verifier ===
+
+impl rustls::ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        roots: &rustls::RootCertStore,
+        chain: &[rustls::Certificate],
+        _: webpki::DNSNameRef<'_>,
+        _: &[u8],
+    ) -> Result<rustls::ServerCertVerified, rustls::TLSError> {
+        verify(roots, chain, &self.0)?;
+        Ok(rustls::ServerCertVerified::assertion())
+    }
+}
+
+// === impl Sessions ===
+
+impl Sessions {
+    fn key(&self, key: &[u8]) -> Vec<u8> {
+        // IDs may not contain NUL bytes, so the key can't be ambiguous.
+        let mut scoped = Vec::with_capacity(self.id.id.len() + 1 + key.len());
+        scoped.extend_from_slice(self.id.id.as_bytes());
+        scoped.push(0);
+        scoped.extend_from_slice(key);
+        scoped
+    }
+}
+
+impl rustls::StoresClientSessions for Sessions {
+    fn put(&self, key: Vec<u8>, value: Vec<u8>) -> bool {
+        self.inner.put(self.key(&key), value)
+    }
+
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        self.inner.get(&self.key(key))
+    }
+}
+
+/// Verifies that a certificate chain is trusted and that its leaf is valid for
+/// `id`.
+pub(crate) fn verify(
+    roots: &rustls::RootCertStore,
+    chain: &[rustls::Certificate],
+    id: &SpiffeId,
+) -> Result<(), rustls::TLSError> {
+    let (leaf, intermediates) = chain
+        .split_first()
+        .ok_or(rustls::TLSError::NoCertificatesPresented)?;
+    let crt = webpki::EndEntityCert::from(&leaf.0).map_err(rustls::TLSError::WebPKIError)?;
+
+    let anchors = roots
+        .roots
+        .iter()
+        .map(|r| r.to_trust_anchor())
+        .collect::<Vec<_>>();
+    let intermediates = intermediates
+        .iter()
+        .map(|c| c.0.as_slice())
+        .collect::<Vec<_>>();
+    let now = webpki::Time::try_from(SystemTime::now())
+        .map_err(|_| rustls::TLSError::FailedToGetCurrentTime)?;
+    crt.verify_is_valid_tls_server_cert(
+        SUPPORTED_SIG_ALGS,
+        &webpki::TLSServerTrustAnchors(&anchors),
+        &intermediates,
+        now,
+    )
+    .map_err(rustls::TLSError::WebPKIError)?;
+
+    if SpiffeId::from_crt(&leaf.0).as_ref() != Some(id) {
+        return Err(rustls::TLSError::WebPKIError(
+            webpki::Error::CertNotValidForName,
+        ));
+    }
+    Ok(())
+}
+
+// === impl InvalidSpiffeId ===
+
+impl fmt::Display for InvalidSpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid SPIFFE ID")
+    }
+}
+
+impl std::error::Error for InvalidSpiffeId {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ids() {
+        let id = SpiffeId::from_str("spiffe://cluster.local/ns/default/sa/web").unwrap();
+        assert_eq!(id.trust_domain().as_ref(), "cluster.local");
+        assert_eq!(id.path(), "/ns/default/sa/web");
+        assert_eq!(id.to_string(), "spiffe://cluster.local/ns/default/sa/web");
+
+        let td = SpiffeId::from_str("spiffe://example_org").unwrap();
+        assert_eq!(td.path(), "");
+
+        for invalid in &[
+            "",
+            "cluster.local",
+            "http://cluster.local/ns",
+            "SPIFFE://cluster.local/ns",
+            "spiffe://",
+            "spiffe:///ns",
+            "spiffe://Cluster.local/ns",
+            "spiffe://cluster.local:8080/ns",
+            "spiffe://user@cluster.local/ns",
+            "spiffe://cluster.local/",
+            "spiffe://cluster.local/ns//sa",
+            "spiffe://cluster.local/ns/../sa",
+            "spiffe://cluster.local/ns?sa=web",
+            "spiffe://cluster.local/ns#web",
+        ] {
+            assert!(SpiffeId::from_str(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn encodes_server_names() {
+        for &(id, name) in &[
+            ("spiffe://cluster.local", "cluster.local"),
+            (
+                "spiffe://cluster.local/ns/default/sa/web",
+                "web.sa.default.ns.cluster.local",
+            ),
+        ] {
+            let id = SpiffeId::from_str(id).unwrap();
+            assert_eq!(id.server_name().as_ref(), name);
+        }
+
+        // Paths that are not DNS labels are hashed.
+        let ids = [
+            SpiffeId::from_str("spiffe://cluster.local/ns/default/sa/Web").unwrap(),
+            SpiffeId::from_str("spiffe://cluster.local/ns/default/sa/web.app").unwrap(),
+            SpiffeId::from_str("spiffe://cluster.local/ns/default/sa/-web").unwrap(),
+        ];
+        for id in &ids {
+            let name = id.server_name().as_ref();
+            let (label, td) = name.split_at(HASH_LEN * 2);
+            assert!(label.bytes().all(|b| b.is_ascii_hexdigit()), "{}", name);
+            assert_eq!(td, ".cluster.local");
+        }
+        assert_ne!(ids[0].server_name(), ids[1].server_name());
+        assert_ne!(ids[1].server_name(), ids[2].server_name());
+    }
+
+    #[test]
+    fn reads_ids_from_crts() {
+        let baz = include_bytes!("testdata/baz-ns1-ca1/crt.der");
+        assert_eq!(
+            SpiffeId::from_crt(baz).map(|id| id.to_string()),
+            Some("spiffe://cluster.local/ns/ns1/sa/baz".to_string())
+        );
+
+        let foo = include_bytes!("testdata/foo-ns1-ca1/crt.der");
+        assert!(SpiffeId::from_crt(foo).is_none());
+
+        // A certificate with several SPIFFE IDs is not an SVID.
+        let qux = include_bytes!("testdata/qux-ns1-ca1/crt.der");
+        assert_eq!(der::uri_sans(qux).map(|sans| sans.len()), Some(2));
+        assert!(SpiffeId::from_crt(qux).is_none());
+    }
+}
@@ -15,6 +15,13 @@ pub static FOO_NS1: Identity = Identity {
     key: include_bytes!("testdata/foo-ns1-ca1/key.p8"),
 };
 
+pub static FOO_NS1_CA2: Identity = Identity {
+    name: "foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+    trust_anchors: include_bytes!("testdata/ca2.pem"),
+    crt: include_bytes!("testdata/foo-ns1-ca2/crt.der"),
+    key: include_bytes!("testdata/foo-ns1-ca2/key.p8"),
+};
+
 pub static BAR_NS1: Identity = Identity {
     name: "bar.ns1.serviceaccount.identity.linkerd.cluster.local",
     trust_anchors: include_bytes!("testdata/ca1.pem"),
@@ -22,6 +29,27 @@ pub static BAR_NS1: Identity = Identity {
     key: include_bytes!("testdata/bar-ns1-ca1/key.p8"),
 };
 
+pub static BAZ_NS1_SVID: Identity = Identity {
+    name: "spiffe://cluster.local/ns/ns1/sa/baz",
+    trust_anchors: include_bytes!("testdata/ca1.pem"),
+    crt: include_bytes!("testdata/baz-ns1-ca1/crt.der"),
+    key: include_bytes!("testdata/baz-ns1-ca1/key.p8"),
+};
+
+pub static FOO_NS1_P384: Identity = Identity {
+    name: "foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+    trust_anchors: include_bytes!("testdata/ca1.pem"),
+    crt: include_bytes!("testdata/foo-ns1-ca1-p384/crt.der"),
+    key: include_bytes!("testdata/foo-ns1-ca1-p384/key.p8"),
+};
+
+pub static FOO_NS1_ED25519: Identity = Identity {
+    name: "foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+    trust_anchors: include_bytes!("testdata/ca1.pem"),
+    crt: include_bytes!("testdata/foo-ns1-ca1-ed25519/crt.der"),
+    key: include_bytes!("testdata/foo-ns1-ca1-ed25519/key.p8"),
+};
+
 impl Identity {
     pub fn trust_anchors(&self) -> TrustAnchors {
         let pem = ::std::str::from_utf8(self.trust_anchors).expect("utf-8");
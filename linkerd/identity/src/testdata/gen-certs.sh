@@ -22,11 +22,33 @@ ee() {
   ee_name=$2
   ee_ns=$3
   cp_ns=$4
+  key_algo=${5:-}
 
   hostname="${ee_name}.${ee_ns}.serviceaccount.identity.${cp_ns}.cluster.local"
-
   ee="${ee_name}-${ee_ns}-${ca_name}"
-  echo '{}' \
+  case "${key_algo}" in
+    "") gen "${ca_name}" "${ee}" "${hostname}" ;;
+    p384) gen "${ca_name}" "${ee}-p384" "${hostname}" '{"key":{"algo":"ecdsa","size":384}}' ;;
+    ed25519) gen "${ca_name}" "${ee}-ed25519" "${hostname}" '{"key":{"algo":"ed25519"}}' ;;
+  esac
+}
+
+# An X.509 SVID, which is only named by a SPIFFE ID URI SAN.
+svid() {
+  ca_name=$1
+  ee_name=$2
+  ee_ns=$3
+
+  gen "${ca_name}" "${ee_name}-${ee_ns}-${ca_name}" "spiffe://cluster.local/ns/${ee_ns}/sa/${ee_name}"
+}
+
+gen() {
+  ca_name=$1
+  ee=$2
+  hostname=$3
+  key_req=${4:-'{}'}
+
+  echo "${key_req}" \
     | cfssl gencert -ca "${ca_name}.pem" -ca-key "${ca_name}-key.pem" -hostname "${hostname}" -config=ca-config.json - \
     | cfssljson -bare "${ee}"
   mkdir -p "${ee}"
@@ -57,3 +79,11 @@ ee ca1 controller linkerd linkerd
 ee ca1 foo ns1 linkerd
 ee ca2 foo ns1 linkerd # Same, but different CA
 ee ca1 bar ns1 linkerd # Different service.
+svid ca1 baz ns1 # Named by a SPIFFE ID.
+
+# Same as foo, but with keys for the other supported algorithms.
+ee ca1 foo ns1 linkerd p384
+ee ca1 foo ns1 linkerd ed25519
+
+# Not an SVID, since it's named by more than one SPIFFE ID.
+gen ca1 qux-ns1-ca1 "spiffe://cluster.local/ns/ns1/sa/qux,spiffe://cluster.local/ns/ns2/sa/qux"
@@ -29,6 +29,10 @@ impl<T, S: Sensor> SensorIo<T, S> {
     pub fn new(io: T, sensor: S) -> Self {
         Self { io, sensor }
     }
+
+    pub fn sensor(&self) -> &S {
+        &self.sensor
+    }
 }
 
 impl<T: AsyncRead + AsyncWrite, S: Sensor> AsyncRead for SensorIo<T, S> {
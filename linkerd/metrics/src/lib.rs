@@ -15,7 +15,7 @@ mod summary;
 
 pub use self::counter::Counter;
 pub use self::gauge::Gauge;
-pub use self::histogram::Histogram;
+pub use self::histogram::{Bucket, Histogram};
 pub use self::prom::{FmtLabels, FmtMetric, FmtMetrics, Metric};
 pub use self::scopes::Scopes;
 pub use self::serve::Serve;
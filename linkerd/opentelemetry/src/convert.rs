@@ -0,0 +1,147 @@
+use linkerd_opencensus::proto::trace::v1 as oc;
+use opentelemetry_proto::{
+    common::v1::{any_value, AnyValue, KeyValue},
+    trace::v1::{span::SpanKind, status::StatusCode, Span, Status},
+};
+
+/// Converts a span recorded by the proxy, as an OpenCensus span, into an
+/// OpenTelemetry span.
+pub fn from_opencensus(span: oc::Span) -> Span {
+    let kind = match oc::span::SpanKind::from_i32(span.kind) {
+        Some(oc::span::SpanKind::Server) => SpanKind::Server,
+        Some(oc::span::SpanKind::Client) => SpanKind::Client,
+        _ => SpanKind::Unspecified,
+    };
+
+    let mut attributes = span
+        .attributes
+        .map(|attrs| attrs.attribute_map)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| KeyValue {
+            key,
+            value: value.value.map(|v| AnyValue {
+                value: Some(any_value(v)),
+            }),
+        })
+        .collect::<Vec<_>>();
+    attributes.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let status = span.status.map(|status| Status {
+        // OpenCensus statuses are gRPC status codes, for which only `OK` is
+        // successful.
+        code: if status.code == 0 {
+            StatusCode::Unset
+        } else {
+            StatusCode::Error
+        } as i32,
+        message: status.message,
+    });
+
+    Span {
+        trace_id: span.trace_id,
+        span_id: span.span_id,
+        parent_span_id: span.parent_span_id,
+        name: span.name.map(|n| n.value).unwrap_or_default(),
+        kind: kind as i32,
+        start_time_unix_nano: span.start_time.map(unix_nanos).unwrap_or_default(),
+        end_time_unix_nano: span.end_time.map(unix_nanos).unwrap_or_default(),
+        attributes,
+        status,
+        ..Span::default()
+    }
+}
+
+fn any_value(value: oc::attribute_value::Value) -> any_value::Value {
+    use oc::attribute_value::Value;
+    match value {
+        Value::StringValue(s) => any_value::Value::StringValue(s.value),
+        Value::IntValue(i) => any_value::Value::IntValue(i),
+        Value::BoolValue(b) => any_value::Value::BoolValue(b),
+        Value::DoubleValue(d) => any_value::Value::DoubleValue(d),
+    }
+}
+
+fn unix_nanos(ts: impl Into<std::time::SystemTime>) -> u64 {
+    ts.into()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    #[test]
+    fn converts_spans() {
+        let start = UNIX_EPOCH + Duration::from_nanos(1_600_000_000_123_456_789);
+        let end = start + Duration::from_millis(5);
+        let mut attribute_map = HashMap::new();
+        attribute_map.insert(
+            "http.path".to_string(),
+            oc::AttributeValue {
+                value: Some(oc::attribute_value::Value::StringValue(
+                    oc::TruncatableString {
+                        value: "/".to_string(),
+                        truncated_byte_count: 0,
+                    },
+                )),
+            },
+        );
+        attribute_map.insert(
+            "http.status_code".to_string(),
+            oc::AttributeValue {
+                value: Some(oc::attribute_value::Value::IntValue(200)),
+            },
+        );
+
+        let span = from_opencensus(oc::Span {
+            trace_id: vec![1; 16],
+            span_id: vec![2; 8],
+            parent_span_id: vec![3; 8],
+            name: Some(oc::TruncatableString {
+                value: "example.com".to_string(),
+                truncated_byte_count: 0,
+            }),
+            kind: oc::span::SpanKind::Client as i32,
+            start_time: Some(start.into()),
+            end_time: Some(end.into()),
+            attributes: Some(oc::span::Attributes {
+                attribute_map,
+                dropped_attributes_count: 0,
+            }),
+            ..oc::Span::default()
+        });
+
+        assert_eq!(span.trace_id, vec![1; 16]);
+        assert_eq!(span.span_id, vec![2; 8]);
+        assert_eq!(span.parent_span_id, vec![3; 8]);
+        assert_eq!(span.name, "example.com");
+        assert_eq!(span.kind, SpanKind::Client as i32);
+        assert_eq!(span.start_time_unix_nano, 1_600_000_000_123_456_789);
+        assert_eq!(span.end_time_unix_nano, 1_600_000_000_128_456_789);
+        assert_eq!(
+            span.attributes,
+            vec![
+                KeyValue {
+                    key: "http.path".to_string(),
+                    value: Some(AnyValue {
+                        value: Some(any_value::Value::StringValue("/".to_string())),
+                    }),
+                },
+                KeyValue {
+                    key: "http.status_code".to_string(),
+                    value: Some(AnyValue {
+                        value: Some(any_value::Value::IntValue(200)),
+                    }),
+                },
+            ]
+        );
+        assert_eq!(span.status, None);
+    }
+}
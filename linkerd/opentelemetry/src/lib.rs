@@ -0,0 +1,203 @@
+#![deny(warnings, rust_2018_idioms)]
+
+mod convert;
+
+pub use self::convert::from_opencensus;
+use futures::{
+    stream::{Stream, StreamExt},
+    FutureExt,
+};
+use http_body::Body as HttpBody;
+use linkerd_error::Error;
+use linkerd_exp_backoff::ExponentialBackoff;
+use linkerd_opencensus::metrics::Registry;
+pub use opentelemetry_proto as proto;
+use opentelemetry_proto::collector::trace::v1::{
+    trace_service_client::TraceServiceClient, ExportTraceServiceRequest,
+};
+use opentelemetry_proto::resource::v1::Resource;
+use opentelemetry_proto::trace::v1::{InstrumentationLibrarySpans, ResourceSpans, Span};
+use tokio::time;
+use tonic::{self as grpc, body::BoxBody, client::GrpcService};
+use tracing::{debug, trace, warn};
+
+pub async fn export_spans<T, S>(
+    client: T,
+    resource: Resource,
+    spans: S,
+    backoff: ExponentialBackoff,
+    metrics: Registry,
+) where
+    T: GrpcService<BoxBody>,
+    T::Error: Into<Error>,
+    <T::ResponseBody as HttpBody>::Error: Into<Error> + Send + Sync,
+    T::ResponseBody: Send + 'static,
+    S: Stream<Item = Span> + Unpin,
+{
+    SpanExporter::new(client, resource, spans, backoff, metrics)
+        .run()
+        .await
+}
+
+/// SpanExporter sends batches of spans to the given OTLP TraceService gRPC
+/// service.
+struct SpanExporter<T, S> {
+    client: T,
+    resource: Resource,
+    spans: S,
+    backoff: ExponentialBackoff,
+    metrics: Registry,
+}
+
+#[derive(Debug)]
+struct SpanRxClosed;
+
+// === impl SpanExporter ===
+
+impl<T, S> SpanExporter<T, S>
+where
+    T: GrpcService<BoxBody>,
+    T::Error: Into<Error>,
+    <T::ResponseBody as HttpBody>::Error: Into<Error> + Send + Sync,
+    T::ResponseBody: Send + 'static,
+    S: Stream<Item = Span> + Unpin,
+{
+    const MAX_BATCH_SIZE: usize = 1000;
+    const MAX_BATCH_IDLE: time::Duration = time::Duration::from_secs(5);
+    const MAX_EXPORT_ATTEMPTS: u32 = 5;
+
+    fn new(
+        client: T,
+        resource: Resource,
+        spans: S,
+        backoff: ExponentialBackoff,
+        metrics: Registry,
+    ) -> Self {
+        Self {
+            client,
+            resource,
+            spans,
+            backoff,
+            metrics,
+        }
+    }
+
+    async fn run(self) {
+        let Self {
+            client,
+            resource,
+            mut spans,
+            backoff,
+            mut metrics,
+        } = self;
+
+        // Holds the batch of pending spans. Cleared as the spans are flushed.
+        // Contains no more than MAX_BATCH_SIZE spans.
+        let mut accum = Vec::new();
+
+        let mut svc = TraceServiceClient::new(client);
+        loop {
+            // Collect spans into a batch.
+            let collect = Self::collect_batch(&mut spans, &mut accum).await;
+
+            // If we collected spans, flush them. Spans that are recorded while
+            // the batch is being exported are buffered by the span stream.
+            if !accum.is_empty() {
+                let spans = accum.len() as u64;
+                let req = ExportTraceServiceRequest {
+                    resource_spans: vec![ResourceSpans {
+                        resource: Some(resource.clone()),
+                        instrumentation_library_spans: vec![InstrumentationLibrarySpans {
+                            instrumentation_library: None,
+                            spans: std::mem::take(&mut accum),
+                        }],
+                    }],
+                };
+                if Self::export(&mut svc, req, &backoff).await {
+                    metrics.send(spans);
+                }
+            }
+
+            // If the span source was closed, end the task.
+            if let Err(SpanRxClosed) = collect {
+                debug!("Span channel lost");
+                return;
+            }
+        }
+    }
+
+    /// Sends a batch of spans to the collector, retrying failures that the
+    /// collector indicates are transient.
+    ///
+    /// Returns false if the batch could not be exported and was dropped.
+    async fn export(
+        svc: &mut TraceServiceClient<T>,
+        req: ExportTraceServiceRequest,
+        backoff: &ExponentialBackoff,
+    ) -> bool {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            trace!(attempts, "Sending batch");
+            let status = match svc.export(grpc::Request::new(req.clone())).await {
+                Ok(_rsp) => return true,
+                Err(status) => status,
+            };
+
+            if attempts == Self::MAX_EXPORT_ATTEMPTS || !is_retryable(status.code()) {
+                warn!(%status, attempts, "Failed to export spans");
+                return false;
+            }
+
+            let delay = backoff.delay(attempts - 1);
+            debug!(%status, ?delay, "Export failed; retrying");
+            time::sleep(delay).await;
+        }
+    }
+
+    /// Collects spans from the proxy into `accum`.
+    ///
+    /// Returns an error when the span sream has completed. An error may be
+    /// returned after accumulating spans.
+    async fn collect_batch(spans: &mut S, accum: &mut Vec<Span>) -> Result<(), SpanRxClosed> {
+        loop {
+            if accum.len() == Self::MAX_BATCH_SIZE {
+                trace!(capacity = Self::MAX_BATCH_SIZE, "Batch capacity reached");
+                return Ok(());
+            }
+
+            futures::select_biased! {
+                res = spans.next().fuse() => match res {
+                    Some(span) => {
+                        trace!(?span, "Adding to batch");
+                        accum.push(span);
+                    }
+                    None => return Err(SpanRxClosed),
+                },
+                // Don't hold spans indefinitely. Return if we hit an idle
+                // timeout and spans have been collected.
+                _ = time::sleep(Self::MAX_BATCH_IDLE).fuse() => {
+                    if !accum.is_empty() {
+                        trace!(spans = accum.len(), "Flushing spans due to inactivitiy");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Indicates whether an export that failed with `code` may succeed if it is
+/// retried, as described by the OTLP specification.
+fn is_retryable(code: grpc::Code) -> bool {
+    matches!(
+        code,
+        grpc::Code::Cancelled
+            | grpc::Code::DeadlineExceeded
+            | grpc::Code::ResourceExhausted
+            | grpc::Code::Aborted
+            | grpc::Code::OutOfRange
+            | grpc::Code::Unavailable
+            | grpc::Code::DataLoss
+    )
+}
@@ -0,0 +1,570 @@
+//! A load balancer that routes requests to endpoints by consistently hashing a
+//! property of each request.
+//!
+//! Requests with the same key are dispatched to the same endpoint for as long
+//! as that endpoint is available. When endpoints are added or removed, only a
+//! small share of keys are remapped.
+
+use crate::{ClientHandle, Error};
+use fnv::FnvHasher;
+use futures::prelude::*;
+use rand::Rng;
+use std::{
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tower::{
+    discover::{Change, Discover},
+    ready_cache::{error::Failed, ReadyCache},
+};
+use tracing::{debug, trace};
+
+/// The number of points each endpoint occupies on a hash ring.
+const RING_POINTS_PER_ENDPOINT: usize = 128;
+
+/// The size of a Maglev lookup table.
+///
+/// This must be prime and should be much larger than the number of endpoints.
+const MAGLEV_TABLE_SIZE: usize = 65_537;
+
+/// Configures consistent-hash load balancing.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Config {
+    pub key: Key,
+    pub table: Table,
+}
+
+/// The request property that determines a request's endpoint.
+///
+/// Requests that do not have the property are distributed randomly.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// The value of a request header.
+    Header(http::header::HeaderName),
+
+    /// The value of a cookie.
+    Cookie(String),
+
+    /// The client's IP address.
+    SourceIp,
+}
+
+/// The algorithm used to map hashes to endpoints.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Table {
+    /// Places each endpoint at many points on a ring, selecting the first
+    /// endpoint at or after a request's hash.
+    Ring,
+
+    /// Uses a Maglev lookup table, which spreads load more evenly than a ring
+    /// at the cost of more memory.
+    Maglev,
+}
+
+/// Encodes an endpoint's key for hashing.
+///
+/// Endpoints are placed in the lookup table by this hash, so it must be
+/// defined independently of the platform and of `std::hash::Hash` for all
+/// proxies to build the same table.
+pub trait EndpointHash {
+    fn endpoint_hash(&self) -> u64;
+}
+
+/// Returned when a request is dispatched without a ready endpoint, i.e.
+/// without the balancer having become ready.
+#[derive(Debug)]
+pub struct NoReadyEndpoint(());
+
+/// Balances requests over endpoints by consistently hashing a request key.
+pub struct HashBalance<D, Req>
+where
+    D: Discover,
+    D::Key: Hash,
+{
+    discover: D,
+    services: ReadyCache<D::Key, D::Service, Req>,
+    config: Config,
+    lookup: Lookup<D::Key>,
+    _req: PhantomData<fn(Req)>,
+}
+
+/// Maps request hashes onto endpoint keys.
+#[derive(Debug)]
+struct Lookup<K> {
+    endpoints: Vec<K>,
+
+    /// The hash of each endpoint.
+    hashes: Vec<u64>,
+
+    index: Index,
+}
+
+#[derive(Debug)]
+enum Index {
+    /// Sorted `(point, endpoint)` pairs.
+    Ring(Vec<(u64, usize)>),
+
+    /// Each slot holds an endpoint.
+    Maglev(Vec<u32>),
+}
+
+// === impl Key ===
+
+impl Key {
+    fn hash<B>(&self, req: &http::Request<B>) -> Option<u64> {
+        match self {
+            Self::Header(name) => req.headers().get(name).map(|v| hash(v.as_bytes())),
+            Self::Cookie(name) => cookie(req.headers(), name).map(|v| hash(v.as_bytes())),
+            Self::SourceIp => req
+                .extensions()
+                .get::<ClientHandle>()
+                .map(|c| hash_ip(c.addr.ip(), &[])),
+        }
+    }
+}
+
+fn cookie<'h>(headers: &'h http::HeaderMap, name: &str) -> Option<&'h str> {
+    headers
+        .get_all(http::header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| {
+            let mut kv = pair.trim().splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if k == name => Some(v),
+                _ => None,
+            }
+        })
+}
+
+/// Hashes bytes with FNV-1a.
+///
+/// Only explicitly encoded bytes are hashed (rather than values' `Hash`
+/// implementations, which may change between Rust releases or differ between
+/// platforms), so that all proxies map keys to the same endpoints.
+fn hash(bytes: &[u8]) -> u64 {
+    hash_parts(&[bytes])
+}
+
+fn hash_parts(parts: &[&[u8]]) -> u64 {
+    let mut hasher = FnvHasher::default();
+    for part in parts {
+        hasher.write(part);
+    }
+    // FNV mixes short inputs poorly into the high bits, which order points on
+    // the ring, so the hash is finalized as in MurmurHash3.
+    let mut h = hasher.finish();
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// Hashes an IP address's octets, followed by `suffix`.
+fn hash_ip(ip: IpAddr, suffix: &[u8]) -> u64 {
+    match ip {
+        IpAddr::V4(ip) => hash_parts(&[&ip.octets(), suffix]),
+        IpAddr::V6(ip) => hash_parts(&[&ip.octets(), suffix]),
+    }
+}
+
+/// Derives one of an endpoint's hashes (e.g. its points on the ring) from the
+/// endpoint's hash.
+fn derive(hash: u64, n: u64) -> u64 {
+    hash_parts(&[&hash.to_le_bytes(), &n.to_le_bytes()])
+}
+
+// === impl EndpointHash ===
+
+impl EndpointHash for SocketAddr {
+    /// Hashes the address's IP octets followed by its little-endian port.
+    fn endpoint_hash(&self) -> u64 {
+        hash_ip(self.ip(), &self.port().to_le_bytes())
+    }
+}
+
+// === impl NoReadyEndpoint ===
+
+impl std::fmt::Display for NoReadyEndpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no ready endpoint")
+    }
+}
+
+impl std::error::Error for NoReadyEndpoint {}
+
+// === impl HashBalance ===
+
+impl<D, Req> HashBalance<D, Req>
+where
+    D: Discover,
+    D::Key: EndpointHash + Hash,
+    D::Service: tower::Service<Req>,
+{
+    pub fn new(config: Config, discover: D) -> Self {
+        let lookup = Lookup::new(config.table, Vec::new());
+        Self {
+            discover,
+            services: ReadyCache::default(),
+            config,
+            lookup,
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<D, B> HashBalance<D, http::Request<B>>
+where
+    D: Discover + Unpin,
+    D::Key: EndpointHash + Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<http::Request<B>>,
+    <D::Service as tower::Service<http::Request<B>>>::Error: Into<Error>,
+{
+    /// Applies all pending updates from discovery, rebuilding the lookup table
+    /// if the set of endpoints changed.
+    fn update_from_discover(&mut self, cx: &mut Context<'_>) -> Result<(), Error> {
+        let mut changed = false;
+        while let Poll::Ready(change) = Pin::new(&mut self.discover).poll_discover(cx) {
+            match change.transpose().map_err(Into::into)? {
+                None => break,
+                Some(Change::Remove(key)) => {
+                    trace!("remove");
+                    self.services.evict(&key);
+                    self.lookup.endpoints.retain(|k| k != &key);
+                    changed = true;
+                }
+                Some(Change::Insert(key, svc)) => {
+                    trace!("insert");
+                    // If this service already existed in the set, it will be
+                    // replaced as the new one becomes ready.
+                    if !self.lookup.endpoints.contains(&key) {
+                        self.lookup.endpoints.push(key.clone());
+                        changed = true;
+                    }
+                    self.services.push(key, svc);
+                }
+            }
+        }
+
+        if changed {
+            let endpoints = std::mem::take(&mut self.lookup.endpoints);
+            self.lookup = Lookup::new(self.config.table, endpoints);
+            debug!(
+                endpoints = self.lookup.endpoints.len(),
+                "Rebuilt hash table"
+            );
+        }
+        Ok(())
+    }
+
+    fn promote_pending_to_ready(&mut self, cx: &mut Context<'_>) {
+        loop {
+            match self.services.poll_pending(cx) {
+                Poll::Ready(Ok(())) | Poll::Pending => break,
+                Poll::Ready(Err(Failed(_, error))) => {
+                    // An individual service was lost; continue processing
+                    // pending services.
+                    debug!(%error, "Dropping failed endpoint");
+                }
+            }
+        }
+        trace!(
+            ready = %self.services.ready_len(),
+            pending = %self.services.pending_len(),
+            "poll_unready"
+        );
+    }
+
+    /// Selects the ready endpoint for the given hash.
+    fn ready_index(&self, hash: u64) -> Option<usize> {
+        let ready = |key: &D::Key| self.services.get_ready(key).map(|(i, _, _)| i);
+        if let Some(i) = self.lookup.primary(hash).and_then(ready) {
+            return Some(i);
+        }
+        let key = self
+            .lookup
+            .fallback(hash, |key| self.services.get_ready(key).is_some())?;
+        ready(key)
+    }
+}
+
+impl<D, B> tower::Service<http::Request<B>> for HashBalance<D, http::Request<B>>
+where
+    D: Discover + Unpin,
+    D::Key: EndpointHash + Hash + Clone,
+    D::Error: Into<Error>,
+    D::Service: tower::Service<http::Request<B>>,
+    <D::Service as tower::Service<http::Request<B>>>::Error: Into<Error>,
+{
+    type Response = <D::Service as tower::Service<http::Request<B>>>::Response;
+    type Error = Error;
+    type Future = future::Either<
+        future::MapErr<
+            <D::Service as tower::Service<http::Request<B>>>::Future,
+            fn(<D::Service as tower::Service<http::Request<B>>>::Error) -> Error,
+        >,
+        future::Ready<Result<Self::Response, Error>>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.update_from_discover(cx)?;
+        self.promote_pending_to_ready(cx);
+
+        if self.services.ready_len() == 0 {
+            // We have previously registered interest in updates from discover
+            // and pending services.
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let hash = self
+            .config
+            .key
+            .hash(&req)
+            .unwrap_or_else(|| rand::thread_rng().gen());
+        let index = match self.ready_index(hash) {
+            Some(index) => index,
+            None => return future::Either::Right(future::err(NoReadyEndpoint(()).into())),
+        };
+        trace!(hash, index, "Dispatching");
+        future::Either::Left(
+            self.services
+                .call_ready_index(index, req)
+                .map_err(Into::into as fn(_) -> _),
+        )
+    }
+}
+
+// === impl Lookup ===
+
+impl<K: EndpointHash> Lookup<K> {
+    fn new(table: Table, mut endpoints: Vec<K>) -> Self {
+        // Order endpoints independently of the order in which they were
+        // discovered so that all balancers build the same table.
+        endpoints.sort_by_cached_key(EndpointHash::endpoint_hash);
+        let hashes = endpoints
+            .iter()
+            .map(EndpointHash::endpoint_hash)
+            .collect::<Vec<_>>();
+
+        let index = match table {
+            Table::Ring => Index::ring(&hashes),
+            Table::Maglev => Index::maglev(&hashes),
+        };
+        Self {
+            endpoints,
+            hashes,
+            index,
+        }
+    }
+
+    /// Returns the endpoint that the table maps the hash to.
+    fn primary(&self, hash: u64) -> Option<&K> {
+        let i = match self.index {
+            Index::Ring(ref points) => {
+                let start = match points.binary_search_by(|(p, _)| p.cmp(&hash)) {
+                    Ok(i) | Err(i) => i,
+                };
+                // The ring wraps around to its first point.
+                points.get(start).or_else(|| points.first())?.1
+            }
+            Index::Maglev(ref slots) if slots.is_empty() => return None,
+            Index::Maglev(ref slots) => slots[(hash % slots.len() as u64) as usize] as usize,
+        };
+        self.endpoints.get(i)
+    }
+
+    /// Selects an alternate endpoint, among those that are `available`, for a
+    /// hash whose primary endpoint is unavailable.
+    ///
+    /// Endpoints are ranked by rendezvous hashing, so a key's requests fall
+    /// back to the same endpoint and the keys of an unavailable endpoint are
+    /// spread over the others.
+    fn fallback(&self, hash: u64, available: impl Fn(&K) -> bool) -> Option<&K> {
+        self.endpoints
+            .iter()
+            .zip(&self.hashes)
+            .filter(|(key, _)| available(key))
+            .max_by_key(|(_, h)| derive(**h, hash))
+            .map(|(key, _)| key)
+    }
+}
+
+impl Index {
+    fn ring(endpoints: &[u64]) -> Self {
+        let mut points = Vec::with_capacity(endpoints.len() * RING_POINTS_PER_ENDPOINT);
+        for (i, h) in endpoints.iter().enumerate() {
+            for point in 0..RING_POINTS_PER_ENDPOINT as u64 {
+                points.push((derive(*h, point), i));
+            }
+        }
+        points.sort_unstable();
+        Index::Ring(points)
+    }
+
+    /// Populates a lookup table as described in the Maglev paper: each
+    /// endpoint takes turns claiming its next preferred empty slot until the
+    /// table is full.
+    fn maglev(endpoints: &[u64]) -> Self {
+        if endpoints.is_empty() {
+            return Index::Maglev(Vec::new());
+        }
+
+        let m = MAGLEV_TABLE_SIZE as u64;
+        let permutations = endpoints
+            .iter()
+            .map(|h| {
+                let offset = derive(*h, 0) % m;
+                let skip = derive(*h, 1) % (m - 1) + 1;
+                (offset, skip)
+            })
+            .collect::<Vec<_>>();
+
+        const EMPTY: u32 = u32::MAX;
+        let mut slots = vec![EMPTY; MAGLEV_TABLE_SIZE];
+        let mut next = vec![0u64; endpoints.len()];
+        let mut filled = 0;
+        'fill: loop {
+            for (i, (offset, skip)) in permutations.iter().enumerate() {
+                let mut slot = ((offset + next[i] * skip) % m) as usize;
+                while slots[slot] != EMPTY {
+                    next[i] += 1;
+                    slot = ((offset + next[i] * skip) % m) as usize;
+                }
+                slots[slot] = i as u32;
+                next[i] += 1;
+                filled += 1;
+                if filled == MAGLEV_TABLE_SIZE {
+                    break 'fill;
+                }
+            }
+        }
+        Index::Maglev(slots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn endpoints(n: u16) -> Vec<SocketAddr> {
+        (0..n)
+            .map(|i| SocketAddr::from(([10, 0, 0, 1], 8080 + i)))
+            .collect()
+    }
+
+    /// Maps each of many keys to its primary endpoint.
+    fn assignments(table: Table, endpoints: Vec<SocketAddr>) -> HashMap<u64, SocketAddr> {
+        let lookup = Lookup::new(table, endpoints);
+        (0..10_000u64)
+            .map(|k| (k, *lookup.primary(hash(&k.to_le_bytes())).unwrap()))
+            .collect()
+    }
+
+    fn remapped(table: Table) -> usize {
+        let all = endpoints(10);
+        let removed = all[3];
+        let before = assignments(table, all.clone());
+        let after = assignments(table, all.into_iter().filter(|e| *e != removed).collect());
+        before
+            .iter()
+            .filter(|(k, e)| **e != removed && after[k] != **e)
+            .count()
+    }
+
+    #[test]
+    fn ring_remaps_minimally() {
+        assert_eq!(remapped(Table::Ring), 0);
+    }
+
+    #[test]
+    fn maglev_remaps_minimally() {
+        // Maglev trades perfect stability for balance; only a small share of
+        // keys on surviving endpoints may move.
+        assert!(remapped(Table::Maglev) < 10_000 / 100);
+    }
+
+    #[test]
+    fn distributes_keys() {
+        for table in &[Table::Ring, Table::Maglev] {
+            let mut counts = HashMap::<SocketAddr, usize>::new();
+            for e in assignments(*table, endpoints(10)).values() {
+                *counts.entry(*e).or_default() += 1;
+            }
+            assert_eq!(counts.len(), 10, "{:?} must use every endpoint", table);
+            for (e, n) in counts {
+                assert!(n > 500, "{:?} endpoint {} has only {} keys", table, e, n);
+            }
+        }
+    }
+
+    #[test]
+    fn independent_of_discovery_order() {
+        for table in &[Table::Ring, Table::Maglev] {
+            let mut reversed = endpoints(10);
+            reversed.reverse();
+            assert_eq!(
+                assignments(*table, endpoints(10)),
+                assignments(*table, reversed)
+            );
+        }
+    }
+
+    #[test]
+    fn falls_back_consistently() {
+        let all = endpoints(5);
+        let lookup = Lookup::new(Table::Maglev, all.clone());
+        let mut fallbacks = HashMap::<SocketAddr, usize>::new();
+        for k in 0..1_000u64 {
+            let h = hash(&k.to_le_bytes());
+            let primary = *lookup.primary(h).unwrap();
+            let fallback = *lookup.fallback(h, |e| *e != primary).unwrap();
+            assert_ne!(fallback, primary);
+            assert_eq!(lookup.fallback(h, |e| *e != primary), Some(&fallback));
+            *fallbacks.entry(fallback).or_default() += 1;
+        }
+        assert_eq!(fallbacks.len(), 5, "fallbacks must use every endpoint");
+        assert_eq!(lookup.fallback(0, |_| false), None);
+    }
+
+    #[test]
+    fn hashes_are_fixed() {
+        // Every proxy must map a key to the same endpoint, however it was
+        // built, so the hashes must never change.
+        assert_eq!(hash(b"alice"), 0x3507_d047_a67c_08f4);
+        assert_eq!(
+            SocketAddr::from(([10, 0, 0, 1], 8080)).endpoint_hash(),
+            0xea2a_aacd_f798_d50b
+        );
+        assert_eq!(derive(1, 2), 0x0083_950b_668a_424a);
+    }
+
+    #[test]
+    fn hashes_request_keys() {
+        let req = http::Request::builder()
+            .header("x-user", "alice")
+            .header("cookie", "a=1; session=abc")
+            .body(())
+            .unwrap();
+
+        let header = Key::Header(http::header::HeaderName::from_static("x-user"));
+        assert_eq!(header.hash(&req), Some(hash(b"alice")));
+
+        let cookie = Key::Cookie("session".to_string());
+        assert_eq!(cookie.hash(&req), Some(hash(b"abc")));
+
+        let missing = Key::Cookie("other".to_string());
+        assert_eq!(missing.hash(&req), None);
+
+        assert_eq!(Key::SourceIp.hash(&req), None);
+    }
+}
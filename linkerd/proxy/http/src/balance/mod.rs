@@ -0,0 +1,254 @@
+use crate::{BoxBody, Error};
+use futures::{future, prelude::*, ready};
+use hyper::body::HttpBody;
+pub use hyper_balance::{PendingUntilFirstData, PendingUntilFirstDataBody};
+use linkerd_stack::{layer, Param};
+use pin_project::pin_project;
+use rand::thread_rng;
+use std::{
+    hash::Hash,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tower::discover::Discover;
+pub use tower::{
+    balance::p2c::Balance,
+    load::{peak_ewma, Load, PeakEwmaDiscover},
+};
+
+pub mod hash;
+
+/// Configures a stack to resolve `T` typed targets to balance requests over
+/// `M`-typed endpoint stacks.
+#[derive(Debug)]
+pub struct Layer<A, B> {
+    decay: Duration,
+    default_rtt: Duration,
+    _marker: PhantomData<fn(A) -> B>,
+}
+
+/// Builds a balancer for each `T`-typed target.
+///
+/// Targets that configure consistent hashing are balanced with a
+/// [`hash::HashBalance`]; all others use P2C over peak-EWMA load.
+#[derive(Debug)]
+pub struct MakeBalance<M, A> {
+    inner: M,
+    decay: Duration,
+    default_rtt: Duration,
+    _marker: PhantomData<fn(A)>,
+}
+
+#[pin_project]
+#[derive(Debug)]
+pub struct MakeFuture<F, A> {
+    #[pin]
+    inner: F,
+    hash: Option<hash::Config>,
+    decay: Duration,
+    default_rtt: Duration,
+    _marker: PhantomData<fn(A)>,
+}
+
+/// A load balancer over `D`-typed discovered endpoints.
+pub enum Balancer<D, A>
+where
+    D: Discover,
+    D::Key: Hash,
+{
+    PeakEwma(Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>>),
+    Hash(hash::HashBalance<D, http::Request<A>>),
+}
+
+type ResponseFuture<F> = future::MapOk<F, fn(<F as TryFuture>::Ok) -> http::Response<BoxBody>>;
+
+// === impl Layer ===
+
+pub fn layer<A, B>(default_rtt: Duration, decay: Duration) -> Layer<A, B> {
+    Layer {
+        decay,
+        default_rtt,
+        _marker: PhantomData,
+    }
+}
+
+impl<A, B> Clone for Layer<A, B> {
+    fn clone(&self) -> Self {
+        Self {
+            decay: self.decay,
+            default_rtt: self.default_rtt,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<D, S, A, B> tower::layer::Layer<D> for Layer<A, B>
+where
+    A: HttpBody,
+    B: HttpBody,
+    D: Discover<Service = S>,
+    D::Key: Hash,
+    S: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+    Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>>:
+        tower::Service<http::Request<A>>,
+{
+    type Service = Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>>;
+
+    fn layer(&self, discover: D) -> Self::Service {
+        let instrument = PendingUntilFirstData::default();
+        let loaded = PeakEwmaDiscover::new(discover, self.default_rtt, self.decay, instrument);
+        Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid")
+    }
+}
+
+// === impl MakeBalance ===
+
+impl<M, A> MakeBalance<M, A> {
+    pub fn layer(
+        default_rtt: Duration,
+        decay: Duration,
+    ) -> impl layer::Layer<M, Service = Self> + Clone {
+        layer::mk(move |inner| Self {
+            inner,
+            decay,
+            default_rtt,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<M: Clone, A> Clone for MakeBalance<M, A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            decay: self.decay,
+            default_rtt: self.default_rtt,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, M, D, A, B> tower::Service<T> for MakeBalance<M, A>
+where
+    T: Param<Option<hash::Config>>,
+    M: tower::Service<T, Response = D>,
+    D: Discover,
+    D::Key: hash::EndpointHash + Hash,
+    D::Service: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    <D::Service as tower::Service<http::Request<A>>>::Error: Into<Error>,
+    A: HttpBody,
+    B: HttpBody,
+    Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>>:
+        tower::Service<http::Request<A>>,
+{
+    type Response = Balancer<D, A>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future, A>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        MakeFuture {
+            hash: target.param(),
+            inner: self.inner.call(target),
+            decay: self.decay,
+            default_rtt: self.default_rtt,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F, D, A, B> Future for MakeFuture<F, A>
+where
+    F: TryFuture<Ok = D>,
+    D: Discover,
+    D::Key: hash::EndpointHash + Hash,
+    D::Service: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    <D::Service as tower::Service<http::Request<A>>>::Error: Into<Error>,
+    A: HttpBody,
+    B: HttpBody,
+    Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>>:
+        tower::Service<http::Request<A>>,
+{
+    type Output = Result<Balancer<D, A>, F::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let discover = ready!(this.inner.try_poll(cx))?;
+        let balancer = match this.hash.take() {
+            Some(config) => {
+                tracing::debug!(?config, "Balancing by consistent hash");
+                Balancer::Hash(hash::HashBalance::new(config, discover))
+            }
+            None => {
+                let instrument = PendingUntilFirstData::default();
+                let loaded =
+                    PeakEwmaDiscover::new(discover, *this.default_rtt, *this.decay, instrument);
+                Balancer::PeakEwma(
+                    Balance::from_rng(loaded, &mut thread_rng()).expect("RNG must be valid"),
+                )
+            }
+        };
+        Poll::Ready(Ok(balancer))
+    }
+}
+
+// === impl Balancer ===
+
+impl<D, A, B> tower::Service<http::Request<A>> for Balancer<D, A>
+where
+    D: Discover,
+    D::Key: Hash,
+    D::Service: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send + 'static,
+    B::Error: Into<Error>,
+    Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>>: tower::Service<
+        http::Request<A>,
+        Response = http::Response<PendingUntilFirstDataBody<peak_ewma::Handle, B>>,
+        Error = Error,
+    >,
+    hash::HashBalance<D, http::Request<A>>:
+        tower::Service<http::Request<A>, Response = http::Response<B>, Error = Error>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Error;
+    type Future = future::Either<
+        ResponseFuture<
+            <Balance<PeakEwmaDiscover<D, PendingUntilFirstData>, http::Request<A>> as tower::Service<
+                http::Request<A>,
+            >>::Future,
+        >,
+        ResponseFuture<<hash::HashBalance<D, http::Request<A>> as tower::Service<http::Request<A>>>::Future>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            Self::PeakEwma(b) => b.poll_ready(cx),
+            Self::Hash(b) => b.poll_ready(cx),
+        }
+    }
+
+    fn call(&mut self, req: http::Request<A>) -> Self::Future {
+        match self {
+            Self::PeakEwma(b) => {
+                future::Either::Left(b.call(req).map_ok(box_response as fn(_) -> _))
+            }
+            Self::Hash(b) => future::Either::Right(b.call(req).map_ok(box_response as fn(_) -> _)),
+        }
+    }
+}
+
+fn box_response<B>(rsp: http::Response<B>) -> http::Response<BoxBody>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send + 'static,
+    B::Error: Into<Error>,
+{
+    rsp.map(BoxBody::new)
+}
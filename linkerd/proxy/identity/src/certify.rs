@@ -1,12 +1,15 @@
+use futures::future::{self, Either};
 use http_body::Body as HttpBody;
 use linkerd2_proxy_api::identity as api;
 use linkerd_error::Error;
 use linkerd_identity as id;
 use linkerd_metrics::Counter;
+use linkerd_proxy_transport::metrics::Handshakes;
 use linkerd_stack::Param;
 use linkerd_tls as tls;
 use pin_project::pin_project;
 use std::convert::TryFrom;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::watch;
@@ -16,7 +19,7 @@ use tonic::{
     body::{Body, BoxBody},
     client::GrpcService,
 };
-use tracing::{debug, error, trace};
+use tracing::{debug, error, info, trace, warn};
 
 /// Configures the Identity service and local identity.
 #[derive(Clone, Debug)]
@@ -28,18 +31,43 @@ pub struct Config {
     pub local_id: id::LocalId,
     pub min_refresh: Duration,
     pub max_refresh: Duration,
+    pub trust_bundle: Option<TrustBundle>,
+}
+
+/// Configures a trust anchors file that is reloaded when its contents change.
+#[derive(Clone, Debug)]
+pub struct TrustBundle {
+    pub path: PathBuf,
+    pub reload_interval: Duration,
+}
+
+/// Configures a denylist file that is reloaded when its contents change.
+#[derive(Clone, Debug)]
+pub struct DenylistFile {
+    pub path: PathBuf,
+
+    /// The denylist that is used until the file is reloaded.
+    pub denylist: id::Denylist,
+
+    /// How often the file is checked for changes.
+    pub reload_interval: Duration,
 }
 
 /// Holds the process's local TLS identity state.
 ///
-/// Updates dynamically as certificates are provisioned from the Identity service.
+/// Updates dynamically as certificates are provisioned by a certificate source:
+/// the Identity service, local files, or a SPIFFE Workload API.
 #[pin_project]
 #[derive(Clone, Debug)]
 pub struct LocalCrtKey {
-    trust_anchors: id::TrustAnchors,
+    trust_anchors: watch::Receiver<id::TrustAnchors>,
     id: id::LocalId,
     crt_key: watch::Receiver<Option<id::CrtKey>>,
     refreshes: Arc<Counter>,
+    reloads: Option<Arc<crate::metrics::Reloads>>,
+    denylist: id::SharedDenylist,
+    denylist_reloads: Option<Arc<crate::metrics::Reloads>>,
+    handshakes: Handshakes,
 }
 
 /// Produces a `Local` identity once a certificate is available.
@@ -51,13 +79,60 @@ pub struct LostDaemon;
 
 pub type CrtKeySender = watch::Sender<Option<id::CrtKey>>;
 
+/// Publishes the certificates and trust anchors obtained by a certificate
+/// source to a `LocalCrtKey`.
+///
+/// Published configurations reject peers whose certificates are on the local
+/// identity's denylist.
 #[derive(Debug)]
-pub struct Daemon {
+pub struct Publisher {
     crt_key_watch: CrtKeySender,
-    refreshes: Arc<linkerd_metrics::Counter>,
+    trust_anchors_watch: watch::Sender<id::TrustAnchors>,
+    refreshes: Arc<Counter>,
+    reloads: Option<Arc<crate::metrics::Reloads>>,
+    denylist: id::SharedDenylist,
+}
+
+/// Indicates that all `LocalCrtKey`s have been dropped, so there is no need to
+/// publish further updates.
+#[derive(Copy, Clone, Debug)]
+pub struct LostLocal;
+
+/// Obtains certificates from the Identity service.
+#[derive(Debug)]
+pub struct Daemon {
+    publisher: Publisher,
     config: Config,
 }
 
+/// Reloads a denylist file.
+#[derive(Debug)]
+pub struct DenylistDaemon {
+    file: DenylistFile,
+    denylist: id::SharedDenylist,
+    reloads: Arc<crate::metrics::Reloads>,
+}
+
+/// The outcome of reloading a trust bundle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Reload {
+    /// The trust anchors did not change.
+    Unchanged,
+    /// New trust anchors were published, along with the current certificate
+    /// (if any) rebuilt for them.
+    Updated,
+    /// New trust anchors were published, but they did not issue the current
+    /// certificate, so a new certificate must be obtained.
+    Refresh,
+}
+
+/// Indicates that a trust bundle file did not contain any valid trust anchors.
+#[derive(Copy, Clone, Debug)]
+pub struct InvalidTrustBundle;
+
+#[derive(Debug)]
+pub struct InvalidKey(id::KeyRejected);
+
 // === impl Config ===
 
 impl Config {
@@ -91,13 +166,11 @@ impl Daemon {
         <T::ResponseBody as Body>::Data: Send,
         <T::ResponseBody as HttpBody>::Error: Into<Error> + Send,
     {
-        let Self {
-            crt_key_watch,
-            refreshes,
-            config,
-        } = self;
+        let Self { publisher, config } = self;
 
         let mut curr_expiry = UNIX_EPOCH;
+        let mut curr_crt = None;
+        let mut trust_anchors = config.trust_anchors.clone();
         let mut client = api::identity_client::IdentityClient::new(client);
 
         loop {
@@ -131,20 +204,20 @@ impl Daemon {
                                         expiry,
                                     );
 
-                                    match config.trust_anchors.certify(key, crt) {
+                                    match trust_anchors.certify(key, crt.clone()) {
                                         Err(e) => {
                                             error!("Received invalid certificate: {}", e);
                                         }
                                         Ok(crt_key) => {
                                             debug!("daemon certified until {:?}", expiry);
-                                            if crt_key_watch.send(Some(crt_key)).is_err() {
+                                            if publisher.publish_crt_key(crt_key).is_err() {
                                                 // If we can't store a value, than all observations
                                                 // have been dropped and we can stop refreshing.
                                                 return;
                                             }
 
-                                            refreshes.incr();
                                             curr_expiry = expiry;
+                                            curr_crt = Some(crt);
                                         }
                                     }
                                 }
@@ -154,29 +227,246 @@ impl Daemon {
                 }
                 Err(e) => error!("Failed to read authentication token: {}", e),
             }
-            config.refresh(curr_expiry).await;
+
+            let refresh = config.refresh(curr_expiry);
+            let bundle = match config.trust_bundle.as_ref() {
+                Some(bundle) => bundle,
+                None => {
+                    refresh.await;
+                    continue;
+                }
+            };
+
+            // Until the certificate should be refreshed, poll the trust bundle
+            // for changes.
+            tokio::pin!(refresh);
+            loop {
+                let reload = time::sleep(bundle.reload_interval);
+                tokio::pin!(reload);
+                if let Either::Left(_) = future::select(refresh.as_mut(), reload).await {
+                    break;
+                }
+
+                match publisher.reload(bundle, &config.key, &mut trust_anchors, curr_crt.as_ref()) {
+                    Ok(Reload::Unchanged) | Ok(Reload::Updated) => {}
+                    Ok(Reload::Refresh) => break,
+                    Err(LostLocal) => return,
+                }
+            }
+        }
+    }
+}
+
+// === impl Publisher ===
+
+impl Publisher {
+    /// Publishes a newly-obtained certificate.
+    pub fn publish_crt_key(&self, crt_key: id::CrtKey) -> Result<(), LostLocal> {
+        self.republish_crt_key(crt_key)?;
+        self.refreshes.incr();
+        Ok(())
+    }
+
+    /// Publishes a certificate that was rebuilt for new trust anchors.
+    fn republish_crt_key(&self, crt_key: id::CrtKey) -> Result<(), LostLocal> {
+        self.crt_key_watch
+            .send(Some(crt_key.with_denylist(&self.denylist)))
+            .map_err(|_| LostLocal)
+    }
+
+    pub fn publish_trust_anchors(&self, trust_anchors: id::TrustAnchors) -> Result<(), LostLocal> {
+        self.trust_anchors_watch
+            .send(trust_anchors.with_denylist(&self.denylist))
+            .map_err(|_| LostLocal)
+    }
+
+    /// Reloads the trust bundle, publishing its anchors if they differ from
+    /// the current anchors.
+    ///
+    /// When the anchors change, the current certificate is rebuilt so that
+    /// the client and server configurations use the new anchors.
+    fn reload(
+        &self,
+        bundle: &TrustBundle,
+        key: &id::Key,
+        trust_anchors: &mut id::TrustAnchors,
+        crt: Option<&id::Crt>,
+    ) -> Result<Reload, LostLocal> {
+        let anchors = match self.reload_trust_bundle(bundle, trust_anchors) {
+            Some(anchors) => anchors,
+            None => return Ok(Reload::Unchanged),
+        };
+        *trust_anchors = anchors.clone();
+        self.publish_trust_anchors(anchors)?;
+
+        if let Some(crt) = crt {
+            match trust_anchors.certify(key.clone(), crt.clone()) {
+                Ok(crt_key) => self.republish_crt_key(crt_key)?,
+                Err(e) => {
+                    warn!("Certificate is not valid for reloaded trust anchors: {}", e);
+                    return Ok(Reload::Refresh);
+                }
+            }
+        }
+
+        Ok(Reload::Updated)
+    }
+
+    /// Reads the trust bundle, returning its anchors if they differ from the
+    /// current anchors.
+    pub(crate) fn reload_trust_bundle(
+        &self,
+        bundle: &TrustBundle,
+        current: &id::TrustAnchors,
+    ) -> Option<id::TrustAnchors> {
+        let result = bundle.load();
+        if let Some(reloads) = self.reloads.as_ref() {
+            match result {
+                Ok(_) => reloads.success.incr(),
+                Err(_) => reloads.failure.incr(),
+            }
+        }
+
+        let anchors = match result {
+            Ok(anchors) => anchors,
+            Err(e) => {
+                warn!(path = %bundle.path.display(), "Failed to reload trust anchors: {}", e);
+                return None;
+            }
+        };
+        if anchors.fingerprint() == current.fingerprint() {
+            return None;
+        }
+
+        info!(fingerprint = %anchors.fingerprint(), "Reloaded trust anchors");
+        Some(anchors)
+    }
+}
+
+// === impl TrustBundle ===
+
+impl TrustBundle {
+    fn load(&self) -> Result<id::TrustAnchors, Error> {
+        let pem = std::fs::read_to_string(&self.path)?;
+        let anchors = id::TrustAnchors::from_pem(&pem).ok_or(InvalidTrustBundle)?;
+        Ok(anchors)
+    }
+}
+
+// === impl DenylistFile ===
+
+impl DenylistFile {
+    fn load(&self) -> Result<id::Denylist, Error> {
+        let denylist = std::fs::read_to_string(&self.path)?.parse::<id::Denylist>()?;
+        Ok(denylist)
+    }
+}
+
+// === impl DenylistDaemon ===
+
+impl DenylistDaemon {
+    pub async fn run(self) {
+        let Self {
+            file,
+            denylist,
+            reloads,
+        } = self;
+
+        let start = time::Instant::now() + file.reload_interval;
+        let mut interval = time::interval_at(start, file.reload_interval);
+        loop {
+            interval.tick().await;
+
+            match file.load() {
+                Ok(update) => {
+                    reloads.success.incr();
+                    if denylist.update(update) {
+                        info!(entries = denylist.entries(), "Reloaded denylist");
+                    }
+                }
+                Err(e) => {
+                    reloads.failure.incr();
+                    warn!(path = %file.path.display(), "Failed to reload denylist: {}", e);
+                }
+            }
         }
     }
 }
 
+// === impl InvalidTrustBundle ===
+
+impl std::fmt::Display for InvalidTrustBundle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no valid trust anchors")
+    }
+}
+
+impl std::error::Error for InvalidTrustBundle {}
+
+// === impl InvalidKey ===
+
+impl From<id::KeyRejected> for InvalidKey {
+    fn from(e: id::KeyRejected) -> Self {
+        Self(e)
+    }
+}
+
+impl std::fmt::Display for InvalidKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid key: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidKey {}
+
 // === impl LocalCrtKey ===
 
 impl LocalCrtKey {
     pub fn new(config: &Config) -> (Self, Daemon) {
+        let (l, publisher) = Self::with_publisher(
+            config.local_id.clone(),
+            config.trust_anchors.clone(),
+            config.trust_bundle.as_ref(),
+        );
+        let daemon = Daemon {
+            config: config.clone(),
+            publisher,
+        };
+        (l, daemon)
+    }
+
+    /// Creates a local identity that is updated by the returned `Publisher`.
+    ///
+    /// If a trust bundle is provided, its reloads are included in the identity's
+    /// metrics.
+    pub fn with_publisher(
+        id: id::LocalId,
+        trust_anchors: id::TrustAnchors,
+        trust_bundle: Option<&TrustBundle>,
+    ) -> (Self, Publisher) {
+        let denylist = id::SharedDenylist::default();
         let (s, w) = watch::channel(None);
+        let (anchors_tx, anchors_rx) = watch::channel(trust_anchors.with_denylist(&denylist));
         let refreshes = Arc::new(Counter::new());
+        let reloads = trust_bundle.map(|_| Arc::default());
         let l = Self {
-            id: config.local_id.clone(),
-            trust_anchors: config.trust_anchors.clone(),
+            id,
+            trust_anchors: anchors_rx,
             crt_key: w,
             refreshes: refreshes.clone(),
+            reloads: reloads.clone(),
+            denylist: denylist.clone(),
+            denylist_reloads: None,
+            handshakes: Handshakes::default(),
         };
-        let daemon = Daemon {
-            config: config.clone(),
-            refreshes,
+        let publisher = Publisher {
             crt_key_watch: s,
+            trust_anchors_watch: anchors_tx,
+            refreshes,
+            reloads,
+            denylist,
         };
-        (l, daemon)
+        (l, publisher)
     }
 
     pub async fn await_crt(mut self) -> Result<Self, LostDaemon> {
@@ -189,8 +479,37 @@ impl LocalCrtKey {
         Ok(self)
     }
 
+    /// Watches the certificates published for this identity.
+    #[cfg(test)]
+    pub(crate) fn crt_key_watch(&self) -> watch::Receiver<Option<id::CrtKey>> {
+        self.crt_key.clone()
+    }
+
     pub fn metrics(&self) -> crate::metrics::Report {
-        crate::metrics::Report::new(self.crt_key.clone(), self.refreshes.clone())
+        crate::metrics::Report::new(
+            self.crt_key.clone(),
+            self.refreshes.clone(),
+            self.trust_anchors.clone(),
+            self.reloads.clone(),
+            self.denylist.clone(),
+            self.denylist_reloads.clone(),
+            self.handshakes.clone(),
+        )
+    }
+
+    /// Configures the local identity to reject peers whose certificates are
+    /// denied by the configured file, which is reloaded by the returned
+    /// `DenylistDaemon`.
+    pub fn with_denylist(mut self, file: DenylistFile) -> (Self, DenylistDaemon) {
+        self.denylist.update(file.denylist.clone());
+        let reloads = Arc::new(crate::metrics::Reloads::default());
+        self.denylist_reloads = Some(reloads.clone());
+        let daemon = DenylistDaemon {
+            file,
+            denylist: self.denylist.clone(),
+            reloads,
+        };
+        (self, daemon)
     }
 
     pub fn id(&self) -> &id::LocalId {
@@ -206,7 +525,7 @@ impl LocalCrtKey {
             return c.client_config();
         }
 
-        self.trust_anchors.client_config()
+        self.trust_anchors.borrow().client_config()
     }
 
     pub fn server_config(&self) -> tls::server::Config {
@@ -230,8 +549,127 @@ impl Param<tls::server::Config> for LocalCrtKey {
     }
 }
 
+impl Param<tls::SharedDenylist> for LocalCrtKey {
+    fn param(&self) -> tls::SharedDenylist {
+        self.denylist.clone()
+    }
+}
+
+impl Param<Handshakes> for LocalCrtKey {
+    fn param(&self) -> Handshakes {
+        self.handshakes.clone()
+    }
+}
+
 impl Param<id::LocalId> for LocalCrtKey {
     fn param(&self) -> id::LocalId {
         self.id().clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_identity::test_util::FOO_NS1;
+    use std::io::Write;
+
+    static CA1: &[u8] = include_bytes!("../../../identity/src/testdata/ca1.pem");
+    static CA2: &[u8] = include_bytes!("../../../identity/src/testdata/ca2.pem");
+
+    fn anchors(pem: &[u8]) -> id::TrustAnchors {
+        id::TrustAnchors::from_pem(std::str::from_utf8(pem).unwrap()).unwrap()
+    }
+
+    fn bundle(pems: &[&[u8]]) -> (tempfile::NamedTempFile, TrustBundle) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        for pem in pems {
+            file.write_all(pem).unwrap();
+        }
+        let bundle = TrustBundle {
+            path: file.path().to_path_buf(),
+            reload_interval: Duration::from_secs(1),
+        };
+        (file, bundle)
+    }
+
+    fn local() -> (LocalCrtKey, Publisher, id::TrustAnchors) {
+        let trust_anchors = anchors(CA1);
+        let (_, no_bundle) = bundle(&[]);
+        let (local, publisher) = LocalCrtKey::with_publisher(
+            id::LocalId::from(FOO_NS1.crt().name().clone()),
+            trust_anchors.clone(),
+            Some(&no_bundle),
+        );
+        (local, publisher, trust_anchors)
+    }
+
+    #[test]
+    fn publishes_changed_anchors() {
+        let (local, publisher, mut trust_anchors) = local();
+
+        let (_file, unchanged) = bundle(&[CA1]);
+        let reload = publisher.reload(&unchanged, &FOO_NS1.key(), &mut trust_anchors, None);
+        assert_eq!(reload.unwrap(), Reload::Unchanged);
+
+        let (_file, changed) = bundle(&[CA2]);
+        let reload = publisher.reload(&changed, &FOO_NS1.key(), &mut trust_anchors, None);
+        assert_eq!(reload.unwrap(), Reload::Updated);
+        let fingerprint = anchors(CA2).fingerprint().to_string();
+        assert_eq!(trust_anchors.fingerprint(), fingerprint);
+        assert_eq!(local.trust_anchors.borrow().fingerprint(), fingerprint);
+        assert!(local.crt_key.borrow().is_none());
+    }
+
+    #[test]
+    fn recertifies_current_crt() {
+        let (local, publisher, mut trust_anchors) = local();
+        let crt = FOO_NS1.crt();
+
+        let (_file, both) = bundle(&[CA1, CA2]);
+        let reload = publisher.reload(&both, &FOO_NS1.key(), &mut trust_anchors, Some(&crt));
+        assert_eq!(reload.unwrap(), Reload::Updated);
+        assert_eq!(
+            local.trust_anchors.borrow().fingerprint(),
+            trust_anchors.fingerprint()
+        );
+        assert!(local.crt_key.borrow().is_some());
+        // Rebuilding the certificate is not a refresh.
+        assert_eq!(local.refreshes.value(), 0.0);
+    }
+
+    #[test]
+    fn refreshes_crt_not_issued_by_anchors() {
+        let (local, publisher, mut trust_anchors) = local();
+        let crt = FOO_NS1.crt();
+
+        let (_file, other) = bundle(&[CA2]);
+        let reload = publisher.reload(&other, &FOO_NS1.key(), &mut trust_anchors, Some(&crt));
+        assert_eq!(reload.unwrap(), Reload::Refresh);
+        assert_eq!(
+            local.trust_anchors.borrow().fingerprint(),
+            anchors(CA2).fingerprint()
+        );
+        assert!(local.crt_key.borrow().is_none());
+    }
+
+    #[test]
+    fn ignores_unreadable_bundle() {
+        let (local, publisher, mut trust_anchors) = local();
+        let fingerprint = trust_anchors.fingerprint().to_string();
+
+        let (file, missing) = bundle(&[CA2]);
+        drop(file);
+        let reload = publisher.reload(&missing, &FOO_NS1.key(), &mut trust_anchors, None);
+        assert_eq!(reload.unwrap(), Reload::Unchanged);
+
+        let (_file, invalid) = bundle(&[&b"not a certificate"[..]]);
+        let reload = publisher.reload(&invalid, &FOO_NS1.key(), &mut trust_anchors, None);
+        assert_eq!(reload.unwrap(), Reload::Unchanged);
+
+        assert_eq!(trust_anchors.fingerprint(), fingerprint);
+        assert_eq!(local.trust_anchors.borrow().fingerprint(), fingerprint);
+        let reloads = local.reloads.as_ref().unwrap();
+        assert_eq!(reloads.failure.value(), 2.0);
+        assert_eq!(reloads.success.value(), 0.0);
+    }
+}
@@ -0,0 +1,192 @@
+//! Loads the local identity's certificate and key from files.
+//!
+//! The files are read periodically so that certificates that are rotated by
+//! another process (e.g. a certificate manager that writes to a shared
+//! volume) are used without restarting the proxy.
+
+use crate::certify::{InvalidKey, LocalCrtKey, Publisher, TrustBundle};
+use linkerd_error::Error;
+use linkerd_identity as id;
+use std::{fs, path::PathBuf, time::Duration};
+use tokio::time;
+use tracing::{debug, warn};
+
+/// Configures a local identity that is loaded from files.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub local_id: id::LocalId,
+    pub trust_anchors: id::TrustAnchors,
+    pub trust_bundle: Option<TrustBundle>,
+
+    /// A file containing a PEM-encoded certificate chain, leaf first.
+    pub crt: PathBuf,
+
+    /// A file containing a PEM-encoded PKCS#8 private key.
+    pub key: PathBuf,
+
+    /// How often the files are checked for changes.
+    pub reload_interval: Duration,
+}
+
+#[derive(Debug)]
+pub struct Daemon {
+    publisher: Publisher,
+    config: Config,
+}
+
+// === impl Config ===
+
+impl Config {
+    pub fn build(self) -> (LocalCrtKey, Daemon) {
+        let (local, publisher) = LocalCrtKey::with_publisher(
+            self.local_id.clone(),
+            self.trust_anchors.clone(),
+            self.trust_bundle.as_ref(),
+        );
+        let daemon = Daemon {
+            publisher,
+            config: self,
+        };
+        (local, daemon)
+    }
+
+    fn certify(
+        &self,
+        trust_anchors: &id::TrustAnchors,
+        crt: &[u8],
+        key: &[u8],
+    ) -> Result<id::CrtKey, Error> {
+        let crt = id::Crt::from_pem(self.local_id.clone(), std::str::from_utf8(crt)?)?;
+        let key = id::Key::from_pkcs8_pem(std::str::from_utf8(key)?).map_err(InvalidKey::from)?;
+        let crt_key = trust_anchors.certify(key, crt)?;
+        Ok(crt_key)
+    }
+}
+
+// === impl Daemon ===
+
+impl Daemon {
+    pub async fn run(self) {
+        let Self { publisher, config } = self;
+
+        let mut trust_anchors = config.trust_anchors.clone();
+        let mut loaded = None;
+        let mut interval = time::interval(config.reload_interval);
+        loop {
+            interval.tick().await;
+
+            let mut anchors_changed = false;
+            if let Some(bundle) = config.trust_bundle.as_ref() {
+                if let Some(anchors) = publisher.reload_trust_bundle(bundle, &trust_anchors) {
+                    trust_anchors = anchors.clone();
+                    if publisher.publish_trust_anchors(anchors).is_err() {
+                        return;
+                    }
+                    anchors_changed = true;
+                }
+            }
+
+            let files = match (fs::read(&config.crt), fs::read(&config.key)) {
+                (Ok(crt), Ok(key)) => (crt, key),
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!("Failed to read certificate files: {}", e);
+                    continue;
+                }
+            };
+            if !anchors_changed && loaded.as_ref() == Some(&files) {
+                continue;
+            }
+
+            match config.certify(&trust_anchors, &files.0, &files.1) {
+                Ok(crt_key) => {
+                    debug!("Loaded certificate valid until {:?}", crt_key.expiry());
+                    if publisher.publish_crt_key(crt_key).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => warn!(crt = %config.crt.display(), "Invalid certificate: {}", e),
+            }
+            // Don't try to load the same files again until they change.
+            loaded = Some(files);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd_identity::test_util::{Identity, FOO_NS1, FOO_NS1_CA2};
+    use tokio::sync::watch;
+
+    const RELOAD_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn pem(label: &str, der: &[u8]) -> String {
+        let mut pem = format!("-----BEGIN {}-----\n", label);
+        for line in base64::encode(der).as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line).unwrap());
+            pem.push('\n');
+        }
+        pem.push_str(&format!("-----END {}-----\n", label));
+        pem
+    }
+
+    fn write(config: &Config, identity: &Identity) {
+        fs::write(&config.crt, pem("CERTIFICATE", identity.crt)).unwrap();
+        fs::write(&config.key, pem("PRIVATE KEY", identity.key)).unwrap();
+    }
+
+    /// Waits for several reload intervals, returning whether a certificate was
+    /// published.
+    async fn published(crt_key: &mut watch::Receiver<Option<id::CrtKey>>) -> bool {
+        time::timeout(RELOAD_INTERVAL * 5, crt_key.changed())
+            .await
+            .is_ok()
+    }
+
+    // The daemon runs on the test's single thread, so files are never read
+    // while they are being written.
+    #[tokio::test]
+    async fn reloads_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        // Both CAs are trusted so that the certificate may be rotated to one
+        // issued by the other CA.
+        let pems = [FOO_NS1.trust_anchors, &b"\n"[..], FOO_NS1_CA2.trust_anchors].concat();
+        let trust_anchors = id::TrustAnchors::from_pem(std::str::from_utf8(&pems).unwrap());
+        let config = Config {
+            local_id: id::LocalId::from(FOO_NS1.crt().name().clone()),
+            trust_anchors: trust_anchors.expect("bundle must be valid"),
+            trust_bundle: None,
+            crt: dir.path().join("crt.pem"),
+            key: dir.path().join("key.pem"),
+            reload_interval: RELOAD_INTERVAL,
+        };
+        write(&config, &FOO_NS1);
+
+        let (local, daemon) = config.clone().build();
+        let mut crt_key = local.crt_key_watch();
+        tokio::spawn(daemon.run());
+
+        crt_key.changed().await.expect("daemon must run");
+        assert_eq!(
+            crt_key.borrow().as_ref().expect("must be certified").name(),
+            local.name()
+        );
+
+        // Files that have not changed are not published again.
+        assert!(!published(&mut crt_key).await);
+
+        // A rotated certificate is published.
+        write(&config, &FOO_NS1_CA2);
+        crt_key.changed().await.expect("daemon must run");
+        assert!(!published(&mut crt_key).await);
+
+        // Invalid files don't replace the current certificate.
+        fs::write(&config.crt, "not a certificate").unwrap();
+        assert!(!published(&mut crt_key).await);
+        assert!(crt_key.borrow().is_some());
+
+        fs::remove_file(&config.key).unwrap();
+        assert!(!published(&mut crt_key).await);
+        assert!(crt_key.borrow().is_some());
+    }
+}
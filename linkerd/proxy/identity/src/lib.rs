@@ -1,6 +1,8 @@
 #![deny(warnings, rust_2018_idioms)]
 
 pub mod certify;
+pub mod file;
 pub mod metrics;
+pub mod spiffe;
 
-pub use self::certify::{AwaitCrt, CrtKeySender, LocalCrtKey};
+pub use self::certify::{AwaitCrt, CrtKeySender, LocalCrtKey, Publisher};
@@ -1,5 +1,7 @@
-use linkerd_identity::CrtKey;
-use linkerd_metrics::{metrics, Counter, FmtMetrics, Gauge};
+use linkerd_identity::{CrtKey, TrustAnchors};
+use linkerd_metrics::{metrics, Counter, FmtLabels, FmtMetrics, Gauge};
+use linkerd_proxy_transport::metrics::Handshakes;
+use linkerd_tls as tls;
 use std::{fmt, sync::Arc, time::UNIX_EPOCH};
 use tokio::sync::watch;
 
@@ -8,6 +10,13 @@ pub struct Report {
     inner: Option<Inner>,
 }
 
+/// Counts attempts to reload a trust bundle or denylist file.
+#[derive(Debug, Default)]
+pub(crate) struct Reloads {
+    pub(crate) success: Counter,
+    pub(crate) failure: Counter,
+}
+
 metrics! {
     identity_cert_expiration_timestamp_seconds: Gauge {
         "Time when the this proxy's current mTLS identity certificate will expire (in seconds since the UNIX epoch)."
@@ -15,18 +24,54 @@ metrics! {
 
     identity_cert_refresh_count: Counter {
         "The total number of times this proxy's mTLS identity certificate has been refreshed by the Identity service."
+    },
+
+    identity_trust_anchors_info: Gauge {
+        "Describes the trust anchors currently in use, labeled by their SHA-256 fingerprint."
+    },
+
+    identity_trust_anchors_reload_total: Counter {
+        "The total number of times the trust anchors file has been read, labeled by whether it was valid."
+    },
+
+    identity_denylist_entries: Gauge {
+        "The number of certificate serials and identities in the current denylist."
+    },
+
+    identity_denylist_reload_total: Counter {
+        "The total number of times the denylist file has been read, labeled by whether it was valid."
+    },
+
+    identity_denylist_rejections_total: Counter {
+        "The total number of TLS connections rejected because the peer's certificate is denied, labeled by whether the peer was the client (src) or the server (dst)."
     }
 }
 
+struct Fingerprint<'a>(&'a str);
+
+struct Status(&'static str);
+
+struct Peer(&'static str);
+
 impl Report {
     pub(crate) fn new(
         crt_key_watch: watch::Receiver<Option<CrtKey>>,
         refreshes: Arc<Counter>,
+        trust_anchors_watch: watch::Receiver<TrustAnchors>,
+        reloads: Option<Arc<Reloads>>,
+        denylist: tls::SharedDenylist,
+        denylist_reloads: Option<Arc<Reloads>>,
+        handshakes: Handshakes,
     ) -> Self {
         Self {
             inner: Some(Inner {
                 crt_key_watch,
                 refreshes,
+                trust_anchors_watch,
+                reloads,
+                denylist,
+                denylist_reloads,
+                handshakes,
             }),
         }
     }
@@ -40,6 +85,11 @@ impl Report {
 struct Inner {
     crt_key_watch: watch::Receiver<Option<CrtKey>>,
     refreshes: Arc<Counter>,
+    trust_anchors_watch: watch::Receiver<TrustAnchors>,
+    reloads: Option<Arc<Reloads>>,
+    denylist: tls::SharedDenylist,
+    denylist_reloads: Option<Arc<Reloads>>,
+    handshakes: Handshakes,
 }
 
 impl FmtMetrics for Report {
@@ -65,6 +115,77 @@ impl FmtMetrics for Report {
         identity_cert_refresh_count.fmt_help(f)?;
         identity_cert_refresh_count.fmt_metric(f, &this.refreshes)?;
 
+        identity_trust_anchors_info.fmt_help(f)?;
+        identity_trust_anchors_info.fmt_metric_labeled(
+            f,
+            &Gauge::from(1),
+            &Fingerprint(this.trust_anchors_watch.borrow().fingerprint()),
+        )?;
+
+        if let Some(reloads) = this.reloads.as_ref() {
+            identity_trust_anchors_reload_total.fmt_help(f)?;
+            identity_trust_anchors_reload_total.fmt_metric_labeled(
+                f,
+                &reloads.success,
+                &Status("success"),
+            )?;
+            identity_trust_anchors_reload_total.fmt_metric_labeled(
+                f,
+                &reloads.failure,
+                &Status("failure"),
+            )?;
+        }
+
+        if let Some(reloads) = this.denylist_reloads.as_ref() {
+            identity_denylist_entries.fmt_help(f)?;
+            identity_denylist_entries
+                .fmt_metric(f, &Gauge::from(this.denylist.entries() as u64))?;
+
+            identity_denylist_reload_total.fmt_help(f)?;
+            identity_denylist_reload_total.fmt_metric_labeled(
+                f,
+                &reloads.success,
+                &Status("success"),
+            )?;
+            identity_denylist_reload_total.fmt_metric_labeled(
+                f,
+                &reloads.failure,
+                &Status("failure"),
+            )?;
+
+            identity_denylist_rejections_total.fmt_help(f)?;
+            identity_denylist_rejections_total.fmt_metric_labeled(
+                f,
+                &Counter::from(this.denylist.rejected_clients()),
+                &Peer("src"),
+            )?;
+            identity_denylist_rejections_total.fmt_metric_labeled(
+                f,
+                &Counter::from(this.denylist.rejected_servers()),
+                &Peer("dst"),
+            )?;
+        }
+
+        this.handshakes.fmt_metrics(f)?;
+
         Ok(())
     }
 }
+
+impl FmtLabels for Fingerprint<'_> {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256=\"{}\"", self.0)
+    }
+}
+
+impl FmtLabels for Status {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "status=\"{}\"", self.0)
+    }
+}
+
+impl FmtLabels for Peer {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "peer=\"{}\"", self.0)
+    }
+}
@@ -0,0 +1,273 @@
+//! Obtains the local identity's certificates from a SPIFFE Workload API.
+//!
+//! The Workload API streams X.509 SVIDs, with their private keys and trust
+//! bundles, whenever they are rotated. The first (default) SVID of each
+//! response is used; it must be valid for the local identity name.
+
+use crate::certify::{InvalidKey, LocalCrtKey, Publisher};
+use http_body::Body as HttpBody;
+use linkerd_error::Error;
+use linkerd_identity as id;
+use std::time::Duration;
+use tokio::time;
+use tonic::{
+    self as grpc,
+    body::{Body, BoxBody},
+    client::GrpcService,
+    codec::ProstCodec,
+    metadata::MetadataValue,
+};
+use tracing::{debug, warn};
+
+/// Configures a local identity that is obtained from a SPIFFE Workload API.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub local_id: id::LocalId,
+
+    /// How long to wait before fetching SVIDs again after the Workload API
+    /// fails.
+    pub retry_interval: Duration,
+}
+
+#[derive(Debug)]
+pub struct Daemon {
+    publisher: Publisher,
+    config: Config,
+}
+
+/// Indicates that a Workload API response did not include an SVID.
+#[derive(Copy, Clone, Debug)]
+pub struct NoSvid;
+
+/// Indicates that an SVID's trust bundle did not contain any valid trust
+/// anchors.
+#[derive(Copy, Clone, Debug)]
+pub struct InvalidBundle;
+
+// Messages from the Workload API (`workload.proto`). Only the fields that the
+// proxy uses are decoded.
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct X509SvidRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct X509SvidResponse {
+    #[prost(message, repeated, tag = "1")]
+    svids: Vec<X509Svid>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct X509Svid {
+    #[prost(string, tag = "1")]
+    spiffe_id: String,
+
+    /// The SVID's concatenated DER-encoded certificate chain, leaf first.
+    #[prost(bytes, tag = "2")]
+    x509_svid: Vec<u8>,
+
+    /// The SVID's DER-encoded PKCS#8 private key.
+    #[prost(bytes, tag = "3")]
+    x509_svid_key: Vec<u8>,
+
+    /// The trust domain's concatenated DER-encoded CA certificates.
+    #[prost(bytes, tag = "4")]
+    bundle: Vec<u8>,
+}
+
+const FETCH_X509_SVID: &str = "/SpiffeWorkloadAPI/FetchX509SVID";
+
+/// The Workload API requires this header on all requests to guard against
+/// server-side request forgery.
+const SECURITY_HEADER: &str = "workload.spiffe.io";
+
+// === impl Config ===
+
+impl Config {
+    pub fn build(self) -> (LocalCrtKey, Daemon) {
+        // Nothing is trusted until the first SVID's bundle is received.
+        let (local, publisher) =
+            LocalCrtKey::with_publisher(self.local_id.clone(), id::TrustAnchors::empty(), None);
+        let daemon = Daemon {
+            publisher,
+            config: self,
+        };
+        (local, daemon)
+    }
+
+    fn certify(&self, rsp: X509SvidResponse) -> Result<(id::TrustAnchors, id::CrtKey), Error> {
+        let svid = rsp.svids.into_iter().next().ok_or(NoSvid)?;
+        debug!(spiffe.id = %svid.spiffe_id, "Received SVID");
+        let trust_anchors = id::TrustAnchors::from_der(&svid.bundle).ok_or(InvalidBundle)?;
+        let crt = id::Crt::from_der(self.local_id.clone(), &svid.x509_svid)?;
+        let key = id::Key::from_pkcs8(&svid.x509_svid_key).map_err(InvalidKey::from)?;
+        let crt_key = trust_anchors.certify(key, crt)?;
+        Ok((trust_anchors, crt_key))
+    }
+}
+
+// === impl Daemon ===
+
+impl Daemon {
+    pub async fn run<T>(self, client: T)
+    where
+        T: GrpcService<BoxBody>,
+        T::ResponseBody: Send + 'static,
+        <T::ResponseBody as Body>::Data: Send,
+        <T::ResponseBody as HttpBody>::Error: Into<Error> + Send,
+    {
+        let Self { publisher, config } = self;
+        let mut client = grpc::client::Grpc::new(client);
+
+        loop {
+            match client.ready().await {
+                Err(e) => warn!("Workload API unavailable: {}", e.into()),
+                Ok(()) => {
+                    let mut req = grpc::Request::new(X509SvidRequest {});
+                    req.metadata_mut()
+                        .insert(SECURITY_HEADER, MetadataValue::from_static("true"));
+                    let path = http::uri::PathAndQuery::from_static(FETCH_X509_SVID);
+                    match client
+                        .server_streaming(req, path, ProstCodec::default())
+                        .await
+                    {
+                        Err(status) => warn!("Failed to fetch SVIDs: {}", status),
+                        Ok(rsp) => {
+                            let mut svids = rsp.into_inner();
+                            loop {
+                                match svids.message().await {
+                                    Ok(Some(rsp)) => match config.certify(rsp) {
+                                        Ok((trust_anchors, crt_key)) => {
+                                            debug!("SVID valid until {:?}", crt_key.expiry());
+                                            if publisher
+                                                .publish_trust_anchors(trust_anchors)
+                                                .is_err()
+                                                || publisher.publish_crt_key(crt_key).is_err()
+                                            {
+                                                return;
+                                            }
+                                        }
+                                        Err(e) => warn!("Received invalid SVID: {}", e),
+                                    },
+                                    Ok(None) => {
+                                        debug!("Workload API stream ended");
+                                        break;
+                                    }
+                                    Err(status) => {
+                                        warn!("Workload API stream failed: {}", status);
+                                        break;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            time::sleep(config.retry_interval).await;
+        }
+    }
+}
+
+// === impl NoSvid ===
+
+impl std::fmt::Display for NoSvid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "response did not include an SVID")
+    }
+}
+
+impl std::error::Error for NoSvid {}
+
+// === impl InvalidBundle ===
+
+impl std::fmt::Display for InvalidBundle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SVID bundle has no valid trust anchors")
+    }
+}
+
+impl std::error::Error for InvalidBundle {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, prelude::*};
+    use hyper::body::{Bytes, Sender};
+    use linkerd_identity::test_util::{Identity, BAR_NS1, FOO_NS1, FOO_NS1_CA2};
+    use prost::Message;
+    use tokio::sync::mpsc;
+
+    /// Encodes a Workload API response as a gRPC message.
+    fn svid(identity: &Identity) -> Bytes {
+        let pem = std::str::from_utf8(identity.trust_anchors).unwrap();
+        let bundle = pem
+            .lines()
+            .filter(|l| !l.starts_with("-----"))
+            .collect::<String>();
+        let rsp = X509SvidResponse {
+            svids: vec![X509Svid {
+                spiffe_id: identity.name.to_string(),
+                x509_svid: identity.crt.to_vec(),
+                x509_svid_key: identity.key.to_vec(),
+                bundle: base64::decode(bundle).unwrap(),
+            }],
+        };
+
+        // Each message is prefixed by an uncompressed flag and its length.
+        let mut buf = vec![0; 5];
+        rsp.encode(&mut buf).unwrap();
+        let len = (buf.len() - 5) as u32;
+        buf[1..5].copy_from_slice(&len.to_be_bytes());
+        buf.into()
+    }
+
+    #[tokio::test]
+    async fn publishes_rotated_svids() {
+        let config = Config {
+            local_id: id::LocalId::from(FOO_NS1.crt().name().clone()),
+            retry_interval: Duration::from_millis(1),
+        };
+        let (local, daemon) = config.build();
+        let mut crt_key = local.crt_key_watch();
+
+        // Serves Workload API streams, which are fed by the test.
+        let (streams_tx, mut streams) = mpsc::unbounded_channel::<Sender>();
+        let api = tower::service_fn(move |_: http::Request<BoxBody>| {
+            let (tx, body) = hyper::Body::channel();
+            let _ = streams_tx.send(tx);
+            let rsp = http::Response::builder()
+                .header("content-type", "application/grpc")
+                .body(body)
+                .unwrap();
+            future::ok::<_, std::convert::Infallible>(rsp)
+        });
+        tokio::spawn(daemon.run(api));
+
+        let mut stream = streams.recv().await.expect("daemon must connect");
+        stream.send_data(svid(&FOO_NS1)).await.unwrap();
+        crt_key.changed().await.expect("daemon must run");
+        assert_eq!(
+            crt_key.borrow().as_ref().expect("must be certified").name(),
+            local.name()
+        );
+
+        // A rotated SVID is published.
+        stream.send_data(svid(&FOO_NS1_CA2)).await.unwrap();
+        crt_key.changed().await.expect("daemon must run");
+
+        // An SVID for another identity is ignored.
+        stream.send_data(svid(&BAR_NS1)).await.unwrap();
+
+        // When the stream ends, the daemon reconnects.
+        drop(stream);
+        let mut stream = streams.recv().await.expect("daemon must reconnect");
+        assert!(
+            crt_key.changed().now_or_never().is_none(),
+            "invalid SVID must not be published"
+        );
+
+        stream.send_data(svid(&FOO_NS1)).await.unwrap();
+        crt_key.changed().await.expect("daemon must run");
+        assert!(crt_key.borrow().is_some());
+    }
+}
@@ -25,7 +25,12 @@ metrics! {
     tcp_write_bytes_total: Counter { "Total count of bytes written to peers" },
 
     tcp_close_total: Counter { "Total count of closed connections" },
-    tcp_connection_duration_ms: Histogram<latency::Ms> { "Connection lifetimes" }
+    tcp_connection_duration_ms: Histogram<latency::Ms> { "Connection lifetimes" },
+
+    tls_handshake_total: Counter {
+        "Total count of completed TLS 1.2 handshakes, labeled by whether the peer was the client (src) or the server (dst) and whether the session was resumed"
+    },
+    tls_handshake_duration_ms: Histogram<latency::Ms> { "TLS 1.2 handshake latencies" }
 }
 
 pub fn new<K: Eq + Hash + FmtLabels>(retain_idle: Duration) -> (Registry<K>, Report<K>) {
@@ -114,10 +119,64 @@ struct EosMetrics {
 pub struct Sensor {
     metrics: Option<Arc<Metrics>>,
     opened_at: Instant,
+    totals: Totals,
 }
 
+/// Counts the bytes read from and written to a single transport.
+///
+/// The counts are shared with the transport's `Sensor`, so they may be read
+/// after the transport has been moved into a service.
+#[derive(Clone, Debug, Default)]
+pub struct Totals(Arc<TotalBytes>);
+
 pub type SensorIo<T> = io::SensorIo<T, Sensor>;
 
+/// Records the TLS handshakes completed by a local identity.
+///
+/// Implements `FmtMetrics`.
+#[derive(Clone, Debug, Default)]
+pub struct Handshakes(Arc<HandshakesByPeer>);
+
+/// Indicates whether a TLS handshake established a new session or resumed a
+/// prior one.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum HandshakeKind {
+    Full,
+    Resumed,
+}
+
+#[derive(Debug, Default)]
+struct HandshakesByPeer {
+    accept: HandshakesByKind,
+    connect: HandshakesByKind,
+}
+
+#[derive(Debug, Default)]
+struct HandshakesByKind {
+    full: HandshakeMetrics,
+    resumed: HandshakeMetrics,
+}
+
+#[derive(Debug, Default)]
+struct HandshakeMetrics {
+    total: Counter,
+    duration: Histogram<latency::Ms>,
+}
+
+/// Describes a class of TLS handshake.
+///
+/// Implements `FmtLabels`.
+struct HandshakeLabels {
+    peer: &'static str,
+    kind: HandshakeKind,
+}
+
+#[derive(Debug, Default)]
+struct TotalBytes {
+    read: Counter,
+    write: Counter,
+}
+
 /// Lazily builds instances of `Sensor`.
 #[derive(Clone, Debug)]
 struct NewSensor(Arc<Metrics>);
@@ -359,12 +418,19 @@ impl Sensor {
         Self {
             metrics: Some(metrics),
             opened_at: Instant::now(),
+            totals: Totals::default(),
         }
     }
+
+    /// Returns a handle to the transport's byte counts.
+    pub fn totals(&self) -> Totals {
+        self.totals.clone()
+    }
 }
 
 impl io::Sensor for Sensor {
     fn record_read(&mut self, sz: usize) {
+        self.totals.0.read.add(sz as u64);
         if let Some(ref m) = self.metrics {
             m.read_bytes_total.add(sz as u64);
             if let Ok(mut by_eos) = m.by_eos.lock() {
@@ -374,6 +440,7 @@ impl io::Sensor for Sensor {
     }
 
     fn record_write(&mut self, sz: usize) {
+        self.totals.0.write.add(sz as u64);
         if let Some(ref m) = self.metrics {
             m.write_bytes_total.add(sz as u64);
             if let Ok(mut by_eos) = m.by_eos.lock() {
@@ -427,6 +494,18 @@ impl Drop for Sensor {
     }
 }
 
+// ===== impl Totals =====
+
+impl Totals {
+    pub fn read_bytes(&self) -> u64 {
+        (&self.0.read).into()
+    }
+
+    pub fn write_bytes(&self) -> u64 {
+        (&self.0.write).into()
+    }
+}
+
 // ===== impl NewSensor =====
 
 impl NewSensor {
@@ -446,6 +525,70 @@ impl FmtLabels for Eos {
     }
 }
 
+// ===== impl Handshakes =====
+
+impl Handshakes {
+    /// Records a handshake on a connection accepted from a client.
+    pub fn record_accept(&self, kind: HandshakeKind, elapsed: Duration) {
+        self.0.accept.get(kind).record(elapsed);
+    }
+
+    /// Records a handshake on a connection made to a server.
+    pub fn record_connect(&self, kind: HandshakeKind, elapsed: Duration) {
+        self.0.connect.get(kind).record(elapsed);
+    }
+
+    fn fmt_by<M: FmtMetric>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        metric: Metric<'_, &str, M>,
+        get_metric: impl Fn(&HandshakeMetrics) -> &M,
+    ) -> fmt::Result {
+        metric.fmt_help(f)?;
+        for (peer, by_kind) in &[("src", &self.0.accept), ("dst", &self.0.connect)] {
+            for kind in &[HandshakeKind::Full, HandshakeKind::Resumed] {
+                let labels = HandshakeLabels { peer, kind: *kind };
+                get_metric(by_kind.get(*kind)).fmt_metric_labeled(f, metric.name, labels)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FmtMetrics for Handshakes {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_by(f, tls_handshake_total, |m| &m.total)?;
+        self.fmt_by(f, tls_handshake_duration_ms, |m| &m.duration)?;
+        Ok(())
+    }
+}
+
+impl HandshakesByKind {
+    fn get(&self, kind: HandshakeKind) -> &HandshakeMetrics {
+        match kind {
+            HandshakeKind::Full => &self.full,
+            HandshakeKind::Resumed => &self.resumed,
+        }
+    }
+}
+
+impl HandshakeMetrics {
+    fn record(&self, elapsed: Duration) {
+        self.total.incr();
+        self.duration.add(elapsed);
+    }
+}
+
+impl FmtLabels for HandshakeLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let session = match self.kind {
+            HandshakeKind::Full => "full",
+            HandshakeKind::Resumed => "resumed",
+        };
+        write!(f, "peer=\"{}\",session=\"{}\"", self.peer, session)
+    }
+}
+
 // ===== impl Metrics =====
 
 impl LastUpdate for Metrics {
@@ -0,0 +1,418 @@
+//! Hedges requests that have been outstanding for too long.
+//!
+//! When a request has not completed after a policy-determined delay, a copy of
+//! the request is sent and the first successful response is used.
+//!
+//! The hedge is sent through the same inner service as the original request.
+//! It may be dispatched to a different endpoint only if the inner service
+//! (e.g. a load balancer) selects one.
+
+use crate::NewPolicy;
+use linkerd_error::Error;
+use linkerd_stack::{NewService, Proxy, ProxyService};
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time;
+use tower::util::{Oneshot, ServiceExt};
+use tracing::{debug, trace};
+
+/// Determines whether and when requests are hedged.
+pub trait Policy<Req, Rsp> {
+    /// Returns how long to wait for a response to `req` before hedging it, or
+    /// `None` if it may not be hedged.
+    fn delay(&self, req: &Req) -> Option<Duration>;
+
+    /// Clones `req` so that it may be sent as a hedge.
+    fn clone_request(&self, req: &Req) -> Option<Req>;
+
+    /// Called once a request has been outstanding for its delay. Returns
+    /// whether the hedge may be sent.
+    fn can_hedge(&self) -> bool;
+
+    /// Classifies a response once both the request and its hedge have been
+    /// sent. The response is only used while the other request is outstanding
+    /// if it is successful.
+    type Classify: Future<Output = (Rsp, bool)>;
+
+    /// Determines whether a response is successful. Classification may need
+    /// to read the response (e.g. its trailers), so the response is returned
+    /// with its classification.
+    fn classify(&self, rsp: Rsp) -> Self::Classify;
+}
+
+/// A layer that applies per-target hedging policies.
+///
+/// Composes `NewService`s that produce a `Proxy`.
+#[derive(Clone, Debug)]
+pub struct NewHedgeLayer<P> {
+    new_policy: P,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewHedge<P, N> {
+    new_policy: P,
+    inner: N,
+}
+
+#[derive(Clone, Debug)]
+pub struct Hedge<P, S> {
+    policy: Option<P>,
+    inner: S,
+}
+
+#[pin_project]
+pub struct ResponseFuture<R, P, S, Req>
+where
+    R: Policy<Req, P::Response>,
+    P: Proxy<Req, S>,
+    S: tower::Service<P::Request>,
+    S::Error: Into<Error>,
+{
+    #[pin]
+    primary: Attempt<P::Future, R::Classify>,
+    #[pin]
+    hedge: Attempt<Oneshot<ProxyService<P, S>, Req>, R::Classify>,
+    #[pin]
+    delay: Option<time::Sleep>,
+    pending: Option<(Req, ProxyService<P, S>)>,
+    policy: Option<R>,
+}
+
+/// The state of the original request or of its hedge.
+#[pin_project(project = AttemptProj)]
+enum Attempt<F, C> {
+    /// Awaiting a response.
+    Response(#[pin] F),
+
+    /// Awaiting a response's classification.
+    Classify(#[pin] C),
+
+    /// The request has not been sent or its response was discarded.
+    Idle,
+}
+
+// === impl NewHedgeLayer ===
+
+impl<P> NewHedgeLayer<P> {
+    pub fn new(new_policy: P) -> Self {
+        Self { new_policy }
+    }
+}
+
+impl<P: Clone, N> tower::layer::Layer<N> for NewHedgeLayer<P> {
+    type Service = NewHedge<P, N>;
+
+    fn layer(&self, inner: N) -> Self::Service {
+        Self::Service {
+            inner,
+            new_policy: self.new_policy.clone(),
+        }
+    }
+}
+
+// === impl NewHedge ===
+
+impl<T, N, P> NewService<T> for NewHedge<P, N>
+where
+    N: NewService<T>,
+    P: NewPolicy<T>,
+{
+    type Service = Hedge<P::Policy, N::Service>;
+
+    fn new_service(&mut self, target: T) -> Self::Service {
+        // Determine if there is a hedging policy for the given target.
+        let policy = self.new_policy.new_policy(&target);
+
+        let inner = self.inner.new_service(target);
+        Hedge { policy, inner }
+    }
+}
+
+// === impl Hedge ===
+
+impl<R, P, Req, S> Proxy<Req, S> for Hedge<R, P>
+where
+    R: Policy<Req, P::Response> + Clone,
+    P: Proxy<Req, S> + Clone,
+    S: tower::Service<P::Request> + Clone,
+    S::Error: Into<Error>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = Error;
+    type Future = ResponseFuture<R, P, S, Req>;
+
+    fn proxy(&self, svc: &mut S, req: Req) -> Self::Future {
+        let hedge = self.policy.as_ref().and_then(|policy| {
+            let delay = policy.delay(&req)?;
+            let hedge = policy.clone_request(&req)?;
+            let svc = self.inner.clone().wrap_service(svc.clone());
+            Some((delay, hedge, svc))
+        });
+        trace!(hedged = %hedge.is_some());
+
+        let (delay, pending) = match hedge {
+            Some((delay, req, svc)) => (Some(time::sleep(delay)), Some((req, svc))),
+            None => (None, None),
+        };
+        ResponseFuture {
+            primary: Attempt::Response(self.inner.proxy(svc, req)),
+            hedge: Attempt::Idle,
+            delay,
+            pending,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<R, P, S, Req> Future for ResponseFuture<R, P, S, Req>
+where
+    R: Policy<Req, P::Response>,
+    P: Proxy<Req, S>,
+    S: tower::Service<P::Request>,
+    S::Error: Into<Error>,
+{
+    type Output = Result<P::Response, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        // Once the delay elapses, send the hedge if the policy permits it.
+        if let Some(delay) = this.delay.as_mut().as_pin_mut() {
+            if delay.poll(cx).is_ready() {
+                this.delay.set(None);
+                if let Some((req, svc)) = this.pending.take() {
+                    if matches!(this.policy, Some(p) if p.can_hedge()) {
+                        debug!("Hedging request");
+                        this.hedge.set(Attempt::Response(svc.oneshot(req)));
+                    }
+                }
+            }
+        }
+
+        // Use the first successful response. A failed response is only used if
+        // the other request is not outstanding.
+        let hedging = this.hedge.is_outstanding();
+        if let Some(res) =
+            this.primary
+                .as_mut()
+                .poll_response::<R, Req>(cx, this.policy.as_ref(), hedging)
+        {
+            return Poll::Ready(res);
+        }
+
+        let primary = this.primary.is_outstanding();
+        if let Some(res) =
+            this.hedge
+                .as_mut()
+                .poll_response::<R, Req>(cx, this.policy.as_ref(), primary)
+        {
+            return Poll::Ready(res);
+        }
+
+        Poll::Pending
+    }
+}
+
+// === impl Attempt ===
+
+impl<F, C, Rsp, E> Attempt<F, C>
+where
+    F: Future<Output = Result<Rsp, E>>,
+    E: Into<Error>,
+    C: Future<Output = (Rsp, bool)>,
+{
+    fn is_outstanding(&self) -> bool {
+        !matches!(self, Attempt::Idle)
+    }
+
+    /// Polls for a response that may be used. While the `other` request is
+    /// outstanding, responses are classified and failures are discarded.
+    fn poll_response<R, Req>(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        policy: Option<&R>,
+        other: bool,
+    ) -> Option<Result<Rsp, Error>>
+    where
+        R: Policy<Req, Rsp, Classify = C>,
+    {
+        loop {
+            match self.as_mut().project() {
+                AttemptProj::Idle => return None,
+                AttemptProj::Response(f) => {
+                    let res = match f.poll(cx) {
+                        Poll::Pending => return None,
+                        Poll::Ready(res) => res.map_err(Into::into),
+                    };
+                    match (res, policy) {
+                        (Ok(rsp), Some(policy)) if other => {
+                            self.set(Attempt::Classify(policy.classify(rsp)));
+                        }
+                        (res, _) => {
+                            self.set(Attempt::Idle);
+                            if res.is_ok() || !other {
+                                return Some(res);
+                            }
+                            trace!("Request failed; awaiting the other request");
+                            return None;
+                        }
+                    }
+                }
+                AttemptProj::Classify(f) => {
+                    let (rsp, is_success) = match f.poll(cx) {
+                        Poll::Pending => return None,
+                        Poll::Ready(classified) => classified,
+                    };
+                    self.set(Attempt::Idle);
+                    if is_success || !other {
+                        return Some(Ok(rsp));
+                    }
+                    trace!("Response failed; awaiting the other request");
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio_test::{assert_pending, assert_ready_ok, task};
+    use tower_test::mock;
+
+    const DELAY: Duration = Duration::from_millis(100);
+
+    /// Hedges every request after `DELAY` while `budget` remains.
+    #[derive(Clone, Debug, Default)]
+    struct TestPolicy {
+        budget: Arc<AtomicUsize>,
+    }
+
+    impl TestPolicy {
+        fn with_budget(n: usize) -> Self {
+            Self {
+                budget: Arc::new(AtomicUsize::new(n)),
+            }
+        }
+    }
+
+    impl Policy<&'static str, &'static str> for TestPolicy {
+        fn delay(&self, _: &&'static str) -> Option<Duration> {
+            Some(DELAY)
+        }
+
+        fn clone_request(&self, req: &&'static str) -> Option<&'static str> {
+            Some(*req)
+        }
+
+        fn can_hedge(&self) -> bool {
+            self.budget
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+        }
+
+        type Classify = future::Ready<(&'static str, bool)>;
+
+        fn classify(&self, rsp: &'static str) -> Self::Classify {
+            future::ready((rsp, rsp != "failure"))
+        }
+    }
+
+    type Mock = mock::Mock<&'static str, &'static str>;
+    type Handle = mock::Handle<&'static str, &'static str>;
+
+    async fn send(
+        policy: TestPolicy,
+    ) -> (
+        task::Spawn<ResponseFuture<TestPolicy, (), Mock, &'static str>>,
+        Handle,
+    ) {
+        let (mut svc, handle) = mock::pair();
+        svc.ready().await.unwrap();
+        let hedge = Hedge {
+            policy: Some(policy),
+            inner: (),
+        };
+        (task::spawn(hedge.proxy(&mut svc, "req")), handle)
+    }
+
+    async fn advance(d: Duration) {
+        time::advance(d + Duration::from_millis(1)).await;
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn does_not_hedge_fast_responses() {
+        time::pause();
+        let (mut rsp, mut handle) = send(TestPolicy::with_budget(1)).await;
+
+        assert_pending!(rsp.poll());
+        let (_, tx) = handle.next_request().await.unwrap();
+        tx.send_response("primary");
+        assert_eq!(assert_ready_ok!(rsp.poll()), "primary");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn uses_first_response_after_hedging() {
+        time::pause();
+        let (mut rsp, mut handle) = send(TestPolicy::with_budget(1)).await;
+
+        assert_pending!(rsp.poll());
+        let (_, _primary) = handle.next_request().await.unwrap();
+        advance(DELAY).await;
+        assert_pending!(rsp.poll());
+
+        let (req, hedge) = handle.next_request().await.unwrap();
+        assert_eq!(req, "req");
+        hedge.send_response("hedge");
+        assert_eq!(assert_ready_ok!(rsp.poll()), "hedge");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn awaits_hedge_when_primary_fails() {
+        time::pause();
+        let (mut rsp, mut handle) = send(TestPolicy::with_budget(1)).await;
+
+        assert_pending!(rsp.poll());
+        let (_, primary) = handle.next_request().await.unwrap();
+        advance(DELAY).await;
+        assert_pending!(rsp.poll());
+        let (_, hedge) = handle.next_request().await.unwrap();
+
+        primary.send_response("failure");
+        assert_pending!(rsp.poll());
+        hedge.send_response("hedge");
+        assert_eq!(assert_ready_ok!(rsp.poll()), "hedge");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn does_not_hedge_without_budget() {
+        time::pause();
+        let (mut rsp, mut handle) = send(TestPolicy::with_budget(0)).await;
+
+        assert_pending!(rsp.poll());
+        let (_, primary) = handle.next_request().await.unwrap();
+        advance(DELAY).await;
+        assert_pending!(rsp.poll());
+        assert!(
+            !matches!(handle.poll_request(), Poll::Ready(Some(_))),
+            "request must not be hedged"
+        );
+
+        primary.send_response("failure");
+        assert_eq!(assert_ready_ok!(rsp.poll()), "failure");
+    }
+}
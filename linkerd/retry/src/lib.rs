@@ -10,6 +10,11 @@ pub use tower::retry::{budget::Budget, Policy};
 use tower::util::{Oneshot, ServiceExt};
 use tracing::trace;
 
+pub mod hedge;
+mod timeout;
+
+pub use self::timeout::{PerTry, PerTryFuture, PerTryTimeout, PolicyTimeout};
+
 /// A strategy for obtaining per-target retry polices.
 pub trait NewPolicy<T> {
     type Policy;
@@ -40,13 +45,13 @@ pub struct Retry<P, S> {
 #[pin_project(project = ResponseFutureProj)]
 pub enum ResponseFuture<R, P, S, Req>
 where
-    R: tower::retry::Policy<Req, P::Response, Error> + Clone,
+    R: tower::retry::Policy<Req, P::Response, Error> + PolicyTimeout + Clone,
     P: Proxy<Req, S> + Clone,
     S: tower::Service<P::Request> + Clone,
     S::Error: Into<Error>,
 {
     Disabled(#[pin] P::Future),
-    Retry(#[pin] Oneshot<tower::retry::Retry<R, ProxyService<P, S>>, Req>),
+    Retry(#[pin] Oneshot<tower::retry::Retry<R, ProxyService<PerTry<P>, S>>, Req>),
 }
 
 // === impl NewRetryLayer ===
@@ -90,7 +95,7 @@ where
 
 impl<R, P, Req, S> Proxy<Req, S> for Retry<R, P>
 where
-    R: tower::retry::Policy<Req, P::Response, Error> + Clone,
+    R: tower::retry::Policy<Req, P::Response, Error> + PolicyTimeout + Clone,
     P: Proxy<Req, S> + Clone,
     S: tower::Service<P::Request> + Clone,
     S::Error: Into<Error>,
@@ -104,7 +109,9 @@ where
         trace!(retryable = %self.policy.is_some());
 
         if let Some(policy) = self.policy.as_ref() {
-            let inner = self.inner.clone().wrap_service(svc.clone());
+            // Each attempt is bounded by the policy's per-try timeout.
+            let inner =
+                PerTry::new(self.inner.clone(), policy.per_try_timeout()).wrap_service(svc.clone());
             let retry = tower::retry::Retry::new(policy.clone(), inner);
             return ResponseFuture::Retry(retry.oneshot(req));
         }
@@ -115,7 +122,7 @@ where
 
 impl<R, P, S, Req> Future for ResponseFuture<R, P, S, Req>
 where
-    R: tower::retry::Policy<Req, P::Response, Error> + Clone,
+    R: tower::retry::Policy<Req, P::Response, Error> + PolicyTimeout + Clone,
     P: Proxy<Req, S> + Clone,
     S: tower::Service<P::Request> + Clone,
     S::Error: Into<Error>,
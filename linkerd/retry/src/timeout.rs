@@ -0,0 +1,145 @@
+//! Bounds the time each attempt of a retried request may take.
+
+use linkerd_error::Error;
+use linkerd_stack::Proxy;
+use pin_project::pin_project;
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time;
+
+/// A retry policy that may bound the time each attempt takes.
+pub trait PolicyTimeout {
+    /// Returns how long each attempt may take before it fails with a
+    /// [`PerTryTimeout`] error.
+    fn per_try_timeout(&self) -> Option<Duration>;
+}
+
+/// An error indicating that a single attempt of a request timed out.
+///
+/// Distinct from a timeout of the request as a whole, since the request may be
+/// retried.
+#[derive(Debug)]
+pub struct PerTryTimeout(Duration);
+
+#[derive(Clone, Debug)]
+pub struct PerTry<P> {
+    inner: P,
+    timeout: Option<Duration>,
+}
+
+#[pin_project(project = PerTryFutureProj)]
+pub enum PerTryFuture<F> {
+    Passthru(#[pin] F),
+    Timeout(#[pin] time::Timeout<F>, Duration),
+}
+
+// === impl PerTry ===
+
+impl<P> PerTry<P> {
+    pub(crate) fn new(inner: P, timeout: Option<Duration>) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl<P, S, Req> Proxy<Req, S> for PerTry<P>
+where
+    P: Proxy<Req, S>,
+    S: tower::Service<P::Request>,
+{
+    type Request = P::Request;
+    type Response = P::Response;
+    type Error = Error;
+    type Future = PerTryFuture<P::Future>;
+
+    fn proxy(&self, svc: &mut S, req: Req) -> Self::Future {
+        let inner = self.inner.proxy(svc, req);
+        match self.timeout {
+            None => PerTryFuture::Passthru(inner),
+            Some(t) => PerTryFuture::Timeout(time::timeout(t, inner), t),
+        }
+    }
+}
+
+// === impl PerTryFuture ===
+
+impl<F, T, E> Future for PerTryFuture<F>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<Error>,
+{
+    type Output = Result<T, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            PerTryFutureProj::Passthru(f) => f.poll(cx).map_err(Into::into),
+            PerTryFutureProj::Timeout(f, duration) => match f.poll(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(Ok(rsp)) => Poll::Ready(rsp.map_err(Into::into)),
+                Poll::Ready(Err(_)) => Poll::Ready(Err(PerTryTimeout(*duration).into())),
+            },
+        }
+    }
+}
+
+// === impl PerTryTimeout ===
+
+impl PerTryTimeout {
+    /// Get the amount of time waited until this error was triggered.
+    pub fn duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl fmt::Display for PerTryTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request attempt timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for PerTryTimeout {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_test::{assert_pending, assert_ready, task};
+    use tower::util::ServiceExt;
+    use tower_test::mock;
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn fails_slow_attempts() {
+        time::pause();
+        let (mut svc, mut handle) = mock::pair::<(), ()>();
+        svc.ready().await.unwrap();
+        let per_try = PerTry::new((), Some(TIMEOUT));
+        let mut rsp = task::spawn(per_try.proxy(&mut svc, ()));
+
+        assert_pending!(rsp.poll());
+        let (_, _tx) = handle.next_request().await.unwrap();
+        time::advance(TIMEOUT + Duration::from_millis(1)).await;
+        let err = assert_ready!(rsp.poll()).unwrap_err();
+        assert!(err.is::<PerTryTimeout>());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn passes_through_without_timeout() {
+        time::pause();
+        let (mut svc, mut handle) = mock::pair::<(), ()>();
+        svc.ready().await.unwrap();
+        let per_try = PerTry::new((), None);
+        let mut rsp = task::spawn(per_try.proxy(&mut svc, ()));
+
+        assert_pending!(rsp.poll());
+        let (_, tx) = handle.next_request().await.unwrap();
+        time::advance(TIMEOUT * 10).await;
+        assert_pending!(rsp.poll());
+        tx.send_response(());
+        assert!(assert_ready!(rsp.poll()).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{http, LogicalAddr, Profile, Receiver, Target};
+use crate::{http, LogicalAddr, Overrides, Profile, Receiver, Target};
 use api::destination_client::DestinationClient;
 use futures::{future, prelude::*, ready, select_biased};
 use http_body::Body as HttpBody;
@@ -34,6 +34,7 @@ pub struct Client<S, R> {
     service: DestinationClient<S>,
     recover: R,
     context_token: String,
+    overrides: Overrides,
 }
 
 #[pin_project]
@@ -57,6 +58,8 @@ where
     #[pin]
     state: State<R::Backoff>,
     request: api::GetDestination,
+    addr: Addr,
+    overrides: Overrides,
 }
 
 #[pin_project(project = StateProj)]
@@ -105,8 +108,15 @@ where
             service: DestinationClient::new(service),
             recover,
             context_token,
+            overrides: Overrides::default(),
         }
     }
+
+    /// Applies locally-configured route policies to the profiles that are
+    /// discovered.
+    pub fn with_overrides(self, overrides: Overrides) -> Self {
+        Self { overrides, ..self }
+    }
 }
 
 impl<T, S, R> tower::Service<T> for Client<S, R>
@@ -140,6 +150,8 @@ where
 
         let inner = Inner {
             request,
+            addr,
+            overrides: self.overrides.clone(),
             service: self.service.clone(),
             recover: self.recover.clone(),
             state: State::Disconnected { backoff: None },
@@ -257,6 +269,11 @@ where
                     targets,
                     opaque_protocol: proto.opaque_protocol,
                     endpoint,
+                    // The destination API does not describe consistent
+                    // hashing or TLS origination, so they're only configured
+                    // by `Overrides`.
+                    consistent_hash: None,
+                    tls_server_name: None,
                 }
             })
         });
@@ -298,7 +315,10 @@ where
                 StateProj::Streaming(s) => {
                     trace!("streaming");
                     let status = match ready!(Self::poll_rx(s, cx)) {
-                        Some(Ok(profile)) => return Poll::Ready(Ok(profile)),
+                        Some(Ok(mut profile)) => {
+                            this.overrides.apply(this.addr, &mut profile);
+                            return Poll::Ready(Ok(profile));
+                        }
                         None => grpc::Status::new(grpc::Code::Ok, ""),
                         Some(Err(status)) => status,
                     };
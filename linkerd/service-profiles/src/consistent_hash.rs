@@ -0,0 +1,34 @@
+use http::header::HeaderName;
+
+/// Configures a service's load balancer to route requests to endpoints by
+/// consistently hashing a property of each request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Config {
+    pub key: Key,
+    pub table: Table,
+}
+
+/// The request property that determines a request's endpoint.
+///
+/// Requests that do not have the property are distributed randomly.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    /// The value of a request header.
+    Header(HeaderName),
+
+    /// The value of a cookie.
+    Cookie(String),
+
+    /// The client's IP address.
+    SourceIp,
+}
+
+/// The algorithm used to map request hashes to endpoints.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Table {
+    /// A hash ring, which remaps only the keys of endpoints that are removed.
+    Ring,
+
+    /// A Maglev lookup table, which spreads load more evenly than a ring.
+    Maglev,
+}
@@ -1,5 +1,7 @@
 use crate::Receiver;
 use indexmap::IndexMap;
+use linkerd_addr::Addr;
+use linkerd_exp_backoff::ExponentialBackoff;
 use regex::Regex;
 use std::{
     fmt,
@@ -18,7 +20,10 @@ pub struct Route {
     labels: Labels,
     response_classes: ResponseClasses,
     retries: Option<Retries>,
+    attempts: Attempts,
     timeout: Option<Duration>,
+    mirror: Option<Mirror>,
+    hedge: Option<Hedge>,
 }
 
 #[derive(Clone, Debug)]
@@ -28,6 +33,16 @@ pub enum RequestMatch {
     Not(Box<RequestMatch>),
     Path(Box<Regex>),
     Method(http::Method),
+    Header(http::header::HeaderName, ValueMatch),
+    QueryParam(String, ValueMatch),
+}
+
+/// Matches a single header or query parameter value.
+#[derive(Clone, Debug)]
+pub enum ValueMatch {
+    Exact(String),
+    Prefix(String),
+    Regex(Box<Regex>),
 }
 
 #[derive(Clone, Debug)]
@@ -39,6 +54,27 @@ pub struct ResponseClass {
 #[derive(Clone, Default)]
 pub struct ResponseClasses(Arc<Vec<ResponseClass>>);
 
+/// The result of matching a response's head against its response classes.
+#[derive(Debug)]
+pub enum HeadMatch<'a> {
+    Class(&'a ResponseClass),
+    None,
+    /// A response class depends on the response's trailers, so the response
+    /// can only be classified at the end of its stream.
+    Trailers(ResponseHead),
+}
+
+/// The parts of a response's head that are needed to match it against its
+/// remaining response classes once its trailers are available, so that the
+/// head's headers need not be retained.
+#[derive(Clone, Debug)]
+pub struct ResponseHead {
+    first: usize,
+    status: http::StatusCode,
+    grpc_status: Option<u32>,
+    header_matches: Vec<bool>,
+}
+
 #[derive(Clone, Debug)]
 pub enum ResponseMatch {
     All(Vec<ResponseMatch>),
@@ -48,6 +84,13 @@ pub enum ResponseMatch {
         min: http::StatusCode,
         max: http::StatusCode,
     },
+    Header(http::header::HeaderName, ValueMatch),
+    /// Matches a trailer value. Trailers are only available once the response
+    /// stream completes.
+    Trailer(http::header::HeaderName, ValueMatch),
+    /// Matches a response's `grpc-status`, which is read from the trailers or,
+    /// for trailers-only responses, from the headers.
+    GrpcStatus(Vec<u32>),
 }
 
 #[derive(Clone, Debug)]
@@ -55,6 +98,36 @@ pub struct Retries {
     budget: Arc<Budget>,
 }
 
+/// Configures each attempt of a request on a retryable route.
+///
+/// These are configured independently of whether the route is retryable and
+/// only apply once it is.
+#[derive(Clone, Debug, Default)]
+struct Attempts {
+    per_try_timeout: Option<Duration>,
+    backoff: Option<ExponentialBackoff>,
+}
+
+/// Configures a route to send a copy of a sample of its requests to another
+/// destination. Responses to mirrored requests are discarded.
+#[derive(Clone, Debug)]
+pub struct Mirror {
+    addr: Addr,
+    ratio: f32,
+}
+
+/// Configures a retryable route to send a duplicate of a request when no
+/// response has been received after a delay. The first successful response is
+/// used.
+#[derive(Clone, Debug)]
+pub enum Hedge {
+    /// Hedges requests that have been outstanding for a fixed duration.
+    After(Duration),
+    /// Hedges requests that have been outstanding for longer than the given
+    /// quantile (between 0.0 and 1.0) of the route's observed latency.
+    LatencyQuantile(f64),
+}
+
 #[derive(Clone, Default)]
 struct Labels(Arc<IndexMap<String, String>>);
 
@@ -75,7 +148,10 @@ impl Route {
             labels,
             response_classes: ResponseClasses(response_classes.into()),
             retries: None,
+            attempts: Attempts::default(),
             timeout: None,
+            mirror: None,
+            hedge: None,
         }
     }
 
@@ -99,9 +175,60 @@ impl Route {
         self.retries = Some(Retries { budget });
     }
 
+    pub fn per_try_timeout(&self) -> Option<Duration> {
+        self.attempts.per_try_timeout
+    }
+
+    /// Bounds the time each attempt of a retryable request may take,
+    /// independently of the route's overall timeout. Only applies while the
+    /// route is retryable.
+    pub fn set_per_try_timeout(&mut self, timeout: Duration) {
+        self.attempts.per_try_timeout = Some(timeout);
+    }
+
+    pub fn retry_backoff(&self) -> Option<&ExponentialBackoff> {
+        self.attempts.backoff.as_ref()
+    }
+
+    /// Waits for a jittered, exponentially increasing delay before each retry.
+    /// Only applies while the route is retryable.
+    pub fn set_retry_backoff(&mut self, backoff: ExponentialBackoff) {
+        self.attempts.backoff = Some(backoff);
+    }
+
     pub fn set_timeout(&mut self, timeout: Duration) {
         self.timeout = Some(timeout);
     }
+
+    pub fn mirror(&self) -> Option<&Mirror> {
+        self.mirror.as_ref()
+    }
+
+    /// Mirrors `ratio` (between 0.0 and 1.0) of this route's requests to
+    /// `addr`.
+    pub fn set_mirror(&mut self, addr: Addr, ratio: f32) {
+        let ratio = if ratio.is_nan() {
+            0.0
+        } else {
+            ratio.max(0.0).min(1.0)
+        };
+        self.mirror = Some(Mirror { addr, ratio });
+    }
+
+    /// Hedging only applies to routes that are also retryable, since hedged
+    /// requests are withdrawn from the route's retry budget.
+    pub fn hedge(&self) -> Option<&Hedge> {
+        self.hedge.as_ref()
+    }
+
+    pub fn set_hedge(&mut self, hedge: Hedge) {
+        let hedge = match hedge {
+            Hedge::LatencyQuantile(q) if q.is_nan() => return,
+            Hedge::LatencyQuantile(q) => Hedge::LatencyQuantile(q.max(0.0).min(1.0)),
+            hedge => hedge,
+        };
+        self.hedge = Some(hedge);
+    }
 }
 
 // === impl RequestMatch ===
@@ -111,6 +238,10 @@ impl RequestMatch {
         match self {
             RequestMatch::Method(ref method) => req.method() == *method,
             RequestMatch::Path(ref re) => re.is_match(req.uri().path()),
+            RequestMatch::Header(ref name, ref value) => header_matches(req.headers(), name, value),
+            RequestMatch::QueryParam(ref name, ref value) => query_params(req.uri())
+                .filter(|(k, _)| k == name)
+                .any(|(_, v)| value.is_match(v)),
             RequestMatch::Not(ref m) => !m.is_match(req),
             RequestMatch::All(ref ms) => ms.iter().all(|m| m.is_match(req)),
             RequestMatch::Any(ref ms) => ms.iter().any(|m| m.is_match(req)),
@@ -118,6 +249,48 @@ impl RequestMatch {
     }
 }
 
+/// Returns true if any of the values for `name` match.
+fn header_matches(
+    headers: &http::HeaderMap,
+    name: &http::header::HeaderName,
+    value: &ValueMatch,
+) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| value.is_match(v))
+}
+
+/// Iterates over the key-value pairs in a URI's query string.
+///
+/// Parameters without a value (i.e. `?flag`) are yielded with an empty value.
+/// Keys and values are not percent-decoded.
+fn query_params(uri: &http::Uri) -> impl Iterator<Item = (&str, &str)> {
+    uri.query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let mut parts = kv.splitn(2, '=');
+            let k = parts.next().unwrap_or("");
+            let v = parts.next().unwrap_or("");
+            (k, v)
+        })
+}
+
+// === impl ValueMatch ===
+
+impl ValueMatch {
+    pub fn is_match(&self, value: &str) -> bool {
+        match self {
+            ValueMatch::Exact(ref v) => value == v,
+            ValueMatch::Prefix(ref p) => value.starts_with(p.as_str()),
+            ValueMatch::Regex(ref re) => re.is_match(value),
+        }
+    }
+}
+
 // === impl ResponseClass ===
 
 impl ResponseClass {
@@ -129,13 +302,69 @@ impl ResponseClass {
         self.is_failure
     }
 
-    pub fn is_match<B>(&self, req: &http::Response<B>) -> bool {
-        self.match_.is_match(req)
+    /// Matches a response's head, without considering its trailers.
+    pub fn is_match<B>(&self, rsp: &http::Response<B>) -> bool {
+        self.match_.is_match(rsp.status(), rsp.headers())
+    }
+}
+
+// === impl ResponseHead ===
+
+impl ResponseHead {
+    pub fn status(&self) -> http::StatusCode {
+        self.status
     }
 }
 
 // === impl ResponseClasses ===
 
+impl ResponseClasses {
+    /// Matches a response's head against each response class, in order.
+    ///
+    /// Once a class that depends on the response's trailers is reached, the
+    /// remaining classes can only be matched at the end of the stream; the
+    /// parts of the head that they match on are returned so that the response
+    /// can be classified by `match_eos` once its trailers are available.
+    pub fn match_head<B>(&self, rsp: &http::Response<B>) -> HeadMatch<'_> {
+        for (first, class) in self.iter().enumerate() {
+            if class.match_.needs_trailers() {
+                let mut header_matches = Vec::new();
+                for class in &self[first..] {
+                    class
+                        .match_
+                        .match_headers(rsp.headers(), &mut header_matches);
+                }
+                return HeadMatch::Trailers(ResponseHead {
+                    first,
+                    status: rsp.status(),
+                    grpc_status: grpc_status(rsp.headers()),
+                    header_matches,
+                });
+            }
+            if class.is_match(rsp) {
+                return HeadMatch::Class(class);
+            }
+        }
+
+        HeadMatch::None
+    }
+
+    /// Matches a response at the end of its stream, given the result of
+    /// `match_head`.
+    pub fn match_eos(
+        &self,
+        head: &ResponseHead,
+        trailers: Option<&http::HeaderMap>,
+    ) -> Option<&ResponseClass> {
+        let mut header_matches = head.header_matches.iter();
+        self[head.first..].iter().find(|class| {
+            class
+                .match_
+                .is_match_eos(head, &mut header_matches, trailers)
+        })
+    }
+}
+
 impl Deref for ResponseClasses {
     type Target = [ResponseClass];
 
@@ -167,18 +396,91 @@ impl fmt::Debug for ResponseClasses {
 // === impl ResponseMatch ===
 
 impl ResponseMatch {
-    fn is_match<B>(&self, req: &http::Response<B>) -> bool {
+    fn is_match(&self, status: http::StatusCode, headers: &http::HeaderMap) -> bool {
+        match self {
+            ResponseMatch::Status { ref min, ref max } => *min <= status && status <= *max,
+            ResponseMatch::Header(ref name, ref value) => header_matches(headers, name, value),
+            ResponseMatch::Trailer(..) => false,
+            // A trailers-only gRPC response carries its status in its headers.
+            ResponseMatch::GrpcStatus(ref codes) => grpc_status(headers)
+                .map(|code| codes.contains(&code))
+                .unwrap_or(false),
+            ResponseMatch::Not(ref m) => !m.is_match(status, headers),
+            ResponseMatch::All(ref ms) => ms.iter().all(|m| m.is_match(status, headers)),
+            ResponseMatch::Any(ref ms) => ms.iter().any(|m| m.is_match(status, headers)),
+        }
+    }
+
+    /// Matches a response at the end of its stream, consuming the results of
+    /// its header matches as recorded by `match_headers`.
+    fn is_match_eos(
+        &self,
+        head: &ResponseHead,
+        matched: &mut std::slice::Iter<'_, bool>,
+        trailers: Option<&http::HeaderMap>,
+    ) -> bool {
         match self {
             ResponseMatch::Status { ref min, ref max } => {
-                *min <= req.status() && req.status() <= *max
+                *min <= head.status && head.status <= *max
+            }
+            ResponseMatch::Header(..) => matched.next().copied().unwrap_or(false),
+            ResponseMatch::Trailer(ref name, ref value) => trailers
+                .map(|t| header_matches(t, name, value))
+                .unwrap_or(false),
+            ResponseMatch::GrpcStatus(ref codes) => trailers
+                .and_then(grpc_status)
+                .or(head.grpc_status)
+                .map(|code| codes.contains(&code))
+                .unwrap_or(false),
+            ResponseMatch::Not(ref m) => !m.is_match_eos(head, matched, trailers),
+            // Every match is evaluated, without short-circuiting, so that the
+            // header matches are consumed in the order they were recorded.
+            ResponseMatch::All(ref ms) => ms.iter().fold(true, |all, m| {
+                m.is_match_eos(head, matched, trailers) && all
+            }),
+            ResponseMatch::Any(ref ms) => ms.iter().fold(false, |any, m| {
+                m.is_match_eos(head, matched, trailers) || any
+            }),
+        }
+    }
+
+    /// Records the result of each header match, in order.
+    fn match_headers(&self, headers: &http::HeaderMap, matches: &mut Vec<bool>) {
+        match self {
+            ResponseMatch::Header(ref name, ref value) => {
+                matches.push(header_matches(headers, name, value))
+            }
+            ResponseMatch::Not(ref m) => m.match_headers(headers, matches),
+            ResponseMatch::All(ref ms) | ResponseMatch::Any(ref ms) => {
+                for m in ms {
+                    m.match_headers(headers, matches);
+                }
+            }
+            ResponseMatch::Status { .. }
+            | ResponseMatch::Trailer(..)
+            | ResponseMatch::GrpcStatus(_) => {}
+        }
+    }
+
+    fn needs_trailers(&self) -> bool {
+        match self {
+            ResponseMatch::Trailer(..) | ResponseMatch::GrpcStatus(_) => true,
+            ResponseMatch::Status { .. } | ResponseMatch::Header(..) => false,
+            ResponseMatch::Not(ref m) => m.needs_trailers(),
+            ResponseMatch::All(ref ms) | ResponseMatch::Any(ref ms) => {
+                ms.iter().any(ResponseMatch::needs_trailers)
             }
-            ResponseMatch::Not(ref m) => !m.is_match(req),
-            ResponseMatch::All(ref ms) => ms.iter().all(|m| m.is_match(req)),
-            ResponseMatch::Any(ref ms) => ms.iter().any(|m| m.is_match(req)),
         }
     }
 }
 
+fn grpc_status(headers: &http::HeaderMap) -> Option<u32> {
+    headers
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|s| s.parse::<u32>().ok())
+}
+
 // === impl Retries ===
 
 impl Retries {
@@ -201,6 +503,85 @@ impl Hash for Retries {
     }
 }
 
+// === impl Attempts ===
+
+impl PartialEq for Attempts {
+    fn eq(&self, other: &Self) -> bool {
+        self.per_try_timeout == other.per_try_timeout
+            && match (self.backoff, other.backoff) {
+                (Some(a), Some(b)) => {
+                    a.min == b.min && a.max == b.max && a.jitter.to_bits() == b.jitter.to_bits()
+                }
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+impl Eq for Attempts {}
+
+impl Hash for Attempts {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.per_try_timeout.hash(state);
+        if let Some(ref backoff) = self.backoff {
+            backoff.min.hash(state);
+            backoff.max.hash(state);
+            backoff.jitter.to_bits().hash(state);
+        }
+    }
+}
+
+// === impl Mirror ===
+
+impl Mirror {
+    pub fn addr(&self) -> &Addr {
+        &self.addr
+    }
+
+    pub fn ratio(&self) -> f32 {
+        self.ratio
+    }
+}
+
+impl PartialEq for Mirror {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr == other.addr && self.ratio.to_bits() == other.ratio.to_bits()
+    }
+}
+
+impl Eq for Mirror {}
+
+impl Hash for Mirror {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.addr.hash(state);
+        self.ratio.to_bits().hash(state);
+    }
+}
+
+// === impl Hedge ===
+
+impl PartialEq for Hedge {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Hedge::After(a), Hedge::After(b)) => a == b,
+            (Hedge::LatencyQuantile(a), Hedge::LatencyQuantile(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Hedge {}
+
+impl Hash for Hedge {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Hedge::After(d) => d.hash(state),
+            Hedge::LatencyQuantile(q) => q.to_bits().hash(state),
+        }
+    }
+}
+
 // === impl Labels ===
 
 impl PartialEq for Labels {
@@ -136,3 +136,184 @@ where
         self.default.proxy(&mut self.inner, req).err_into::<Error>()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::ValueMatch;
+    use linkerd_stack::layer::Layer;
+    use regex::Regex;
+    use tower::ServiceExt;
+
+    #[derive(Clone, Debug)]
+    struct SetRoute(Route);
+
+    impl<S> Proxy<http::Request<()>, S> for SetRoute
+    where
+        S: tower::Service<http::Request<()>>,
+        S::Error: Into<Error>,
+    {
+        type Request = http::Request<()>;
+        type Response = S::Response;
+        type Error = S::Error;
+        type Future = S::Future;
+
+        fn proxy(&self, inner: &mut S, mut req: http::Request<()>) -> Self::Future {
+            req.extensions_mut().insert(self.0.clone());
+            inner.call(req)
+        }
+    }
+
+    fn route(name: &str) -> Route {
+        let labels = vec![("route".to_string(), name.to_string())];
+        Route::new(labels.into_iter(), Vec::new())
+    }
+
+    fn route_name(route: Option<Route>) -> Option<String> {
+        route.and_then(|r| r.labels().get("route").cloned())
+    }
+
+    async fn routed(routes: Vec<(RequestMatch, Route)>, req: http::Request<()>) -> Option<String> {
+        let (_tx, rx) = tokio::sync::watch::channel(Profile {
+            http_routes: routes,
+            ..Default::default()
+        });
+
+        let inner = |_: Option<Receiver>| {
+            tower::service_fn(|req: http::Request<()>| {
+                future::ok::<_, Error>(req.extensions().get::<Route>().cloned())
+            })
+        };
+        let new_route = |(route, _): (Route, Option<Receiver>)| SetRoute(route);
+        let svc = layer(new_route).layer(inner).new_service(Some(rx));
+
+        let route = svc.oneshot(req).await.unwrap();
+        route_name(route)
+    }
+
+    fn req(uri: &str, headers: &[(&str, &str)]) -> http::Request<()> {
+        let mut builder = http::Request::builder().uri(uri);
+        for (k, v) in headers {
+            builder = builder.header(*k, *v);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn routes_by_header_exact() {
+        let routes = vec![(
+            RequestMatch::Header(
+                http::header::HeaderName::from_static("x-tenant"),
+                ValueMatch::Exact("alpha".into()),
+            ),
+            route("alpha"),
+        )];
+
+        let rsp = routed(routes.clone(), req("/", &[("x-tenant", "alpha")])).await;
+        assert_eq!(rsp.as_deref(), Some("alpha"));
+
+        let rsp = routed(routes.clone(), req("/", &[("x-tenant", "alphabet")])).await;
+        assert_eq!(rsp, None, "exact matches must not match prefixes");
+
+        let rsp = routed(routes, req("/", &[])).await;
+        assert_eq!(rsp, None, "missing headers must not match");
+    }
+
+    #[tokio::test]
+    async fn routes_by_any_header_value() {
+        let routes = vec![(
+            RequestMatch::Header(
+                http::header::CONTENT_TYPE,
+                ValueMatch::Prefix("application/grpc".into()),
+            ),
+            route("grpc"),
+        )];
+
+        let rsp = routed(
+            routes,
+            req(
+                "/",
+                &[
+                    ("content-type", "text/plain"),
+                    ("content-type", "application/grpc+proto"),
+                ],
+            ),
+        )
+        .await;
+        assert_eq!(rsp.as_deref(), Some("grpc"));
+    }
+
+    #[tokio::test]
+    async fn routes_by_header_regex() {
+        let routes = vec![(
+            RequestMatch::Header(
+                http::header::HeaderName::from_static("x-tenant"),
+                ValueMatch::Regex(Box::new(Regex::new("^tenant-[0-9]+$").unwrap())),
+            ),
+            route("numbered"),
+        )];
+
+        let rsp = routed(routes.clone(), req("/", &[("x-tenant", "tenant-42")])).await;
+        assert_eq!(rsp.as_deref(), Some("numbered"));
+
+        let rsp = routed(routes, req("/", &[("x-tenant", "tenant-x")])).await;
+        assert_eq!(rsp, None);
+    }
+
+    #[tokio::test]
+    async fn routes_by_query_param() {
+        let routes = vec![
+            (
+                RequestMatch::QueryParam("debug".into(), ValueMatch::Exact("".into())),
+                route("debug"),
+            ),
+            (
+                RequestMatch::QueryParam("version".into(), ValueMatch::Prefix("v2".into())),
+                route("v2"),
+            ),
+        ];
+
+        let rsp = routed(routes.clone(), req("/foo?a=b&version=v2.1", &[])).await;
+        assert_eq!(rsp.as_deref(), Some("v2"));
+
+        let rsp = routed(routes.clone(), req("/foo?debug&version=v2", &[])).await;
+        assert_eq!(rsp.as_deref(), Some("debug"), "routes are matched in order");
+
+        let rsp = routed(routes.clone(), req("/foo?version=v1", &[])).await;
+        assert_eq!(rsp, None);
+
+        let rsp = routed(routes, req("/foo", &[])).await;
+        assert_eq!(rsp, None);
+    }
+
+    #[tokio::test]
+    async fn routes_by_combined_matches() {
+        let routes = vec![(
+            RequestMatch::All(vec![
+                RequestMatch::Method(http::Method::POST),
+                RequestMatch::Header(
+                    http::header::HeaderName::from_static("x-tenant"),
+                    ValueMatch::Exact("alpha".into()),
+                ),
+                RequestMatch::Not(Box::new(RequestMatch::QueryParam(
+                    "dry-run".into(),
+                    ValueMatch::Exact("true".into()),
+                ))),
+            ]),
+            route("alpha-writes"),
+        )];
+
+        let mut post = req("/?dry-run=false", &[("x-tenant", "alpha")]);
+        *post.method_mut() = http::Method::POST;
+        let rsp = routed(routes.clone(), post).await;
+        assert_eq!(rsp.as_deref(), Some("alpha-writes"));
+
+        let mut dry_run = req("/?dry-run=true", &[("x-tenant", "alpha")]);
+        *dry_run.method_mut() = http::Method::POST;
+        let rsp = routed(routes.clone(), dry_run).await;
+        assert_eq!(rsp, None);
+
+        let rsp = routed(routes, req("/", &[("x-tenant", "alpha")])).await;
+        assert_eq!(rsp, None);
+    }
+}
@@ -14,12 +14,14 @@ use std::{
 use tower::util::{Oneshot, ServiceExt};
 
 mod client;
+pub mod consistent_hash;
 mod default;
 pub mod discover;
 pub mod http;
+mod overrides;
 pub mod split;
 
-pub use self::client::Client;
+pub use self::{client::Client, overrides::Overrides};
 
 pub type Receiver = tokio::sync::watch::Receiver<Profile>;
 
@@ -30,6 +32,12 @@ pub struct Profile {
     pub targets: Vec<Target>,
     pub opaque_protocol: bool,
     pub endpoint: Option<(SocketAddr, Metadata)>,
+    /// When set, requests are balanced over endpoints by consistently hashing
+    /// a request key rather than by load.
+    pub consistent_hash: Option<consistent_hash::Config>,
+    /// When set, connections to the destination's endpoints that are not
+    /// meshed originate TLS, verifying the server's certificate for this name.
+    pub tls_server_name: Option<Name>,
 }
 
 /// A profile lookup target.
@@ -0,0 +1,323 @@
+//! Policies that are configured locally rather than discovered.
+//!
+//! The destination API does not describe every policy that the proxy
+//! supports, so policies may also be configured for a destination by the
+//! proxy's configuration. They are applied to each profile that is discovered
+//! for the destination.
+
+use crate::{
+    consistent_hash,
+    http::{Hedge, Route},
+    Profile,
+};
+use linkerd_addr::Addr;
+use linkerd_dns_name::Name;
+use linkerd_exp_backoff::ExponentialBackoff;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// The policies configured for each destination.
+///
+/// Destinations are identified by their logical address (e.g.
+/// `web.ns.svc.cluster.local:8080`) or by the IP address and port with which
+/// their profile is looked up.
+#[derive(Clone, Debug, Default)]
+pub struct Overrides(Arc<HashMap<Addr, Destination>>);
+
+#[derive(Clone, Debug, Default)]
+struct Destination {
+    consistent_hash: Option<consistent_hash::Config>,
+    tls_server_name: Option<Name>,
+
+    /// Applies to each of the destination's routes.
+    all_routes: RouteOverrides,
+
+    /// Applies to the routes whose `route` label has the given value, taking
+    /// precedence over the policies for all routes.
+    routes: HashMap<String, RouteOverrides>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct RouteOverrides {
+    mirror: Option<(Addr, f32)>,
+    hedge: Option<Hedge>,
+    per_try_timeout: Option<Duration>,
+    retry_backoff: Option<ExponentialBackoff>,
+}
+
+// === impl Overrides ===
+
+impl Overrides {
+    /// Balances the destination's requests by consistently hashing a request
+    /// key.
+    pub fn set_consistent_hash(&mut self, dst: Addr, config: consistent_hash::Config) {
+        Arc::make_mut(&mut self.0)
+            .entry(dst)
+            .or_default()
+            .consistent_hash = Some(config);
+    }
+
+    /// Originates TLS to the destination's endpoints that are not meshed,
+    /// verifying each server's certificate for `name`.
+    pub fn set_tls_server_name(&mut self, dst: Addr, name: Name) {
+        Arc::make_mut(&mut self.0)
+            .entry(dst)
+            .or_default()
+            .tls_server_name = Some(name);
+    }
+
+    /// Mirrors `ratio` of the requests on the destination's routes (or on the
+    /// named route) to `addr`.
+    pub fn set_mirror(&mut self, dst: Addr, route: Option<String>, addr: Addr, ratio: f32) {
+        self.route_mut(dst, route).mirror = Some((addr, ratio));
+    }
+
+    /// Hedges requests on the destination's routes (or on the named route).
+    ///
+    /// Hedges are not configured when the destination is balanced by
+    /// consistent hashing, since a hedge would be sent to the same endpoint as
+    /// the original request.
+    pub fn set_hedge(&mut self, dst: Addr, route: Option<String>, hedge: Hedge) {
+        self.route_mut(dst, route).hedge = Some(hedge);
+    }
+
+    /// Bounds each attempt of a retryable request on the destination's routes
+    /// (or on the named route).
+    pub fn set_per_try_timeout(&mut self, dst: Addr, route: Option<String>, timeout: Duration) {
+        self.route_mut(dst, route).per_try_timeout = Some(timeout);
+    }
+
+    /// Backs off before retrying requests on the destination's routes (or on
+    /// the named route).
+    pub fn set_retry_backoff(
+        &mut self,
+        dst: Addr,
+        route: Option<String>,
+        backoff: ExponentialBackoff,
+    ) {
+        self.route_mut(dst, route).retry_backoff = Some(backoff);
+    }
+
+    fn route_mut(&mut self, dst: Addr, route: Option<String>) -> &mut RouteOverrides {
+        let dst = Arc::make_mut(&mut self.0).entry(dst).or_default();
+        match route {
+            Some(route) => dst.routes.entry(route).or_default(),
+            None => &mut dst.all_routes,
+        }
+    }
+
+    /// Applies the policies configured for the destination that was looked up
+    /// as `addr` to its profile.
+    pub(crate) fn apply(&self, addr: &Addr, profile: &mut Profile) {
+        let dst = match self.get(addr, profile.name.as_ref()) {
+            Some(dst) => dst,
+            None => return,
+        };
+
+        if let Some(ref config) = dst.consistent_hash {
+            profile.consistent_hash = Some(config.clone());
+        }
+        if let Some(ref name) = dst.tls_server_name {
+            profile.tls_server_name = Some(name.clone());
+        }
+
+        let is_hashed = profile.consistent_hash.is_some();
+        for (_, route) in profile.http_routes.iter_mut() {
+            dst.all_routes.apply(route, is_hashed);
+            let name = route.labels().get("route").cloned();
+            if let Some(overrides) = name.and_then(|n| dst.routes.get(&n)) {
+                overrides.apply(route, is_hashed);
+            }
+        }
+    }
+
+    /// Finds the destination by the address with which its profile was looked
+    /// up or, failing that, by the profile's name.
+    fn get(&self, addr: &Addr, name: Option<&Name>) -> Option<&Destination> {
+        if let Some(dst) = self.0.get(addr) {
+            return Some(dst);
+        }
+        let logical = Addr::from((name?.clone(), addr.port()));
+        self.0.get(&logical)
+    }
+}
+
+// === impl RouteOverrides ===
+
+impl RouteOverrides {
+    fn apply(&self, route: &mut Route, is_hashed: bool) {
+        if let Some((ref addr, ratio)) = self.mirror {
+            route.set_mirror(addr.clone(), ratio);
+        }
+        if let Some(ref hedge) = self.hedge {
+            if !is_hashed {
+                route.set_hedge(hedge.clone());
+            }
+        }
+        if let Some(timeout) = self.per_try_timeout {
+            route.set_per_try_timeout(timeout);
+        }
+        if let Some(backoff) = self.retry_backoff {
+            route.set_retry_backoff(backoff);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::RequestMatch;
+    use std::str::FromStr;
+    use tower::retry::budget::Budget;
+
+    fn route(name: &str) -> (RequestMatch, Route) {
+        let labels = Some(("route".to_string(), name.to_string()));
+        (
+            RequestMatch::Method(::http::Method::GET),
+            Route::new(labels.into_iter(), Vec::new()),
+        )
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            name: Some(Name::from_str("web.ns.svc.cluster.local").unwrap()),
+            http_routes: vec![route("GET /books"), route("GET /authors")],
+            ..Profile::default()
+        }
+    }
+
+    #[test]
+    fn applies_to_named_routes() {
+        let dst = Addr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let all = Addr::from_str("web-v2.ns.svc.cluster.local:8080").unwrap();
+        let books = Addr::from_str("books.ns.svc.cluster.local:8080").unwrap();
+
+        let mut overrides = Overrides::default();
+        overrides.set_mirror(dst.clone(), None, all.clone(), 0.5);
+        overrides.set_mirror(dst, Some("GET /books".into()), books.clone(), 1.0);
+
+        // The profile is looked up by IP, so the destination is found by the
+        // profile's name.
+        let mut profile = profile();
+        let ip = Addr::from_str("10.1.2.3:8080").unwrap();
+        overrides.apply(&ip, &mut profile);
+
+        let mirror = profile.http_routes[0]
+            .1
+            .mirror()
+            .expect("route is mirrored");
+        assert_eq!(mirror.addr(), &books);
+        assert_eq!(mirror.ratio(), 1.0);
+        let mirror = profile.http_routes[1]
+            .1
+            .mirror()
+            .expect("route is mirrored");
+        assert_eq!(mirror.addr(), &all);
+        assert_eq!(mirror.ratio(), 0.5);
+    }
+
+    #[test]
+    fn sets_consistent_hash() {
+        let config = consistent_hash::Config {
+            key: consistent_hash::Key::SourceIp,
+            table: consistent_hash::Table::Maglev,
+        };
+        let mut overrides = Overrides::default();
+        overrides.set_consistent_hash(
+            Addr::from_str("web.ns.svc.cluster.local:8080").unwrap(),
+            config.clone(),
+        );
+
+        let mut profile = profile();
+        let ip = Addr::from_str("10.1.2.3:8080").unwrap();
+        overrides.apply(&ip, &mut profile);
+        assert_eq!(profile.consistent_hash, Some(config));
+    }
+
+    #[test]
+    fn sets_tls_server_name() {
+        let name = Name::from_str("api.example.com").unwrap();
+        let mut overrides = Overrides::default();
+        overrides.set_tls_server_name(
+            Addr::from_str("web.ns.svc.cluster.local:8080").unwrap(),
+            name.clone(),
+        );
+
+        let mut profile = profile();
+        let ip = Addr::from_str("10.1.2.3:8080").unwrap();
+        overrides.apply(&ip, &mut profile);
+        assert_eq!(profile.tls_server_name, Some(name));
+
+        let mut other = Profile::default();
+        overrides.apply(&ip, &mut other);
+        assert_eq!(other.tls_server_name, None);
+    }
+
+    #[test]
+    fn does_not_hedge_hashed_destinations() {
+        let dst = Addr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let ip = Addr::from_str("10.1.2.3:8080").unwrap();
+        let hedge = Hedge::After(std::time::Duration::from_millis(50));
+
+        let mut overrides = Overrides::default();
+        overrides.set_hedge(dst.clone(), Some("GET /books".into()), hedge.clone());
+        let mut hedged = profile();
+        overrides.apply(&ip, &mut hedged);
+        assert_eq!(hedged.http_routes[0].1.hedge(), Some(&hedge));
+        assert_eq!(hedged.http_routes[1].1.hedge(), None);
+
+        overrides.set_consistent_hash(
+            dst,
+            consistent_hash::Config {
+                key: consistent_hash::Key::SourceIp,
+                table: consistent_hash::Table::Ring,
+            },
+        );
+        let mut hashed = profile();
+        overrides.apply(&ip, &mut hashed);
+        assert_eq!(hashed.http_routes[0].1.hedge(), None);
+    }
+
+    #[test]
+    fn configures_attempts_of_routes_that_are_not_yet_retryable() {
+        let dst = Addr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        let timeout = Duration::from_millis(100);
+        let backoff = ExponentialBackoff::new(timeout, timeout * 10, 0.5).unwrap();
+
+        let mut overrides = Overrides::default();
+        overrides.set_per_try_timeout(dst.clone(), None, timeout);
+        overrides.set_retry_backoff(dst, Some("GET /books".into()), backoff);
+
+        let mut profile = profile();
+        let ip = Addr::from_str("10.1.2.3:8080").unwrap();
+        overrides.apply(&ip, &mut profile);
+        let (_, books) = &mut profile.http_routes[0];
+        assert!(books.retries().is_none());
+        assert_eq!(books.per_try_timeout(), Some(timeout));
+        assert_eq!(books.retry_backoff().map(|b| b.min), Some(timeout));
+
+        // Attempts are configured independently of the retry budget.
+        let budget = Budget::new(Duration::from_secs(10), 1, 0.1);
+        books.set_retries(Arc::new(budget));
+        assert_eq!(books.per_try_timeout(), Some(timeout));
+        assert!(profile.http_routes[1].1.retry_backoff().is_none());
+    }
+
+    #[test]
+    fn ignores_other_destinations() {
+        let mut overrides = Overrides::default();
+        overrides.set_mirror(
+            Addr::from_str("web.ns.svc.cluster.local:80").unwrap(),
+            None,
+            Addr::from_str("web-v2.ns.svc.cluster.local:80").unwrap(),
+            1.0,
+        );
+
+        let mut profile = profile();
+        let ip = Addr::from_str("10.1.2.3:8080").unwrap();
+        overrides.apply(&ip, &mut profile);
+        assert!(profile
+            .http_routes
+            .iter()
+            .all(|(_, r)| r.mirror().is_none()));
+    }
+}
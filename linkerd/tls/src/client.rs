@@ -1,3 +1,4 @@
+use crate::{HandshakeIo, SharedDenylist};
 use futures::{
     future::{Either, MapOk},
     prelude::*,
@@ -5,6 +6,7 @@ use futures::{
 use linkerd_conditional::Conditional;
 use linkerd_identity as id;
 use linkerd_io as io;
+use linkerd_proxy_transport::metrics::Handshakes;
 use linkerd_stack::{layer, Param};
 use rustls::Session;
 use std::{
@@ -14,6 +16,7 @@ use std::{
     str::FromStr,
     sync::Arc,
     task::{Context, Poll},
+    time::Instant,
 };
 pub use tokio_rustls::client::TlsStream;
 use tracing::{debug, trace};
@@ -62,11 +65,10 @@ pub struct Client<L, C> {
     inner: C,
 }
 
-type Connect<F, I> = MapOk<F, fn(I) -> io::EitherIo<I, TlsStream<I>>>;
-type Handshake<I> =
-    Pin<Box<dyn Future<Output = io::Result<io::EitherIo<I, TlsStream<I>>>> + Send + 'static>>;
+type Connect<F, I> = MapOk<F, fn(I) -> Io<I>>;
+type Handshake<I> = Pin<Box<dyn Future<Output = io::Result<Io<I>>> + Send + 'static>>;
 
-pub type Io<I> = io::EitherIo<I, TlsStream<I>>;
+pub type Io<I> = io::EitherIo<I, TlsStream<HandshakeIo<I>>>;
 
 // === impl ClientTls ===
 
@@ -92,7 +94,7 @@ impl<L: Clone, C> Client<L, C> {
 
 impl<L, C, T> tower::Service<T> for Client<L, C>
 where
-    L: Clone + Param<Config>,
+    L: Clone + Param<Config> + Param<SharedDenylist> + Param<Handshakes>,
     T: Param<ConditionalClientTls>,
     C: tower::Service<T, Error = io::Error>,
     C::Response: io::AsyncRead + io::AsyncWrite + Send + Unpin,
@@ -116,7 +118,7 @@ where
             }
         };
 
-        let handshake = match self.local.as_ref() {
+        let (handshake, handshakes) = match self.local.as_ref() {
             Some(local) => {
                 // Build a rustls ClientConfig for this connection.
                 //
@@ -125,16 +127,27 @@ where
                 // ALPN options, clone the Arc'd base configuration without
                 // extra allocation.
                 //
+                // Likewise, servers identified by a SPIFFE ID are verified
+                // against that ID rather than their TLS server name (and
+                // are still checked against the local denylist).
+                //
                 // TODO it would be better to avoid cloning the whole TLS config
                 // per-connection.
-                match alpn {
-                    None => tokio_rustls::TlsConnector::from(local.param()),
-                    Some(AlpnProtocols(protocols)) => {
-                        let mut config: rustls::ClientConfig = local.param().as_ref().clone();
-                        config.alpn_protocols = protocols;
+                let connector = match (alpn, server_id.0.spiffe_id()) {
+                    (None, None) => tokio_rustls::TlsConnector::from(Param::<Config>::param(local)),
+                    (alpn, spiffe_id) => {
+                        let mut config: rustls::ClientConfig =
+                            Param::<Config>::param(local).as_ref().clone();
+                        if let Some(AlpnProtocols(protocols)) = alpn {
+                            config.alpn_protocols = protocols;
+                        }
+                        if let Some(id) = spiffe_id {
+                            id.verify_servers(&mut config, &Param::<SharedDenylist>::param(local));
+                        }
                         tokio_rustls::TlsConnector::from(Arc::new(config))
                     }
-                }
+                };
+                (connector, Param::<Handshakes>::param(local))
             }
             None => {
                 trace!("Local identity disabled");
@@ -146,7 +159,16 @@ where
         let connect = self.inner.call(target);
         Either::Right(Box::pin(async move {
             let io = connect.await?;
-            let io = handshake.connect((&server_id.0).into(), io).await?;
+            let start = Instant::now();
+            let io = handshake
+                .connect((&server_id.0).into(), HandshakeIo::new(io))
+                .await?;
+            let version = io.get_ref().1.get_protocol_version();
+            let kind = io.get_ref().0.handshake_kind(version);
+            if let Some(kind) = kind {
+                handshakes.record_connect(kind, start.elapsed());
+            }
+            debug!(?version, handshake = ?kind, "Established TLS connection");
             if let Some(alpn) = io.get_ref().1.get_alpn_protocol() {
                 debug!(alpn = ?std::str::from_utf8(alpn));
             }
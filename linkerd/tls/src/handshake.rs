@@ -0,0 +1,276 @@
+use linkerd_io as io;
+use linkerd_proxy_transport::metrics::HandshakeKind;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+const CHANGE_CIPHER_SPEC: u8 = 20;
+const HANDSHAKE: u8 = 22;
+const CERTIFICATE: u8 = 11;
+
+const RECORD_HEADER_LEN: usize = 5;
+const MESSAGE_HEADER_LEN: usize = 4;
+
+/// Wraps a transport to determine whether a TLS 1.2 handshake resumed a
+/// session.
+///
+/// Rustls doesn't expose whether a TLS 1.2 session was resumed, so the
+/// plaintext records read from the peer are inspected until it changes cipher
+/// specs: a peer only sends a `Certificate` message in a full handshake (our
+/// servers always request client certificates).
+#[derive(Debug)]
+pub struct HandshakeIo<I> {
+    io: I,
+    records: Records,
+}
+
+/// Tracks the TLS record and handshake message boundaries read from the peer.
+#[derive(Debug, Default)]
+struct Records {
+    kind: Option<HandshakeKind>,
+
+    header: [u8; RECORD_HEADER_LEN],
+    header_len: usize,
+    content_type: u8,
+    remaining: usize,
+
+    msg_header: [u8; MESSAGE_HEADER_LEN],
+    msg_header_len: usize,
+    msg_remaining: usize,
+}
+
+// === impl HandshakeIo ===
+
+impl<I> HandshakeIo<I> {
+    pub(crate) fn new(io: I) -> Self {
+        Self {
+            io,
+            records: Records::default(),
+        }
+    }
+
+    /// Returns the kind of handshake read from the peer, if the session
+    /// negotiated TLS 1.2.
+    ///
+    /// TLS 1.3 peers send a `ChangeCipherSpec` record for middlebox
+    /// compatibility and encrypt their certificates, so the records read from
+    /// the peer don't indicate whether a TLS 1.3 session was resumed.
+    pub(crate) fn handshake_kind(
+        &self,
+        version: Option<rustls::ProtocolVersion>,
+    ) -> Option<HandshakeKind> {
+        if version != Some(rustls::ProtocolVersion::TLSv1_2) {
+            return None;
+        }
+        Some(self.records.kind.unwrap_or(HandshakeKind::Full))
+    }
+
+    pub fn get_ref(&self) -> &I {
+        &self.io
+    }
+}
+
+impl<I: io::PeerAddr> io::PeerAddr for HandshakeIo<I> {
+    #[inline]
+    fn peer_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.io.peer_addr()
+    }
+}
+
+impl<I: io::AsyncRead + Unpin> io::AsyncRead for HandshakeIo<I> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> io::Poll<()> {
+        let this = self.get_mut();
+        if this.records.kind.is_some() {
+            return Pin::new(&mut this.io).poll_read(cx, buf);
+        }
+
+        let filled = buf.filled().len();
+        futures::ready!(Pin::new(&mut this.io).poll_read(cx, buf))?;
+        this.records.read(&buf.filled()[filled..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<I: io::AsyncWrite + Unpin> io::AsyncWrite for HandshakeIo<I> {
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> io::Poll<()> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> io::Poll<()> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> io::Poll<usize> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> io::Poll<usize> {
+        Pin::new(&mut self.get_mut().io).poll_write_vectored(cx, bufs)
+    }
+
+    #[inline]
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+}
+
+// === impl Records ===
+
+impl Records {
+    fn read(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() && self.kind.is_none() {
+            if self.remaining == 0 {
+                let n = (RECORD_HEADER_LEN - self.header_len).min(bytes.len());
+                self.header[self.header_len..self.header_len + n].copy_from_slice(&bytes[..n]);
+                self.header_len += n;
+                bytes = &bytes[n..];
+                if self.header_len < RECORD_HEADER_LEN {
+                    return;
+                }
+
+                self.header_len = 0;
+                self.content_type = self.header[0];
+                self.remaining = u16::from_be_bytes([self.header[3], self.header[4]]) as usize;
+                if self.content_type == CHANGE_CIPHER_SPEC {
+                    self.kind = Some(HandshakeKind::Resumed);
+                }
+            } else {
+                let n = self.remaining.min(bytes.len());
+                if self.content_type == HANDSHAKE {
+                    self.read_messages(&bytes[..n]);
+                }
+                self.remaining -= n;
+                bytes = &bytes[n..];
+            }
+        }
+    }
+
+    fn read_messages(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.msg_remaining == 0 {
+                let n = (MESSAGE_HEADER_LEN - self.msg_header_len).min(bytes.len());
+                self.msg_header[self.msg_header_len..self.msg_header_len + n]
+                    .copy_from_slice(&bytes[..n]);
+                self.msg_header_len += n;
+                bytes = &bytes[n..];
+                if self.msg_header_len < MESSAGE_HEADER_LEN {
+                    return;
+                }
+
+                self.msg_header_len = 0;
+                if self.msg_header[0] == CERTIFICATE {
+                    self.kind = Some(HandshakeKind::Full);
+                    return;
+                }
+                let [_, a, b, c] = self.msg_header;
+                self.msg_remaining = u32::from_be_bytes([0, a, b, c]) as usize;
+            } else {
+                let n = self.msg_remaining.min(bytes.len());
+                self.msg_remaining -= n;
+                bytes = &bytes[n..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(content_type: u8, payload: &[u8]) -> Vec<u8> {
+        let len = (payload.len() as u16).to_be_bytes();
+        let mut record = vec![content_type, 3, 3, len[0], len[1]];
+        record.extend_from_slice(payload);
+        record
+    }
+
+    fn message(msg_type: u8, body: &[u8]) -> Vec<u8> {
+        let len = (body.len() as u32).to_be_bytes();
+        let mut msg = vec![msg_type, len[1], len[2], len[3]];
+        msg.extend_from_slice(body);
+        msg
+    }
+
+    fn read_bytewise(bytes: &[u8]) -> Option<HandshakeKind> {
+        let mut records = Records::default();
+        for b in bytes {
+            records.read(std::slice::from_ref(b));
+        }
+        records.kind
+    }
+
+    #[test]
+    fn full_handshake() {
+        // A ServerHello and Certificate may be coalesced into a single record.
+        let mut msgs = message(2, &[0xaa; 70]);
+        msgs.extend(message(CERTIFICATE, &[0xbb; 800]));
+        let mut bytes = record(HANDSHAKE, &msgs);
+        bytes.extend(record(CHANGE_CIPHER_SPEC, &[1]));
+
+        let mut records = Records::default();
+        records.read(&bytes);
+        assert_eq!(records.kind, Some(HandshakeKind::Full));
+        assert_eq!(read_bytewise(&bytes), Some(HandshakeKind::Full));
+    }
+
+    #[test]
+    fn full_handshake_split_across_records() {
+        // A Certificate message may span several records, and a message body
+        // may contain the Certificate message type.
+        let msgs = [message(2, &[CERTIFICATE; 70]), message(CERTIFICATE, &[])].concat();
+        let (a, b) = msgs.split_at(72);
+        let bytes = [record(HANDSHAKE, a), record(HANDSHAKE, b)].concat();
+        assert_eq!(read_bytewise(&bytes), Some(HandshakeKind::Full));
+    }
+
+    #[test]
+    fn resumed_handshake() {
+        // The ServerHello may contain the Certificate message type.
+        let mut bytes = record(HANDSHAKE, &message(2, &[CERTIFICATE; 70]));
+        bytes.extend(record(HANDSHAKE, &message(4, &[0xcc; 100])));
+        bytes.extend(record(CHANGE_CIPHER_SPEC, &[1]));
+        bytes.extend(record(HANDSHAKE, &[CERTIFICATE; 40]));
+
+        let mut records = Records::default();
+        records.read(&bytes);
+        assert_eq!(records.kind, Some(HandshakeKind::Resumed));
+        assert_eq!(read_bytewise(&bytes), Some(HandshakeKind::Resumed));
+    }
+
+    #[test]
+    fn tls13_handshake() {
+        let mut bytes = record(HANDSHAKE, &message(2, &[0xaa; 70]));
+        bytes.extend(record(CHANGE_CIPHER_SPEC, &[1]));
+
+        let mut io = HandshakeIo::new(());
+        io.records.read(&bytes);
+        assert_eq!(
+            io.handshake_kind(Some(rustls::ProtocolVersion::TLSv1_2)),
+            Some(HandshakeKind::Resumed)
+        );
+        assert_eq!(
+            io.handshake_kind(Some(rustls::ProtocolVersion::TLSv1_3)),
+            None
+        );
+    }
+
+    #[test]
+    fn incomplete_handshake() {
+        let bytes = record(HANDSHAKE, &message(2, &[0xaa; 70]));
+        assert_eq!(read_bytewise(&bytes[..40]), None);
+        assert_eq!(read_bytewise(&bytes), None);
+    }
+}
@@ -1,14 +1,16 @@
 #![deny(warnings, rust_2018_idioms)]
 
-pub use linkerd_identity::LocalId;
+pub use linkerd_identity::{LocalId, SharedDenylist};
 use linkerd_io as io;
 pub use rustls::Session;
 
 pub mod client;
+mod handshake;
 pub mod server;
 
 pub use self::{
     client::{Client, ClientTls, ConditionalClientTls, NoClientTls, ServerId},
+    handshake::HandshakeIo,
     server::{ClientId, ConditionalServerTls, NewDetectTls, NoServerTls, ServerTls},
 };
 
@@ -1,6 +1,6 @@
 mod client_hello;
 
-use crate::{LocalId, NegotiatedProtocol, ServerId};
+use crate::{HandshakeIo, LocalId, NegotiatedProtocol, ServerId};
 use bytes::BytesMut;
 use futures::prelude::*;
 use linkerd_conditional::Conditional;
@@ -8,6 +8,7 @@ use linkerd_dns_name as dns;
 use linkerd_error::Error;
 use linkerd_identity as id;
 use linkerd_io::{self as io, AsyncReadExt, EitherIo, PrefixedIo};
+use linkerd_proxy_transport::metrics::Handshakes;
 use linkerd_stack::{layer, NewService, Param};
 use rustls::Session;
 use std::{
@@ -16,7 +17,7 @@ use std::{
     str::FromStr,
     sync::Arc,
     task::{Context, Poll},
-    time::Duration,
+    time::{Duration, Instant},
 };
 pub use tokio_rustls::server::TlsStream;
 use tower::util::ServiceExt;
@@ -68,7 +69,7 @@ pub type ConditionalServerTls = Conditional<ServerTls, NoServerTls>;
 
 pub type Meta<T> = (ConditionalServerTls, T);
 
-pub type Io<T> = EitherIo<PrefixedIo<T>, TlsStream<PrefixedIo<T>>>;
+pub type Io<T> = EitherIo<PrefixedIo<T>, TlsStream<HandshakeIo<PrefixedIo<T>>>>;
 
 pub type Connection<T, I> = (Meta<T>, Io<I>);
 
@@ -120,7 +121,7 @@ impl<I, N> NewDetectTls<I, N> {
 
 impl<T, L, N> NewService<T> for NewDetectTls<L, N>
 where
-    L: Clone + Param<LocalId> + Param<Config>,
+    L: Clone + Param<LocalId> + Param<Config> + Param<Handshakes>,
     N: NewService<Meta<T>> + Clone,
 {
     type Service = DetectTls<T, L, N>;
@@ -138,7 +139,7 @@ where
 impl<I, L, N, NSvc, T> tower::Service<I> for DetectTls<T, L, N>
 where
     I: io::Peek + io::AsyncRead + io::AsyncWrite + Send + Sync + Unpin + 'static,
-    L: Param<LocalId> + Param<Config>,
+    L: Param<LocalId> + Param<Config> + Param<Handshakes>,
     N: NewService<Meta<T>, Service = NSvc> + Clone + Send + 'static,
     NSvc: tower::Service<Io<I>, Response = ()> + Send + 'static,
     NSvc::Error: Into<Error>,
@@ -161,11 +162,12 @@ where
             Some(local) => {
                 let config = Param::<Config>::param(local);
                 let local_id = Param::<LocalId>::param(local);
+                let handshakes = Param::<Handshakes>::param(local);
                 let timeout = tokio::time::sleep(self.timeout);
 
                 Box::pin(async move {
                     let (peer, io) = tokio::select! {
-                        res = detect(io, config, local_id) => { res? }
+                        res = detect(io, config, local_id, handshakes) => { res? }
                         () = timeout => {
                             return Err(DetectTimeout(()).into());
                         }
@@ -191,6 +193,7 @@ async fn detect<I>(
     mut io: I,
     tls_config: Config,
     LocalId(local_id): LocalId,
+    handshakes: Handshakes,
 ) -> io::Result<(ConditionalServerTls, Io<I>)>
 where
     I: io::Peek + io::AsyncRead + io::AsyncWrite + Send + Sync + Unpin,
@@ -206,11 +209,13 @@ where
     let mut buf = [0u8; PEEK_CAPACITY];
     let sz = io.peek(&mut buf).await?;
     debug!(sz, "Peeked bytes from TCP stream");
+    // Clients connect to a SPIFFE ID with its encoded server name as the SNI,
+    // so a local SPIFFE ID is matched by its server name.
     match client_hello::parse_sni(&buf) {
-        Ok(Some(ServerId(sni))) if sni == local_id => {
+        Ok(Some(ServerId(sni))) if sni.server_name() == local_id.server_name() => {
             trace!(%sni, "Identified matching SNI via peek");
             // Terminate the TLS stream.
-            let (tls, io) = handshake(tls_config, PrefixedIo::from(io)).await?;
+            let (tls, io) = handshake(tls_config, &handshakes, PrefixedIo::from(io)).await?;
             return Ok((Conditional::Some(tls), EitherIo::Right(io)));
         }
 
@@ -236,11 +241,11 @@ where
     while io.read_buf(&mut buf).await? != 0 {
         debug!(buf.len = %buf.len(), "Read bytes from TCP stream");
         match client_hello::parse_sni(buf.as_ref()) {
-            Ok(Some(ServerId(sni))) if sni == local_id => {
+            Ok(Some(ServerId(sni))) if sni.server_name() == local_id.server_name() => {
                 trace!(%sni, "Identified matching SNI via buffered read");
                 // Terminate the TLS stream.
-                let (tls, io) =
-                    handshake(tls_config.clone(), PrefixedIo::new(buf.freeze(), io)).await?;
+                let io = PrefixedIo::new(buf.freeze(), io);
+                let (tls, io) = handshake(tls_config.clone(), &handshakes, io).await?;
                 return Ok((Conditional::Some(tls), EitherIo::Right(io)));
             }
 
@@ -273,13 +278,23 @@ where
     Ok((NO_TLS_META, io))
 }
 
-async fn handshake<T>(tls_config: Config, io: T) -> io::Result<(ServerTls, TlsStream<T>)>
+async fn handshake<T>(
+    tls_config: Config,
+    handshakes: &Handshakes,
+    io: T,
+) -> io::Result<(ServerTls, TlsStream<HandshakeIo<T>>)>
 where
     T: io::AsyncRead + io::AsyncWrite + Unpin,
 {
+    let start = Instant::now();
     let io = tokio_rustls::TlsAcceptor::from(tls_config)
-        .accept(io)
+        .accept(HandshakeIo::new(io))
         .await?;
+    let version = io.get_ref().1.get_protocol_version();
+    let kind = io.get_ref().0.handshake_kind(version);
+    if let Some(kind) = kind {
+        handshakes.record_accept(kind, start.elapsed());
+    }
 
     // Determine the peer's identity, if it exist.
     let client_id = client_identity(&io);
@@ -290,7 +305,12 @@ where
         .get_alpn_protocol()
         .map(|b| NegotiatedProtocol(b.into()));
 
-    debug!(client.id = ?client_id, alpn = ?negotiated_protocol, "Accepted TLS connection");
+    debug!(
+        client.id = ?client_id,
+        alpn = ?negotiated_protocol,
+        handshake = ?kind,
+        "Accepted TLS connection"
+    );
     let tls = ServerTls::Established {
         client_id,
         negotiated_protocol,
@@ -307,14 +327,16 @@ fn client_identity<S>(tls: &TlsStream<S>) -> Option<ClientId> {
     let end_cert = webpki::EndEntityCert::from(c).ok()?;
     let dns_names = end_cert.dns_names().ok()?;
 
-    match dns_names.first()? {
-        GeneralDNSNameRef::DNSName(n) => {
+    match dns_names.first() {
+        Some(GeneralDNSNameRef::DNSName(n)) => {
             Some(ClientId(id::Name::from(dns::Name::from(n.to_owned()))))
         }
-        GeneralDNSNameRef::Wildcard(_) => {
+        Some(GeneralDNSNameRef::Wildcard(_)) => {
             // Wildcards can perhaps be handled in a future path...
             None
         }
+        // Certificates without a DNS name may be identified by a SPIFFE ID.
+        None => id::SpiffeId::from_crt(c).map(|id| ClientId(id.into())),
     }
 }
 
@@ -10,7 +10,10 @@ use linkerd_conditional::Conditional;
 use linkerd_error::Never;
 use linkerd_identity as id;
 use linkerd_io::{self as io, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use linkerd_proxy_transport::{listen::Addrs, BindTcp, ConnectAddr, ConnectTcp};
+use linkerd_metrics::FmtMetrics;
+use linkerd_proxy_transport::{
+    listen::Addrs, metrics::Handshakes, BindTcp, ConnectAddr, ConnectTcp,
+};
 use linkerd_stack::{NewService, Param};
 use linkerd_tls as tls;
 use std::future::Future;
@@ -73,6 +76,219 @@ fn proxy_to_proxy_tls_works() {
     assert_eq!(&server_result.result.expect("ping")[..], PING);
 }
 
+#[test]
+fn proxy_to_proxy_tls_works_with_spiffe_ids() {
+    let server_tls = id::test_util::BAZ_NS1_SVID.validate().unwrap();
+    let client_tls = id::test_util::BAZ_NS1_SVID.validate().unwrap();
+    let server_id = tls::ServerId(server_tls.name().clone());
+    let (client_result, server_result) = run_test(
+        Conditional::Some((client_tls.clone(), server_id.clone())),
+        |conn| write_then_read(conn, PING),
+        Some(server_tls),
+        |(_, conn)| read_then_write(conn, PING.len(), PONG),
+    );
+    assert_eq!(
+        client_result.tls,
+        Some(Conditional::Some(tls::ClientTls {
+            server_id,
+            alpn: None,
+        }))
+    );
+    assert_eq!(&client_result.result.expect("pong")[..], PONG);
+    assert_eq!(
+        server_result.tls,
+        Some(Conditional::Some(tls::ServerTls::Established {
+            client_id: Some(tls::ClientId(client_tls.name().clone())),
+            negotiated_protocol: None,
+        }))
+    );
+    assert_eq!(&server_result.result.expect("ping")[..], PING);
+}
+
+#[test]
+fn proxy_to_proxy_tls_fails_when_spiffe_id_does_not_match() {
+    let server_tls = id::test_util::BAZ_NS1_SVID.validate().unwrap();
+    let client_tls = id::test_util::BAZ_NS1_SVID.validate().unwrap();
+    let server_id = tls::ServerId("spiffe://cluster.local/ns/ns1/sa/foo".parse().unwrap());
+    let (client_result, server_result) = run_test(
+        Conditional::Some((client_tls, server_id.clone())),
+        |conn| write_then_read(conn, PING),
+        Some(server_tls),
+        |(_, conn)| read_then_write(conn, START_OF_TLS.len(), PONG),
+    );
+
+    // The SNI identifies another workload, so the server passes the
+    // connection through and the client's handshake fails.
+    assert_eq!(client_result.tls, None);
+    assert!(client_result.result.is_err());
+    let sni = tls::ServerId(server_id.0.server_name().clone().into());
+    assert_eq!(sni.to_string(), "foo.sa.ns1.ns.cluster.local");
+    assert_eq!(
+        server_result.tls,
+        Some(Conditional::Some(tls::ServerTls::Passthru { sni }))
+    );
+}
+
+#[test]
+fn proxy_to_proxy_tls_verifies_spiffe_ids_with_the_same_server_name() {
+    let server_tls = id::test_util::BAZ_NS1_SVID.validate().unwrap();
+    let client_tls = id::test_util::BAZ_NS1_SVID.validate().unwrap();
+
+    // This ID is in another trust domain, but it is encoded as the same
+    // server name as the server's ID, so the server presents its SVID.
+    let server_id: id::Name = "spiffe://ns.cluster.local/ns1/sa/baz".parse().unwrap();
+    assert_eq!(server_id.server_name(), server_tls.name().server_name());
+
+    let (client_result, server_result) = run_test(
+        Conditional::Some((client_tls, tls::ServerId(server_id))),
+        |conn| write_then_read(conn, PING),
+        Some(server_tls),
+        |(_, conn)| read_then_write(conn, PING.len(), PONG),
+    );
+
+    // The client rejects the server's certificate.
+    assert_eq!(client_result.tls, None);
+    assert!(client_result.result.is_err());
+    assert_eq!(server_result.tls, None);
+    assert!(server_result.result.is_err());
+}
+
+#[test]
+fn proxy_to_proxy_tls_works_with_ecdsa_p384_keys() {
+    proxy_to_proxy_tls_works_with(&id::test_util::FOO_NS1_P384, &id::test_util::FOO_NS1_P384);
+}
+
+#[test]
+fn proxy_to_proxy_tls_works_with_ed25519_keys() {
+    proxy_to_proxy_tls_works_with(
+        &id::test_util::FOO_NS1_ED25519,
+        &id::test_util::FOO_NS1_ED25519,
+    );
+}
+
+#[test]
+fn proxy_to_proxy_tls_works_with_mixed_key_algorithms() {
+    proxy_to_proxy_tls_works_with(&id::test_util::FOO_NS1_P384, &id::test_util::BAR_NS1);
+    proxy_to_proxy_tls_works_with(&id::test_util::FOO_NS1_ED25519, &id::test_util::BAR_NS1);
+    proxy_to_proxy_tls_works_with(&id::test_util::FOO_NS1, &id::test_util::FOO_NS1_ED25519);
+}
+
+fn proxy_to_proxy_tls_works_with(
+    server: &id::test_util::Identity,
+    client: &id::test_util::Identity,
+) {
+    let server_tls = server.validate().unwrap();
+    let client_tls = client.validate().unwrap();
+    let server_id = tls::ServerId(server_tls.name().clone());
+    let (client_result, server_result) = run_test(
+        Conditional::Some((client_tls.clone(), server_id.clone())),
+        |conn| write_then_read(conn, PING),
+        Some(server_tls),
+        |(_, conn)| read_then_write(conn, PING.len(), PONG),
+    );
+    assert_eq!(
+        client_result.tls,
+        Some(Conditional::Some(tls::ClientTls {
+            server_id,
+            alpn: None,
+        }))
+    );
+    assert_eq!(&client_result.result.expect("pong")[..], PONG);
+    assert_eq!(
+        server_result.tls,
+        Some(Conditional::Some(tls::ServerTls::Established {
+            client_id: Some(tls::ClientId(client_tls.name().clone())),
+            negotiated_protocol: None,
+        }))
+    );
+    assert_eq!(&server_result.result.expect("ping")[..], PING);
+}
+
+#[test]
+fn proxy_to_proxy_tls_rejects_denied_clients() {
+    let server_tls = id::test_util::FOO_NS1.validate().unwrap();
+    let client_tls = id::test_util::BAR_NS1.validate().unwrap();
+    let server_id = tls::ServerId(server_tls.name().clone());
+    let denylist = tls::SharedDenylist::default();
+    denylist.update(id::test_util::BAR_NS1.name.parse().unwrap());
+    let server_tls = Tls::with_denylist(server_tls, denylist.clone());
+    let (_, server_result) = run_test_with(
+        Conditional::Some((client_tls.into(), server_id)),
+        |conn| write_then_read(conn, PING),
+        Some(server_tls),
+        |(_, conn)| read_then_write(conn, PING.len(), PONG),
+    );
+
+    // The server terminates TLS but closes the connection before it is
+    // served.
+    assert_eq!(server_result.tls, None);
+    assert!(server_result.result.is_err());
+    assert_eq!(denylist.rejected_clients(), 1);
+    assert_eq!(denylist.rejected_servers(), 0);
+}
+
+#[test]
+fn proxy_to_proxy_tls_rejects_denied_servers() {
+    let server_tls = id::test_util::FOO_NS1.validate().unwrap();
+    let client_tls = id::test_util::BAR_NS1.validate().unwrap();
+    let server_id = tls::ServerId(server_tls.name().clone());
+    let denylist = tls::SharedDenylist::default();
+    denylist.update(
+        "serial:581c43d107565376427973d83c36c2ef5d49a6a8"
+            .parse()
+            .unwrap(),
+    );
+    let client_tls = Tls::with_denylist(client_tls, denylist.clone());
+    let (client_result, _) = run_test_with(
+        Conditional::Some((client_tls, server_id)),
+        |conn| write_then_read(conn, PING),
+        Some(server_tls.into()),
+        |(_, conn)| read_then_write(conn, PING.len(), PONG),
+    );
+
+    assert_eq!(client_result.tls, None);
+    assert!(
+        client_result.result.is_err(),
+        "server must be denied during the handshake"
+    );
+    assert_eq!(denylist.rejected_servers(), 1);
+    assert_eq!(denylist.rejected_clients(), 0);
+}
+
+#[test]
+fn proxy_to_proxy_tls_resumes_sessions() {
+    let server_tls = Tls::from(id::test_util::FOO_NS1.validate().unwrap());
+    let client_tls = Tls::from(id::test_util::BAR_NS1.validate().unwrap());
+    let server_id = tls::ServerId(server_tls.crt_key.name().clone());
+
+    // The first connection establishes a session that is resumed by the
+    // second. The client's identity is known either way.
+    for _ in 0..2 {
+        let (client_result, server_result) = run_test_with(
+            Conditional::Some((client_tls.clone(), server_id.clone())),
+            |conn| write_then_read(conn, PING),
+            Some(server_tls.clone()),
+            |(_, conn)| read_then_write(conn, PING.len(), PONG),
+        );
+        assert_eq!(&client_result.result.expect("pong")[..], PONG);
+        assert_eq!(
+            server_result.tls,
+            Some(Conditional::Some(tls::ServerTls::Established {
+                client_id: Some(tls::ClientId(client_tls.crt_key.name().clone())),
+                negotiated_protocol: None,
+            }))
+        );
+        assert_eq!(&server_result.result.expect("ping")[..], PING);
+    }
+
+    let client_metrics = client_tls.handshakes.as_display().to_string();
+    assert!(client_metrics.contains("tls_handshake_total{peer=\"dst\",session=\"full\"} 1\n"));
+    assert!(client_metrics.contains("tls_handshake_total{peer=\"dst\",session=\"resumed\"} 1\n"));
+    let server_metrics = server_tls.handshakes.as_display().to_string();
+    assert!(server_metrics.contains("tls_handshake_total{peer=\"src\",session=\"full\"} 1\n"));
+    assert!(server_metrics.contains("tls_handshake_total{peer=\"src\",session=\"resumed\"} 1\n"));
+}
+
 #[test]
 fn proxy_to_proxy_tls_pass_through_when_identity_does_not_match() {
     let server_tls = id::test_util::FOO_NS1.validate().unwrap();
@@ -123,6 +339,35 @@ fn run_test<C, CF, CR, S, SF, SR>(
     Transported<tls::ConditionalClientTls, CR>,
     Transported<tls::ConditionalServerTls, SR>,
 )
+where
+    // Client
+    C: FnOnce(tls::client::Io<io::ScopedIo<TcpStream>>) -> CF + Clone + Send + 'static,
+    CF: Future<Output = Result<CR, io::Error>> + Send + 'static,
+    CR: Send + 'static,
+    // Server
+    S: Fn(tls::server::Connection<Addrs, TcpStream>) -> SF + Clone + Send + 'static,
+    SF: Future<Output = Result<SR, io::Error>> + Send + 'static,
+    SR: Send + 'static,
+{
+    run_test_with(
+        client_tls.map(|(crt_key, name)| (crt_key.into(), name)),
+        client,
+        server_tls.map(Into::into),
+        server,
+    )
+}
+
+/// Like `run_test`, but the client and server may each be configured with a
+/// denylist and handshake metrics.
+fn run_test_with<C, CF, CR, S, SF, SR>(
+    client_tls: Conditional<(Tls, tls::ServerId), tls::NoClientTls>,
+    client: C,
+    server_tls: Option<Tls>,
+    server: S,
+) -> (
+    Transported<tls::ConditionalClientTls, CR>,
+    Transported<tls::ConditionalServerTls, SR>,
+)
 where
     // Client
     C: FnOnce(tls::client::Io<io::ScopedIo<TcpStream>>) -> CF + Clone + Send + 'static,
@@ -142,7 +387,7 @@ where
     }
 
     let (client_tls, client_server_id) = match client_tls {
-        Conditional::Some((crtkey, name)) => (Some(Tls(crtkey)), Conditional::Some(name)),
+        Conditional::Some((tls, name)) => (Some(tls), Conditional::Some(name)),
         Conditional::None(reason) => (None, Conditional::None(reason)),
     };
 
@@ -150,6 +395,7 @@ where
     let (server, server_addr, server_result) = {
         // Saves the result of every connection.
         let (sender, receiver) = mpsc::channel::<Transported<tls::ConditionalServerTls, SR>>();
+        let sender_clone = sender.clone();
 
         // Let the OS decide the port number and then return the resulting
         // `SocketAddr` so the client can connect to it. This allows multiple
@@ -160,7 +406,7 @@ where
         let (listen_addr, listen) = BindTcp::new(addr, None).bind().expect("must bind");
 
         let mut detect = tls::NewDetectTls::new(
-            server_tls.map(Tls),
+            server_tls,
             move |meta: tls::server::Meta<Addrs>| {
                 let server = server.clone();
                 let sender = sender.clone();
@@ -194,7 +440,16 @@ where
                 .expect("listener closed");
             tracing::debug!("incoming connection");
             let accept = detect.new_service(meta);
-            accept.oneshot(io).await.expect("connection failed");
+            if let Err(e) = accept.oneshot(io).await {
+                // The connection failed before it could be served, e.g.
+                // because the client's certificate was denied.
+                sender_clone
+                    .send(Transported {
+                        tls: None,
+                        result: Err(io::Error::new(io::ErrorKind::Other, e)),
+                    })
+                    .expect("send result");
+            }
             tracing::debug!("done");
         }
         .instrument(tracing::info_span!("run_server", %listen_addr));
@@ -306,7 +561,11 @@ const START_OF_TLS: &[u8] = &[22, 3, 1]; // ContentType::handshake version 3.1
 struct Target(SocketAddr, tls::ConditionalClientTls);
 
 #[derive(Clone)]
-struct Tls(id::CrtKey);
+struct Tls {
+    crt_key: id::CrtKey,
+    denylist: tls::SharedDenylist,
+    handshakes: Handshakes,
+}
 
 impl Param<ConnectAddr> for Target {
     fn param(&self) -> ConnectAddr {
@@ -320,20 +579,48 @@ impl Param<tls::ConditionalClientTls> for Target {
     }
 }
 
+impl Tls {
+    fn with_denylist(crt_key: id::CrtKey, denylist: tls::SharedDenylist) -> Self {
+        Self {
+            crt_key: crt_key.with_denylist(&denylist),
+            denylist,
+            handshakes: Handshakes::default(),
+        }
+    }
+}
+
+impl From<id::CrtKey> for Tls {
+    fn from(crt_key: id::CrtKey) -> Self {
+        Self::with_denylist(crt_key, tls::SharedDenylist::default())
+    }
+}
+
 impl Param<tls::client::Config> for Tls {
     fn param(&self) -> tls::client::Config {
-        self.0.client_config()
+        self.crt_key.client_config()
     }
 }
 
 impl Param<tls::server::Config> for Tls {
     fn param(&self) -> tls::server::Config {
-        self.0.server_config()
+        self.crt_key.server_config()
     }
 }
 
 impl Param<tls::LocalId> for Tls {
     fn param(&self) -> tls::LocalId {
-        self.0.id().clone()
+        self.crt_key.id().clone()
+    }
+}
+
+impl Param<tls::SharedDenylist> for Tls {
+    fn param(&self) -> tls::SharedDenylist {
+        self.denylist.clone()
+    }
+}
+
+impl Param<Handshakes> for Tls {
+    fn param(&self) -> Handshakes {
+        self.handshakes.clone()
     }
 }
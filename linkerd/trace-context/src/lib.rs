@@ -1,9 +1,10 @@
 #![deny(warnings, rust_2018_idioms)]
 
 mod propagation;
+mod sampler;
 mod service;
 
-pub use self::service::TraceContext;
+pub use self::{propagation::unpack_trace_context, sampler::Sampler, service::TraceContext};
 use bytes::Bytes;
 use linkerd_channel as mpsc;
 use linkerd_error::Error;
@@ -11,9 +12,11 @@ use rand::Rng;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
 const SPAN_ID_LEN: usize = 8;
+const TRACE_ID_LEN: usize = 16;
 
 #[derive(Debug, Default)]
 pub struct Id(Vec<u8>);
@@ -32,9 +35,16 @@ pub struct Span {
     pub span_name: String,
     pub start: SystemTime,
     pub end: SystemTime,
-    pub labels: HashMap<&'static str, String>,
+    pub labels: HashMap<String, String>,
 }
 
+/// Attributes that the services handling a request add to its span.
+///
+/// `TraceContext` sets this as an extension on each request for which it
+/// records a span. Attributes are added to the span when it completes.
+#[derive(Clone, Debug, Default)]
+pub struct SpanAttributes(Arc<Mutex<HashMap<String, String>>>);
+
 pub trait SpanSink {
     fn is_enabled(&self) -> bool;
 
@@ -65,6 +75,23 @@ impl<K: SpanSink> SpanSink for Option<K> {
     }
 }
 
+// === impl SpanAttributes ===
+
+impl SpanAttributes {
+    pub fn insert(&self, key: impl Into<String>, value: impl Into<String>) {
+        if let Ok(mut attrs) = self.0.lock() {
+            attrs.insert(key.into(), value.into());
+        }
+    }
+
+    fn take(&self) -> HashMap<String, String> {
+        self.0
+            .lock()
+            .map(|mut attrs| std::mem::take(&mut *attrs))
+            .unwrap_or_default()
+    }
+}
+
 // === impl Id ===
 
 impl Id {
@@ -73,6 +100,12 @@ impl Id {
         rng.fill(bytes.as_mut_slice());
         Self(bytes)
     }
+
+    fn new_trace_id<R: Rng>(rng: &mut R) -> Self {
+        let mut bytes = vec![0; TRACE_ID_LEN];
+        rng.fill(bytes.as_mut_slice());
+        Self(bytes)
+    }
 }
 
 impl Into<Vec<u8>> for Id {
@@ -108,6 +141,10 @@ impl Flags {
     pub fn is_sampled(&self) -> bool {
         self.0 & 1 == 1
     }
+
+    fn set_sampled(&mut self) {
+        self.0 |= 1;
+    }
 }
 
 impl fmt::Display for Flags {
@@ -11,15 +11,31 @@ const HTTP_TRACE_ID_HEADER: &str = "x-b3-traceid";
 const HTTP_SPAN_ID_HEADER: &str = "x-b3-spanid";
 const HTTP_SAMPLED_HEADER: &str = "x-b3-sampled";
 
+const B3_SINGLE_HEADER: &str = "b3";
+
 const GRPC_TRACE_HEADER: &str = "grpc-trace-bin";
 const GRPC_TRACE_FIELD_TRACE_ID: u8 = 0;
 const GRPC_TRACE_FIELD_SPAN_ID: u8 = 1;
 const GRPC_TRACE_FIELD_TRACE_OPTIONS: u8 = 2;
 
+const W3C_TRACEPARENT_HEADER: &str = "traceparent";
+const W3C_TRACESTATE_HEADER: &str = "tracestate";
+const W3C_VERSION: u8 = 0;
+const W3C_MAX_TRACESTATE_MEMBERS: usize = 32;
+
 #[derive(Debug)]
 pub enum Propagation {
+    /// B3 multi-header (`x-b3-*`) propagation.
     Http,
+    /// Binary (`grpc-trace-bin`) propagation.
     Grpc,
+    /// W3C Trace Context (`traceparent` and `tracestate`) propagation.
+    ///
+    /// The trace state is `None` if the request had no `tracestate` or if it
+    /// was malformed, in which case it is not forwarded.
+    W3c { trace_state: Option<String> },
+    /// B3 single-header (`b3`) propagation.
+    B3Single,
 }
 
 #[derive(Debug)]
@@ -33,6 +49,15 @@ pub struct TraceContext {
 #[derive(Debug)]
 struct UnknownFieldId(u8);
 
+/// The fields of a `b3` header, i.e.
+/// `{TraceId}-{SpanId}[-{SamplingState}[-{ParentSpanId}]]`.
+#[derive(Debug)]
+struct B3Single<'a> {
+    trace_id: &'a str,
+    span_id: &'a str,
+    sampling_state: Option<&'a str>,
+}
+
 // === impl UnknownFieldId ===
 
 impl std::error::Error for UnknownFieldId {}
@@ -51,8 +76,33 @@ impl TraceContext {
     }
 }
 
+/// Reads the trace context from a request's headers.
+///
+/// If a request includes more than one propagation format, the context is read
+/// from the first valid header of `grpc-trace-bin`, `traceparent`, `b3` and
+/// `x-b3-*`. Only the headers of that format are updated when the request is
+/// forwarded; the others are forwarded unmodified.
 pub fn unpack_trace_context<B>(request: &http::Request<B>) -> Option<TraceContext> {
-    unpack_grpc_trace_context(request).or_else(|| unpack_http_trace_context(request))
+    unpack_grpc_trace_context(request)
+        .or_else(|| unpack_w3c_trace_context(request))
+        .or_else(|| unpack_b3_single_trace_context(request))
+        .or_else(|| unpack_http_trace_context(request))
+}
+
+/// Starts a new trace, which is propagated in the W3C Trace Context format.
+pub fn new_trace_context(sampled: bool) -> TraceContext {
+    TraceContext {
+        propagation: Propagation::W3c { trace_state: None },
+        trace_id: Id::new_trace_id(&mut thread_rng()),
+        // The trace's first span has no parent.
+        parent_id: Id::default(),
+        flags: Flags(sampled as u8),
+    }
+}
+
+/// Generates a new span id for a span that is not propagated to the request.
+pub fn new_span_id() -> Id {
+    Id::new_span_id(&mut thread_rng())
 }
 
 // Generates a new span id, writes it to the request in the appropriate
@@ -60,7 +110,11 @@ pub fn unpack_trace_context<B>(request: &http::Request<B>) -> Option<TraceContex
 pub fn increment_span_id<B>(request: &mut http::Request<B>, context: &TraceContext) -> Id {
     match context.propagation {
         Propagation::Grpc => increment_grpc_span_id(request, context),
-        Propagation::Http => increment_http_span_id(request),
+        Propagation::Http => increment_http_span_id(request, context),
+        Propagation::W3c { ref trace_state } => {
+            increment_w3c_span_id(request, context, trace_state.as_deref())
+        }
+        Propagation::B3Single => increment_b3_single_span_id(request, context),
     }
 }
 
@@ -194,7 +248,7 @@ fn unpack_http_trace_context<B>(request: &http::Request<B>) -> Option<TraceConte
     })
 }
 
-fn increment_http_span_id<B>(request: &mut http::Request<B>) -> Id {
+fn increment_http_span_id<B>(request: &mut http::Request<B>, context: &TraceContext) -> Id {
     let span_id = Id::new_span_id(&mut thread_rng());
 
     trace!("incremented span id: {}", span_id);
@@ -206,9 +260,274 @@ fn increment_http_span_id<B>(request: &mut http::Request<B>) -> Id {
     } else {
         warn!("invalid {} header: {:?}", HTTP_SPAN_ID_HEADER, span_str);
     }
+
+    // An unsampled trace's sampling state is forwarded as it was received,
+    // since it may defer the sampling decision.
+    if context.is_sampled() {
+        request
+            .headers_mut()
+            .insert(HTTP_SAMPLED_HEADER, HeaderValue::from_static("1"));
+    }
+    span_id
+}
+
+fn unpack_w3c_trace_context<B>(request: &http::Request<B>) -> Option<TraceContext> {
+    let traceparent = get_header_str(request, W3C_TRACEPARENT_HEADER)?;
+    let (trace_id, parent_id, flags) = parse_traceparent(traceparent).or_else(|| {
+        warn!(
+            "Invalid {} header: {:?}",
+            W3C_TRACEPARENT_HEADER, traceparent
+        );
+        None
+    })?;
+
+    Some(TraceContext {
+        propagation: Propagation::W3c {
+            trace_state: unpack_w3c_trace_state(request),
+        },
+        trace_id,
+        parent_id,
+        flags,
+    })
+}
+
+/// Parses a `traceparent` value, i.e.
+/// `{version}-{trace-id}-{parent-id}-{trace-flags}`.
+fn parse_traceparent(value: &str) -> Option<(Id, Id, Flags)> {
+    let mut fields = value.splitn(5, '-');
+    let version = parse_lower_hex(fields.next()?, 1)?[0];
+    let trace_id = parse_lower_hex(fields.next()?, 16)?;
+    let parent_id = parse_lower_hex(fields.next()?, 8)?;
+    let flags = parse_lower_hex(fields.next()?, 1)?[0];
+
+    // Version 255 is invalid. Later versions may append fields, but the
+    // current version must have exactly four.
+    if version == 0xff || (version == W3C_VERSION && fields.next().is_some()) {
+        return None;
+    }
+
+    // All-zero IDs are invalid.
+    if trace_id.iter().all(|b| *b == 0) || parent_id.iter().all(|b| *b == 0) {
+        return None;
+    }
+
+    Some((Id(trace_id), Id(parent_id), Flags(flags)))
+}
+
+/// Reads the `tracestate` list, which may be split over multiple headers.
+///
+/// Returns `None` if the header is absent or if any of its values are
+/// malformed.
+fn unpack_w3c_trace_state<B>(request: &http::Request<B>) -> Option<String> {
+    let mut members = Vec::new();
+    let mut keys = Vec::new();
+    for value in request.headers().get_all(W3C_TRACESTATE_HEADER) {
+        let value = value
+            .to_str()
+            .map_err(|e| warn!("Invalid trace header {}: {}", W3C_TRACESTATE_HEADER, e))
+            .ok()?;
+        for member in value.split(',') {
+            let member = member.trim_matches(|c| c == ' ' || c == '\t');
+            if member.is_empty() {
+                continue;
+            }
+            let key = parse_trace_state_member(member).or_else(|| {
+                warn!("Invalid {} member: {:?}", W3C_TRACESTATE_HEADER, member);
+                None
+            })?;
+            if keys.contains(&key) {
+                warn!("Duplicate {} key: {:?}", W3C_TRACESTATE_HEADER, key);
+                return None;
+            }
+            keys.push(key);
+            members.push(member);
+        }
+    }
+
+    if members.len() > W3C_MAX_TRACESTATE_MEMBERS {
+        warn!(
+            "{} has more than {} members",
+            W3C_TRACESTATE_HEADER, W3C_MAX_TRACESTATE_MEMBERS
+        );
+        return None;
+    }
+    if members.is_empty() {
+        return None;
+    }
+    Some(members.join(","))
+}
+
+/// Validates a `tracestate` list member, i.e. `{key}={value}`, returning its
+/// key.
+fn parse_trace_state_member(member: &str) -> Option<&str> {
+    let eq = member.find('=')?;
+    let (key, value) = (&member[..eq], &member[eq + 1..]);
+
+    let is_key_char = |b: u8| b.is_ascii_lowercase() || b.is_ascii_digit() || b"_-*/".contains(&b);
+    let valid_key = match key.find('@') {
+        // A simple key.
+        None => {
+            (1..=256).contains(&key.len())
+                && key.as_bytes()[0].is_ascii_lowercase()
+                && key.bytes().all(is_key_char)
+        }
+        // A multi-tenant key, i.e. `{tenant}@{system}`.
+        Some(at) => {
+            let (tenant, system) = (&key[..at], &key[at + 1..]);
+            (1..=241).contains(&tenant.len())
+                && tenant.bytes().all(is_key_char)
+                && (1..=14).contains(&system.len())
+                && system.as_bytes()[0].is_ascii_lowercase()
+                && system.bytes().all(is_key_char)
+        }
+    };
+
+    let valid_value = (1..=256).contains(&value.len())
+        && value
+            .bytes()
+            .all(|b| (0x20..=0x7e).contains(&b) && b != b',' && b != b'=')
+        && !value.ends_with(' ');
+
+    if valid_key && valid_value {
+        Some(key)
+    } else {
+        None
+    }
+}
+
+fn increment_w3c_span_id<B>(
+    request: &mut http::Request<B>,
+    context: &TraceContext,
+    trace_state: Option<&str>,
+) -> Id {
+    let span_id = Id::new_span_id(&mut thread_rng());
+
+    trace!("incremented span id: {}", span_id);
+
+    // The trace context is always forwarded in the current version's format.
+    let traceparent = format!(
+        "{:02x}-{}-{}-{:02x}",
+        W3C_VERSION,
+        hex::encode(context.trace_id.as_ref()),
+        hex::encode(span_id.as_ref()),
+        context.flags.0
+    );
+    if let Result::Ok(hv) = HeaderValue::from_str(&traceparent) {
+        request.headers_mut().insert(W3C_TRACEPARENT_HEADER, hv);
+    } else {
+        warn!(
+            "invalid {} header: {:?}",
+            W3C_TRACEPARENT_HEADER, traceparent
+        );
+    }
+
+    // A malformed trace state must not be forwarded. A valid trace state is
+    // forwarded as a single header.
+    match trace_state.map(HeaderValue::from_str) {
+        Some(Ok(hv)) => {
+            request.headers_mut().insert(W3C_TRACESTATE_HEADER, hv);
+        }
+        _ => {
+            request.headers_mut().remove(W3C_TRACESTATE_HEADER);
+        }
+    }
+
     span_id
 }
 
+fn unpack_b3_single_trace_context<B>(request: &http::Request<B>) -> Option<TraceContext> {
+    let value = get_header_str(request, B3_SINGLE_HEADER)?;
+    let context = B3Single::parse(value).and_then(|b3| {
+        let flags = match b3.sampling_state {
+            // Debug implies that the trace is sampled.
+            Some("1") | Some("d") => Flags(1),
+            Some("0") | None => Flags(0),
+            Some(_) => return None,
+        };
+        let trace_id = match b3.trace_id.len() {
+            16 => {
+                let mut id = vec![0; 8];
+                id.append(&mut parse_lower_hex(b3.trace_id, 8)?);
+                id
+            }
+            _ => parse_lower_hex(b3.trace_id, 16)?,
+        };
+        let parent_id = parse_lower_hex(b3.span_id, 8)?;
+        Some(TraceContext {
+            propagation: Propagation::B3Single,
+            trace_id: Id(trace_id),
+            parent_id: Id(parent_id),
+            flags,
+        })
+    });
+    if context.is_none() {
+        warn!("Invalid {} header: {:?}", B3_SINGLE_HEADER, value);
+    }
+    context
+}
+
+fn increment_b3_single_span_id<B>(request: &mut http::Request<B>, context: &TraceContext) -> Id {
+    let span_id = Id::new_span_id(&mut thread_rng());
+
+    trace!("incremented span id: {}", span_id);
+
+    // The trace ID and sampling state are forwarded as they were received, and
+    // the received span ID becomes the parent span ID. If the proxy sampled
+    // the trace, the sampling state is updated to reflect that.
+    let value = get_header_str(request, B3_SINGLE_HEADER)
+        .and_then(B3Single::parse)
+        .map(|b3| {
+            let sampling_state = match b3.sampling_state {
+                Some("0") | None if context.is_sampled() => Some("1"),
+                sampling_state => sampling_state,
+            };
+            match sampling_state {
+                Some(sampling_state) => format!(
+                    "{}-{}-{}-{}",
+                    b3.trace_id,
+                    hex::encode(span_id.as_ref()),
+                    sampling_state,
+                    hex::encode(context.parent_id.as_ref())
+                ),
+                None => format!("{}-{}", b3.trace_id, hex::encode(span_id.as_ref())),
+            }
+        });
+    match value.as_deref().map(HeaderValue::from_str) {
+        Some(Ok(hv)) => {
+            request.headers_mut().insert(B3_SINGLE_HEADER, hv);
+        }
+        _ => warn!("invalid {} header: {:?}", B3_SINGLE_HEADER, value),
+    }
+
+    span_id
+}
+
+// === impl B3Single ===
+
+impl<'a> B3Single<'a> {
+    /// Splits a `b3` header into its fields, validating the parent span ID.
+    ///
+    /// A header that only has a sampling state (e.g. `b3: 0`) has no trace
+    /// context.
+    fn parse(value: &'a str) -> Option<Self> {
+        let mut fields = value.split('-');
+        let trace_id = fields.next()?;
+        let span_id = fields.next()?;
+        let sampling_state = fields.next();
+        if let Some(parent_span_id) = fields.next() {
+            parse_lower_hex(parent_span_id, 8)?;
+        }
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampling_state,
+        })
+    }
+}
+
 fn get_header_str<'a, B>(request: &'a http::Request<B>, header: &str) -> Option<&'a str> {
     let hv = request.headers().get(header)?;
     hv.to_str()
@@ -233,6 +552,18 @@ fn parse_header_id<B>(request: &http::Request<B>, header: &str, pad_to: usize) -
         .ok()
 }
 
+/// Decodes exactly `len` bytes from lowercase hex.
+fn parse_lower_hex(value: &str, len: usize) -> Option<Vec<u8>> {
+    if value.len() != len * 2
+        || !value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    {
+        return None;
+    }
+    hex::decode(value).ok()
+}
+
 /// Attempt to split_to the given index.  If there are not enough bytes then
 /// Err is returned and the given Bytes is not modified.
 fn try_split_to(buf: &mut Bytes, n: usize) -> Result<Bytes, InsufficientBytes> {
@@ -242,3 +573,314 @@ fn try_split_to(buf: &mut Bytes, n: usize) -> Result<Bytes, InsufficientBytes> {
         Err(InsufficientBytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    fn request(headers: &[(&'static str, &str)]) -> http::Request<()> {
+        let mut req = http::Request::new(());
+        for (name, value) in headers {
+            req.headers_mut()
+                .append(*name, HeaderValue::from_bytes(value.as_bytes()).unwrap());
+        }
+        req
+    }
+
+    fn header<'a>(req: &'a http::Request<()>, name: &str) -> Option<&'a str> {
+        req.headers().get(name).map(|v| v.to_str().unwrap())
+    }
+
+    #[test]
+    fn unpacks_traceparent() {
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        let req = request(&[
+            ("traceparent", &traceparent),
+            ("tracestate", "congo=t61rcWkgMzE, rojo=00f067aa0ba902b7"),
+            ("tracestate", "vendor@tenant=1"),
+        ]);
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        assert_eq!(ctx.trace_id.to_string(), TRACE_ID);
+        assert_eq!(ctx.parent_id.to_string(), PARENT_ID);
+        assert!(ctx.is_sampled());
+        match ctx.propagation {
+            Propagation::W3c { trace_state } => assert_eq!(
+                trace_state.as_deref(),
+                Some("congo=t61rcWkgMzE,rojo=00f067aa0ba902b7,vendor@tenant=1")
+            ),
+            p => panic!("unexpected propagation: {:?}", p),
+        }
+
+        // Later versions may append fields.
+        let traceparent = format!("cc-{}-{}-00-future", TRACE_ID, PARENT_ID);
+        let ctx = unpack_trace_context(&request(&[("traceparent", &traceparent)]))
+            .expect("must have a trace context");
+        assert!(!ctx.is_sampled());
+    }
+
+    #[test]
+    fn ignores_malformed_traceparent() {
+        for traceparent in &[
+            "",
+            "00",
+            format!("00-{}-{}", TRACE_ID, PARENT_ID).as_str(),
+            format!("00-{}-{}-01-extra", TRACE_ID, PARENT_ID).as_str(),
+            format!("ff-{}-{}-01", TRACE_ID, PARENT_ID).as_str(),
+            format!("0-{}-{}-01", TRACE_ID, PARENT_ID).as_str(),
+            format!("00-{}-{}-1", TRACE_ID, PARENT_ID).as_str(),
+            format!("00-{}-{}-01", TRACE_ID.to_uppercase(), PARENT_ID).as_str(),
+            format!("00-{}-{}-01", &TRACE_ID[1..], PARENT_ID).as_str(),
+            format!("00-{}-{}-01", TRACE_ID, &PARENT_ID[1..]).as_str(),
+            format!("00-{}-{}-0g", TRACE_ID, PARENT_ID).as_str(),
+            format!("00-{}-{}-01", "0".repeat(32), PARENT_ID).as_str(),
+            format!("00-{}-{}-01", TRACE_ID, "0".repeat(16)).as_str(),
+            format!("00_{}_{}_01", TRACE_ID, PARENT_ID).as_str(),
+        ] {
+            assert!(
+                unpack_trace_context(&request(&[("traceparent", traceparent)])).is_none(),
+                "{:?} must be invalid",
+                traceparent
+            );
+        }
+    }
+
+    #[test]
+    fn drops_malformed_tracestate() {
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        let too_many = (0..33)
+            .map(|i| format!("k{}=v", i))
+            .collect::<Vec<_>>()
+            .join(",");
+        for tracestate in &[
+            "novalue",
+            "=value",
+            "Upper=value",
+            "1key=value",
+            "key=",
+            "key=a,b=c=d",
+            "key=tab\tinside",
+            "key=1,key=2",
+            "tenant@=value",
+            "tenant@1system=value",
+            "key=caf\u{e9}",
+            too_many.as_str(),
+        ] {
+            let mut req = request(&[("traceparent", &traceparent), ("tracestate", tracestate)]);
+            let ctx = unpack_trace_context(&req).expect("must have a trace context");
+            match ctx.propagation {
+                Propagation::W3c { ref trace_state } => {
+                    assert_eq!(*trace_state, None, "{:?} must be invalid", tracestate)
+                }
+                ref p => panic!("unexpected propagation: {:?}", p),
+            }
+
+            // A malformed trace state is not forwarded.
+            increment_span_id(&mut req, &ctx);
+            assert_eq!(header(&req, "tracestate"), None);
+        }
+    }
+
+    #[test]
+    fn increments_traceparent() {
+        let traceparent = format!("01-{}-{}-01-future", TRACE_ID, PARENT_ID);
+        let mut req = request(&[
+            ("traceparent", &traceparent),
+            ("tracestate", "congo=t61rcWkgMzE"),
+            ("tracestate", "rojo=00f067aa0ba902b7"),
+        ]);
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        let span_id = increment_span_id(&mut req, &ctx);
+        assert_ne!(span_id.to_string(), PARENT_ID);
+        assert_eq!(
+            header(&req, "traceparent"),
+            Some(format!("00-{}-{}-01", TRACE_ID, span_id).as_str())
+        );
+        assert_eq!(
+            header(&req, "tracestate"),
+            Some("congo=t61rcWkgMzE,rojo=00f067aa0ba902b7")
+        );
+    }
+
+    #[test]
+    fn unpacks_b3_single() {
+        for (b3, sampled) in &[
+            (format!("{}-{}", TRACE_ID, PARENT_ID), false),
+            (format!("{}-{}-1", TRACE_ID, PARENT_ID), true),
+            (format!("{}-{}-d", TRACE_ID, PARENT_ID), true),
+            (
+                format!("{}-{}-0-{}", TRACE_ID, PARENT_ID, "05e3ac9a4f6e3b90"),
+                false,
+            ),
+        ] {
+            let ctx = unpack_trace_context(&request(&[("b3", b3)]))
+                .unwrap_or_else(|| panic!("{:?} must be valid", b3));
+            assert!(matches!(ctx.propagation, Propagation::B3Single));
+            assert_eq!(ctx.trace_id.to_string(), TRACE_ID);
+            assert_eq!(ctx.parent_id.to_string(), PARENT_ID);
+            assert_eq!(ctx.is_sampled(), *sampled, "{:?}", b3);
+        }
+
+        // 64-bit trace IDs are padded.
+        let ctx = unpack_trace_context(&request(&[(
+            "b3",
+            &format!("{}-{}-1", &TRACE_ID[16..], PARENT_ID),
+        )]))
+        .expect("must have a trace context");
+        assert_eq!(
+            ctx.trace_id.to_string(),
+            format!("{}{}", "0".repeat(16), &TRACE_ID[16..])
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_b3_single() {
+        for b3 in &[
+            "",
+            "0",
+            "1",
+            "d",
+            TRACE_ID,
+            format!("{}-{}-x", TRACE_ID, PARENT_ID).as_str(),
+            format!("{}-{}-1-", TRACE_ID, PARENT_ID).as_str(),
+            format!("{}-{}-1-{}-extra", TRACE_ID, PARENT_ID, PARENT_ID).as_str(),
+            format!("{}-{}", TRACE_ID.to_uppercase(), PARENT_ID).as_str(),
+            format!("{}-{}", &TRACE_ID[1..], PARENT_ID).as_str(),
+            format!("{}-{}", TRACE_ID, &PARENT_ID[1..]).as_str(),
+        ] {
+            assert!(
+                unpack_trace_context(&request(&[("b3", b3)])).is_none(),
+                "{:?} must be invalid",
+                b3
+            );
+        }
+    }
+
+    #[test]
+    fn increments_b3_single() {
+        let short_trace_id = &TRACE_ID[16..];
+        let mut req = request(&[("b3", &format!("{}-{}-d", short_trace_id, PARENT_ID))]);
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        let span_id = increment_span_id(&mut req, &ctx);
+        assert_eq!(
+            header(&req, "b3"),
+            Some(format!("{}-{}-d-{}", short_trace_id, span_id, PARENT_ID).as_str())
+        );
+
+        let mut req = request(&[("b3", &format!("{}-{}", TRACE_ID, PARENT_ID))]);
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        let span_id = increment_span_id(&mut req, &ctx);
+        assert_eq!(
+            header(&req, "b3"),
+            Some(format!("{}-{}", TRACE_ID, span_id).as_str())
+        );
+    }
+
+    #[test]
+    fn propagates_sampling_decisions() {
+        let mut req = request(&[
+            ("x-b3-traceid", TRACE_ID),
+            ("x-b3-spanid", PARENT_ID),
+            ("x-b3-sampled", "0"),
+        ]);
+        let mut ctx = unpack_trace_context(&req).expect("must have a trace context");
+        increment_span_id(&mut req, &ctx);
+        assert_eq!(header(&req, "x-b3-sampled"), Some("0"));
+        ctx.flags.set_sampled();
+        increment_span_id(&mut req, &ctx);
+        assert_eq!(header(&req, "x-b3-sampled"), Some("1"));
+
+        let mut req = request(&[("b3", &format!("{}-{}-0", TRACE_ID, PARENT_ID))]);
+        let mut ctx = unpack_trace_context(&req).expect("must have a trace context");
+        ctx.flags.set_sampled();
+        let span_id = increment_span_id(&mut req, &ctx);
+        assert_eq!(
+            header(&req, "b3"),
+            Some(format!("{}-{}-1-{}", TRACE_ID, span_id, PARENT_ID).as_str())
+        );
+
+        let mut req = request(&[("b3", &format!("{}-{}", TRACE_ID, PARENT_ID))]);
+        let mut ctx = unpack_trace_context(&req).expect("must have a trace context");
+        ctx.flags.set_sampled();
+        let span_id = increment_span_id(&mut req, &ctx);
+        assert_eq!(
+            header(&req, "b3"),
+            Some(format!("{}-{}-1-{}", TRACE_ID, span_id, PARENT_ID).as_str())
+        );
+
+        let mut req = request(&[("traceparent", &format!("00-{}-{}-00", TRACE_ID, PARENT_ID))]);
+        let mut ctx = unpack_trace_context(&req).expect("must have a trace context");
+        ctx.flags.set_sampled();
+        let span_id = increment_span_id(&mut req, &ctx);
+        assert_eq!(
+            header(&req, "traceparent"),
+            Some(format!("00-{}-{}-01", TRACE_ID, span_id).as_str())
+        );
+    }
+
+    #[test]
+    fn starts_new_traces() {
+        let mut req = request(&[]);
+        let ctx = new_trace_context(true);
+        assert!(ctx.is_sampled());
+        assert!(ctx.parent_id.as_ref().is_empty());
+        let span_id = increment_span_id(&mut req, &ctx);
+
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        assert!(matches!(ctx.propagation, Propagation::W3c { .. }));
+        assert_eq!(ctx.trace_id.as_ref().len(), 16);
+        assert_eq!(ctx.parent_id.as_ref(), span_id.as_ref());
+        assert!(ctx.is_sampled());
+        assert_eq!(header(&req, "tracestate"), None);
+
+        assert!(!new_trace_context(false).is_sampled());
+    }
+
+    #[test]
+    fn prefers_formats_deterministically() {
+        let other_trace_id = "a3ce929d0e0e47364bf92f3577b34da6";
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        let b3 = format!("{}-{}-1", other_trace_id, PARENT_ID);
+
+        // W3C is preferred over B3, and only its header is updated.
+        let mut req = request(&[
+            ("x-b3-traceid", other_trace_id),
+            ("x-b3-spanid", PARENT_ID),
+            ("x-b3-sampled", "1"),
+            ("b3", &b3),
+            ("traceparent", &traceparent),
+        ]);
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        assert!(matches!(ctx.propagation, Propagation::W3c { .. }));
+        assert_eq!(ctx.trace_id.to_string(), TRACE_ID);
+        let span_id = increment_span_id(&mut req, &ctx);
+        assert_eq!(
+            header(&req, "traceparent"),
+            Some(format!("00-{}-{}-01", TRACE_ID, span_id).as_str())
+        );
+        assert_eq!(header(&req, "b3"), Some(b3.as_str()));
+        assert_eq!(header(&req, "x-b3-spanid"), Some(PARENT_ID));
+
+        // A malformed header is skipped in favor of the next format.
+        let req = request(&[
+            ("traceparent", "00-malformed"),
+            ("b3", &b3),
+            ("x-b3-traceid", TRACE_ID),
+            ("x-b3-spanid", PARENT_ID),
+        ]);
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        assert!(matches!(ctx.propagation, Propagation::B3Single));
+        assert_eq!(ctx.trace_id.to_string(), other_trace_id);
+
+        let req = request(&[
+            ("b3", "0"),
+            ("x-b3-traceid", TRACE_ID),
+            ("x-b3-spanid", PARENT_ID),
+        ]);
+        let ctx = unpack_trace_context(&req).expect("must have a trace context");
+        assert!(matches!(ctx.propagation, Propagation::Http));
+        assert_eq!(ctx.trace_id.to_string(), TRACE_ID);
+    }
+}
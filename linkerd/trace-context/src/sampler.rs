@@ -0,0 +1,152 @@
+use rand::Rng;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Decides whether the proxy samples traces that it starts or that it receives
+/// unsampled.
+///
+/// Traces that are received sampled are always sampled. By default, the
+/// proxy never samples a trace itself.
+#[derive(Clone, Debug, Default)]
+pub struct Sampler {
+    probability: f64,
+    rate_limit: Option<Arc<RateLimit>>,
+    errors: bool,
+}
+
+/// Limits the number of traces that are sampled in each one-second window.
+#[derive(Debug)]
+struct RateLimit {
+    max_per_second: u32,
+    window: Mutex<Window>,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    sampled: u32,
+}
+
+// === impl Sampler ===
+
+impl Sampler {
+    /// Samples traces with the given `probability`, which is limited to
+    /// `[0, 1]`.
+    ///
+    /// If `max_per_second` is set, no more than that many traces are sampled
+    /// each second. If `errors` is set, spans are recorded for failed requests
+    /// in unsampled traces, though the propagated trace remains unsampled.
+    pub fn new(probability: f64, max_per_second: Option<u32>, errors: bool) -> Self {
+        let rate_limit = max_per_second.map(|max_per_second| {
+            Arc::new(RateLimit {
+                max_per_second,
+                window: Mutex::new(Window {
+                    start: Instant::now(),
+                    sampled: 0,
+                }),
+            })
+        });
+        // NaN is treated as 0.
+        let probability = if probability > 1.0 {
+            1.0
+        } else if probability > 0.0 {
+            probability
+        } else {
+            0.0
+        };
+        Self {
+            probability,
+            rate_limit,
+            errors,
+        }
+    }
+
+    /// Indicates whether the proxy starts traces for requests that do not
+    /// have a trace context.
+    pub fn is_enabled(&self) -> bool {
+        self.probability > 0.0 || self.errors
+    }
+
+    /// Indicates whether spans are recorded for failed requests in unsampled
+    /// traces.
+    pub fn samples_errors(&self) -> bool {
+        self.errors
+    }
+
+    /// Decides whether an unsampled trace is sampled.
+    pub(crate) fn sample(&self) -> bool {
+        if self.probability <= 0.0 {
+            return false;
+        }
+        if self.probability < 1.0 && !rand::thread_rng().gen_bool(self.probability) {
+            return false;
+        }
+        match self.rate_limit {
+            Some(ref rate_limit) => rate_limit.acquire(Instant::now()),
+            None => true,
+        }
+    }
+}
+
+// === impl RateLimit ===
+
+impl RateLimit {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    fn acquire(&self, now: Instant) -> bool {
+        let mut window = self.window.lock().unwrap();
+        if now.saturating_duration_since(window.start) >= Self::WINDOW {
+            window.start = now;
+            window.sampled = 0;
+        }
+        if window.sampled < self.max_per_second {
+            window.sampled += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_by_probability() {
+        assert!(!Sampler::default().sample());
+        assert!(!Sampler::new(0.0, None, false).sample());
+        assert!((0..100).all(|_| Sampler::new(1.0, None, false).sample()));
+        assert!((0..100).all(|_| Sampler::new(2.0, None, false).sample()));
+        assert!(!Sampler::new(-1.0, None, false).sample());
+        assert!(!Sampler::new(f64::NAN, None, false).sample());
+    }
+
+    #[test]
+    fn limits_samples_per_second() {
+        let sampler = Sampler::new(1.0, Some(2), false);
+        assert!(sampler.sample());
+        assert!(sampler.clone().sample());
+        assert!(!sampler.sample());
+
+        let rate_limit = sampler.rate_limit.as_ref().unwrap();
+        let start = rate_limit.window.lock().unwrap().start;
+        assert!(!rate_limit.acquire(start + Duration::from_millis(999)));
+        assert!(rate_limit.acquire(start + Duration::from_secs(1)));
+
+        assert!(!Sampler::new(1.0, Some(0), false).sample());
+    }
+
+    #[test]
+    fn enabled_for_errors() {
+        assert!(!Sampler::default().is_enabled());
+        assert!(Sampler::new(0.5, None, false).is_enabled());
+
+        let errors = Sampler::new(0.0, None, true);
+        assert!(errors.is_enabled());
+        assert!(errors.samples_errors());
+        assert!(!errors.sample());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{propagation, Span, SpanSink};
+use crate::{propagation, Sampler, Span, SpanAttributes, SpanSink};
 use futures::{future::Either, prelude::*};
 use linkerd_stack::layer;
 use std::{
@@ -12,54 +12,102 @@ use tracing::{debug, info, trace};
 
 /// A layer that adds distributed tracing instrumentation.
 ///
-/// This layer reads the `traceparent` HTTP header from the request. If this
-/// header is absent, the request is fowarded unmodified.  If the header is
-/// present, a new span will be started in the current trace by creating a new
-/// random span id setting it into the `traceparent` header before forwarding
-/// the request. If the sampled bit of the header was set, we emit metadata
-/// about the span to the given SpanSink when the span is complete, i.e. when
-/// we receive the response.
+/// This layer reads the trace context from the request's `grpc-trace-bin`,
+/// `traceparent`, `b3`, or `x-b3-*` headers. If these headers are absent (and
+/// the sampler does not start a trace), the request is fowarded unmodified.  If
+/// a context is present, a new span will be started in the current trace by
+/// creating a new random span id setting it into the context's headers, in the
+/// same format, before forwarding the request. If the sampled bit of the context was set, we emit metadata about
+/// the span to the given SpanSink when the span is complete, i.e. when we
+/// receive the response.
+///
+/// The sampler may sample a trace that was received unsampled, or start a new
+/// trace for a request without a trace context. Its decision is propagated in
+/// the request's headers. If the sampler samples errors, spans are emitted for
+/// requests in unsampled traces that fail. A trace that the proxy starts is only
+/// propagated if it is sampled.
 #[derive(Clone, Debug)]
 pub struct TraceContext<K, S> {
     inner: S,
     sink: K,
+    sampler: Sampler,
 }
 
 // === impl TraceContext ===
 
 impl<K: Clone, S> TraceContext<K, S> {
-    pub fn layer(sink: K) -> impl layer::Layer<S, Service = TraceContext<K, S>> + Clone {
+    pub fn layer(
+        sink: K,
+        sampler: Sampler,
+    ) -> impl layer::Layer<S, Service = TraceContext<K, S>> + Clone {
         layer::mk(move |inner| TraceContext {
             inner,
             sink: sink.clone(),
+            sampler: sampler.clone(),
         })
     }
 
-    fn request_labels<B>(req: &http::Request<B>) -> HashMap<&'static str, String> {
+    /// Reads the request's trace context, applying the sampler to traces that
+    /// are not sampled, or starts a new trace if the request has none.
+    ///
+    /// Returns the context along with whether it is propagated in the
+    /// request's headers. A new trace is only propagated if it is sampled;
+    /// otherwise, it is started only so that a span may be recorded if the
+    /// request fails.
+    fn trace_context<B>(
+        &self,
+        req: &http::Request<B>,
+    ) -> Option<(propagation::TraceContext, bool)> {
+        match propagation::unpack_trace_context(req) {
+            Some(mut context) => {
+                if !context.is_sampled() && self.sampler.sample() {
+                    debug!("Sampling trace");
+                    context.flags.set_sampled();
+                }
+                Some((context, true))
+            }
+            None if self.sampler.is_enabled() => {
+                let sampled = self.sampler.sample();
+                if !sampled && !self.sampler.samples_errors() {
+                    return None;
+                }
+                debug!(sampled, "Starting trace");
+                Some((propagation::new_trace_context(sampled), sampled))
+            }
+            None => None,
+        }
+    }
+
+    fn request_labels<B>(req: &http::Request<B>) -> HashMap<String, String> {
         let mut labels = HashMap::with_capacity(5);
-        labels.insert("http.method", format!("{}", req.method()));
+        labels.insert("http.method".to_string(), format!("{}", req.method()));
         let path = req
             .uri()
             .path_and_query()
             .map(|pq| pq.as_str().to_owned())
             .unwrap_or_default();
-        labels.insert("http.path", path);
+        labels.insert("http.path".to_string(), path);
         if let Some(authority) = req.uri().authority() {
-            labels.insert("http.authority", authority.as_str().to_string());
+            labels.insert("http.authority".to_string(), authority.as_str().to_string());
         }
         if let Some(host) = req.headers().get("host") {
             if let Ok(host) = host.to_str() {
-                labels.insert("http.host", host.to_string());
+                labels.insert("http.host".to_string(), host.to_string());
             }
         }
         labels
     }
 
     fn add_response_labels<B>(
-        mut labels: HashMap<&'static str, String>,
+        mut labels: HashMap<String, String>,
+        attributes: SpanAttributes,
         rsp: &http::Response<B>,
-    ) -> HashMap<&'static str, String> {
-        labels.insert("http.status_code", rsp.status().as_str().to_string());
+    ) -> HashMap<String, String> {
+        labels.extend(attributes.take());
+        labels.insert(
+            "http.status_code".to_string(),
+            rsp.status().as_str().to_string(),
+        );
         labels
     }
 }
@@ -85,14 +133,20 @@ where
 
     fn call(&mut self, mut req: http::Request<ReqB>) -> Self::Future {
         if self.sink.is_enabled() {
-            if let Some(context) = propagation::unpack_trace_context(&req) {
+            if let Some((context, propagate)) = self.trace_context(&req) {
                 // Update the trace ID if the request set one and the proxy is configured to emit
                 // spans.
-                let span_id = propagation::increment_span_id(&mut req, &context);
-                debug!(?span_id, sampled = context.is_sampled());
+                let span_id = if propagate {
+                    propagation::increment_span_id(&mut req, &context)
+                } else {
+                    propagation::new_span_id()
+                };
+                let sampled = context.is_sampled();
+                debug!(?span_id, sampled);
 
-                if context.is_sampled() {
-                    // If the request has been marked for sampling, record its metadata.
+                if sampled || self.sampler.samples_errors() {
+                    // If the request has been marked for sampling, record its metadata. Otherwise,
+                    // it's only recorded if it fails.
                     let start = SystemTime::now();
                     let req_labels = Self::request_labels(&req);
                     let mut sink = self.sink.clone();
@@ -101,7 +155,14 @@ where
                         .path_and_query()
                         .map(|pq| pq.as_str().to_owned())
                         .unwrap_or_default();
+                    // Inner services may describe the request on its span.
+                    let attributes = SpanAttributes::default();
+                    req.extensions_mut().insert(attributes.clone());
                     return Either::Right(Box::pin(self.inner.call(req).map_ok(move |rsp| {
+                        if !sampled && !is_error(&rsp) {
+                            return rsp;
+                        }
+
                         // Emit the completed span with the response metadata.
                         let span = Span {
                             span_id,
@@ -110,7 +171,7 @@ where
                             span_name,
                             start,
                             end: SystemTime::now(),
-                            labels: Self::add_response_labels(req_labels, &rsp),
+                            labels: Self::add_response_labels(req_labels, attributes, &rsp),
                         };
                         trace!(?span);
                         if let Err(error) = sink.try_send(span) {
@@ -126,3 +187,139 @@ where
         Either::Left(self.inner.call(req))
     }
 }
+
+/// Indicates whether a response describes a failed request, either with a 5XX
+/// status or, for a gRPC response without a body, a non-OK `grpc-status`.
+///
+/// Spans are recorded when the response's headers are received, so a gRPC
+/// error that is only reported in the response's trailers is not detected.
+fn is_error<B>(rsp: &http::Response<B>) -> bool {
+    rsp.status().is_server_error()
+        || rsp
+            .headers()
+            .get("grpc-status")
+            .map(|code| code.as_bytes() != b"0")
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use futures::executor::block_on;
+    use std::sync::{Arc, Mutex};
+    use tower::{service_fn, Layer, Service};
+
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<Span>>>);
+
+    impl SpanSink for Spans {
+        fn is_enabled(&self) -> bool {
+            true
+        }
+
+        fn try_send(&mut self, span: Span) -> Result<(), Error> {
+            self.0.lock().unwrap().push(span);
+            Ok(())
+        }
+    }
+
+    fn request(sampled: &str) -> http::Request<()> {
+        http::Request::builder()
+            .uri("/hello")
+            .header("x-b3-traceid", "4bf92f3577b34da6a3ce929d0e0e4736")
+            .header("x-b3-spanid", "00f067aa0ba902b7")
+            .header("x-b3-sampled", sampled)
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn records_span_attributes() {
+        let spans = Spans::default();
+        let mut svc = TraceContext::layer(spans.clone(), Sampler::default()).layer(service_fn(
+            |req: http::Request<()>| async move {
+                let attrs = req
+                    .extensions()
+                    .get::<SpanAttributes>()
+                    .expect("sampled requests must have span attributes");
+                attrs.insert("rt.route", "GET /hello");
+                Ok::<_, Error>(http::Response::new(()))
+            },
+        ));
+
+        block_on(svc.call(request("1"))).unwrap();
+
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(
+            spans[0].labels.get("rt.route").map(String::as_str),
+            Some("GET /hello")
+        );
+        assert_eq!(
+            spans[0].labels.get("http.status_code").map(String::as_str),
+            Some("200")
+        );
+    }
+
+    #[test]
+    fn records_unsampled_errors() {
+        let spans = Spans::default();
+        let sampler = Sampler::new(0.0, None, true);
+        let mut svc = TraceContext::layer(spans.clone(), sampler).layer(service_fn(
+            |req: http::Request<()>| async move {
+                let status = if req.headers().contains_key("x-fail") {
+                    http::StatusCode::SERVICE_UNAVAILABLE
+                } else {
+                    http::StatusCode::OK
+                };
+                let rsp = http::Response::builder().status(status).body(()).unwrap();
+                Ok::<_, Error>(rsp)
+            },
+        ));
+
+        block_on(svc.call(request("0"))).unwrap();
+        assert!(spans.0.lock().unwrap().is_empty());
+
+        let mut req = request("0");
+        req.headers_mut()
+            .insert("x-fail", http::HeaderValue::from_static("1"));
+        block_on(svc.call(req)).unwrap();
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(
+            spans[0].labels.get("http.status_code").map(String::as_str),
+            Some("503")
+        );
+    }
+
+    #[test]
+    fn propagates_only_sampled_new_traces() {
+        // Fails every request, reporting whether it was given a trace context.
+        let inner = service_fn(|req: http::Request<()>| async move {
+            let rsp = http::Response::builder()
+                .status(http::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(req.headers().contains_key("traceparent"))
+                .unwrap();
+            Ok::<_, Error>(rsp)
+        });
+        let new_request = || http::Request::builder().uri("/hello").body(()).unwrap();
+
+        let spans = Spans::default();
+        let mut svc =
+            TraceContext::layer(spans.clone(), Sampler::new(1.0, None, false)).layer(inner);
+        let rsp = block_on(svc.call(new_request())).unwrap();
+        assert!(rsp.body(), "sampled traces must be propagated");
+        assert_eq!(spans.0.lock().unwrap().len(), 1);
+
+        // An unsampled trace is only started to record errors.
+        let spans = Spans::default();
+        let mut svc =
+            TraceContext::layer(spans.clone(), Sampler::new(0.0, None, true)).layer(inner);
+        let rsp = block_on(svc.call(new_request())).unwrap();
+        assert!(!rsp.body(), "unsampled traces must not be propagated");
+        let spans = spans.0.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        assert!(spans[0].parent_id.0.is_empty());
+    }
+}